@@ -0,0 +1,68 @@
+//! 排除模式性能测试：模拟导入 10 万个文件时的排除过滤
+//!
+//! 运行：`cargo bench --bench exclude_patterns`
+
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use stowr_core::{Matcher, PatternSet};
+
+const FILE_COUNT: usize = 100_000;
+const ROUNDS: usize = 5;
+
+const EXCLUDES: &[&str] = &[
+    "*.log",
+    "*.tmp",
+    "**/target/**",
+    "/data/project/module1?/**",
+    "[!a-z]*.png",
+];
+
+fn paths() -> Vec<PathBuf> {
+    let extensions = ["rs", "txt", "log", "tmp", "png"];
+    (0..FILE_COUNT)
+        .map(|i| {
+            let dir = if i % 13 == 0 { "target" } else { "src" };
+            PathBuf::from(format!(
+                "/data/project/module{}/{}/sub{}/file{}.{}",
+                i % 100, dir, i % 7, i, extensions[i % extensions.len()]
+            ))
+        })
+        .collect()
+}
+
+/// 多轮运行取最短时间
+fn bench(name: &str, mut f: impl FnMut() -> usize) {
+    let mut best = Duration::MAX;
+    let mut kept = 0;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        kept = black_box(f());
+        best = best.min(start.elapsed());
+    }
+    println!(
+        "{:<24} {:>8.2} ms  ({} of {} files kept, {:.0} ns/file)",
+        name,
+        best.as_secs_f64() * 1000.0,
+        kept,
+        FILE_COUNT,
+        best.as_nanos() as f64 / FILE_COUNT as f64
+    );
+}
+
+fn main() {
+    let files = paths();
+
+    bench("PatternSet", || {
+        let excludes = PatternSet::new(EXCLUDES).unwrap();
+        files.iter().filter(|path| !excludes.is_match(path)).count()
+    });
+
+    bench("Matcher per pattern", || {
+        let matchers: Vec<Matcher> = EXCLUDES.iter().map(|p| Matcher::new(p).unwrap()).collect();
+        files.iter()
+            .filter(|path| !matchers.iter().any(|matcher| matcher.is_match(path)))
+            .count()
+    });
+}
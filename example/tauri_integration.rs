@@ -1,5 +1,5 @@
 // Tauri 集成示例
-use stowr_core::{Config, StorageManager, create_index, FileEntry};
+use stowr_core::{BatchReport, Config, DedupStats, DeleteMode, OnConflict, StorageManager, create_index, FileEntry};
 use std::path::Path;
 use serde::{Deserialize, Serialize};
 
@@ -80,28 +80,40 @@ impl StorageService {
         Ok(files.into_iter().map(FileInfo::from).collect())
     }
     
+    // Tauri 命令：按列表文件批量存储，统计和报告类型实现了 Serialize，可以直接返回
+    pub fn store_from_list(&mut self, list_file: String) -> Result<BatchReport, String> {
+        self.storage
+            .store_files_from_list(Path::new(&list_file), false)
+            .map_err(|e| e.to_string())
+    }
+
+    // Tauri 命令：去重统计
+    pub fn dedup_stats(&self) -> DedupStats {
+        self.storage.get_dedup_stats()
+    }
+    
     // Tauri 命令：删除文件
     pub fn delete_file(&mut self, file_path: String) -> Result<String, String> {
         self.storage
-            .delete_file(Path::new(&file_path))
+            .delete_file(Path::new(&file_path), DeleteMode::Promote)
             .map_err(|e| e.to_string())?;
         
         Ok(format!("File '{}' deleted successfully", file_path))
     }
     
-    // Tauri 命令：重命名文件
-    pub fn rename_file(&mut self, old_path: String, new_path: String) -> Result<String, String> {
+    // Tauri 命令：重命名文件，目标已存在时按前端传入的 on_conflict 处理（省略时为 Fail）
+    pub fn rename_file(&mut self, old_path: String, new_path: String, on_conflict: Option<OnConflict>) -> Result<String, String> {
         self.storage
-            .rename_file(Path::new(&old_path), Path::new(&new_path))
+            .rename_file(Path::new(&old_path), Path::new(&new_path), on_conflict.unwrap_or_default())
             .map_err(|e| e.to_string())?;
         
         Ok(format!("File renamed from '{}' to '{}'", old_path, new_path))
     }
     
-    // Tauri 命令：移动文件
-    pub fn move_file(&mut self, file_path: String, new_location: String) -> Result<String, String> {
+    // Tauri 命令：移动文件，目标已存在时按前端传入的 on_conflict 处理（省略时为 Fail）
+    pub fn move_file(&mut self, file_path: String, new_location: String, on_conflict: Option<OnConflict>) -> Result<String, String> {
         self.storage
-            .move_file(Path::new(&file_path), Path::new(&new_location))
+            .move_file(Path::new(&file_path), Path::new(&new_location), on_conflict.unwrap_or_default())
             .map_err(|e| e.to_string())?;
         
         Ok(format!("File '{}' moved to '{}'", file_path, new_location))
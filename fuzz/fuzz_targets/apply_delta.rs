@@ -0,0 +1,28 @@
+//! 差分数据解析的模糊测试：任意输入都不能导致 panic、越界读取或超过上限的内存分配
+//!
+//! 运行：`cargo +nightly fuzz run apply_delta`（在仓库根目录）
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stowr_core::{DeltaAlgorithm, DeltaStorage};
+
+/// 远小于默认上限，使超大目标长度的输入能被快速拒绝
+const MAX_TARGET_SIZE: u64 = 1 << 20;
+
+fuzz_target!(|data: &[u8]| {
+    // 第一个字节决定基础文件长度，其余部分作为差分数据
+    let Some((&base_len, rest)) = data.split_first() else {
+        return;
+    };
+    let (base, delta) = rest.split_at((base_len as usize).min(rest.len()));
+
+    let mut delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::Simple);
+    delta_storage.set_max_target_size(MAX_TARGET_SIZE);
+    if let Ok(target) = delta_storage.apply_delta(base, delta) {
+        assert!(target.len() as u64 <= MAX_TARGET_SIZE);
+        // 重建结果再次差分后必须能还原
+        let again = delta_storage.create_delta(base, &target).unwrap();
+        assert_eq!(delta_storage.apply_delta(base, &again).unwrap(), target);
+    }
+});
@@ -0,0 +1,217 @@
+//! 最近活动记录
+//!
+//! 存储、提取和删除操作成功后追加到存储目录下的 `activity.log`（每行一条 JSON 记录），
+//! 供界面显示"最近活动"。提取和删除后的条目已不在索引中，因此不能从索引的时间戳推断。
+//! 日志只保留最近 `Config::activity_log_limit` 条记录，超过两倍时截断。
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::fsutil;
+use crate::paths::{decode_path, encode_path};
+
+/// 活动日志文件名
+pub const ACTIVITY_LOG_FILE: &str = "activity.log";
+
+/// 记录的操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Store,
+    Extract,
+    Delete,
+}
+
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
+impl Operation {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "store" => Ok(Operation::Store),
+            "extract" => Ok(Operation::Extract),
+            "delete" => Ok(Operation::Delete),
+            _ => Err(anyhow!("Invalid operation. Valid values: store, extract, delete")),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            Operation::Store => "store".to_string(),
+            Operation::Extract => "extract".to_string(),
+            Operation::Delete => "delete".to_string(),
+        }
+    }
+}
+
+/// 一条活动记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivityRecord {
+    pub operation: Operation,
+    pub path: PathBuf,
+    pub entry_id: String,
+    /// 原始内容大小
+    pub size: u64,
+    /// RFC 3339 格式的时间
+    pub timestamp: String,
+}
+
+/// 日志中的记录格式，路径按索引相同的方式编码
+#[derive(Serialize, Deserialize)]
+struct RawRecord {
+    operation: Operation,
+    path: String,
+    entry_id: String,
+    size: u64,
+    timestamp: String,
+}
+
+impl From<&ActivityRecord> for RawRecord {
+    fn from(record: &ActivityRecord) -> Self {
+        Self {
+            operation: record.operation,
+            path: encode_path(&record.path),
+            entry_id: record.entry_id.clone(),
+            size: record.size,
+            timestamp: record.timestamp.clone(),
+        }
+    }
+}
+
+impl RawRecord {
+    fn decode(self) -> Option<ActivityRecord> {
+        Some(ActivityRecord {
+            operation: self.operation,
+            path: decode_path(&self.path).ok()?,
+            entry_id: self.entry_id,
+            size: self.size,
+            timestamp: self.timestamp,
+        })
+    }
+}
+
+/// 存储目录下的活动日志
+#[derive(Debug)]
+pub struct ActivityLog {
+    path: PathBuf,
+    /// 保留的记录数，0 表示不记录
+    limit: usize,
+    /// 日志中的行数，打开时统计
+    lines: usize,
+}
+
+impl ActivityLog {
+    pub fn open(storage_path: &Path, limit: usize) -> Self {
+        let path = storage_path.join(ACTIVITY_LOG_FILE);
+        let lines = fs::read(&path)
+            .map(|data| data.iter().filter(|&&b| b == b'\n').count())
+            .unwrap_or(0);
+        Self { path, limit, lines }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.limit > 0
+    }
+
+    /// 追加记录，超过两倍上限时只保留最近的记录
+    pub fn append(&mut self, records: &[ActivityRecord]) -> Result<()> {
+        if !self.is_enabled() || records.is_empty() {
+            return Ok(());
+        }
+        let mut content = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut content, &RawRecord::from(record))
+                .context("Failed to serialize activity record")?;
+            content.push(b'\n');
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open activity log: {}", self.path.display()))?;
+        file.write_all(&content)
+            .with_context(|| format!("Failed to write activity log: {}", self.path.display()))?;
+        self.lines += records.len();
+
+        if self.lines > self.limit * 2 {
+            self.truncate()?;
+        }
+        Ok(())
+    }
+
+    /// 最近的记录，从新到旧排列；`operation` 为 None 时包含所有操作
+    pub fn recent(&self, operation: Option<Operation>, limit: usize) -> Result<Vec<ActivityRecord>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read activity log: {}", self.path.display()))
+            }
+        };
+        // 中断写入可能留下不完整的行，跳过无法解析的记录
+        Ok(data.split(|&b| b == b'\n')
+            .rev()
+            .filter_map(|line| serde_json::from_slice::<RawRecord>(line).ok())
+            .filter_map(RawRecord::decode)
+            .filter(|record| operation.is_none_or(|op| record.operation == op))
+            .take(limit)
+            .collect())
+    }
+
+    fn truncate(&mut self) -> Result<()> {
+        let data = fs::read(&self.path)
+            .with_context(|| format!("Failed to read activity log: {}", self.path.display()))?;
+        let lines: Vec<&[u8]> = data.split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .collect();
+        let kept = &lines[lines.len().saturating_sub(self.limit)..];
+        let mut content = kept.join(&b'\n');
+        content.push(b'\n');
+        fsutil::atomic_write(&self.path, &content, None, false)
+            .context("Failed to truncate activity log")?;
+        self.lines = kept.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(operation: Operation, n: usize) -> ActivityRecord {
+        ActivityRecord {
+            operation,
+            path: PathBuf::from(format!("/data/file{}.txt", n)),
+            entry_id: n.to_string(),
+            size: n as u64,
+            timestamp: format!("2024-01-01T00:00:{:02}Z", n),
+        }
+    }
+
+    #[test]
+    fn test_recent_filters_and_truncates() {
+        let dir = TempDir::new().unwrap();
+        let mut log = ActivityLog::open(dir.path(), 3);
+        for n in 0..7 {
+            let operation = if n % 2 == 0 { Operation::Store } else { Operation::Delete };
+            log.append(&[record(operation, n)]).unwrap();
+        }
+
+        // 第 7 条记录触发截断，只保留最近 3 条
+        let all = log.recent(None, 10).unwrap();
+        assert_eq!(all.iter().map(|r| r.size).collect::<Vec<_>>(), vec![6, 5, 4]);
+        let stores = log.recent(Some(Operation::Store), 1).unwrap();
+        assert_eq!(stores, vec![record(Operation::Store, 6)]);
+
+        // 重新打开后继续按已有行数计算
+        let mut log = ActivityLog::open(dir.path(), 3);
+        log.append(&[record(Operation::Extract, 7)]).unwrap();
+        assert_eq!(log.recent(None, 10).unwrap().len(), 4);
+
+        let mut disabled = ActivityLog::open(&dir.path().join("none"), 0);
+        disabled.append(&[record(Operation::Store, 0)]).unwrap();
+        assert!(disabled.recent(None, 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+//! 存储后端与分层存储
+//!
+//! 新写入的存储文件始终位于存储目录（热层）。长时间未访问的条目可以由
+//! `StorageManager::tier_migrate` 迁移到注册的冷层后端，读取时按条目记录的层级透明地取回。
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fsutil;
+
+/// 条目的存储文件所在的层级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageTier {
+    /// 存储目录
+    #[default]
+    Hot,
+    /// 通过 `StorageManager::set_cold_backend` 注册的后端
+    Cold,
+    /// 内容保存在索引条目中（见 `Config::inline_threshold`），没有存储文件
+    Inline,
+}
+
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
+impl StorageTier {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "hot" => Ok(StorageTier::Hot),
+            "cold" => Ok(StorageTier::Cold),
+            "inline" => Ok(StorageTier::Inline),
+            _ => Err(anyhow!("Invalid storage tier. Valid values: hot, cold, inline")),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            StorageTier::Hot => "hot".to_string(),
+            StorageTier::Cold => "cold".to_string(),
+            StorageTier::Inline => "inline".to_string(),
+        }
+    }
+
+    pub fn is_hot(&self) -> bool {
+        *self == StorageTier::Hot
+    }
+}
+
+/// 按键存取存储文件内容的后端
+///
+/// 键是存储文件的文件名，不含路径分隔符。内容按原样保存（已压缩，启用加密时已加密），
+/// 后端不需要理解其格式
+pub trait StorageBackend: Send + Sync {
+    /// 后端名称，出现在错误信息中
+    fn name(&self) -> &str;
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// 删除内容，键不存在时视为成功
+    fn delete(&self, key: &str) -> Result<()>;
+
+    fn exists(&self, key: &str) -> Result<bool>;
+}
+
+/// 以本地目录（如另一块较慢、较便宜的磁盘或挂载的网络存储）作为后端
+#[derive(Debug, Clone)]
+pub struct DirectoryBackend {
+    root: PathBuf,
+}
+
+impl DirectoryBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)
+            .with_context(|| format!("Failed to create backend directory: {}", root.display()))?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf> {
+        if key.is_empty() || key.contains(['/', '\\']) || key == "." || key == ".." {
+            return Err(anyhow!("Invalid backend key: {}", key));
+        }
+        Ok(self.root.join(key))
+    }
+}
+
+impl StorageBackend for DirectoryBackend {
+    fn name(&self) -> &str {
+        "directory"
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        fsutil::atomic_write(&self.path_for(key)?, data, None, false)
+            .with_context(|| format!("Failed to write {} to {}", key, self.root.display()))
+    }
+
+    fn get(&self, key: &str) -> Result<Vec<u8>> {
+        fs::read(self.path_for(key)?)
+            .with_context(|| format!("Failed to read {} from {}", key, self.root.display()))
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path_for(key)?;
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to delete {} from {}", key, self.root.display()))?;
+        }
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.path_for(key)?.exists())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_directory_backend_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let backend = DirectoryBackend::new(dir.path().join("cold")).unwrap();
+
+        backend.put("blob.gz", b"data").unwrap();
+        assert!(backend.exists("blob.gz").unwrap());
+        assert_eq!(backend.get("blob.gz").unwrap(), b"data");
+
+        backend.delete("blob.gz").unwrap();
+        backend.delete("blob.gz").unwrap();
+        assert!(!backend.exists("blob.gz").unwrap());
+        assert!(backend.get("blob.gz").is_err());
+        assert!(backend.put("../escape", b"x").is_err());
+    }
+}
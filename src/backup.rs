@@ -0,0 +1,175 @@
+//! 增量仓库备份
+//!
+//! [`StorageManager::backup_since`](crate::StorageManager::backup_since) 把上次备份（或某个时间点）之后新增或修改的
+//! 索引条目，连同它们引用的、尚未备份过的存储文件，写入一个备份目录：
+//!
+//! ```text
+//! <dest>/backup.json     备份清单：变化的条目、当前全部条目ID、保留的基础文件
+//! <dest>/blobs/<name>    原样复制的存储文件（加密的存储文件保持加密）
+//! ```
+//!
+//! 源仓库在存储目录下的 `backup_marker.json` 中记录每次备份时各条目的指纹和已备份的存储文件，
+//! 下一次备份据此只写入变化的部分。恢复端按顺序对同一个仓库调用
+//! [`apply_backup`](crate::StorageManager::apply_backup)，得到与源仓库一致的索引。
+//! 校验文件（`.parity`）和分块签名（`.sig`）不在备份中，需要时在恢复端重新生成。
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::crypto;
+use crate::fsutil;
+use crate::index::FileEntry;
+
+/// 备份清单文件名（位于备份目录下）
+pub const BACKUP_MANIFEST_FILE: &str = "backup.json";
+/// 备份目录下存放存储文件的子目录
+pub const BACKUP_BLOB_DIR: &str = "blobs";
+/// 源仓库记录上次备份的文件名（位于存储目录下）
+pub const BACKUP_MARKER_FILE: &str = "backup_marker.json";
+/// 恢复端记录最近一次应用的备份的文件名（位于存储目录下）
+pub const BACKUP_APPLIED_FILE: &str = "backup_applied.json";
+/// 当前备份格式版本
+pub const FORMAT_VERSION: u32 = 1;
+
+/// 备份的起点
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupSince {
+    /// 上次 `backup_since` 之后的变化，没有记录时备份全部条目
+    LastBackup,
+    /// 创建时间不早于该时间的条目；这种备份不能发现元数据的修改和删除，也不更新备份记录
+    Time(chrono::DateTime<chrono::Utc>),
+}
+
+/// 备份清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    /// 本次备份的ID
+    pub backup_id: String,
+    /// 增量备份所基于的上一次备份的ID，恢复端必须先应用那一次备份；None 表示完整备份或按时间的备份
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+    /// RFC 3339 格式的备份时间
+    pub created_at: String,
+    /// 新增或修改的条目
+    pub entries: Vec<FileEntry>,
+    /// 备份时仓库中全部条目的ID，恢复端删除不在其中的条目；按时间的备份为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub live_ids: Option<Vec<String>>,
+    /// 备份时保留的基础文件（已提取但仍被差分条目依赖）
+    #[serde(default)]
+    pub retained_bases: Vec<FileEntry>,
+    /// 备份目录中的存储文件名
+    pub blobs: Vec<String>,
+}
+
+/// 源仓库记录的上次备份
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct BackupMarker {
+    pub backup_id: String,
+    /// 条目ID -> 备份时的条目指纹
+    pub fingerprints: HashMap<String, String>,
+    /// 已备份的存储文件名
+    pub blobs: HashSet<String>,
+}
+
+/// 备份或应用备份的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupReport {
+    /// 写入或更新的条目数
+    pub entries: usize,
+    /// 复制的存储文件数
+    pub blobs: usize,
+    /// 复制的存储文件总大小
+    pub bytes: u64,
+    /// 应用备份时删除的条目数
+    pub removed: usize,
+}
+
+/// 条目的指纹：读取时间以外的字段都相同时才相同
+pub(crate) fn fingerprint(entry: &FileEntry) -> Result<String> {
+    let mut entry = entry.clone();
+    entry.last_accessed = None;
+    let data = serde_json::to_vec(&entry).context("Failed to serialize entry")?;
+    Ok(crypto::to_hex(&Sha256::digest(&data)))
+}
+
+/// 读取 JSON 文件，不存在时返回 None
+pub(crate) fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// 写入 JSON 文件
+pub(crate) fn write_json<T: Serialize>(path: &Path, value: &T, sync: bool) -> Result<()> {
+    let data = serde_json::to_vec_pretty(value)
+        .with_context(|| format!("Failed to serialize {}", path.display()))?;
+    fsutil::atomic_write(path, &data, None, sync)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// 读取备份目录中的清单并检查格式版本和存储文件名
+pub(crate) fn read_manifest(src: &Path) -> Result<BackupManifest> {
+    let manifest: BackupManifest = read_json(&src.join(BACKUP_MANIFEST_FILE))?
+        .ok_or_else(|| anyhow!("Not a stowr backup: {}", src.display()))?;
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(anyhow!("Unsupported backup format version: {}", manifest.format_version));
+    }
+    // 存储文件名来自备份目录，不允许指向存储目录之外
+    if let Some(name) = manifest.blobs.iter().find(|name| !is_plain_file_name(name)) {
+        return Err(anyhow!("Invalid blob name in backup: {}", name));
+    }
+    Ok(manifest)
+}
+
+fn is_plain_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fingerprint_ignores_access_time_and_manifest_rejects_bad_names() {
+        let mut entry = FileEntry::new(
+            "id".to_string(),
+            PathBuf::from("a.txt"),
+            PathBuf::from("blob"),
+            1,
+            1,
+            crate::config::CompressionAlgorithm::Gzip,
+        );
+        let original = fingerprint(&entry).unwrap();
+        entry.last_accessed = Some("2026-01-01T00:00:00Z".to_string());
+        assert_eq!(fingerprint(&entry).unwrap(), original);
+        entry.description = Some("changed".to_string());
+        assert_ne!(fingerprint(&entry).unwrap(), original);
+
+        let dir = TempDir::new().unwrap();
+        assert!(read_manifest(dir.path()).is_err());
+        let manifest = BackupManifest {
+            format_version: FORMAT_VERSION,
+            backup_id: "b1".to_string(),
+            previous: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            entries: vec![entry],
+            live_ids: None,
+            retained_bases: Vec::new(),
+            blobs: vec!["../escape".to_string()],
+        };
+        write_json(&dir.path().join(BACKUP_MANIFEST_FILE), &manifest, false).unwrap();
+        assert!(read_manifest(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,412 @@
+//! stowr 命令行工具
+//!
+//! 基于 stowr-core 的参考命令行实现，与库一起演进，需要启用 `cli` feature 构建：
+//! `cargo install stowr-core --features cli`。配置和存储目录与库的默认值一致（当前目录下的 `.stowr`）。
+//! 所有子命令都支持 `--output table|plain|json`（`--json` 是 `--output json` 的简写），
+//! 错误按类别返回不同的退出码，见 [`output`]。
+//! 启用 `tui` feature 后提供 `browse` 交互界面，见 [`tui`]。
+
+mod output;
+#[cfg(feature = "tui")]
+mod tui;
+
+use anyhow::{Context, Result, anyhow};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use stowr_core::{BatchReport, Config, EntryOrder, FileEntry, GrepOptions, StorageManager, StoreOutcome, Verbosity, create_index};
+
+use output::{OutputFormat, print_json, print_rows};
+
+#[derive(Debug, Parser)]
+#[command(name = "stowr", version, about = "Compress and store files, and extract them back on demand")]
+struct Cli {
+    #[arg(long, global = true, value_enum, help = "Output format [default: table]")]
+    output: Option<OutputFormat>,
+    #[arg(long, global = true, conflicts_with = "output", help = "Shorthand for --output json")]
+    json: bool,
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, help = "Show progress (-v) or details (-vv)")]
+    verbose: u8,
+    #[command(subcommand)]
+    command: Command,
+}
+
+impl Cli {
+    fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.output.unwrap_or_default()
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(about = "Store files; directories are stored incrementally and keep their sources")]
+    Store {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        #[arg(long, help = "Delete source files after storing them")]
+        delete: bool,
+        #[arg(long, help = "Add the stored entries to this collection")]
+        collection: Option<String>,
+    },
+    #[command(about = "Extract stored files back to their original paths and remove them from storage")]
+    Owe {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    #[command(about = "List stored files")]
+    List {
+        #[arg(long, default_value = "path", help = "Sort order: path, created_at or size")]
+        order: String,
+    },
+    #[command(about = "Search stored files by glob pattern")]
+    Search {
+        pattern: String,
+    },
+    #[command(about = "Search the content of stored text files by regular expression")]
+    Grep {
+        pattern: String,
+        #[arg(long, help = "Only search entries whose path matches this glob pattern")]
+        glob: Option<String>,
+        #[arg(short, long, help = "Ignore case")]
+        ignore_case: bool,
+    },
+    #[command(about = "Show or change configuration")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    #[command(about = "Check stored content against recorded hashes, one batch per call")]
+    Verify {
+        #[arg(long, help = "Resume token printed by the previous batch")]
+        resume: Option<String>,
+    },
+    #[command(about = "Release retained delta bases that no entry depends on")]
+    Gc,
+    #[cfg(feature = "tui")]
+    #[command(about = "Browse stored files interactively: search, preview, restore and delete")]
+    Browse {
+        #[arg(help = "Initial path filter")]
+        filter: Option<String>,
+    },
+    #[command(about = "Print a shell completion script")]
+    Completions {
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigAction {
+    #[command(about = "Print all configuration keys")]
+    List,
+    #[command(about = "Print one configuration key")]
+    Get {
+        key: String,
+    },
+    #[command(about = "Set a configuration key")]
+    Set {
+        key: String,
+        value: String,
+    },
+}
+
+/// `store` 子命令中单个路径的结果
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum StoreResult {
+    File {
+        #[serde(with = "stowr_core::paths::serde_path")]
+        path: PathBuf,
+        outcome: Box<StoreOutcome>,
+    },
+    Directory {
+        #[serde(with = "stowr_core::paths::serde_path")]
+        path: PathBuf,
+        report: BatchReport,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            // --help 和 --version 也以错误的形式返回
+            return ExitCode::from(if e.use_stderr() { output::EXIT_USAGE } else { output::EXIT_OK });
+        }
+    };
+    match run(&cli) {
+        Ok(true) => ExitCode::from(output::EXIT_OK),
+        Ok(false) => ExitCode::from(output::EXIT_PARTIAL),
+        Err(e) => ExitCode::from(output::report_error(cli.format(), &e)),
+    }
+}
+
+/// 执行子命令，返回操作是否完全成功
+fn run(cli: &Cli) -> Result<bool> {
+    let format = cli.format();
+    if let Command::Completions { shell } = &cli.command {
+        let mut script = Vec::new();
+        clap_complete::generate(*shell, &mut Cli::command(), "stowr", &mut script);
+        std::io::stdout().write_all(&script).context("Failed to write completion script")?;
+        return Ok(true);
+    }
+
+    let mut config = Config::load()?;
+    config.verbosity = match (format, cli.verbose) {
+        (OutputFormat::Json, _) => Verbosity::Silent,
+        (_, 0) => Verbosity::Warn,
+        (_, 1) => Verbosity::Info,
+        (_, _) => Verbosity::Debug,
+    };
+
+    match &cli.command {
+        Command::Config { action } => return run_config(format, config, action),
+        Command::List { order } => {
+            let order = EntryOrder::from_str(order)?;
+            let entries = open(config)?.list_files_ordered(order)?;
+            print_entries(format, &entries)?;
+        }
+        Command::Search { pattern } => {
+            let entries = open(config)?.search_files(pattern)?;
+            print_entries(format, &entries)?;
+        }
+        Command::Grep { pattern, glob, ignore_case } => {
+            let options = GrepOptions { ignore_case: *ignore_case, ..GrepOptions::default() };
+            let matches = open(config)?.grep(pattern, glob.as_deref(), &options)?;
+            if format == OutputFormat::Json {
+                print_json(&matches)?;
+            } else {
+                let rows: Vec<Vec<String>> = matches.iter()
+                    .map(|m| vec![m.path.display().to_string(), m.line_number.to_string(), m.line.clone()])
+                    .collect();
+                print_rows(format, &["PATH", "LINE", "TEXT"], &rows);
+            }
+        }
+        Command::Store { paths, delete, collection } => {
+            let mut manager = open(config)?;
+            let mut results = Vec::new();
+            for path in paths {
+                let result = if path.is_dir() {
+                    let report = match collection {
+                        Some(collection) => manager.store_directory_into(path, collection)?,
+                        None => manager.store_directory_incremental(path)?,
+                    };
+                    StoreResult::Directory { path: path.clone(), report }
+                } else {
+                    let outcome = manager.store_file(path, *delete)
+                        .with_context(|| format!("Failed to store {}", path.display()))?;
+                    if let Some(collection) = collection {
+                        manager.set_collection(path, Some(collection))?;
+                    }
+                    StoreResult::File { path: path.clone(), outcome: Box::new(outcome) }
+                };
+                results.push(result);
+            }
+            print_store_results(format, &results)?;
+            return Ok(results.iter().all(|result| match result {
+                StoreResult::File { .. } => true,
+                StoreResult::Directory { report, .. } => report.is_success(),
+            }));
+        }
+        Command::Owe { paths } => {
+            let mut manager = open(config)?;
+            for path in paths {
+                manager.owe_file(path)
+                    .with_context(|| format!("Failed to extract {}", path.display()))?;
+            }
+            match format {
+                OutputFormat::Json => print_json(&serde_json::json!({ "extracted": paths }))?,
+                _ => print_rows(format, &["EXTRACTED"], &paths.iter().map(|path| vec![path.display().to_string()]).collect::<Vec<_>>()),
+            }
+        }
+        Command::Verify { resume } => {
+            let report = open(config)?.scrub(resume.as_deref())?;
+            match format {
+                OutputFormat::Json => print_json(&report)?,
+                OutputFormat::Table => {
+                    println!("Checked {} entries ({} bytes), {} corrupted",
+                        report.entries_checked, report.bytes_checked, report.corrupted.len());
+                    for (path, reason) in &report.corrupted {
+                        println!("corrupted  {}: {}", path.display(), reason);
+                    }
+                    if let Some(token) = &report.resume_token {
+                        println!("More entries remain; continue with: stowr verify --resume {}", token);
+                    }
+                }
+                OutputFormat::Plain => {
+                    for (path, reason) in &report.corrupted {
+                        println!("corrupted\t{}\t{}", path.display(), reason);
+                    }
+                    if let Some(token) = &report.resume_token {
+                        println!("resume\t{}", token);
+                    }
+                }
+            }
+            return Ok(report.corrupted.is_empty());
+        }
+        Command::Gc => {
+            let report = open(config)?.gc()?;
+            match format {
+                OutputFormat::Json => print_json(&report)?,
+                OutputFormat::Table => {
+                    for path in &report.promoted {
+                        println!("promoted  {}", path.display());
+                    }
+                    println!("Released {} retained bases, reclaimed {} bytes", report.bases_released, report.bytes_reclaimed);
+                }
+                OutputFormat::Plain => {
+                    for path in &report.promoted {
+                        println!("promoted\t{}", path.display());
+                    }
+                    println!("released\t{}\t{}", report.bases_released, report.bytes_reclaimed);
+                }
+            }
+        }
+        #[cfg(feature = "tui")]
+        Command::Browse { filter } => {
+            // 库的提示信息会打乱界面，操作结果显示在状态栏中
+            config.verbosity = Verbosity::Silent;
+            tui::run(&mut open(config)?, filter.as_deref())?;
+        }
+        Command::Completions { .. } => unreachable!("handled before loading the configuration"),
+    }
+    Ok(true)
+}
+
+fn run_config(format: OutputFormat, mut config: Config, action: &ConfigAction) -> Result<bool> {
+    match action {
+        ConfigAction::List => {
+            let values = config.list();
+            match format {
+                OutputFormat::Json => print_json(&values.into_iter().collect::<std::collections::BTreeMap<_, _>>())?,
+                _ => print_rows(format, &["KEY", "VALUE"], &values.into_iter().map(|(key, value)| vec![key, value]).collect::<Vec<_>>()),
+            }
+        }
+        ConfigAction::Get { key } => {
+            let value = config_value(&config, key)?;
+            match format {
+                OutputFormat::Json => print_json(&serde_json::json!({ "key": key, "value": value }))?,
+                _ => println!("{}", value),
+            }
+        }
+        ConfigAction::Set { key, value } => {
+            config.set(key, value)?;
+            config.save()?;
+            // 部分键会调整输入值（如 lz4 的压缩级别），输出实际保存的值
+            let value = config_value(&config, key)?;
+            match format {
+                OutputFormat::Json => print_json(&serde_json::json!({ "key": key, "value": value }))?,
+                _ => print_rows(format, &["KEY", "VALUE"], &[vec![key.clone(), value]]),
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn config_value(config: &Config, key: &str) -> Result<String> {
+    config.list().into_iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+        .ok_or_else(|| anyhow!("Unknown config key: {}", key))
+}
+
+fn open(config: Config) -> Result<StorageManager> {
+    // 输出级别是进程级的，打开存储库前按本次命令的设置调整
+    stowr_core::output::set_verbosity(config.verbosity);
+    let index = create_index(&config)?;
+    Ok(StorageManager::new(config, index))
+}
+
+fn print_entries(format: OutputFormat, entries: &[FileEntry]) -> Result<()> {
+    if format == OutputFormat::Json {
+        return print_json(&entries);
+    }
+    let rows: Vec<Vec<String>> = entries.iter()
+        .map(|entry| vec![
+            entry.file_size.to_string(),
+            entry.compressed_size.to_string(),
+            entry.original_path.display().to_string(),
+        ])
+        .collect();
+    print_rows(format, &["SIZE", "STORED", "PATH"], &rows);
+    Ok(())
+}
+
+fn print_store_results(format: OutputFormat, results: &[StoreResult]) -> Result<()> {
+    if format == OutputFormat::Json {
+        return print_json(results);
+    }
+    let mut rows = Vec::new();
+    for result in results {
+        match result {
+            StoreResult::File { path, outcome } => {
+                let how = match outcome.as_ref() {
+                    StoreOutcome::Stored(_) | StoreOutcome::DeltaBudgetExhausted(_) => "stored",
+                    StoreOutcome::Deduplicated(_) => "deduplicated",
+                    StoreOutcome::Delta(_) => "delta",
+                    StoreOutcome::AlreadyStored(_) => "unchanged",
+                };
+                rows.push(vec![how.to_string(), path.display().to_string(), String::new()]);
+            }
+            StoreResult::Directory { path, report } => {
+                for file in &report.succeeded {
+                    rows.push(vec!["stored".to_string(), file.display().to_string(), String::new()]);
+                }
+                for file in &report.skipped {
+                    rows.push(vec!["unchanged".to_string(), file.display().to_string(), String::new()]);
+                }
+                for (file, reason) in &report.failed {
+                    rows.push(vec!["failed".to_string(), file.display().to_string(), reason.clone()]);
+                }
+                for (file, reason) in &report.rejected {
+                    rows.push(vec!["rejected".to_string(), file.display().to_string(), reason.clone()]);
+                }
+                if report.total() == 0 {
+                    rows.push(vec!["empty".to_string(), path.display().to_string(), String::new()]);
+                }
+            }
+        }
+    }
+    print_rows(format, &["RESULT", "PATH", "REASON"], &rows);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("stowr").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_cli_arguments() {
+        Cli::command().debug_assert();
+
+        let cli = parse(&["store", "a.txt", "dir", "--delete", "--json"]);
+        assert_eq!(cli.format(), OutputFormat::Json);
+        assert!(matches!(cli.command, Command::Store { ref paths, delete: true, collection: None } if paths == &[Path::new("a.txt"), Path::new("dir")]));
+        assert!(matches!(parse(&["store", "dir", "--collection", "project-x"]).command, Command::Store { collection: Some(_), .. }));
+
+        let cli = parse(&["-vv", "config", "set", "compression.algorithm", "zstd"]);
+        assert_eq!(cli.verbose, 2);
+        assert_eq!(cli.format(), OutputFormat::Table);
+        assert!(matches!(cli.command, Command::Config { action: ConfigAction::Set { .. } }));
+        assert!(matches!(parse(&["verify", "--resume", "t"]).command, Command::Verify { resume: Some(_) }));
+        assert!(matches!(parse(&["grep", "-i", "todo", "--glob", "**/*.rs"]).command, Command::Grep { ignore_case: true, glob: Some(_), .. }));
+        assert_eq!(parse(&["list", "--output", "plain"]).format(), OutputFormat::Plain);
+        assert!(matches!(parse(&["completions", "bash"]).command, Command::Completions { shell: Shell::Bash }));
+
+        assert!(Cli::try_parse_from(["stowr", "store"]).is_err());
+        assert!(Cli::try_parse_from(["stowr", "list", "--json", "--output", "table"]).is_err());
+        assert!(Cli::try_parse_from(["stowr", "list", "--output", "yaml"]).is_err());
+    }
+}
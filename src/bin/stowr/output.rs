@@ -0,0 +1,121 @@
+//! 输出格式和退出码
+//!
+//! `table` 面向终端，按列对齐；`plain` 每行一条记录、字段以制表符分隔，没有表头，适合 `cut`、`awk` 处理；
+//! `json` 输出完整的结构化结果。错误在 JSON 模式下同样以 JSON 写到标准错误。
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use stowr_core::StowrError;
+
+/// 结果的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    #[value(help = "Aligned columns for terminals")]
+    Table,
+    #[value(help = "Tab-separated fields without a header")]
+    Plain,
+    #[value(help = "Structured JSON; errors are written to stderr as JSON")]
+    Json,
+}
+
+/// 操作成功
+pub const EXIT_OK: u8 = 0;
+/// 其他错误（读写失败、配置无效等）
+pub const EXIT_ERROR: u8 = 1;
+/// 命令行参数错误，与 clap 一致
+pub const EXIT_USAGE: u8 = 2;
+/// 部分文件处理失败，或校验发现损坏的条目
+pub const EXIT_PARTIAL: u8 = 3;
+/// 文件被内容过滤器拒绝
+pub const EXIT_REJECTED: u8 = 4;
+/// 条目被依赖或已固定，操作被拒绝
+pub const EXIT_CONFLICT: u8 = 5;
+/// 操作超时
+pub const EXIT_TIMEOUT: u8 = 6;
+/// 磁盘空间或内存不足
+pub const EXIT_RESOURCES: u8 = 7;
+/// 存储内容损坏（哈希不一致、解压输出超限）
+pub const EXIT_CORRUPTED: u8 = 8;
+
+/// 错误对应的类别名称和退出码
+pub fn classify(error: &anyhow::Error) -> (&'static str, u8) {
+    // 上下文信息会包装原始错误，沿错误链查找
+    let stowr_error = error.chain().find_map(|cause| cause.downcast_ref::<StowrError>());
+    match stowr_error {
+        Some(StowrError::Rejected { .. }) => ("rejected", EXIT_REJECTED),
+        Some(StowrError::HasDependents { .. }) => ("has_dependents", EXIT_CONFLICT),
+        Some(StowrError::Pinned { .. }) => ("pinned", EXIT_CONFLICT),
+        Some(StowrError::Timeout { .. }) => ("timeout", EXIT_TIMEOUT),
+        Some(StowrError::InsufficientSpace { .. }) => ("insufficient_space", EXIT_RESOURCES),
+        Some(StowrError::MemoryLimitExceeded { .. }) => ("memory_limit_exceeded", EXIT_RESOURCES),
+        Some(StowrError::ChecksumMismatch { .. }) => ("checksum_mismatch", EXIT_CORRUPTED),
+        Some(StowrError::DecompressionLimitExceeded { .. }) => ("decompression_limit_exceeded", EXIT_CORRUPTED),
+        Some(StowrError::InvalidShareToken) => ("invalid_share_token", EXIT_ERROR),
+        Some(StowrError::ShareExpired { .. }) => ("share_expired", EXIT_ERROR),
+        None => ("error", EXIT_ERROR),
+    }
+}
+
+/// 报告错误并返回退出码
+pub fn report_error(format: OutputFormat, error: &anyhow::Error) -> u8 {
+    let (kind, code) = classify(error);
+    if format == OutputFormat::Json {
+        let json = serde_json::json!({ "error": { "kind": kind, "code": code, "message": format!("{:#}", error) } });
+        eprintln!("{}", json);
+    } else {
+        eprintln!("Error: {:#}", error);
+    }
+    code
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value).context("Failed to serialize output")?;
+    println!("{}", json);
+    Ok(())
+}
+
+/// 输出表格，最后一列不补齐；`plain` 格式不输出表头，字段以制表符分隔
+pub fn print_rows(format: OutputFormat, header: &[&str], rows: &[Vec<String>]) {
+    if format == OutputFormat::Plain {
+        for row in rows {
+            println!("{}", row.join("\t"));
+        }
+        return;
+    }
+    let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+    for row in rows {
+        for (width, field) in widths.iter_mut().zip(row) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+    let line = |fields: &mut dyn Iterator<Item = &str>| {
+        let fields: Vec<&str> = fields.collect();
+        let last = fields.len().saturating_sub(1);
+        let padded: Vec<String> = fields.iter().enumerate()
+            .map(|(i, field)| if i == last { field.to_string() } else { format!("{:<width$}", field, width = widths[i]) })
+            .collect();
+        println!("{}", padded.join("  "));
+    };
+    line(&mut header.iter().copied());
+    for row in rows {
+        line(&mut row.iter().map(String::as_str));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_error_exit_codes() {
+        let pinned = anyhow::Error::from(StowrError::Pinned { path: PathBuf::from("a") })
+            .context("Failed to delete a");
+        assert_eq!(classify(&pinned), ("pinned", EXIT_CONFLICT));
+        let corrupted = anyhow::Error::from(StowrError::DecompressionLimitExceeded { limit: 1 });
+        assert_eq!(classify(&corrupted).1, EXIT_CORRUPTED);
+        assert_eq!(classify(&anyhow::anyhow!("other")), ("error", EXIT_ERROR));
+    }
+}
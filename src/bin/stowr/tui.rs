@@ -0,0 +1,367 @@
+//! `browse` 子命令的交互界面
+//!
+//! 列出所有已存储的文件，支持按路径筛选、预览内容、提取回原路径和删除。
+//! 界面状态与终端绘制分开，[`Browser`] 只处理按键和数据，便于测试。
+
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use stowr_core::{DeleteMode, FileEntry, StorageManager};
+
+/// 预览最多显示的字节数
+const PREVIEW_LIMIT: usize = 64 * 1024;
+/// 二进制内容预览的字节数
+const HEX_PREVIEW_BYTES: usize = 512;
+
+/// 当前的输入模式
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    /// 正在输入筛选条件
+    Search,
+    /// 等待确认删除选中的条目
+    ConfirmDelete,
+}
+
+/// 按键处理后需要对存储执行的操作
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    None,
+    Quit,
+    Preview,
+    Restore,
+    Delete,
+}
+
+/// 浏览器状态
+struct Browser {
+    entries: Vec<FileEntry>,
+    /// 符合筛选条件的条目在 `entries` 中的下标
+    visible: Vec<usize>,
+    table: TableState,
+    filter: String,
+    mode: Mode,
+    preview: Option<String>,
+    status: String,
+}
+
+impl Browser {
+    fn new(entries: Vec<FileEntry>) -> Self {
+        let mut browser = Self {
+            entries,
+            visible: Vec::new(),
+            table: TableState::default(),
+            filter: String::new(),
+            mode: Mode::Browse,
+            preview: None,
+            status: String::new(),
+        };
+        browser.apply_filter();
+        browser
+    }
+
+    /// 按筛选条件（路径中不区分大小写的子串）重新计算可见条目
+    fn apply_filter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.visible = self.entries.iter().enumerate()
+            .filter(|(_, entry)| entry.original_path.to_string_lossy().to_lowercase().contains(&filter))
+            .map(|(i, _)| i)
+            .collect();
+        let selected = self.table.selected().unwrap_or(0).min(self.visible.len().saturating_sub(1));
+        self.table.select(if self.visible.is_empty() { None } else { Some(selected) });
+        self.preview = None;
+    }
+
+    fn selected(&self) -> Option<&FileEntry> {
+        self.table.selected().map(|i| &self.entries[self.visible[i]])
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let current = self.table.selected().unwrap_or(0) as isize;
+        let last = self.visible.len() as isize - 1;
+        self.table.select(Some((current + offset).clamp(0, last) as usize));
+        self.preview = None;
+    }
+
+    /// 从列表中移除选中的条目（已提取或已删除）
+    fn remove_selected(&mut self) {
+        if let Some(i) = self.table.selected() {
+            self.entries.remove(self.visible[i]);
+            self.apply_filter();
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        match self.mode {
+            Mode::Search => {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Esc => self.mode = Mode::Browse,
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                        self.apply_filter();
+                    }
+                    KeyCode::Char(c) => {
+                        self.filter.push(c);
+                        self.apply_filter();
+                    }
+                    _ => {}
+                }
+                Action::None
+            }
+            Mode::ConfirmDelete => {
+                self.mode = Mode::Browse;
+                if key.code == KeyCode::Char('y') {
+                    Action::Delete
+                } else {
+                    self.status = "Delete cancelled".to_string();
+                    Action::None
+                }
+            }
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') => Action::Quit,
+                KeyCode::Esc if self.preview.is_some() => {
+                    self.preview = None;
+                    Action::None
+                }
+                KeyCode::Esc => Action::Quit,
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.move_selection(-1);
+                    Action::None
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.move_selection(1);
+                    Action::None
+                }
+                KeyCode::PageUp => {
+                    self.move_selection(-20);
+                    Action::None
+                }
+                KeyCode::PageDown => {
+                    self.move_selection(20);
+                    Action::None
+                }
+                KeyCode::Char('/') => {
+                    self.mode = Mode::Search;
+                    Action::None
+                }
+                KeyCode::Enter | KeyCode::Char('p') if self.selected().is_some() => Action::Preview,
+                KeyCode::Char('r') if self.selected().is_some() => Action::Restore,
+                KeyCode::Char('d') if self.selected().is_some() => {
+                    self.mode = Mode::ConfirmDelete;
+                    Action::None
+                }
+                _ => Action::None,
+            },
+        }
+    }
+
+    /// 执行按键对应的存储操作，结果写入状态栏
+    fn perform(&mut self, manager: &mut StorageManager, action: Action) {
+        let Some(path) = self.selected().map(|entry| entry.original_path.clone()) else {
+            return;
+        };
+        match action {
+            Action::Preview => match manager.read_file(&path) {
+                Ok(data) => self.preview = Some(render_preview(&data)),
+                Err(e) => self.status = format!("Failed to read {}: {:#}", path.display(), e),
+            },
+            Action::Restore => match manager.owe_file(&path) {
+                Ok(()) => {
+                    self.status = format!("Restored {}", path.display());
+                    self.remove_selected();
+                }
+                Err(e) => self.status = format!("Failed to restore {}: {:#}", path.display(), e),
+            },
+            Action::Delete => match manager.delete_file(&path, DeleteMode::Refuse) {
+                Ok(()) => {
+                    self.status = format!("Deleted {}", path.display());
+                    self.remove_selected();
+                }
+                Err(e) => self.status = format!("Failed to delete {}: {:#}", path.display(), e),
+            },
+            Action::None | Action::Quit => {}
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list_area, preview_area, status_area] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(if self.preview.is_some() { 14 } else { 0 }),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+
+        let rows = self.visible.iter().map(|&i| {
+            let entry = &self.entries[i];
+            Row::new(vec![
+                format_size(entry.file_size),
+                format_size(entry.compressed_size),
+                entry.original_path.display().to_string(),
+            ])
+        });
+        let title = format!(" stowr: {} of {} entries ", self.visible.len(), self.entries.len());
+        let table = Table::new(rows, [Constraint::Length(10), Constraint::Length(10), Constraint::Fill(1)])
+            .header(Row::new(vec!["SIZE", "STORED", "PATH"]).style(Style::new().add_modifier(Modifier::BOLD)))
+            .block(Block::new().borders(Borders::ALL).title(title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, list_area, &mut self.table);
+
+        if let Some(preview) = &self.preview {
+            let title = self.selected().map(|entry| format!(" {} ", entry.original_path.display())).unwrap_or_default();
+            let paragraph = Paragraph::new(preview.as_str())
+                .block(Block::new().borders(Borders::ALL).title(title))
+                .wrap(Wrap { trim: false });
+            frame.render_widget(paragraph, preview_area);
+        }
+
+        let status = match self.mode {
+            Mode::Search => format!("/{}", self.filter),
+            Mode::ConfirmDelete => match self.selected() {
+                Some(entry) => format!("Delete {} from storage? (y/N)", entry.original_path.display()),
+                None => String::new(),
+            },
+            Mode::Browse if !self.status.is_empty() => self.status.clone(),
+            Mode::Browse => "↑/↓ move  / search  Enter preview  r restore  d delete  q quit".to_string(),
+        };
+        frame.render_widget(Line::from(status), status_area);
+    }
+}
+
+/// 文本内容按 UTF-8 显示，其他内容显示十六进制
+fn render_preview(data: &[u8]) -> String {
+    let head = &data[..data.len().min(PREVIEW_LIMIT)];
+    match std::str::from_utf8(head) {
+        // 截断处可能落在多字节字符中间
+        Ok(text) => text.to_string(),
+        Err(e) if e.error_len().is_none() => String::from_utf8_lossy(&head[..e.valid_up_to()]).into_owned(),
+        Err(_) => head[..head.len().min(HEX_PREVIEW_BYTES)]
+            .chunks(16)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("{:08x}  {}", i * 16, hex.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// 运行交互界面，直到用户退出
+pub fn run(manager: &mut StorageManager, filter: Option<&str>) -> Result<()> {
+    let mut browser = Browser::new(manager.list_files()?);
+    if let Some(filter) = filter {
+        browser.filter = filter.to_string();
+        browser.apply_filter();
+    }
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut browser, manager);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, browser: &mut Browser, manager: &mut StorageManager) -> Result<()> {
+    loop {
+        terminal.draw(|frame| browser.draw(frame))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match browser.handle_key(key) {
+            Action::Quit => return Ok(()),
+            Action::None => {}
+            action => {
+                browser.status.clear();
+                browser.perform(manager, action);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+    use std::path::{Path, PathBuf};
+    use stowr_core::CompressionAlgorithm;
+
+    fn visible_paths(browser: &Browser) -> Vec<&Path> {
+        browser.visible.iter().map(|&i| browser.entries[i].original_path.as_path()).collect()
+    }
+
+    fn entry(path: &str) -> FileEntry {
+        FileEntry::new(String::new(), PathBuf::from(path), PathBuf::new(), 2048, 100, CompressionAlgorithm::Gzip)
+    }
+
+    fn press(browser: &mut Browser, code: KeyCode) -> Action {
+        browser.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_browser_keys() {
+        let mut browser = Browser::new(vec![entry("docs/a.txt"), entry("src/main.rs"), entry("docs/B.md")]);
+        assert_eq!(press(&mut browser, KeyCode::Down), Action::None);
+        assert_eq!(browser.selected().unwrap().original_path, Path::new("src/main.rs"));
+        press(&mut browser, KeyCode::Down);
+        press(&mut browser, KeyCode::Down);
+        assert_eq!(browser.table.selected(), Some(2));
+
+        // 筛选不区分大小写，选中项收缩到可见范围内
+        press(&mut browser, KeyCode::Char('/'));
+        for c in "DOCS".chars() {
+            press(&mut browser, KeyCode::Char(c));
+        }
+        assert_eq!(press(&mut browser, KeyCode::Char('q')), Action::None);
+        assert!(visible_paths(&browser).is_empty());
+        press(&mut browser, KeyCode::Backspace);
+        assert_eq!(visible_paths(&browser), [Path::new("docs/a.txt"), Path::new("docs/B.md")]);
+        assert_eq!(browser.table.selected(), Some(0));
+        press(&mut browser, KeyCode::Esc);
+        press(&mut browser, KeyCode::Down);
+
+        // 删除需要确认
+        press(&mut browser, KeyCode::Char('d'));
+        assert_eq!(press(&mut browser, KeyCode::Char('n')), Action::None);
+        press(&mut browser, KeyCode::Char('d'));
+        assert_eq!(press(&mut browser, KeyCode::Char('y')), Action::Delete);
+        browser.remove_selected();
+        assert_eq!(visible_paths(&browser), [Path::new("docs/a.txt")]);
+        assert_eq!(press(&mut browser, KeyCode::Enter), Action::Preview);
+        assert_eq!(press(&mut browser, KeyCode::Char('q')), Action::Quit);
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 10)).unwrap();
+        terminal.draw(|frame| browser.draw(frame)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("1 of 2 entries"));
+        assert!(screen.contains("2.0 KiB"));
+
+        assert_eq!(render_preview(b"hello"), "hello");
+        assert!(render_preview(&[0xff, 0x00, 0x01]).starts_with("00000000  ff 00 01"));
+    }
+}
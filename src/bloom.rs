@@ -0,0 +1,177 @@
+use anyhow::{Context, Result, anyhow};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+
+use crate::fsutil;
+
+/// 持久化文件头部魔数
+const BLOOM_MAGIC: &[u8; 8] = b"STWRBLM2";
+/// 头部长度：魔数 + 哈希函数个数(u32) + 位数(u64) + 设计容量(u64) + 已插入数(u64) + 标记(u64)
+const HEADER_LEN: usize = 8 + 4 + 8 + 8 + 8 + 8;
+
+/// 布隆过滤器
+///
+/// 用于在查询索引前快速判断某个内容哈希是否一定不存在：
+/// `may_contain` 返回 false 时该值一定没有插入过，返回 true 时可能存在（有少量误报）。
+/// 不支持删除，删除条目后留下的误报只会多一次索引查询。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: u64,
+    inserted: u64,
+    stamp: u64,
+}
+
+impl BloomFilter {
+    /// 按预计元素数量和期望误报率创建过滤器
+    pub fn with_capacity(capacity: u64, false_positive_rate: f64) -> Self {
+        let capacity = capacity.max(1);
+        let rate = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = ((-(capacity as f64) * rate.ln() / (ln2 * ln2)).ceil() as u64).max(64);
+        let num_hashes = ((num_bits as f64 / capacity as f64) * ln2).round().clamp(1.0, 32.0) as u32;
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            capacity,
+            inserted: 0,
+            stamp: 0,
+        }
+    }
+
+    pub fn insert(&mut self, item: &str) {
+        for bit in Self::bit_positions(self.num_bits, self.num_hashes, item) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.inserted += 1;
+    }
+
+    /// 值可能已插入时返回 true；返回 false 时一定未插入
+    pub fn may_contain(&self, item: &str) -> bool {
+        Self::bit_positions(self.num_bits, self.num_hashes, item)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// 已插入的元素数（重复插入也会计数）
+    pub fn len(&self) -> u64 {
+        self.inserted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inserted == 0
+    }
+
+    /// 插入数量是否已超过设计容量，超过后误报率会明显上升，应按更大容量重建
+    pub fn is_saturated(&self) -> bool {
+        self.inserted > self.capacity
+    }
+
+    /// 设计容量
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// 调用方随过滤器保存的标记（如生成过滤器时的索引条目数），加载后用来判断过滤器是否已过期
+    pub fn stamp(&self) -> u64 {
+        self.stamp
+    }
+
+    pub fn set_stamp(&mut self, stamp: u64) {
+        self.stamp = stamp;
+    }
+
+    /// 双重哈希：由 SHA256 的前 16 字节得到两个基础哈希值
+    fn bit_positions(num_bits: u64, num_hashes: u32, item: &str) -> impl Iterator<Item = u64> {
+        let digest = Sha256::digest(item.as_bytes());
+        let h1 = u64::from_le_bytes(digest[0..8].try_into().expect("digest has 32 bytes"));
+        let h2 = u64::from_le_bytes(digest[8..16].try_into().expect("digest has 32 bytes")) | 1;
+        (0..num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    /// 保存到文件
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut data = Vec::with_capacity(HEADER_LEN + self.bits.len() * 8);
+        data.extend_from_slice(BLOOM_MAGIC);
+        data.extend_from_slice(&self.num_hashes.to_le_bytes());
+        data.extend_from_slice(&self.num_bits.to_le_bytes());
+        data.extend_from_slice(&self.capacity.to_le_bytes());
+        data.extend_from_slice(&self.inserted.to_le_bytes());
+        data.extend_from_slice(&self.stamp.to_le_bytes());
+        for word in &self.bits {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        fsutil::atomic_write(path, &data, None, false)
+            .context("Failed to write bloom filter")
+    }
+
+    /// 从文件加载
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)
+            .context("Failed to read bloom filter")?;
+        if data.len() < HEADER_LEN || !data.starts_with(BLOOM_MAGIC) {
+            return Err(anyhow!("Not a bloom filter file: {}", path.display()));
+        }
+
+        let read_u64 = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().expect("header length checked"));
+        let num_hashes = u32::from_le_bytes(data[8..12].try_into()?);
+        let num_bits = read_u64(12);
+        let capacity = read_u64(20);
+        let inserted = read_u64(28);
+        let stamp = read_u64(36);
+
+        let words = num_bits.div_ceil(64) as usize;
+        if num_bits == 0 || num_hashes == 0 || data.len() != HEADER_LEN + words * 8 {
+            return Err(anyhow!("Corrupted bloom filter file: {}", path.display()));
+        }
+
+        let bits = data[HEADER_LEN..]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("chunk has 8 bytes")))
+            .collect();
+
+        Ok(Self { bits, num_bits, num_hashes, capacity, inserted, stamp })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_no_false_negatives_and_low_false_positive_rate() {
+        let mut filter = BloomFilter::with_capacity(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(&format!("hash-{}", i));
+        }
+        assert!((0..1000).all(|i| filter.may_contain(&format!("hash-{}", i))));
+
+        let false_positives = (0..10000)
+            .filter(|i| filter.may_contain(&format!("other-{}", i)))
+            .count();
+        assert!(false_positives < 300, "too many false positives: {}", false_positives);
+        assert!(!filter.is_saturated());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("hashes.bloom");
+        let mut filter = BloomFilter::with_capacity(10, 0.01);
+        filter.insert("abc");
+        filter.set_stamp(42);
+        filter.save(&path).unwrap();
+
+        let loaded = BloomFilter::load(&path).unwrap();
+        assert_eq!(loaded, filter);
+        assert!(loaded.may_contain("abc"));
+        assert_eq!(loaded.stamp(), 42);
+
+        fs::write(&path, b"STWRBLM2 truncated").unwrap();
+        assert!(BloomFilter::load(&path).is_err());
+    }
+}
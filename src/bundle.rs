@@ -0,0 +1,224 @@
+//! 可移植的多条目包（`.stowrbundle`）
+//!
+//! 文件格式：魔数(8) + 元数据长度(u32 LE) + 元数据 JSON + 各内容块拼接而成的数据区。
+//! 每个内容块单独压缩，可以是完整内容，也可以是相对包内另一内容块的差分；
+//! 差分条目的基础内容和引用条目指向的内容都会一并写入包中，因此包不依赖原仓库。
+//! 内容相同的条目共用一个内容块。
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::CompressionAlgorithm;
+use crate::fsutil;
+
+/// 多条目包文件头部魔数
+const BUNDLE_MAGIC: &[u8; 8] = b"STWRBDL1";
+/// 当前多条目包格式版本
+pub const FORMAT_VERSION: u32 = 1;
+/// 多条目包文件扩展名
+pub const BUNDLE_EXTENSION: &str = "stowrbundle";
+
+/// 内容块的存放方式
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BlobKind {
+    /// 完整内容
+    Full,
+    /// 相对 `base` 号内容块（必须是完整内容）的差分
+    Delta { base: usize },
+}
+
+/// 包中的一个内容块
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleBlob {
+    #[serde(flatten)]
+    pub kind: BlobKind,
+    /// 在数据区中的偏移
+    pub offset: u64,
+    /// 压缩后的长度
+    pub length: u64,
+    /// 还原后内容的大小
+    pub size: u64,
+    /// 还原后内容的 SHA256
+    pub hash: String,
+}
+
+/// 包中的一个条目
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleItem {
+    #[serde(with = "crate::paths::serde_path")]
+    pub original_path: PathBuf,
+    /// 条目内容所在的内容块序号
+    pub blob: usize,
+    /// 条目在原仓库中的创建时间
+    pub created_at: String,
+    /// 条目的备注
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 条目所属的集合
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+/// 多条目包元数据
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleMetadata {
+    pub format_version: u32,
+    /// 内容块使用的压缩算法
+    pub compression_algorithm: CompressionAlgorithm,
+    pub blobs: Vec<BundleBlob>,
+    pub items: Vec<BundleItem>,
+}
+
+impl BundleMetadata {
+    /// 检查内容块和条目之间的引用是否有效
+    pub fn validate(&self, data_len: u64) -> Result<()> {
+        for (index, blob) in self.blobs.iter().enumerate() {
+            if blob.offset.checked_add(blob.length).is_none_or(|end| end > data_len) {
+                return Err(anyhow!("Bundle blob {} lies outside the data section", index));
+            }
+            if let BlobKind::Delta { base } = blob.kind {
+                if !matches!(self.blobs.get(base), Some(BundleBlob { kind: BlobKind::Full, .. })) {
+                    return Err(anyhow!("Bundle blob {} has an invalid delta base: {}", index, base));
+                }
+            }
+        }
+        if let Some(item) = self.items.iter().find(|item| item.blob >= self.blobs.len()) {
+            return Err(anyhow!("Bundle item refers to a missing blob: {}", item.original_path.display()));
+        }
+        Ok(())
+    }
+}
+
+/// 逐块收集内容，相同哈希的内容只保留一份
+#[derive(Default)]
+pub(crate) struct BundleBuilder {
+    blobs: Vec<BundleBlob>,
+    data: Vec<u8>,
+    by_hash: std::collections::HashMap<String, usize>,
+}
+
+impl BundleBuilder {
+    /// 查找已加入的内容块
+    pub fn find(&self, hash: &str) -> Option<usize> {
+        self.by_hash.get(hash).copied()
+    }
+
+    /// 查找已加入的完整内容块，可以作为差分基础
+    pub fn find_full(&self, hash: &str) -> Option<usize> {
+        self.find(hash).filter(|&index| self.blobs[index].kind == BlobKind::Full)
+    }
+
+    /// 加入压缩后的内容块，返回其序号
+    pub fn push(&mut self, kind: BlobKind, compressed: &[u8], size: u64, hash: String) -> usize {
+        self.blobs.push(BundleBlob {
+            kind,
+            offset: self.data.len() as u64,
+            length: compressed.len() as u64,
+            size,
+            hash: hash.clone(),
+        });
+        self.data.extend_from_slice(compressed);
+        self.by_hash.insert(hash, self.blobs.len() - 1);
+        self.blobs.len() - 1
+    }
+
+    /// 生成元数据和数据区
+    pub fn finish(self, compression_algorithm: CompressionAlgorithm, items: Vec<BundleItem>) -> (BundleMetadata, Vec<u8>) {
+        let metadata = BundleMetadata {
+            format_version: FORMAT_VERSION,
+            compression_algorithm,
+            blobs: self.blobs,
+            items,
+        };
+        (metadata, self.data)
+    }
+}
+
+/// 写入多条目包
+pub fn write_bundle(dest: &Path, metadata: &BundleMetadata, data: &[u8]) -> Result<()> {
+    let metadata_json = serde_json::to_vec(metadata)
+        .context("Failed to serialize bundle metadata")?;
+    let metadata_len = u32::try_from(metadata_json.len())
+        .map_err(|_| anyhow!("Bundle metadata too large"))?;
+
+    let mut bundle = Vec::with_capacity(BUNDLE_MAGIC.len() + 4 + metadata_json.len() + data.len());
+    bundle.extend_from_slice(BUNDLE_MAGIC);
+    bundle.extend_from_slice(&metadata_len.to_le_bytes());
+    bundle.extend_from_slice(&metadata_json);
+    bundle.extend_from_slice(data);
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create bundle directory")?;
+    }
+    fsutil::atomic_write(dest, &bundle, None, false)
+        .context("Failed to write bundle")
+}
+
+/// 读取多条目包，返回元数据和数据区
+pub fn read_bundle(src: &Path) -> Result<(BundleMetadata, Vec<u8>)> {
+    let bundle = fs::read(src)
+        .with_context(|| format!("Failed to read bundle: {}", src.display()))?;
+
+    let header_len = BUNDLE_MAGIC.len() + 4;
+    if bundle.len() < header_len || !bundle.starts_with(BUNDLE_MAGIC) {
+        return Err(anyhow!("Not a stowr bundle: {}", src.display()));
+    }
+
+    let metadata_len = u32::from_le_bytes(bundle[BUNDLE_MAGIC.len()..header_len].try_into()?) as usize;
+    let metadata_end = header_len.checked_add(metadata_len)
+        .filter(|end| *end <= bundle.len())
+        .ok_or_else(|| anyhow!("Truncated stowr bundle: {}", src.display()))?;
+
+    let metadata: BundleMetadata = serde_json::from_slice(&bundle[header_len..metadata_end])
+        .context("Failed to parse bundle metadata")?;
+    if metadata.format_version > FORMAT_VERSION {
+        return Err(anyhow!("Unsupported bundle format version: {}", metadata.format_version));
+    }
+
+    let data = bundle[metadata_end..].to_vec();
+    metadata.validate(data.len() as u64)?;
+    Ok((metadata, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bundle_round_trip_and_validation() {
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("set.stowrbundle");
+        let blob = |kind, offset| BundleBlob { kind, offset, length: 4, size: 4, hash: "abc".to_string() };
+        let mut metadata = BundleMetadata {
+            format_version: FORMAT_VERSION,
+            compression_algorithm: CompressionAlgorithm::Lz4,
+            blobs: vec![blob(BlobKind::Full, 0), blob(BlobKind::Delta { base: 0 }, 4)],
+            items: vec![BundleItem {
+                original_path: PathBuf::from("docs/v2.md"),
+                blob: 1,
+                created_at: "2024-01-01T00:00:00Z".to_string(),
+                description: None,
+                collection: Some("docs".to_string()),
+            }],
+        };
+
+        write_bundle(&dest, &metadata, b"basedata").unwrap();
+        let (read_metadata, data) = read_bundle(&dest).unwrap();
+        assert_eq!(read_metadata, metadata);
+        assert_eq!(data, b"basedata");
+
+        // 数据区越界、差分基础不是完整内容都视为损坏
+        write_bundle(&dest, &metadata, b"short").unwrap();
+        assert!(read_bundle(&dest).is_err());
+        metadata.blobs[0].kind = BlobKind::Delta { base: 1 };
+        assert!(metadata.validate(8).is_err());
+
+        fs::write(&dest, b"not a bundle").unwrap();
+        assert!(read_bundle(&dest).is_err());
+    }
+}
@@ -0,0 +1,634 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use crate::config::CompressionAlgorithm;
+use crate::error::StowrError;
+
+/// 压缩器接口
+///
+/// 每个压缩器以算法标识（`id`）注册到 [`CompressorRegistry`]，
+/// 该标识会记录在文件条目中，提取时据此找回对应的解压实现。
+/// 下游 crate 可以实现此 trait 注册自定义编解码器，
+/// 条目中使用 `CompressionAlgorithm::Custom(id)` 引用它们。
+pub trait Compressor: Send + Sync {
+    /// 算法标识，与 `CompressionAlgorithm::to_string()` 一致
+    fn id(&self) -> &str;
+
+    /// 存储文件使用的扩展名
+    fn file_extension(&self) -> &str;
+
+    /// 压缩数据
+    fn compress(&self, data: &[u8], level: u32) -> Result<Vec<u8>>;
+
+    /// 解压数据
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// 解压数据，输出超过 `max_output` 字节时返回 `StowrError::DecompressionLimitExceeded`
+    ///
+    /// 默认实现在解压完成后检查大小；内置压缩器在解压过程中检查，不会先分配超限的内存
+    fn decompress_limited(&self, data: &[u8], max_output: u64) -> Result<Vec<u8>> {
+        let content = self.decompress(data)?;
+        check_output(content.len() as u64, max_output)?;
+        Ok(content)
+    }
+
+    /// 解压 `input` 中的数据并写入 `output`，返回解压后的字节数，超过 `max_output` 时返回
+    /// `StowrError::DecompressionLimitExceeded`（此时 `output` 中可能已有部分内容）
+    ///
+    /// 默认实现读入全部数据后调用 `decompress_limited`；gzip 和 zstd 边解压边写入，不在内存中保留完整内容
+    fn decompress_to(&self, input: &mut dyn Read, output: &mut dyn Write, max_output: u64) -> Result<u64> {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)
+            .context("Failed to read compressed data")?;
+        let content = self.decompress_limited(&data, max_output)?;
+        output.write_all(&content)
+            .context("Failed to write decompressed data")?;
+        Ok(content.len() as u64)
+    }
+
+    /// 压缩 `input_len` 字节数据预计占用的内存（字节），包括输入和输出缓冲区
+    fn compress_memory_estimate(&self, _level: u32, input_len: u64) -> u64 {
+        input_len.saturating_mul(2)
+    }
+
+    /// 解压出 `output_len` 字节数据预计占用的内存（字节）
+    fn decompress_memory_estimate(&self, output_len: u64) -> u64 {
+        output_len.saturating_mul(2)
+    }
+}
+
+/// 解压输出大小的限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecompressionLimits {
+    /// 输出大小上限（字节），0 表示不限制
+    pub max_bytes: u64,
+    /// 输出与输入大小之比的上限，0 表示不限制
+    pub max_ratio: u64,
+}
+
+impl DecompressionLimits {
+    /// 解压 `input_len` 字节数据时允许的输出大小，两项限制取较小者
+    pub fn max_output(&self, input_len: u64) -> u64 {
+        let by_size = if self.max_bytes == 0 { u64::MAX } else { self.max_bytes };
+        let by_ratio = if self.max_ratio == 0 {
+            u64::MAX
+        } else {
+            input_len.max(1).saturating_mul(self.max_ratio)
+        };
+        by_size.min(by_ratio)
+    }
+}
+
+fn check_output(len: u64, max_output: u64) -> Result<()> {
+    if len > max_output {
+        return Err(StowrError::DecompressionLimitExceeded { limit: max_output }.into());
+    }
+    Ok(())
+}
+
+/// 从解码器读取最多 `max_output` 字节，多出的一个字节用于判断是否超限
+fn read_limited(reader: impl Read, max_output: u64) -> std::io::Result<Result<Vec<u8>>> {
+    let mut content = Vec::new();
+    reader.take(max_output.saturating_add(1)).read_to_end(&mut content)?;
+    Ok(check_output(content.len() as u64, max_output).map(|_| content))
+}
+
+/// 内存上限内可用的最高压缩级别，不超过 `level`
+///
+/// 高级别的窗口和匹配表占用大量内存（zstd 22 约需数百 MB），超过 `limit` 时逐级降低；
+/// 降到 1 仍然超限时返回原级别，由调用方报告 `StowrError::MemoryLimitExceeded`。`limit` 为 0 表示不限制
+pub(crate) fn level_within_memory(compressor: &dyn Compressor, level: u32, input_len: u64, limit: u64) -> u32 {
+    if limit == 0 {
+        return level;
+    }
+    (1..=level).rev()
+        .find(|&candidate| compressor.compress_memory_estimate(candidate, input_len) <= limit)
+        .unwrap_or(level)
+}
+
+/// 从解码器复制最多 `max_output` 字节到 `output`，多出的一个字节用于判断是否超限
+fn copy_limited(reader: impl Read, output: &mut dyn Write, max_output: u64) -> std::io::Result<Result<u64>> {
+    let copied = std::io::copy(&mut reader.take(max_output.saturating_add(1)), output)?;
+    Ok(check_output(copied, max_output).map(|_| copied))
+}
+
+/// gzip 压缩器
+#[derive(Debug, Default)]
+pub struct GzipCompressor;
+
+impl Compressor for GzipCompressor {
+    fn id(&self) -> &str {
+        "gzip"
+    }
+
+    fn file_extension(&self) -> &str {
+        "gz"
+    }
+
+    fn compress(&self, data: &[u8], level: u32) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
+        encoder.write_all(data)
+            .context("Failed to write compressed data")?;
+        encoder.finish()
+            .context("Failed to finish compression")
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut decoder = GzDecoder::new(data);
+        let mut content = Vec::new();
+        decoder.read_to_end(&mut content)
+            .context("Failed to decompress gzip data")?;
+        Ok(content)
+    }
+
+    fn decompress_limited(&self, data: &[u8], max_output: u64) -> Result<Vec<u8>> {
+        read_limited(GzDecoder::new(data), max_output)
+            .context("Failed to decompress gzip data")?
+    }
+
+    fn decompress_to(&self, input: &mut dyn Read, output: &mut dyn Write, max_output: u64) -> Result<u64> {
+        copy_limited(GzDecoder::new(input), output, max_output)
+            .context("Failed to decompress gzip data")?
+    }
+}
+
+/// zstd 压缩器
+#[derive(Debug, Default)]
+pub struct ZstdCompressor;
+
+impl Compressor for ZstdCompressor {
+    fn id(&self) -> &str {
+        "zstd"
+    }
+
+    fn file_extension(&self) -> &str {
+        "zst"
+    }
+
+    fn compress(&self, data: &[u8], level: u32) -> Result<Vec<u8>> {
+        zstd::encode_all(data, level as i32)
+            .context("Failed to compress with zstd")
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        zstd::decode_all(data)
+            .context("Failed to decompress with zstd")
+    }
+
+    fn decompress_limited(&self, data: &[u8], max_output: u64) -> Result<Vec<u8>> {
+        let decoder = zstd::stream::read::Decoder::new(data)
+            .context("Failed to decompress with zstd")?;
+        read_limited(decoder, max_output)
+            .context("Failed to decompress with zstd")?
+    }
+
+    fn decompress_to(&self, input: &mut dyn Read, output: &mut dyn Write, max_output: u64) -> Result<u64> {
+        let decoder = zstd::stream::read::Decoder::new(input)
+            .context("Failed to decompress with zstd")?;
+        copy_limited(decoder, output, max_output)
+            .context("Failed to decompress with zstd")?
+    }
+
+    fn compress_memory_estimate(&self, level: u32, input_len: u64) -> u64 {
+        // 大输入时各级别的 (windowLog, chainLog, hashLog)，取自 zstd 默认参数表
+        const PARAMS: [(u32, u32, u32); 22] = [
+            (19, 12, 13), (19, 13, 14), (21, 16, 17), (21, 18, 18), (21, 18, 19),
+            (21, 19, 20), (21, 19, 20), (21, 19, 20), (21, 19, 21), (22, 20, 22),
+            (22, 21, 22), (22, 21, 22), (22, 22, 22), (22, 22, 23), (22, 23, 23),
+            (22, 22, 22), (23, 23, 22), (23, 23, 22), (23, 24, 22), (25, 25, 23),
+            (26, 26, 24), (27, 27, 25),
+        ];
+        let (window_log, chain_log, hash_log) = PARAMS[(level.clamp(1, 22) - 1) as usize];
+
+        // 输入较小时 zstd 会缩小窗口和表
+        let input_log = 64 - input_len.max(1024).saturating_sub(1).leading_zeros();
+        let window_log = window_log.min(input_log);
+        let table_log_cap = window_log + 1;
+        let tables = (4u64 << chain_log.min(table_log_cap)) + (4u64 << hash_log.min(table_log_cap));
+
+        (1u64 << window_log) + tables + input_len.saturating_mul(2)
+    }
+
+    fn decompress_memory_estimate(&self, output_len: u64) -> u64 {
+        // 解码窗口最大为 128 MiB（windowLog 27）
+        output_len.saturating_mul(2) + output_len.min(1 << 27)
+    }
+}
+
+/// lz4 压缩器（不使用压缩级别）
+#[derive(Debug, Default)]
+pub struct Lz4Compressor;
+
+impl Compressor for Lz4Compressor {
+    fn id(&self) -> &str {
+        "lz4"
+    }
+
+    fn file_extension(&self) -> &str {
+        "lz4"
+    }
+
+    fn compress(&self, data: &[u8], _level: u32) -> Result<Vec<u8>> {
+        Ok(lz4_flex::compress_prepend_size(data))
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        lz4_flex::decompress_size_prepended(data)
+            .context("Failed to decompress with lz4")
+    }
+
+    fn decompress_limited(&self, data: &[u8], max_output: u64) -> Result<Vec<u8>> {
+        // 头部记录的输出大小决定了解压时分配的内存，先检查
+        if let Some(header) = data.get(..4) {
+            let declared = u32::from_le_bytes(header.try_into().expect("slice has 4 bytes"));
+            check_output(declared as u64, max_output)?;
+        }
+        self.decompress(data)
+    }
+}
+
+/// 估算压缩大小时每个样本的长度
+const SAMPLE_LEN: u64 = 64 * 1024;
+/// 估算压缩大小时最多取的样本数，不超过两者之积的数据整体压缩
+const SAMPLE_COUNT: u64 = 16;
+
+/// 压缩大小的估算结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SizeEstimate {
+    /// 原始大小（字节）
+    pub original_size: u64,
+    /// 估算的压缩后大小（字节）
+    pub estimated_size: u64,
+    /// 实际压缩的样本大小（字节），等于原始大小时结果是精确值
+    pub sampled_bytes: u64,
+}
+
+impl SizeEstimate {
+    /// 压缩后与原始大小之比，空数据为 1.0
+    pub fn ratio(&self) -> f64 {
+        if self.original_size == 0 {
+            1.0
+        } else {
+            self.estimated_size as f64 / self.original_size as f64
+        }
+    }
+
+    /// 合并多个文件的估算结果
+    pub fn add(&mut self, other: &SizeEstimate) {
+        self.original_size += other.original_size;
+        self.estimated_size += other.estimated_size;
+        self.sampled_bytes += other.sampled_bytes;
+    }
+
+    /// 按样本的压缩率推算整体大小
+    fn from_samples(original_size: u64, sampled_bytes: u64, compressed_bytes: u64) -> Self {
+        let estimated_size = if sampled_bytes == 0 || sampled_bytes == original_size {
+            compressed_bytes
+        } else {
+            (compressed_bytes as u128 * original_size as u128 / sampled_bytes as u128) as u64
+        };
+        Self { original_size, estimated_size, sampled_bytes }
+    }
+}
+
+/// 按均匀分布的样本估算数据压缩后的大小
+///
+/// 数据不超过 1MB 时整体压缩，结果是精确值；否则取 16 个均匀分布的 64KB 样本分别压缩，
+/// 按样本的压缩率推算。样本各自压缩，长距离重复较多的数据估算值偏大。
+pub fn estimate_compressed_size(compressor: &dyn Compressor, level: u32, data: &[u8]) -> Result<SizeEstimate> {
+    let len = data.len() as u64;
+    let mut sampled = 0;
+    let mut compressed = 0;
+    for (offset, sample_len) in sample_ranges(len) {
+        let sample = &data[offset as usize..(offset + sample_len) as usize];
+        compressed += compressor.compress(sample, level)?.len() as u64;
+        sampled += sample_len;
+    }
+    Ok(SizeEstimate::from_samples(len, sampled, compressed))
+}
+
+/// 与 [`estimate_compressed_size`] 相同，但只读取样本所在的部分，适合估算大文件
+pub fn estimate_compressed_size_from<R: Read + Seek>(
+    compressor: &dyn Compressor,
+    level: u32,
+    reader: &mut R,
+    len: u64,
+) -> Result<SizeEstimate> {
+    let mut sampled = 0;
+    let mut compressed = 0;
+    let mut sample = Vec::new();
+    for (offset, sample_len) in sample_ranges(len) {
+        reader.seek(SeekFrom::Start(offset)).context("Failed to seek to sample")?;
+        sample.clear();
+        reader.by_ref().take(sample_len).read_to_end(&mut sample).context("Failed to read sample")?;
+        compressed += compressor.compress(&sample, level)?.len() as u64;
+        sampled += sample.len() as u64;
+    }
+    Ok(SizeEstimate::from_samples(len, sampled, compressed))
+}
+
+/// 样本的 (偏移, 长度)
+fn sample_ranges(len: u64) -> Vec<(u64, u64)> {
+    if len <= SAMPLE_LEN * SAMPLE_COUNT {
+        return vec![(0, len)];
+    }
+    let stride = (len - SAMPLE_LEN) / (SAMPLE_COUNT - 1);
+    (0..SAMPLE_COUNT).map(|i| (i * stride, SAMPLE_LEN)).collect()
+}
+
+/// 压缩器注册表
+///
+/// 以算法标识为键保存所有可用的压缩器，默认包含 gzip、zstd、lz4。
+#[derive(Clone)]
+pub struct CompressorRegistry {
+    compressors: HashMap<String, Arc<dyn Compressor>>,
+}
+
+impl CompressorRegistry {
+    /// 创建包含内置压缩器的注册表
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Arc::new(GzipCompressor));
+        registry.register(Arc::new(ZstdCompressor));
+        registry.register(Arc::new(Lz4Compressor));
+        registry
+    }
+
+    /// 创建空注册表
+    pub fn empty() -> Self {
+        Self {
+            compressors: HashMap::new(),
+        }
+    }
+
+    /// 注册压缩器，已存在的同名压缩器会被替换
+    pub fn register(&mut self, compressor: Arc<dyn Compressor>) {
+        self.compressors.insert(compressor.id().to_string(), compressor);
+    }
+
+    /// 根据算法获取压缩器
+    pub fn get(&self, algorithm: &CompressionAlgorithm) -> Result<Arc<dyn Compressor>> {
+        let id = algorithm.to_string();
+        self.compressors.get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("No compressor registered for algorithm: {}", id))
+    }
+
+    /// 是否已注册指定算法
+    pub fn contains(&self, algorithm: &CompressionAlgorithm) -> bool {
+        self.compressors.contains_key(&algorithm.to_string())
+    }
+
+    /// 所有已注册的算法标识
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.compressors.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// 调整压缩级别时忽略的小样本上限（字节），小数据的耗时主要是固定开销
+const TUNER_MIN_SAMPLE: usize = 256 * 1024;
+
+/// 按吞吐量目标动态调整 zstd 压缩级别
+///
+/// 每次压缩后记录实际速度（与上一次测量平滑），低于目标时降低一级，超过目标 1.5 倍时提高一级，
+/// 级别始终在 `[min, max]` 之间。级别变化后重新测量，避免沿用上一级别的速度。
+#[derive(Debug, Clone)]
+pub struct LevelTuner {
+    target_bytes_per_sec: f64,
+    min: u32,
+    max: u32,
+    level: u32,
+    throughput: Option<f64>,
+}
+
+impl LevelTuner {
+    /// `target_mbps` 为每秒压缩的输入数据量（MB，10^6 字节），初始级别会被限制在区间内
+    pub fn new(target_mbps: u32, min: u32, max: u32, initial: u32) -> Self {
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+        Self {
+            target_bytes_per_sec: target_mbps as f64 * 1_000_000.0,
+            min,
+            max,
+            level: initial.clamp(min, max),
+            throughput: None,
+        }
+    }
+
+    /// 根据配置创建，只有 zstd 且设置了 `target_throughput_mbps` 时返回 Some
+    pub fn from_config(config: &crate::config::Config) -> Option<Self> {
+        (config.compression_algorithm == CompressionAlgorithm::Zstd && config.target_throughput_mbps > 0).then(|| {
+            Self::new(
+                config.target_throughput_mbps,
+                config.compression_level_min,
+                config.compression_level_max,
+                config.compression_level,
+            )
+        })
+    }
+
+    /// 当前压缩级别
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// 记录一次压缩的输入大小和耗时，必要时调整级别
+    pub fn record(&mut self, bytes: usize, elapsed: std::time::Duration) {
+        if bytes < TUNER_MIN_SAMPLE {
+            return;
+        }
+        let sample = bytes as f64 / elapsed.as_secs_f64().max(1e-9);
+        let throughput = match self.throughput {
+            Some(previous) => (previous + sample) / 2.0,
+            None => sample,
+        };
+        self.throughput = Some(throughput);
+
+        if throughput < self.target_bytes_per_sec && self.level > self.min {
+            self.level -= 1;
+            self.throughput = None;
+        } else if throughput > self.target_bytes_per_sec * 1.5 && self.level < self.max {
+            self.level += 1;
+            self.throughput = None;
+        }
+    }
+}
+
+impl Default for CompressorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for CompressorRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressorRegistry")
+            .field("compressors", &self.ids())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    struct ReverseCompressor;
+
+    impl Compressor for ReverseCompressor {
+        fn id(&self) -> &str {
+            "reverse"
+        }
+
+        fn file_extension(&self) -> &str {
+            "rev"
+        }
+
+        fn compress(&self, data: &[u8], _level: u32) -> Result<Vec<u8>> {
+            Ok(data.iter().rev().copied().collect())
+        }
+
+        fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+            Ok(data.iter().rev().copied().collect())
+        }
+    }
+
+    #[test]
+    fn test_builtin_round_trip() {
+        let registry = CompressorRegistry::new();
+        let data = b"Hello, Stowr! Hello, Stowr! Hello, Stowr!";
+
+        for algorithm in [CompressionAlgorithm::Gzip, CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4] {
+            let compressor = registry.get(&algorithm).unwrap();
+            let compressed = compressor.compress(data, algorithm.default_level()).unwrap();
+            assert_eq!(compressor.decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_zstd_memory_estimate_grows_with_level() {
+        let zstd = ZstdCompressor;
+        let input_len = 1 << 30;
+
+        let low = zstd.compress_memory_estimate(1, input_len);
+        let high = zstd.compress_memory_estimate(22, input_len);
+        assert!(high > low);
+        assert!(high - 2 * input_len > 512 << 20);
+
+        // 小文件不会因为高级别而估算出巨大的窗口
+        assert!(zstd.compress_memory_estimate(22, 4096) < 1 << 20);
+
+        // 超出内存上限时降低级别，最低级别也放不下时保持原级别
+        let limit = zstd.compress_memory_estimate(10, input_len);
+        let level = level_within_memory(&zstd, 22, input_len, limit);
+        assert!((10..22).contains(&level));
+        assert!(zstd.compress_memory_estimate(level, input_len) <= limit);
+        assert_eq!(level_within_memory(&zstd, 22, input_len, 0), 22);
+        assert_eq!(level_within_memory(&zstd, 22, input_len, 1024), 22);
+    }
+
+    #[test]
+    fn test_custom_compressor_registration() {
+        let mut registry = CompressorRegistry::new();
+        let algorithm = CompressionAlgorithm::Custom("reverse".to_string());
+        assert!(registry.get(&algorithm).is_err());
+
+        registry.register(Arc::new(ReverseCompressor));
+        let compressor = registry.get(&algorithm).unwrap();
+        assert_eq!(compressor.file_extension(), "rev");
+        assert_eq!(compressor.decompress(&compressor.compress(b"abc", 0).unwrap()).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn test_decompress_limited_stops_bombs() {
+        let registry = CompressorRegistry::new();
+        let bomb = vec![0u8; 1 << 20];
+        let limits = DecompressionLimits { max_bytes: 0, max_ratio: 100 };
+
+        for algorithm in [CompressionAlgorithm::Gzip, CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4] {
+            let compressor = registry.get(&algorithm).unwrap();
+            let compressed = compressor.compress(&bomb, algorithm.default_level()).unwrap();
+            let max_output = limits.max_output(compressed.len() as u64);
+            assert!(max_output < bomb.len() as u64);
+
+            let err = compressor.decompress_limited(&compressed, max_output).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<StowrError>(),
+                Some(&StowrError::DecompressionLimitExceeded { limit: max_output })
+            );
+            // 恰好等于上限时允许
+            assert_eq!(compressor.decompress_limited(&compressed, bomb.len() as u64).unwrap(), bomb);
+
+            // 流式解压同样检查上限
+            let mut output = Vec::new();
+            let err = compressor.decompress_to(&mut &compressed[..], &mut output, max_output).unwrap_err();
+            assert!(matches!(err.downcast_ref::<StowrError>(), Some(StowrError::DecompressionLimitExceeded { .. })));
+            output.clear();
+            assert_eq!(compressor.decompress_to(&mut &compressed[..], &mut output, bomb.len() as u64).unwrap(), bomb.len() as u64);
+            assert_eq!(output, bomb);
+        }
+
+        // 自定义压缩器使用默认实现
+        let reverse = ReverseCompressor;
+        assert!(reverse.decompress_limited(b"abcd", 3).is_err());
+        assert_eq!(DecompressionLimits::default().max_output(10), u64::MAX);
+        assert_eq!(DecompressionLimits { max_bytes: 50, max_ratio: 10 }.max_output(10), 50);
+    }
+
+    #[test]
+    fn test_estimate_compressed_size() {
+        let zstd = ZstdCompressor;
+        // 小数据整体压缩，结果精确
+        let small = b"estimate me ".repeat(100);
+        let estimate = estimate_compressed_size(&zstd, 3, &small).unwrap();
+        assert_eq!(estimate.sampled_bytes, small.len() as u64);
+        assert_eq!(estimate.estimated_size, zstd.compress(&small, 3).unwrap().len() as u64);
+
+        // 大数据只压缩样本，一半可压缩一半随机时估算接近实际
+        let mut large = Vec::new();
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for chunk in 0..64 {
+            for _ in 0..64 * 1024 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                large.push(if chunk % 2 == 0 { b'a' + (state % 4) as u8 } else { state as u8 });
+            }
+        }
+        let estimate = estimate_compressed_size(&zstd, 3, &large).unwrap();
+        assert_eq!(estimate.sampled_bytes, SAMPLE_LEN * SAMPLE_COUNT);
+        let actual = zstd.compress(&large, 3).unwrap().len() as f64;
+        assert!((estimate.estimated_size as f64 / actual - 1.0).abs() < 0.2, "{:?} vs {}", estimate, actual);
+
+        let mut reader = std::io::Cursor::new(&large);
+        let from_reader = estimate_compressed_size_from(&zstd, 3, &mut reader, large.len() as u64).unwrap();
+        assert_eq!(from_reader, estimate);
+        assert_eq!(estimate_compressed_size(&zstd, 3, b"").unwrap().ratio(), 1.0);
+    }
+
+    #[test]
+    fn test_level_tuner_tracks_throughput_target() {
+        // 目标 100 MB/s，1 MB 数据耗时 20ms 为 50 MB/s，耗时 5ms 为 200 MB/s
+        let mut tuner = LevelTuner::new(100, 2, 5, 9);
+        assert_eq!(tuner.level(), 5);
+        for expected in [4, 3, 2, 2] {
+            tuner.record(1_000_000, Duration::from_millis(20));
+            assert_eq!(tuner.level(), expected);
+        }
+        // 达到目标但未超过 1.5 倍时保持不变
+        tuner.record(1_000_000, Duration::from_millis(8));
+        assert_eq!(tuner.level(), 2);
+        tuner.record(1_000_000, Duration::from_millis(5));
+        tuner.record(1_000_000, Duration::from_millis(5));
+        assert_eq!(tuner.level(), 3);
+        // 小样本不参与测量
+        tuner.record(1024, Duration::from_secs(1));
+        assert_eq!(tuner.level(), 3);
+    }
+}
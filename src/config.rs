@@ -4,11 +4,17 @@ use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+use crate::fsutil;
+use crate::rewrite::PathRule;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub enum CompressionAlgorithm {
+    #[default]
     Gzip,
     Zstd,
     Lz4,
+    /// 通过 `CompressorRegistry` 注册的自定义压缩器，值为算法标识
+    Custom(String),
 }
 
 impl FromStr for CompressionAlgorithm {
@@ -27,12 +33,7 @@ impl FromStr for DeltaAlgorithm {
     }
 }
 
-impl Default for CompressionAlgorithm {
-    fn default() -> Self {
-        CompressionAlgorithm::Gzip
-    }
-}
-
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
 impl CompressionAlgorithm {
     pub fn from_str(s: &str) -> Result<Self> {
         match s.to_lowercase().as_str() {
@@ -43,11 +44,17 @@ impl CompressionAlgorithm {
         }
     }
 
+    /// 根据条目中记录的算法标识还原算法，未知标识视为自定义压缩器
+    pub fn from_id(id: &str) -> Self {
+        Self::from_str(id).unwrap_or_else(|_| CompressionAlgorithm::Custom(id.to_string()))
+    }
+
     pub fn to_string(&self) -> String {
         match self {
             CompressionAlgorithm::Gzip => "gzip".to_string(),
             CompressionAlgorithm::Zstd => "zstd".to_string(),
             CompressionAlgorithm::Lz4 => "lz4".to_string(),
+            CompressionAlgorithm::Custom(id) => id.clone(),
         }
     }
 
@@ -56,6 +63,8 @@ impl CompressionAlgorithm {
             CompressionAlgorithm::Gzip => "gz",
             CompressionAlgorithm::Zstd => "zst",
             CompressionAlgorithm::Lz4 => "lz4",
+            // 自定义压缩器的扩展名由注册的 Compressor 提供
+            CompressionAlgorithm::Custom(_) => "bin",
         }
     }
 
@@ -69,7 +78,7 @@ impl CompressionAlgorithm {
                 }
             }
             CompressionAlgorithm::Zstd => {
-                if level < 1 || level > 22 {
+                if !(1..=22).contains(&level) {
                     Err(anyhow::anyhow!("Zstd compression level must be between 1-22"))
                 } else {
                     Ok(level)
@@ -79,6 +88,8 @@ impl CompressionAlgorithm {
                 // LZ4 不使用压缩级别，始终返回0
                 Ok(0)
             }
+            // 自定义压缩器自行解释压缩级别
+            CompressionAlgorithm::Custom(_) => Ok(level),
         }
     }
 
@@ -87,30 +98,30 @@ impl CompressionAlgorithm {
             CompressionAlgorithm::Gzip => 6,
             CompressionAlgorithm::Zstd => 3,
             CompressionAlgorithm::Lz4 => 0,
+            CompressionAlgorithm::Custom(_) => 0,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub enum DeltaAlgorithm {
+    #[default]
     Simple,    // 简单差分
     XDelta,    // xdelta3 算法
     BsDiff,    // bsdiff 算法
+    /// 按行差分（Myers），适合源代码和日志；二进制内容自动使用简单差分
+    TextLines,
 }
 
-impl Default for DeltaAlgorithm {
-    fn default() -> Self {
-        DeltaAlgorithm::Simple
-    }
-}
-
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
 impl DeltaAlgorithm {
     pub fn from_str(s: &str) -> Result<Self> {
         match s.to_lowercase().as_str() {
             "simple" => Ok(DeltaAlgorithm::Simple),
             "xdelta" => Ok(DeltaAlgorithm::XDelta),
             "bsdiff" => Ok(DeltaAlgorithm::BsDiff),
-            _ => Err(anyhow::anyhow!("Invalid delta algorithm. Valid values: simple, xdelta, bsdiff")),
+            "textlines" | "text-lines" => Ok(DeltaAlgorithm::TextLines),
+            _ => Err(anyhow::anyhow!("Invalid delta algorithm. Valid values: simple, xdelta, bsdiff, textlines")),
         }
     }
 
@@ -119,6 +130,132 @@ impl DeltaAlgorithm {
             DeltaAlgorithm::Simple => "simple".to_string(),
             DeltaAlgorithm::XDelta => "xdelta".to_string(),
             DeltaAlgorithm::BsDiff => "bsdiff".to_string(),
+            DeltaAlgorithm::TextLines => "textlines".to_string(),
+        }
+    }
+}
+
+/// 写入后的 fsync 策略
+///
+/// 级别越高越能保证崩溃或断电后数据完整，但写入吞吐量越低
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// 不主动 fsync，由操作系统决定何时落盘
+    #[default]
+    None,
+    /// 存储文件和提取结果写入后 fsync，并同步其所在目录
+    Blob,
+    /// 在 `Blob` 的基础上同步索引
+    BlobAndIndex,
+}
+
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
+impl Durability {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Durability::None),
+            "blob" => Ok(Durability::Blob),
+            "blob_and_index" => Ok(Durability::BlobAndIndex),
+            _ => Err(anyhow::anyhow!("Invalid durability. Valid values: none, blob, blob_and_index")),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            Durability::None => "none".to_string(),
+            Durability::Blob => "blob".to_string(),
+            Durability::BlobAndIndex => "blob_and_index".to_string(),
+        }
+    }
+
+    /// 是否同步存储文件
+    pub fn sync_blobs(&self) -> bool {
+        matches!(self, Durability::Blob | Durability::BlobAndIndex)
+    }
+
+    /// 是否同步索引
+    ///
+    /// SQLite 索引在回滚日志模式下总是同步提交，见 [`SqliteIndex::set_sync`](crate::index::SqliteIndex::set_sync)
+    pub fn sync_index(&self) -> bool {
+        matches!(self, Durability::BlobAndIndex)
+    }
+}
+
+/// 存储文件的扩展名
+///
+/// 压缩算法记录在索引条目和存储文件头中，读取时不依赖扩展名
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum BlobExtension {
+    /// 按压缩算法命名（`.gz`、`.zst`、`.lz4`）
+    #[default]
+    Algorithm,
+    /// 统一使用 `.blob`
+    Blob,
+    /// 不带扩展名
+    None,
+}
+
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
+impl BlobExtension {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "algorithm" => Ok(BlobExtension::Algorithm),
+            "blob" => Ok(BlobExtension::Blob),
+            "none" => Ok(BlobExtension::None),
+            _ => Err(anyhow::anyhow!("Invalid blob extension. Valid values: algorithm, blob, none")),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            BlobExtension::Algorithm => "algorithm".to_string(),
+            BlobExtension::Blob => "blob".to_string(),
+            BlobExtension::None => "none".to_string(),
+        }
+    }
+
+    /// 存储文件名的后缀（含点号），`algorithm_extension` 为压缩器的扩展名
+    pub fn suffix(&self, algorithm_extension: &str) -> String {
+        match self {
+            BlobExtension::Algorithm => format!(".{}", algorithm_extension),
+            BlobExtension::Blob => ".blob".to_string(),
+            BlobExtension::None => String::new(),
+        }
+    }
+}
+
+/// 库内提示信息的输出级别，见 [`crate::output`]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// 不输出任何信息
+    #[default]
+    Silent = 0,
+    /// 只输出警告和错误
+    Warn = 1,
+    /// 另外输出操作结果和进度
+    Info = 2,
+    /// 另外输出压缩率、相似度等细节
+    Debug = 3,
+}
+
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
+impl Verbosity {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "silent" | "quiet" => Ok(Verbosity::Silent),
+            "warn" => Ok(Verbosity::Warn),
+            "info" => Ok(Verbosity::Info),
+            "debug" => Ok(Verbosity::Debug),
+            _ => Err(anyhow::anyhow!("Invalid verbosity. Valid values: silent, warn, info, debug")),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            Verbosity::Silent => "silent".to_string(),
+            Verbosity::Warn => "warn".to_string(),
+            Verbosity::Info => "info".to_string(),
+            Verbosity::Debug => "debug".to_string(),
         }
     }
 }
@@ -133,6 +270,16 @@ pub struct Config {
     pub compression_algorithm: CompressionAlgorithm,
     #[serde(default = "default_compression_level")]
     pub compression_level: u32,
+    /// 存储时 zstd 压缩的目标速度（MB/s），达不到时在 `compression_level_min`–`compression_level_max`
+    /// 之间自动调整级别，0 表示始终使用 `compression_level`，见 [`crate::compression::LevelTuner`]
+    #[serde(default)]
+    pub target_throughput_mbps: u32,
+    /// 自动调整压缩级别的下限
+    #[serde(default = "default_compression_level_min")]
+    pub compression_level_min: u32,
+    /// 自动调整压缩级别的上限
+    #[serde(default = "default_compression_level_max")]
+    pub compression_level_max: u32,
     #[serde(default = "default_enable_deduplication")]
     pub enable_deduplication: bool,
     #[serde(default = "default_enable_delta_compression")]
@@ -141,6 +288,173 @@ pub struct Config {
     pub similarity_threshold: f32,
     #[serde(default = "default_delta_algorithm")]
     pub delta_algorithm: DeltaAlgorithm,
+    /// 差分前去掉文本文件的 UTF-8 BOM 并将 CRLF 统一为 LF，提取时按条目记录还原
+    #[serde(default)]
+    pub normalize_text: bool,
+    /// 存储前解开 gzip/zstd 压缩的输入，存储其中的内容以便差分，提取时重新压缩还原，见 [`crate::recompress`]
+    #[serde(default)]
+    pub unwrap_compressed: bool,
+    /// 启用内置的预压缩过滤器：解开 zip 容器（Office 文档等）的成员和邮件中的 base64 附件，
+    /// 提取时重新编码还原，见 [`crate::precompress`]
+    #[serde(default)]
+    pub precompress: bool,
+    /// 差分存储的目标文件大小上限（字节），更大的文件不做差分；提取时超过上限的差分数据视为损坏
+    #[serde(default = "default_delta_max_target_size")]
+    pub delta_max_target_size: u64,
+    /// 不小于该大小（字节）的文件按分块签名流式选择基础文件和生成差分，0 表示不使用
+    ///
+    /// 同样大小的基础文件会在存储文件旁写入 `.sig` 签名文件
+    #[serde(default = "default_delta_streaming_threshold")]
+    pub delta_streaming_threshold: u64,
+    /// 相似度搜索的候选基础文件与新文件的大小比例上限（如 2.0 表示 0.5×–2×），0 表示不限制
+    #[serde(default = "default_delta_size_ratio")]
+    pub delta_size_ratio: f32,
+    /// 相似度搜索只比较扩展名相同的基础文件
+    #[serde(default = "default_delta_same_extension")]
+    pub delta_same_extension: bool,
+    /// 每次存储最多比较的候选基础文件数量，0 表示不限制
+    ///
+    /// 候选数量或搜索时间的预算用尽时使用已比较的候选中最相似的一个，都没有达到阈值时按基础文件存储
+    #[serde(default = "default_delta_max_candidates")]
+    pub delta_max_candidates: usize,
+    /// 每次存储的相似度搜索时间上限（毫秒），0 表示不限制
+    #[serde(default)]
+    pub delta_max_search_millis: u64,
+    /// 启用 `image` feature 时，图片只与感知哈希汉明距离不超过该值（0-64）的基础文件比较，见 [`crate::phash`]
+    #[serde(default = "default_delta_image_max_distance")]
+    pub delta_image_max_distance: u32,
+    /// JSON 索引是否使用 zstd 压缩快照加追加日志的格式
+    #[serde(default)]
+    pub compress_index: bool,
+    /// 写入存储文件和提取结果时使用的临时目录，None 表示使用目标所在目录
+    ///
+    /// 与目标不在同一文件系统时会自动退回到目标所在目录
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
+    /// 写入后的 fsync 策略
+    #[serde(default)]
+    pub durability: Durability,
+    /// 以存储内容的 SHA256 命名存储文件（`<sha256>.zst`），便于 rsync 等外部工具复制和校验
+    ///
+    /// 启用后打开存储时会自动重命名已有的存储文件
+    #[serde(default)]
+    pub content_addressed_blobs: bool,
+    /// 新存储文件的扩展名，默认按压缩算法命名
+    ///
+    /// 只影响之后写入的文件，已有的存储文件保持原名
+    #[serde(default)]
+    pub blob_extension: BlobExtension,
+    /// 存储目录所在磁盘需要保留的最小可用空间（字节），0 表示不保留
+    #[serde(default)]
+    pub min_free_bytes: u64,
+    /// 小于该大小（字节）的文件内容直接保存在索引条目中，不创建存储文件，0 表示不使用
+    ///
+    /// 去重和哈希校验与普通存储相同；启用 `encrypt_blobs` 而未启用 `encrypt_index` 时不内联，
+    /// 避免明文内容出现在索引中。内联条目需要支持 `inline` 层级的版本才能读取
+    #[serde(default)]
+    pub inline_threshold: u64,
+    /// 不小于该大小（字节）的本地存储文件在提取和相似度搜索时用内存映射读取，0 表示不使用
+    ///
+    /// 减少大文件读取时的内存占用和复制。本库只整体替换存储文件，不会改写正被映射的内容；
+    /// 存储目录位于网络文件系统上，或可能被其他进程直接改写时建议设为 0
+    #[serde(default = "default_mmap_threshold")]
+    pub mmap_threshold: u64,
+    /// 存储后保留源文件时，在源文件的扩展属性（Windows 上为 NTFS 备用数据流）中写入条目ID和哈希
+    ///
+    /// 文件被移动后再次存储时，大小和修改时间与标记的条目一致即直接去重，不重新计算哈希
+    #[serde(default)]
+    pub source_markers: bool,
+    /// 存储并删除源文件后在原位置留下 `.stowr-stub` 占位文件，`owe_file` 可以接受占位文件路径
+    #[serde(default)]
+    pub leave_stubs: bool,
+    /// 是否加密索引（需要通过 `create_index_with_key` 提供密钥）
+    #[serde(default)]
+    pub encrypt_index: bool,
+    /// 是否加密存储文件（需要通过 `StorageManager::set_key_provider` 提供密钥）
+    #[serde(default)]
+    pub encrypt_blobs: bool,
+    /// 压缩/解压可使用的内存上限（字节），0 表示不限制
+    ///
+    /// 压缩时先逐级降低压缩级别以满足上限，最低级别仍超过上限时返回 `StowrError::MemoryLimitExceeded`；
+    /// 解压超过上限时直接返回该错误，并行提取时的线程数也会按此上限收缩
+    #[serde(default)]
+    pub max_memory_bytes: u64,
+    /// 单个存储文件解压后的大小上限（字节），0 表示不限制
+    #[serde(default)]
+    pub max_decompressed_bytes: u64,
+    /// 解压后大小与存储文件大小之比的上限，0 表示不限制
+    ///
+    /// 与 `max_decompressed_bytes` 同时设置时取较小的限制，超过时返回 `StowrError::DecompressionLimitExceeded`
+    #[serde(default)]
+    pub max_compression_ratio: u64,
+    /// 批量操作每秒处理的字节数上限，0 表示不限制
+    #[serde(default)]
+    pub throttle_bytes_per_sec: u64,
+    /// 批量操作每秒处理的文件数上限，0 表示不限制
+    #[serde(default)]
+    pub throttle_ops_per_sec: u64,
+    /// 批量存储时跳过路径和内容哈希都与已存储条目一致的文件
+    #[serde(default)]
+    pub skip_unchanged: bool,
+    /// 单个文件存储操作的超时时间（毫秒），0 表示不限制
+    #[serde(default)]
+    pub store_timeout_ms: u64,
+    /// 单个文件提取操作的超时时间（毫秒），0 表示不限制
+    #[serde(default)]
+    pub extract_timeout_ms: u64,
+    /// 单次 `scrub` 校验的超时时间（毫秒），0 表示不限制
+    #[serde(default)]
+    pub verify_timeout_ms: u64,
+    /// 提取时的路径重写规则，按顺序尝试，第一条匹配的规则生效
+    #[serde(default)]
+    pub path_rewrites: Vec<PathRule>,
+    /// 超过该天数未访问的条目由 `tier_migrate` 迁移到冷层，0 表示不迁移
+    #[serde(default)]
+    pub tier_after_days: u64,
+    /// 活动日志保留的记录数，0 表示不记录
+    #[serde(default = "default_activity_log_limit")]
+    pub activity_log_limit: usize,
+    /// 读取时按该概率（0.0 到 1.0）校验内容哈希，0 表示不校验
+    #[serde(default)]
+    pub verify_sample_rate: f32,
+    /// `scrub` 每次调用最多校验的条目数
+    #[serde(default = "default_scrub_batch_size")]
+    pub scrub_batch_size: usize,
+    /// `scrub` 同时校验的条目数，1 表示逐个校验
+    #[serde(default = "default_scrub_parallelism")]
+    pub scrub_parallelism: usize,
+    /// 并行校验时内存中解压内容的总量上限（字节），0 表示不限制
+    #[serde(default = "default_scrub_max_inflight_bytes")]
+    pub scrub_max_inflight_bytes: u64,
+    /// 每个存储文件的 Reed-Solomon 校验分片数（数据分片固定为 10），0 表示不写入校验文件
+    #[serde(default)]
+    pub parity_shards: usize,
+    /// 镜像目录：每个存储文件写入时同步复制一份，删除时一并删除
+    #[serde(default)]
+    pub mirror_path: Option<PathBuf>,
+    /// 存储文本内容时建立全文索引，见 [`crate::content_index`]
+    #[serde(default)]
+    pub content_index: bool,
+    /// 建立全文索引的内容大小上限（字节），0 表示不限制
+    #[serde(default = "default_content_index_max_bytes")]
+    pub content_index_max_bytes: u64,
+    /// Ed25519 签名私钥文件，设置后对索引的 Merkle 根哈希签名，见 [`crate::signing`]
+    #[serde(default)]
+    pub signing_key_path: Option<PathBuf>,
+    /// 库内提示信息的输出级别，默认不输出
+    ///
+    /// 级别是进程级的，`StorageManager` 不会自动应用此设置，
+    /// 需要由应用调用 [`crate::output::set_verbosity`]
+    #[serde(default)]
+    pub verbosity: Verbosity,
+}
+
+fn default_content_index_max_bytes() -> u64 {
+    4 * 1024 * 1024
+}
+
+fn default_mmap_threshold() -> u64 {
+    8 * 1024 * 1024
 }
 
 fn default_multithread() -> usize {
@@ -171,6 +485,54 @@ fn default_delta_algorithm() -> DeltaAlgorithm {
     DeltaAlgorithm::Simple
 }
 
+fn default_activity_log_limit() -> usize {
+    1000
+}
+
+fn default_scrub_batch_size() -> usize {
+    1000
+}
+
+fn default_scrub_parallelism() -> usize {
+    1
+}
+
+fn default_scrub_max_inflight_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_delta_max_target_size() -> u64 {
+    crate::delta::DEFAULT_MAX_DELTA_TARGET_SIZE
+}
+
+fn default_compression_level_min() -> u32 {
+    1
+}
+
+fn default_compression_level_max() -> u32 {
+    19
+}
+
+fn default_delta_image_max_distance() -> u32 {
+    10
+}
+
+fn default_delta_streaming_threshold() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_delta_size_ratio() -> f32 {
+    crate::delta::DEFAULT_CANDIDATE_SIZE_RATIO
+}
+
+fn default_delta_same_extension() -> bool {
+    true
+}
+
+fn default_delta_max_candidates() -> usize {
+    256
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum IndexMode {
     Auto,
@@ -186,10 +548,57 @@ impl Default for Config {
             multithread: 1,
             compression_algorithm: CompressionAlgorithm::Gzip,
             compression_level: 6,
+            target_throughput_mbps: 0,
+            compression_level_min: default_compression_level_min(),
+            compression_level_max: default_compression_level_max(),
             enable_deduplication: true,
             enable_delta_compression: false,
             similarity_threshold: 0.7,
             delta_algorithm: DeltaAlgorithm::Simple,
+            normalize_text: false,
+            unwrap_compressed: false,
+            precompress: false,
+            delta_max_target_size: default_delta_max_target_size(),
+            delta_streaming_threshold: default_delta_streaming_threshold(),
+            delta_size_ratio: default_delta_size_ratio(),
+            delta_same_extension: default_delta_same_extension(),
+            delta_max_candidates: default_delta_max_candidates(),
+            delta_max_search_millis: 0,
+            delta_image_max_distance: default_delta_image_max_distance(),
+            compress_index: false,
+            temp_dir: None,
+            durability: Durability::None,
+            content_addressed_blobs: false,
+            blob_extension: BlobExtension::default(),
+            min_free_bytes: 0,
+            inline_threshold: 0,
+            mmap_threshold: default_mmap_threshold(),
+            source_markers: false,
+            leave_stubs: false,
+            encrypt_index: false,
+            encrypt_blobs: false,
+            max_memory_bytes: 0,
+            max_decompressed_bytes: 0,
+            max_compression_ratio: 0,
+            throttle_bytes_per_sec: 0,
+            throttle_ops_per_sec: 0,
+            skip_unchanged: false,
+            store_timeout_ms: 0,
+            extract_timeout_ms: 0,
+            verify_timeout_ms: 0,
+            path_rewrites: Vec::new(),
+            tier_after_days: 0,
+            activity_log_limit: 1000,
+            verify_sample_rate: 0.0,
+            scrub_batch_size: 1000,
+            scrub_parallelism: default_scrub_parallelism(),
+            scrub_max_inflight_bytes: default_scrub_max_inflight_bytes(),
+            parity_shards: 0,
+            mirror_path: None,
+            content_index: false,
+            content_index_max_bytes: default_content_index_max_bytes(),
+            signing_key_path: None,
+            verbosity: Verbosity::Silent,
         }
     }
 }
@@ -227,7 +636,7 @@ impl Config {
         let content = serde_json::to_string_pretty(self)
             .context("Failed to serialize config")?;
         
-        fs::write(&config_path, content)
+        fsutil::atomic_write(&config_path, content.as_bytes(), None, false)
             .context("Failed to write config file")?;
 
         Ok(())
@@ -268,12 +677,26 @@ impl Config {
                 
                 // 对于LZ4，直接设置为0并提示用户
                 if self.compression_algorithm == CompressionAlgorithm::Lz4 {
-                    println!("Note: LZ4 does not use compression levels. Level set to 0.");
+                    crate::output::info!("Note: LZ4 does not use compression levels. Level set to 0.");
                     self.compression_level = 0;
                 } else {
                     self.compression_level = self.compression_algorithm.validate_level(level)?;
                 }
             }
+            "compression.target_throughput_mbps" => {
+                self.target_throughput_mbps = value.parse::<u32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid throughput target. Must be a number of MB/s (0 to disable)"))?;
+            }
+            "compression.level_min" | "compression.level_max" => {
+                let level = value.parse::<u32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid compression level. Must be a number"))?;
+                let level = CompressionAlgorithm::Zstd.validate_level(level)?;
+                if key == "compression.level_min" {
+                    self.compression_level_min = level;
+                } else {
+                    self.compression_level_max = level;
+                }
+            }
             "dedup.enable" => {
                 self.enable_deduplication = value.parse::<bool>()
                     .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
@@ -285,7 +708,7 @@ impl Config {
             "delta.similarity_threshold" => {
                 let threshold = value.parse::<f32>()
                     .map_err(|_| anyhow::anyhow!("Invalid similarity threshold. Must be a number between 0.0 and 1.0"))?;
-                if threshold < 0.0 || threshold > 1.0 {
+                if !(0.0..=1.0).contains(&threshold) {
                     return Err(anyhow::anyhow!("Similarity threshold must be between 0.0 and 1.0"));
                 }
                 self.similarity_threshold = threshold;
@@ -293,6 +716,220 @@ impl Config {
             "delta.algorithm" => {
                 self.delta_algorithm = DeltaAlgorithm::from_str(value)?;
             }
+            "delta.max_target_size" => {
+                let size = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid delta target size limit. Must be a positive number of bytes"))?;
+                if size == 0 {
+                    return Err(anyhow::anyhow!("Delta target size limit must be at least 1 byte"));
+                }
+                self.delta_max_target_size = size;
+            }
+            "delta.streaming_threshold" => {
+                self.delta_streaming_threshold = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid streaming delta threshold. Must be a number of bytes (0 to disable)"))?;
+            }
+            "delta.size_ratio" => {
+                let ratio = value.parse::<f32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid size ratio. Must be a number of at least 1.0 (0 to disable)"))?;
+                if ratio != 0.0 && !(ratio >= 1.0 && ratio.is_finite()) {
+                    return Err(anyhow::anyhow!("Size ratio must be at least 1.0, or 0 to disable"));
+                }
+                self.delta_size_ratio = ratio;
+            }
+            "delta.same_extension" => {
+                self.delta_same_extension = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "delta.max_candidates" => {
+                self.delta_max_candidates = value.parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Invalid candidate limit. Must be a non-negative integer (0 for unlimited)"))?;
+            }
+            "delta.max_search_millis" => {
+                self.delta_max_search_millis = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid search time limit. Must be a number of milliseconds (0 for unlimited)"))?;
+            }
+            "delta.image_max_distance" => {
+                let distance = value.parse::<u32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid image distance. Must be a number between 0 and 64"))?;
+                if distance > 64 {
+                    return Err(anyhow::anyhow!("Image distance must be between 0 and 64"));
+                }
+                self.delta_image_max_distance = distance;
+            }
+            "delta.normalize_text" => {
+                self.normalize_text = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "delta.unwrap_compressed" => {
+                self.unwrap_compressed = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "delta.precompress" => {
+                self.precompress = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "index.compress" => {
+                self.compress_index = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "storage.temp_dir" => {
+                self.temp_dir = if value.is_empty() {
+                    None
+                } else {
+                    Some(PathBuf::from(value))
+                };
+            }
+            "storage.mirror" => {
+                self.mirror_path = if value.is_empty() {
+                    None
+                } else {
+                    Some(PathBuf::from(value))
+                };
+            }
+            "index.content" => {
+                self.content_index = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "index.content_max_bytes" => {
+                self.content_index_max_bytes = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid content index size. Must be a number of bytes (0 for no limit)"))?;
+            }
+            "security.signing_key" => {
+                self.signing_key_path = if value.is_empty() {
+                    None
+                } else {
+                    Some(PathBuf::from(value))
+                };
+            }
+            "storage.durability" => {
+                self.durability = Durability::from_str(value)?;
+            }
+            "storage.content_addressed" => {
+                self.content_addressed_blobs = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "storage.blob_extension" => {
+                self.blob_extension = BlobExtension::from_str(value)?;
+            }
+            "storage.min_free" => {
+                self.min_free_bytes = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid free space reserve. Must be a number of bytes (0 for none)"))?;
+            }
+            "storage.inline_threshold" => {
+                self.inline_threshold = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid inline threshold. Must be a number of bytes (0 to disable)"))?;
+            }
+            "storage.mmap_threshold" => {
+                self.mmap_threshold = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid mmap threshold. Must be a number of bytes (0 to disable)"))?;
+            }
+            "storage.source_markers" => {
+                self.source_markers = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "storage.stubs" => {
+                self.leave_stubs = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "index.encrypt" => {
+                self.encrypt_index = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "storage.encrypt" => {
+                self.encrypt_blobs = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "compression.max_memory" => {
+                self.max_memory_bytes = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid memory limit. Must be a number of bytes (0 for unlimited)"))?;
+            }
+            "compression.max_output" => {
+                self.max_decompressed_bytes = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid decompressed size limit. Must be a number of bytes (0 for unlimited)"))?;
+            }
+            "compression.max_ratio" => {
+                self.max_compression_ratio = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid compression ratio limit. Must be a number (0 for unlimited)"))?;
+            }
+            "throttle.bytes_per_sec" => {
+                self.throttle_bytes_per_sec = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid throttle rate. Must be a number (0 for unlimited)"))?;
+            }
+            "throttle.ops_per_sec" => {
+                self.throttle_ops_per_sec = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid throttle rate. Must be a number (0 for unlimited)"))?;
+            }
+            "batch.skip_unchanged" => {
+                self.skip_unchanged = value.parse::<bool>()
+                    .map_err(|_| anyhow::anyhow!("Invalid boolean value. Must be true or false"))?;
+            }
+            "timeout.store" => {
+                self.store_timeout_ms = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid timeout. Must be a number of milliseconds (0 for none)"))?;
+            }
+            "timeout.extract" => {
+                self.extract_timeout_ms = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid timeout. Must be a number of milliseconds (0 for none)"))?;
+            }
+            "timeout.verify" => {
+                self.verify_timeout_ms = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid timeout. Must be a number of milliseconds (0 for none)"))?;
+            }
+            "extract.rewrite" => {
+                // 每次设置追加一条规则，空值清除所有规则
+                if value.is_empty() {
+                    self.path_rewrites.clear();
+                } else {
+                    self.path_rewrites.push(PathRule::from_str(value)?);
+                }
+            }
+            "tier.after_days" => {
+                self.tier_after_days = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid tier age. Must be a number of days (0 to disable)"))?;
+            }
+            "verify.sample_rate" => {
+                let rate = value.parse::<f32>()
+                    .map_err(|_| anyhow::anyhow!("Invalid sample rate. Must be a number between 0.0 and 1.0"))?;
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(anyhow::anyhow!("Sample rate must be between 0.0 and 1.0"));
+                }
+                self.verify_sample_rate = rate;
+            }
+            "scrub.batch_size" => {
+                let size = value.parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Invalid scrub batch size. Must be a positive number"))?;
+                if size == 0 {
+                    return Err(anyhow::anyhow!("Scrub batch size must be at least 1"));
+                }
+                self.scrub_batch_size = size;
+            }
+            "scrub.parallelism" => {
+                let threads = value.parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Invalid scrub parallelism. Must be a positive number"))?;
+                if threads == 0 {
+                    return Err(anyhow::anyhow!("Scrub parallelism must be at least 1"));
+                }
+                self.scrub_parallelism = threads;
+            }
+            "scrub.max_inflight_bytes" => {
+                self.scrub_max_inflight_bytes = value.parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("Invalid in-flight byte limit. Must be a number of bytes (0 for unlimited)"))?;
+            }
+            "parity.shards" => {
+                let shards = value.parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Invalid parity shard count. Must be a number (0 to disable)"))?;
+                if shards > crate::parity::MAX_PARITY_SHARDS {
+                    return Err(anyhow::anyhow!("Parity shard count must be at most {}", crate::parity::MAX_PARITY_SHARDS));
+                }
+                self.parity_shards = shards;
+            }
+            "activity.limit" => {
+                self.activity_log_limit = value.parse::<usize>()
+                    .map_err(|_| anyhow::anyhow!("Invalid activity log limit. Must be a number of records (0 to disable)"))?;
+            }
+            "verbosity" => {
+                self.verbosity = Verbosity::from_str(value)?;
+            }
             _ => return Err(anyhow::anyhow!("Unknown config key: {}", key)),
         }
         Ok(())
@@ -305,10 +942,57 @@ impl Config {
             ("multithread".to_string(), self.multithread.to_string()),
             ("compression.algorithm".to_string(), self.compression_algorithm.to_string()),
             ("compression.level".to_string(), self.compression_level.to_string()),
+            ("compression.target_throughput_mbps".to_string(), self.target_throughput_mbps.to_string()),
+            ("compression.level_min".to_string(), self.compression_level_min.to_string()),
+            ("compression.level_max".to_string(), self.compression_level_max.to_string()),
             ("dedup.enable".to_string(), self.enable_deduplication.to_string()),
             ("delta.enable".to_string(), self.enable_delta_compression.to_string()),
             ("delta.similarity_threshold".to_string(), self.similarity_threshold.to_string()),
             ("delta.algorithm".to_string(), self.delta_algorithm.to_string()),
+            ("delta.normalize_text".to_string(), self.normalize_text.to_string()),
+            ("delta.unwrap_compressed".to_string(), self.unwrap_compressed.to_string()),
+            ("delta.precompress".to_string(), self.precompress.to_string()),
+            ("delta.max_target_size".to_string(), self.delta_max_target_size.to_string()),
+            ("delta.streaming_threshold".to_string(), self.delta_streaming_threshold.to_string()),
+            ("delta.size_ratio".to_string(), self.delta_size_ratio.to_string()),
+            ("delta.same_extension".to_string(), self.delta_same_extension.to_string()),
+            ("delta.max_candidates".to_string(), self.delta_max_candidates.to_string()),
+            ("delta.max_search_millis".to_string(), self.delta_max_search_millis.to_string()),
+            ("delta.image_max_distance".to_string(), self.delta_image_max_distance.to_string()),
+            ("index.compress".to_string(), self.compress_index.to_string()),
+            ("storage.temp_dir".to_string(), self.temp_dir.as_ref().map(|p| p.display().to_string()).unwrap_or_default()),
+            ("storage.durability".to_string(), self.durability.to_string()),
+            ("storage.content_addressed".to_string(), self.content_addressed_blobs.to_string()),
+            ("storage.blob_extension".to_string(), self.blob_extension.to_string()),
+            ("storage.min_free".to_string(), self.min_free_bytes.to_string()),
+            ("storage.inline_threshold".to_string(), self.inline_threshold.to_string()),
+            ("storage.mmap_threshold".to_string(), self.mmap_threshold.to_string()),
+            ("storage.source_markers".to_string(), self.source_markers.to_string()),
+            ("storage.stubs".to_string(), self.leave_stubs.to_string()),
+            ("index.encrypt".to_string(), self.encrypt_index.to_string()),
+            ("storage.encrypt".to_string(), self.encrypt_blobs.to_string()),
+            ("compression.max_memory".to_string(), self.max_memory_bytes.to_string()),
+            ("compression.max_output".to_string(), self.max_decompressed_bytes.to_string()),
+            ("compression.max_ratio".to_string(), self.max_compression_ratio.to_string()),
+            ("throttle.bytes_per_sec".to_string(), self.throttle_bytes_per_sec.to_string()),
+            ("throttle.ops_per_sec".to_string(), self.throttle_ops_per_sec.to_string()),
+            ("batch.skip_unchanged".to_string(), self.skip_unchanged.to_string()),
+            ("timeout.store".to_string(), self.store_timeout_ms.to_string()),
+            ("timeout.extract".to_string(), self.extract_timeout_ms.to_string()),
+            ("timeout.verify".to_string(), self.verify_timeout_ms.to_string()),
+            ("extract.rewrite".to_string(), self.path_rewrites.iter().map(PathRule::to_string).collect::<Vec<_>>().join("; ")),
+            ("tier.after_days".to_string(), self.tier_after_days.to_string()),
+            ("activity.limit".to_string(), self.activity_log_limit.to_string()),
+            ("verify.sample_rate".to_string(), self.verify_sample_rate.to_string()),
+            ("scrub.batch_size".to_string(), self.scrub_batch_size.to_string()),
+            ("scrub.parallelism".to_string(), self.scrub_parallelism.to_string()),
+            ("scrub.max_inflight_bytes".to_string(), self.scrub_max_inflight_bytes.to_string()),
+            ("parity.shards".to_string(), self.parity_shards.to_string()),
+            ("storage.mirror".to_string(), self.mirror_path.as_ref().map(|p| p.display().to_string()).unwrap_or_default()),
+            ("index.content".to_string(), self.content_index.to_string()),
+            ("index.content_max_bytes".to_string(), self.content_index_max_bytes.to_string()),
+            ("security.signing_key".to_string(), self.signing_key_path.as_ref().map(|p| p.display().to_string()).unwrap_or_default()),
+            ("verbosity".to_string(), self.verbosity.to_string()),
         ]
     }
 }
@@ -0,0 +1,181 @@
+//! 存储文件容器格式
+//!
+//! 文件格式：魔数(8) + 头部长度(u32 LE) + 头部 JSON + 存储内容（已压缩，启用加密时已加密）。
+//! 头部记录压缩算法、解压后的大小和哈希，以及加密时的密钥信息，
+//! 索引丢失时恢复工具也能识别和校验存储文件。没有魔数的存储文件是引入容器格式之前写入的，按原样读取。
+//!
+//! 加密的存储文件不在头部记录明文的大小和哈希，避免不解密就能按哈希确认内容；
+//! 解压后的大小（u64 LE）放在密文开头，压缩数据紧随其后。
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::config::CompressionAlgorithm;
+
+/// 存储文件头部魔数
+const BLOB_MAGIC: &[u8; 8] = b"STWRBLB1";
+/// 当前容器格式版本
+pub const FORMAT_VERSION: u32 = 1;
+/// 头部 JSON 的长度上限，超过时视为损坏
+const MAX_HEADER_LEN: u32 = 64 * 1024;
+
+/// 存储文件头部
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlobHeader {
+    pub format_version: u32,
+    pub compression_algorithm: CompressionAlgorithm,
+    /// 解压后的大小（差分存储文件为差分数据的大小），加密的存储文件不记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_size: Option<u64>,
+    /// 解压后内容的 SHA256，加密的存储文件不记录
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<BlobEncryption>,
+}
+
+/// 加密存储文件的密钥信息
+///
+/// 记录的是写入时的密钥；`StorageManager::rotate_key` 只更新索引，
+/// 轮换后需要用这里记录的旧主密钥解开数据密钥
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlobEncryption {
+    pub key_id: String,
+    /// 用主密钥包装的数据密钥
+    pub wrapped_key: String,
+}
+
+/// 在存储内容前加上头部
+pub fn encode(header: &BlobHeader, payload: &[u8]) -> Result<Vec<u8>> {
+    let header_json = serde_json::to_vec(header)
+        .context("Failed to serialize blob header")?;
+    let header_len = u32::try_from(header_json.len())
+        .ok()
+        .filter(|&len| len <= MAX_HEADER_LEN)
+        .ok_or_else(|| anyhow!("Blob header too large"))?;
+
+    let mut data = Vec::with_capacity(BLOB_MAGIC.len() + 4 + header_json.len() + payload.len());
+    data.extend_from_slice(BLOB_MAGIC);
+    data.extend_from_slice(&header_len.to_le_bytes());
+    data.extend_from_slice(&header_json);
+    data.extend_from_slice(payload);
+    Ok(data)
+}
+
+/// 拆分头部和存储内容，没有头部的旧存储文件返回 None 和完整内容
+pub fn decode(data: &[u8]) -> Result<(Option<BlobHeader>, &[u8])> {
+    if !data.starts_with(BLOB_MAGIC) {
+        return Ok((None, data));
+    }
+    let header_len = parse_header_len(data.get(BLOB_MAGIC.len()..BLOB_MAGIC.len() + 4))?;
+    let start = BLOB_MAGIC.len() + 4;
+    let header_json = data.get(start..start + header_len)
+        .ok_or_else(|| anyhow!("Blob header is truncated"))?;
+    Ok((Some(parse_header(header_json)?), &data[start + header_len..]))
+}
+
+/// 只读取存储文件的头部，不读取存储内容
+pub fn read_header(path: &Path) -> Result<Option<BlobHeader>> {
+    let mut file = File::open(path)
+        .with_context(|| format!("Failed to open stored file: {}", path.display()))?;
+    let mut prefix = [0u8; BLOB_MAGIC.len() + 4];
+    let mut read = 0;
+    while read < prefix.len() {
+        match file.read(&mut prefix[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    if !prefix[..read].starts_with(BLOB_MAGIC) {
+        return Ok(None);
+    }
+    let header_len = parse_header_len(prefix[..read].get(BLOB_MAGIC.len()..))?;
+    let mut header_json = vec![0u8; header_len];
+    file.read_exact(&mut header_json)
+        .map_err(|_| anyhow!("Blob header is truncated"))?;
+    parse_header(&header_json).map(Some)
+}
+
+fn parse_header_len(bytes: Option<&[u8]>) -> Result<usize> {
+    let len = bytes
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or_else(|| anyhow!("Blob header is truncated"))?;
+    if len > MAX_HEADER_LEN {
+        return Err(anyhow!("Blob header is corrupted"));
+    }
+    Ok(len as usize)
+}
+
+fn parse_header(json: &[u8]) -> Result<BlobHeader> {
+    let header: BlobHeader = serde_json::from_slice(json)
+        .context("Failed to parse blob header")?;
+    if header.format_version > FORMAT_VERSION {
+        return Err(anyhow!(
+            "Unsupported blob format version: {} (this build supports up to {})",
+            header.format_version, FORMAT_VERSION
+        ));
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn header() -> BlobHeader {
+        BlobHeader {
+            format_version: FORMAT_VERSION,
+            compression_algorithm: CompressionAlgorithm::Zstd,
+            content_size: None,
+            content_hash: None,
+            encryption: Some(BlobEncryption {
+                key_id: "k1".to_string(),
+                wrapped_key: "wrapped".to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_and_legacy_blobs() {
+        let data = encode(&header(), b"payload").unwrap();
+        let (parsed, payload) = decode(&data).unwrap();
+        assert_eq!(parsed, Some(header()));
+        assert_eq!(payload, b"payload");
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("blob");
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(read_header(&path).unwrap(), Some(header()));
+
+        // 没有魔数的旧存储文件按原样返回
+        let legacy = b"\x1f\x8b legacy gzip";
+        assert_eq!(decode(legacy).unwrap(), (None, &legacy[..]));
+        std::fs::write(&path, legacy).unwrap();
+        assert_eq!(read_header(&path).unwrap(), None);
+
+        // 截断或损坏的头部
+        for len in [BLOB_MAGIC.len(), BLOB_MAGIC.len() + 6, data.len() - b"payload".len() - 1] {
+            assert!(decode(&data[..len]).is_err());
+            std::fs::write(&path, &data[..len]).unwrap();
+            assert!(read_header(&path).is_err());
+        }
+        let mut huge = BLOB_MAGIC.to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode(&huge).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_header_omits_plaintext_metadata() {
+        let encrypted = serde_json::to_string(&header()).unwrap();
+        assert!(!encrypted.contains("content_size") && !encrypted.contains("content_hash"));
+
+        let plain = BlobHeader { content_size: Some(5), content_hash: Some("abc".to_string()), encryption: None, ..header() };
+        let (parsed, _) = decode(&encode(&plain, b"payload").unwrap()).unwrap();
+        assert_eq!(parsed, Some(plain));
+    }
+}
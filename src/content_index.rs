@@ -0,0 +1,187 @@
+//! 可选的全文内容索引
+//!
+//! 启用 `index.content` 后，存储文本内容时同时写入存储目录下的 `content.db`（SQLite FTS5）。
+//! 索引以内容哈希为键：引用条目、重命名和移动都不需要额外维护，搜索时再把哈希对应到
+//! 当前索引中的条目，已删除条目的残留记录会被忽略，并在 [`rebuild_content_index`] 时清理。
+//! FTS5 表中的 `hash` 列不建索引，哈希到文档的对应关系另存在普通表 `content_hashes` 中，
+//! 存储时查重和清理都按主键查找，不扫描全文表。
+//!
+//! 内容索引保存的是明文，因此加密的仓库（`index.encrypt` 或 `storage.encrypt`）不会启用。
+//!
+//! [`rebuild_content_index`]: crate::StorageManager::rebuild_content_index
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::index::FileEntry;
+
+/// 内容索引文件名（位于存储目录下）
+pub const CONTENT_INDEX_FILE: &str = "content.db";
+/// 判断二进制内容时检查的开头字节数
+const BINARY_PEEK_LEN: usize = 8 * 1024;
+
+/// 一条内容搜索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentHit {
+    pub entry: FileEntry,
+    /// 相关度，越大越相关
+    pub score: f64,
+    /// 匹配位置附近的文本，匹配词用 `[` `]` 标出
+    pub snippet: String,
+}
+
+/// 适合建立全文索引的内容返回其文本：不超过 `max_bytes`（0 表示不限制）、开头没有 NUL 字节且是合法 UTF-8
+pub(crate) fn extract_text(content: &[u8], max_bytes: u64) -> Option<&str> {
+    if max_bytes > 0 && content.len() as u64 > max_bytes {
+        return None;
+    }
+    if content[..content.len().min(BINARY_PEEK_LEN)].contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+/// 存储目录下的全文索引
+pub(crate) struct ContentIndex {
+    conn: Connection,
+}
+
+impl ContentIndex {
+    pub fn open(storage_path: &Path) -> Result<Self> {
+        std::fs::create_dir_all(storage_path)
+            .context("Failed to create storage directory")?;
+        let conn = Connection::open(storage_path.join(CONTENT_INDEX_FILE))
+            .context("Failed to open content index")?;
+        let has_hash_table: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'content_hashes')",
+            [],
+            |row| row.get(0),
+        )?;
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS content USING fts5(hash UNINDEXED, body, tokenize = 'unicode61');
+             CREATE TABLE IF NOT EXISTS content_hashes (hash TEXT PRIMARY KEY, doc INTEGER NOT NULL) WITHOUT ROWID;",
+        ).context("Failed to create content index")?;
+        // 旧版本的内容索引没有哈希表，首次打开时扫描一次全文表补齐
+        if !has_hash_table {
+            conn.execute_batch("INSERT OR IGNORE INTO content_hashes (hash, doc) SELECT hash, rowid FROM content;")
+                .context("Failed to upgrade content index")?;
+        }
+        Ok(Self { conn })
+    }
+
+    /// 是否已索引该哈希的内容
+    pub fn contains(&self, hash: &str) -> Result<bool> {
+        let found: Option<i64> = self.conn
+            .prepare_cached("SELECT doc FROM content_hashes WHERE hash = ?1")?
+            .query_row(params![hash], |row| row.get(0))
+            .optional()?;
+        Ok(found.is_some())
+    }
+
+    /// 加入内容，已索引的哈希不重复加入
+    pub fn add(&self, hash: &str, text: &str) -> Result<()> {
+        if self.contains(hash)? {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        tx.prepare_cached("INSERT INTO content (hash, body) VALUES (?1, ?2)")?
+            .execute(params![hash, text])
+            .context("Failed to add content to the content index")?;
+        tx.prepare_cached("INSERT INTO content_hashes (hash, doc) VALUES (?1, last_insert_rowid())")?
+            .execute(params![hash])
+            .context("Failed to add content to the content index")?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 在一个事务中删除不在 `keep` 中的哈希，返回删除的记录数
+    pub fn retain(&self, keep: &HashSet<String>) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let stale: Vec<(String, i64)> = tx.prepare("SELECT hash, doc FROM content_hashes")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter(|row| row.as_ref().map_or(true, |(hash, _)| !keep.contains(hash)))
+            .collect::<rusqlite::Result<_>>()?;
+        {
+            let mut delete_doc = tx.prepare("DELETE FROM content WHERE rowid = ?1")?;
+            let mut delete_hash = tx.prepare("DELETE FROM content_hashes WHERE hash = ?1")?;
+            for (hash, doc) in &stale {
+                delete_doc.execute(params![doc])?;
+                delete_hash.execute(params![hash])?;
+            }
+        }
+        tx.commit()?;
+        Ok(stale.len())
+    }
+
+    /// 按 FTS5 查询语法搜索，返回 (哈希, 相关度, 摘要)，相关度从高到低
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<(String, f64, String)>> {
+        let mut statement = self.conn.prepare(
+            "SELECT hash, -bm25(content), snippet(content, 1, '[', ']', '…', 12) FROM content
+             WHERE content MATCH ?1 ORDER BY bm25(content) LIMIT ?2",
+        )?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let rows = statement.query_map(params![query, limit], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .with_context(|| format!("Invalid content search query: {}", query))?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_content_index_ranks_and_prunes() {
+        let dir = TempDir::new().unwrap();
+        let index = ContentIndex::open(dir.path()).unwrap();
+        index.add("h1", "the quick brown fox jumps over the lazy dog").unwrap();
+        index.add("h2", "fox fox fox: a field guide to foxes").unwrap();
+        index.add("h2", "duplicate content is indexed once").unwrap();
+
+        let hits = index.search("fox", 10).unwrap();
+        assert_eq!(hits.iter().map(|(hash, _, _)| hash.as_str()).collect::<Vec<_>>(), vec!["h2", "h1"]);
+        assert!(hits[0].1 > hits[1].1);
+        assert!(hits[1].2.contains("[fox]"));
+        assert!(index.search("duplicate", 10).unwrap().is_empty());
+        assert!(index.search("\"unbalanced", 10).is_err());
+
+        let keep: HashSet<String> = ["h1".to_string()].into_iter().collect();
+        assert_eq!(index.retain(&keep).unwrap(), 1);
+        assert!(!index.contains("h2").unwrap());
+
+        // 查重按哈希表的主键查找，不扫描全文表
+        let plan: String = index.conn
+            .query_row("EXPLAIN QUERY PLAN SELECT doc FROM content_hashes WHERE hash = 'h1'", [], |row| row.get(3))
+            .unwrap();
+        assert!(plan.contains("PRIMARY KEY"), "{}", plan);
+
+        assert_eq!(extract_text(b"plain text", 0), Some("plain text"));
+        assert_eq!(extract_text(b"plain text", 4), None);
+        assert_eq!(extract_text(b"\0binary", 0), None);
+        assert_eq!(extract_text(&[0xff, 0xfe], 0), None);
+    }
+
+    #[test]
+    fn test_old_content_index_is_upgraded() {
+        let dir = TempDir::new().unwrap();
+        {
+            let conn = Connection::open(dir.path().join(CONTENT_INDEX_FILE)).unwrap();
+            conn.execute_batch(
+                "CREATE VIRTUAL TABLE content USING fts5(hash UNINDEXED, body, tokenize = 'unicode61');
+                 INSERT INTO content (hash, body) VALUES ('old', 'legacy document');",
+            ).unwrap();
+        }
+
+        let index = ContentIndex::open(dir.path()).unwrap();
+        assert!(index.contains("old").unwrap());
+        index.add("old", "not added twice").unwrap();
+        assert!(index.search("twice", 10).unwrap().is_empty());
+        assert_eq!(index.retain(&HashSet::new()).unwrap(), 1);
+        assert!(index.search("legacy", 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+use std::sync::Arc;
+use anyhow::{Result, anyhow};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+/// 加密数据头部魔数
+const ENCRYPTED_MAGIC: &[u8; 8] = b"STWRENC1";
+/// XChaCha20-Poly1305 随机数长度
+const NONCE_LEN: usize = 24;
+/// 密钥长度（256 位）
+pub const KEY_LEN: usize = 32;
+
+/// 对称加密密钥
+///
+/// Debug 输出不会泄露密钥内容
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; KEY_LEN]);
+
+impl EncryptionKey {
+    /// 从原始字节创建密钥
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// 从切片创建密钥，长度必须为 32 字节
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        let bytes: [u8; KEY_LEN] = bytes.try_into()
+            .map_err(|_| anyhow!("Encryption key must be {} bytes", KEY_LEN))?;
+        Ok(Self(bytes))
+    }
+
+    /// 生成随机密钥
+    pub fn generate() -> Self {
+        Self(XChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// 获取原始字节
+    pub fn as_bytes(&self) -> &[u8; KEY_LEN] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// 密钥提供者
+///
+/// 仓库中所有需要加密的部分（索引、存储文件）共用同一个密钥提供者，
+/// 应用可以从系统钥匙串、口令派生或 KMS 获取主密钥。
+pub trait KeyProvider: Send + Sync {
+    /// 当前主密钥的标识
+    fn key_id(&self) -> String;
+
+    /// 获取当前主密钥
+    fn master_key(&self) -> Result<EncryptionKey>;
+}
+
+/// 直接持有密钥的密钥提供者
+#[derive(Debug, Clone)]
+pub struct StaticKeyProvider {
+    key_id: String,
+    key: EncryptionKey,
+}
+
+impl StaticKeyProvider {
+    pub fn new(key_id: impl Into<String>, key: EncryptionKey) -> Self {
+        Self {
+            key_id: key_id.into(),
+            key,
+        }
+    }
+
+    /// 包装为共享的密钥提供者
+    pub fn shared(key_id: impl Into<String>, key: EncryptionKey) -> Arc<dyn KeyProvider> {
+        Arc::new(Self::new(key_id, key))
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn key_id(&self) -> String {
+        self.key_id.clone()
+    }
+
+    fn master_key(&self) -> Result<EncryptionKey> {
+        Ok(self.key.clone())
+    }
+}
+
+/// Argon2id 口令派生参数
+///
+/// 参数与盐值一起保存在仓库清单中，打开仓库时按相同参数重新派生主密钥
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// 内存开销（KiB）
+    pub memory_kib: u32,
+    /// 迭代次数
+    pub iterations: u32,
+    /// 并行度
+    pub parallelism: u32,
+    /// 盐值（十六进制）
+    pub salt: String,
+}
+
+impl KdfParams {
+    /// 使用推荐参数（19 MiB、2 次迭代、并行度 1）和随机盐值
+    pub fn new() -> Self {
+        Self::with_cost(19 * 1024, 2, 1)
+    }
+
+    /// 使用指定开销和随机盐值
+    pub fn with_cost(memory_kib: u32, iterations: u32, parallelism: u32) -> Self {
+        Self {
+            memory_kib,
+            iterations,
+            parallelism,
+            salt: to_hex(&random_bytes(16)),
+        }
+    }
+
+    /// 使用相同开销和新的随机盐值
+    pub fn resalted(&self) -> Self {
+        Self::with_cost(self.memory_kib, self.iterations, self.parallelism)
+    }
+
+    /// 从口令派生主密钥
+    pub fn derive_key(&self, password: &str) -> Result<EncryptionKey> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_LEN))
+            .map_err(|e| anyhow!("Invalid Argon2 parameters: {}", e))?;
+        let salt = from_hex(&self.salt)?;
+
+        let mut key = [0u8; KEY_LEN];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &salt, &mut key)
+            .map_err(|e| anyhow!("Failed to derive key from password: {}", e))?;
+        Ok(EncryptionKey::from_bytes(key))
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 生成随机字节
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// 加密数据
+///
+/// 输出格式：魔数(8) + 随机数(24) + 密文（含 16 字节认证标签）
+pub fn encrypt(key: &EncryptionKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = XChaCha20Poly1305::new(key.as_bytes().into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("Failed to encrypt data"))?;
+
+    let mut output = Vec::with_capacity(ENCRYPTED_MAGIC.len() + NONCE_LEN + ciphertext.len());
+    output.extend_from_slice(ENCRYPTED_MAGIC);
+    output.extend_from_slice(&nonce);
+    output.extend_from_slice(&ciphertext);
+    Ok(output)
+}
+
+/// 解密由 [`encrypt`] 生成的数据
+pub fn decrypt(key: &EncryptionKey, data: &[u8]) -> Result<Vec<u8>> {
+    if !is_encrypted(data) || data.len() < ENCRYPTED_MAGIC.len() + NONCE_LEN {
+        return Err(anyhow!("Invalid encrypted data: missing header"));
+    }
+
+    let nonce = XNonce::from_slice(&data[ENCRYPTED_MAGIC.len()..ENCRYPTED_MAGIC.len() + NONCE_LEN]);
+    let cipher = XChaCha20Poly1305::new(key.as_bytes().into());
+    cipher.decrypt(nonce, &data[ENCRYPTED_MAGIC.len() + NONCE_LEN..])
+        .map_err(|_| anyhow!("Failed to decrypt data: wrong key or corrupted data"))
+}
+
+/// 数据是否为加密格式
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(ENCRYPTED_MAGIC)
+}
+
+/// 用主密钥包装数据密钥（信封加密）
+///
+/// 每个存储文件使用独立的随机数据密钥加密，索引中只保存被主密钥
+/// 包装后的数据密钥。轮换主密钥时只需重新包装这些数据密钥，
+/// 无需重新加密所有存储文件。
+pub fn wrap_key(master: &EncryptionKey, data_key: &EncryptionKey) -> Result<String> {
+    Ok(to_hex(&encrypt(master, data_key.as_bytes())?))
+}
+
+/// 用主密钥解开被包装的数据密钥
+pub fn unwrap_key(master: &EncryptionKey, wrapped: &str) -> Result<EncryptionKey> {
+    let data = from_hex(wrapped)?;
+    EncryptionKey::from_slice(&decrypt(master, &data)?)
+}
+
+/// 编码为小写十六进制字符串
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 解析十六进制字符串，只接受 ASCII 十六进制数字（大小写均可）
+pub fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(anyhow!("Invalid hex string: odd length"));
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| Ok(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
+        .collect()
+}
+
+fn hex_digit(byte: u8) -> Result<u8> {
+    match byte {
+        b'0'..=b'9' => Ok(byte - b'0'),
+        b'a'..=b'f' => Ok(byte - b'a' + 10),
+        b'A'..=b'F' => Ok(byte - b'A' + 10),
+        _ => Err(anyhow!("Invalid hex string")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_hex_rejects_non_hex_digits() {
+        assert_eq!(from_hex("00ffA0").unwrap(), vec![0x00, 0xff, 0xa0]);
+        assert_eq!(from_hex(&to_hex(b"stowr")).unwrap(), b"stowr");
+        // 多字节字符不能按字节切片，`+` 会被 from_str_radix 接受
+        for bad in ["a\u{e9}b", "\u{e9}\u{e9}", "+f", "-1", " f", "0g", "abc"] {
+            assert!(from_hex(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let key = EncryptionKey::generate();
+        let encrypted = encrypt(&key, b"secret index").unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(decrypt(&key, &encrypted).unwrap(), b"secret index");
+    }
+
+    #[test]
+    fn test_wrap_and_rewrap_data_key() {
+        let old_master = EncryptionKey::generate();
+        let new_master = EncryptionKey::generate();
+        let data_key = EncryptionKey::generate();
+
+        let wrapped = wrap_key(&old_master, &data_key).unwrap();
+        let unwrapped = unwrap_key(&old_master, &wrapped).unwrap();
+        assert_eq!(unwrapped, data_key);
+
+        let rewrapped = wrap_key(&new_master, &unwrapped).unwrap();
+        assert!(unwrap_key(&old_master, &rewrapped).is_err());
+        assert_eq!(unwrap_key(&new_master, &rewrapped).unwrap(), data_key);
+    }
+
+    #[test]
+    fn test_derive_key_is_deterministic_per_salt() {
+        let params = KdfParams::with_cost(64, 1, 1);
+        let key = params.derive_key("password").unwrap();
+
+        assert_eq!(params.derive_key("password").unwrap(), key);
+        assert_ne!(params.derive_key("other").unwrap(), key);
+        assert_ne!(params.resalted().derive_key("password").unwrap(), key);
+    }
+
+    #[test]
+    fn test_decrypt_with_wrong_key_fails() {
+        let encrypted = encrypt(&EncryptionKey::generate(), b"data").unwrap();
+        assert!(decrypt(&EncryptionKey::generate(), &encrypted).is_err());
+        assert!(decrypt(&EncryptionKey::generate(), b"plain").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::error::StowrError;
+
+/// 单个操作的截止时间
+///
+/// 在流水线的各个阶段之间调用 [`Deadline::check`]，超时后返回
+/// `StowrError::Timeout`，调用方负责清理已经产生的中间结果
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    operation: &'static str,
+    path: PathBuf,
+    limit: Duration,
+    expires_at: Instant,
+}
+
+impl Deadline {
+    /// 创建截止时间，`timeout_ms` 为 0 表示不限制
+    pub fn new(operation: &'static str, path: &Path, timeout_ms: u64) -> Option<Self> {
+        if timeout_ms == 0 {
+            return None;
+        }
+        let limit = Duration::from_millis(timeout_ms);
+        Some(Self {
+            operation,
+            path: path.to_path_buf(),
+            limit,
+            expires_at: Instant::now() + limit,
+        })
+    }
+
+    /// 是否已超时
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+
+    /// 超时时返回 `StowrError::Timeout`
+    pub fn check(&self) -> Result<()> {
+        if self.is_expired() {
+            return Err(StowrError::Timeout {
+                operation: self.operation.to_string(),
+                path: self.path.clone(),
+                limit_ms: self.limit.as_millis() as u64,
+            }.into());
+        }
+        Ok(())
+    }
+}
+
+/// 差分相似度搜索的候选数量和时间预算
+///
+/// 与 [`Deadline`] 不同，预算用尽不是错误，调用方放弃差分，按基础文件存储
+#[derive(Debug, Clone)]
+pub(crate) struct SearchBudget {
+    max_candidates: usize,
+    max_duration: Option<Duration>,
+    started_at: Instant,
+    examined: usize,
+}
+
+impl SearchBudget {
+    /// `max_candidates` 和 `max_millis` 为 0 表示不限制
+    pub(crate) fn new(max_candidates: usize, max_millis: u64) -> Self {
+        Self {
+            max_candidates,
+            max_duration: (max_millis > 0).then(|| Duration::from_millis(max_millis)),
+            started_at: Instant::now(),
+            examined: 0,
+        }
+    }
+
+    /// 准备比较下一个候选，预算用尽时返回 false
+    pub(crate) fn try_examine(&mut self) -> bool {
+        if self.max_candidates > 0 && self.examined >= self.max_candidates {
+            return false;
+        }
+        if self.max_duration.is_some_and(|limit| self.started_at.elapsed() >= limit) {
+            return false;
+        }
+        self.examined += 1;
+        true
+    }
+}
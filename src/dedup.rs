@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 use sha2::{Sha256, Digest};
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
@@ -47,6 +48,13 @@ impl ContentDeduplicator {
         format!("{:x}", hasher.finalize())
     }
 
+    /// 边读取边计算SHA256哈希值，结果与 `calculate_hash` 一致
+    pub fn calculate_hash_reader(mut reader: impl std::io::Read) -> std::io::Result<String> {
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut reader, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
     /// 检查文件是否重复
     /// 
     /// 返回 Some(storage_id) 如果文件已存在，None 如果是新文件
@@ -167,6 +175,9 @@ impl ContentDeduplicator {
             } else {
                 0.0
             },
+            logical_bytes: 0,
+            physical_bytes: 0,
+            bytes_saved: 0,
         }
     }
 
@@ -194,7 +205,7 @@ impl Default for ContentDeduplicator {
 }
 
 /// 去重统计信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DedupStats {
     /// 总文件数（包括重复）
     pub total_files: u32,
@@ -204,6 +215,25 @@ pub struct DedupStats {
     pub duplicate_files: u32,
     /// 去重率（重复文件数/总文件数）
     pub dedup_ratio: f32,
+    /// 所有条目的原始大小之和
+    pub logical_bytes: u64,
+    /// 存储文件实际占用的大小之和
+    pub physical_bytes: u64,
+    /// 节省的空间（逻辑大小 - 实际占用）
+    pub bytes_saved: u64,
+}
+
+/// 单个条目的去重详情
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryDedupInfo {
+    /// 内容哈希（旧版本存储的条目可能没有）
+    pub hash: Option<String>,
+    /// 共享同一存储文件的路径数量（包括自身）
+    pub ref_count: u32,
+    /// 实际持有存储文件的条目ID
+    pub storage_id: String,
+    /// 共享同一内容的其他路径
+    pub siblings: Vec<PathBuf>,
 }
 
 #[cfg(test)]
@@ -1,7 +1,24 @@
 use std::collections::HashMap;
+use std::io::Write;
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, anyhow};
 use crate::config::DeltaAlgorithm;
+use crate::signature::{self, BlockSignature, SignatureBuilder};
+
+/// 差分数据的格式标识
+const DELTA_MAGIC: &[u8; 14] = b"STOWR_DELTA_V1";
+/// 格式标识 + 基础文件长度 + 目标文件长度
+const DELTA_HEADER_LEN: usize = DELTA_MAGIC.len() + 8 + 8;
+/// 按行差分的格式标识，差分数据是可以直接阅读的文本
+const LINE_DELTA_MAGIC: &[u8] = b"STOWR_LINE_DELTA_V1";
+/// 按行差分允许的最多增删行数，超过时改用简单差分，回溯所需的内存约为其平方
+const MAX_LINE_EDITS: usize = 2048;
+/// 判断是否为文本时检查的前缀长度
+const TEXT_SNIFF_LEN: usize = 8192;
+/// 默认允许的差分目标文件大小上限
+pub const DEFAULT_MAX_DELTA_TARGET_SIZE: u64 = 1 << 30;
+/// 默认的候选基础文件大小比例上限
+pub const DEFAULT_CANDIDATE_SIZE_RATIO: f32 = 2.0;
 
 /// 差分存储管理器
 /// 
@@ -9,7 +26,7 @@ use crate::config::DeltaAlgorithm;
 /// 只存储差异部分，大幅减少存储空间。
 #[derive(Debug)]
 pub struct DeltaStorage {
-    /// 基础文件存储 (storage_id -> 文件数据)
+    /// 已缓存的基础文件数据 (storage_id -> 文件数据)
     base_files: HashMap<String, Vec<u8>>,
     /// 相似度阈值（0.0-1.0）
     similarity_threshold: f32,
@@ -17,6 +34,27 @@ pub struct DeltaStorage {
     delta_algorithm: DeltaAlgorithm,
     /// 基础文件的元信息
     base_file_info: HashMap<String, BaseFileInfo>,
+    /// 差分文件记录 (storage_id -> 记录)
+    delta_records: HashMap<String, DeltaRecord>,
+    /// 创建和应用差分时允许的目标文件大小上限
+    max_target_size: u64,
+    /// 候选基础文件与目标文件的大小比例上限，0 表示不限制
+    candidate_size_ratio: f32,
+    /// 是否只比较相同文件类型的基础文件
+    same_type_only: bool,
+}
+
+/// 差分文件记录，用于统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaRecord {
+    /// 基础文件的存储ID
+    pub base_storage_id: String,
+    /// 与基础文件的相似度
+    pub similarity_score: f32,
+    /// 原始文件大小
+    pub original_size: u64,
+    /// 差分数据存储大小
+    pub delta_size: u64,
 }
 
 /// 基础文件信息
@@ -30,6 +68,9 @@ pub struct BaseFileInfo {
     pub created_at: u64,
     /// 被引用次数
     pub reference_count: u32,
+    /// 图片的感知哈希，见 [`crate::phash`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perceptual_hash: Option<u64>,
 }
 
 /// 差分信息
@@ -68,7 +109,42 @@ impl DeltaStorage {
             similarity_threshold,
             delta_algorithm,
             base_file_info: HashMap::new(),
+            delta_records: HashMap::new(),
+            max_target_size: DEFAULT_MAX_DELTA_TARGET_SIZE,
+            candidate_size_ratio: 0.0,
+            same_type_only: false,
+        }
+    }
+
+    /// 设置目标文件大小上限
+    ///
+    /// 差分数据头部记录的目标长度来自磁盘，超过上限的差分数据在分配内存之前就被拒绝
+    pub fn set_max_target_size(&mut self, max_target_size: u64) {
+        self.max_target_size = max_target_size;
+    }
+
+    /// 设置候选基础文件的筛选条件
+    ///
+    /// `size_ratio` 为较大文件与较小文件的大小比例上限（如 2.0 表示 0.5×–2×），0 表示不限制；
+    /// `same_type_only` 为 true 时只比较扩展名相同的基础文件
+    pub fn set_candidate_filter(&mut self, size_ratio: f32, same_type_only: bool) {
+        self.candidate_size_ratio = size_ratio;
+        self.same_type_only = same_type_only;
+    }
+
+    /// 按大小和文件类型判断基础文件是否值得比较内容
+    pub fn is_candidate(&self, target_size: u64, target_type: &str, base_size: u64, base_type: &str) -> bool {
+        if self.same_type_only && base_type != target_type {
+            return false;
+        }
+        if self.candidate_size_ratio <= 0.0 {
+            return true;
         }
+        let (small, large) = if target_size <= base_size { (target_size, base_size) } else { (base_size, target_size) };
+        if small == 0 {
+            return large == 0;
+        }
+        large as f64 / small as f64 <= self.candidate_size_ratio as f64
     }
 
     /// 计算两个文件的相似度
@@ -155,30 +231,88 @@ impl DeltaStorage {
         matches as f32 / max_len as f32
     }
 
-    /// 寻找最相似的基础文件
+    /// 在已缓存数据的基础文件中寻找最相似的一个
     pub fn find_best_base(&self, data: &[u8], file_type: &str) -> Option<SimilarityMatch> {
+        self.find_best_base_with(data, file_type, |_| None)
+    }
+
+    /// 寻找最相似的基础文件
+    ///
+    /// 未缓存数据的基础文件通过 `loader` 按存储ID读取。
+    /// 先按登记的大小和文件类型筛选候选（见 `set_candidate_filter`），不满足条件的不读取内容。
+    /// 相同文件类型的基础文件在排序时优先，但返回的相似度不含该加成。
+    pub fn find_best_base_with<F>(&self, data: &[u8], file_type: &str, loader: F) -> Option<SimilarityMatch>
+    where
+        F: FnMut(&str) -> Option<Vec<u8>>,
+    {
+        // 按ID排序，保证结果稳定
+        let mut base_ids: Vec<&String> = self.base_file_info.keys().collect();
+        base_ids.sort();
+        self.best_of(base_ids, data, file_type, loader)
+    }
+
+    /// 按图片感知哈希寻找最相似的基础文件
+    ///
+    /// 重新编码的图片字节几乎完全不同，逐个比较内容既慢又没有意义。这里只读取感知哈希与
+    /// `perceptual_hash` 的汉明距离不超过 `max_distance` 的基础文件，按距离从近到远比较；
+    /// 是否采用差分仍由字节相似度和阈值决定。
+    pub fn find_best_image_base_with<F>(
+        &self,
+        data: &[u8],
+        file_type: &str,
+        perceptual_hash: u64,
+        max_distance: u32,
+        loader: F,
+    ) -> Option<SimilarityMatch>
+    where
+        F: FnMut(&str) -> Option<Vec<u8>>,
+    {
+        let mut shortlist: Vec<(u32, &String)> = self.base_file_info.iter()
+            .filter_map(|(id, info)| {
+                let distance = crate::phash::distance(info.perceptual_hash?, perceptual_hash);
+                (distance <= max_distance).then_some((distance, id))
+            })
+            .collect();
+        shortlist.sort();
+        self.best_of(shortlist.into_iter().map(|(_, id)| id).collect(), data, file_type, loader)
+    }
+
+    /// 依次比较候选基础文件，返回排序最高且达到阈值的一个
+    fn best_of<F>(&self, base_ids: Vec<&String>, data: &[u8], file_type: &str, mut loader: F) -> Option<SimilarityMatch>
+    where
+        F: FnMut(&str) -> Option<Vec<u8>>,
+    {
         let mut best_match = None;
-        let mut best_similarity = 0.0;
+        let mut best_rank = 0.0;
+
+        for base_id in base_ids {
+            let base_info = &self.base_file_info[base_id];
+            if !self.is_candidate(data.len() as u64, file_type, base_info.size, &base_info.file_type) {
+                continue;
+            }
+            let similarity = match self.base_files.get(base_id) {
+                Some(base_data) => self.calculate_similarity(data, base_data),
+                None => match loader(base_id) {
+                    Some(base_data) => self.calculate_similarity(data, &base_data),
+                    None => continue,
+                },
+            };
 
-        for (base_id, base_data) in &self.base_files {
             // 优先匹配相同文件类型
-            if let Some(base_info) = self.base_file_info.get(base_id) {
-                let type_bonus = if base_info.file_type == file_type { 0.1 } else { 0.0 };
-                
-                let similarity = self.calculate_similarity(data, base_data) + type_bonus;
-                
-                if similarity > best_similarity && similarity >= self.similarity_threshold {
-                    best_similarity = similarity;
-                    
-                    // 估计压缩率（基于相似度）
-                    let estimated_compression = 1.0 - (1.0 - similarity) * 0.8;
-                    
-                    best_match = Some(SimilarityMatch {
-                        base_storage_id: base_id.clone(),
-                        similarity_score: similarity,
-                        estimated_compression,
-                    });
-                }
+            let type_bonus = if base_info.file_type == file_type { 0.1 } else { 0.0 };
+            let rank = similarity + type_bonus;
+
+            if rank > best_rank && similarity >= self.similarity_threshold {
+                best_rank = rank;
+
+                // 估计压缩率（基于相似度）
+                let estimated_compression = 1.0 - (1.0 - similarity) * 0.8;
+
+                best_match = Some(SimilarityMatch {
+                    base_storage_id: base_id.clone(),
+                    similarity_score: similarity,
+                    estimated_compression,
+                });
             }
         }
 
@@ -187,8 +321,22 @@ impl DeltaStorage {
 
     /// 创建差分数据
     pub fn create_delta(&self, base_data: &[u8], target_data: &[u8]) -> Result<Vec<u8>> {
+        if target_data.len() as u64 > self.max_target_size {
+            return Err(anyhow!(
+                "Delta target of {} bytes exceeds the limit of {} bytes",
+                target_data.len(), self.max_target_size
+            ));
+        }
         match self.delta_algorithm {
             DeltaAlgorithm::Simple => self.create_simple_delta(base_data, target_data),
+            DeltaAlgorithm::TextLines => {
+                if looks_like_text(base_data) && looks_like_text(target_data) {
+                    if let Some(delta) = create_line_delta(base_data, target_data) {
+                        return Ok(delta);
+                    }
+                }
+                self.create_simple_delta(base_data, target_data)
+            }
             DeltaAlgorithm::XDelta => {
                 // TODO: 实现xdelta3算法
                 Err(anyhow!("XDelta algorithm not implemented yet"))
@@ -200,12 +348,64 @@ impl DeltaStorage {
         }
     }
 
+    /// 按基础文件的分块签名流式生成差分，返回差分数据和目标内容的签名
+    ///
+    /// 与基础文件相同位置的分块哈希一致时写入 COPY，否则写入 INSERT，因此不需要读取基础文件内容，
+    /// 内存占用与变化的字节数相关，与目标文件大小无关。生成的差分与 `create_delta` 的格式相同
+    pub fn create_delta_streaming(&self, base: &BlockSignature, mut target: impl std::io::Read) -> Result<(Vec<u8>, BlockSignature)> {
+        if !matches!(self.delta_algorithm, DeltaAlgorithm::Simple | DeltaAlgorithm::TextLines) {
+            return Err(anyhow!("Streaming delta requires the simple delta algorithm"));
+        }
+
+        let mut delta = Vec::new();
+        delta.extend_from_slice(DELTA_MAGIC);
+        delta.extend_from_slice(&base.total_len().to_le_bytes());
+        // 目标长度在读完后回填
+        delta.extend_from_slice(&0u64.to_le_bytes());
+
+        let mut buffer = vec![0u8; base.block_size()];
+        let mut builder = SignatureBuilder::new(base.block_size());
+        let mut target_len = 0u64;
+        // 尚未写出的连续 COPY 长度
+        let mut pending_copy = 0u64;
+        for index in 0.. {
+            let len = signature::read_block(&mut target, &mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            target_len += len as u64;
+            if target_len > self.max_target_size {
+                return Err(anyhow!(
+                    "Delta target exceeds the limit of {} bytes",
+                    self.max_target_size
+                ));
+            }
+
+            let block = &buffer[..len];
+            let hash = builder.add_block(block);
+            if base.block(index) == Some(&hash) {
+                pending_copy += len as u64;
+                continue;
+            }
+            push_copy(&mut delta, &mut pending_copy);
+            for chunk in block.chunks(u32::MAX as usize) {
+                delta.push(0x02);
+                delta.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+                delta.extend_from_slice(chunk);
+            }
+        }
+        push_copy(&mut delta, &mut pending_copy);
+        delta[DELTA_MAGIC.len() + 8..DELTA_HEADER_LEN].copy_from_slice(&target_len.to_le_bytes());
+
+        Ok((delta, builder.finish()))
+    }
+
     /// 简单差分算法实现
     fn create_simple_delta(&self, base_data: &[u8], target_data: &[u8]) -> Result<Vec<u8>> {
         let mut delta = Vec::new();
         
         // 写入头部信息
-        delta.extend_from_slice(b"STOWR_DELTA_V1");
+        delta.extend_from_slice(DELTA_MAGIC);
         delta.extend_from_slice(&(base_data.len() as u64).to_le_bytes());
         delta.extend_from_slice(&(target_data.len() as u64).to_le_bytes());
         
@@ -245,29 +445,42 @@ impl DeltaStorage {
     }
 
     /// 应用差分数据重建原文件
+    ///
+    /// 差分数据来自磁盘，可能损坏或被篡改：所有长度都先做边界检查，
+    /// 目标长度超过上限时直接拒绝，不会按其分配内存
     pub fn apply_delta(&self, base_data: &[u8], delta_data: &[u8]) -> Result<Vec<u8>> {
-        if delta_data.len() < 22 { // 最小头部大小
+        if delta_data.starts_with(LINE_DELTA_MAGIC) {
+            return self.apply_line_delta(base_data, delta_data);
+        }
+        if delta_data.len() < DELTA_HEADER_LEN {
             return Err(anyhow!("Invalid delta data: too short"));
         }
 
         // 检查头部
-        if &delta_data[0..14] != b"STOWR_DELTA_V1" {
+        if &delta_data[..DELTA_MAGIC.len()] != DELTA_MAGIC {
             return Err(anyhow!("Invalid delta data: wrong header"));
         }
 
-        let base_len = u64::from_le_bytes(
-            delta_data[14..22].try_into().map_err(|_| anyhow!("Invalid base length"))?
-        ) as usize;
-        let target_len = u64::from_le_bytes(
-            delta_data[22..30].try_into().map_err(|_| anyhow!("Invalid target length"))?
-        ) as usize;
+        let read_u64 = |at: usize| u64::from_le_bytes(
+            delta_data[at..at + 8].try_into().expect("slice has 8 bytes")
+        );
+        let base_len = read_u64(DELTA_MAGIC.len());
+        let target_len = read_u64(DELTA_MAGIC.len() + 8);
 
-        if base_data.len() != base_len {
+        if base_data.len() as u64 != base_len {
             return Err(anyhow!("Base data length mismatch"));
         }
+        if target_len > self.max_target_size {
+            return Err(anyhow!(
+                "Delta target of {} bytes exceeds the limit of {} bytes",
+                target_len, self.max_target_size
+            ));
+        }
+        let target_len = target_len as usize;
 
         let mut result = Vec::with_capacity(target_len);
-        let mut delta_pos = 30;
+        let mut delta_pos = DELTA_HEADER_LEN;
+        // 指令按目标文件的位置对齐基础文件：COPY 从目标文件当前位置对应的基础文件位置复制
         let mut base_pos = 0;
 
         while delta_pos < delta_data.len() {
@@ -287,6 +500,9 @@ impl DeltaStorage {
                     if base_pos + copy_len > base_data.len() {
                         return Err(anyhow!("COPY command out of bounds"));
                     }
+                    if result.len() + copy_len > target_len {
+                        return Err(anyhow!("Delta data exceeds the recorded target length"));
+                    }
 
                     result.extend_from_slice(&base_data[base_pos..base_pos + copy_len]);
                     base_pos += copy_len;
@@ -303,9 +519,14 @@ impl DeltaStorage {
                     if delta_pos + insert_len > delta_data.len() {
                         return Err(anyhow!("INSERT command out of bounds"));
                     }
+                    if result.len() + insert_len > target_len {
+                        return Err(anyhow!("Delta data exceeds the recorded target length"));
+                    }
 
                     result.extend_from_slice(&delta_data[delta_pos..delta_pos + insert_len]);
                     delta_pos += insert_len;
+                    // 插入的内容替换了基础文件中相同位置的字节
+                    base_pos += insert_len;
                 }
                 _ => return Err(anyhow!("Unknown delta command: {}", command)),
             }
@@ -318,22 +539,155 @@ impl DeltaStorage {
         Ok(result)
     }
 
-    /// 添加基础文件
+    /// 应用按行差分，格式见 [`create_line_delta`]
+    fn apply_line_delta(&self, base_data: &[u8], delta_data: &[u8]) -> Result<Vec<u8>> {
+        let mut lines = delta_data.split_inclusive(|&b| b == b'\n').peekable();
+        let header = lines.next().unwrap_or_default();
+        let mut fields = std::str::from_utf8(header)
+            .map_err(|_| anyhow!("Invalid line delta header"))?
+            .split_whitespace()
+            .skip(1)
+            .map(|field| field.parse::<u64>());
+        let (Some(Ok(base_len)), Some(Ok(target_len)), None) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(anyhow!("Invalid line delta header"));
+        };
+        if base_data.len() as u64 != base_len {
+            return Err(anyhow!("Base data length mismatch"));
+        }
+        if target_len > self.max_target_size {
+            return Err(anyhow!(
+                "Delta target of {} bytes exceeds the limit of {} bytes",
+                target_len, self.max_target_size
+            ));
+        }
+        let target_len = target_len as usize;
+
+        let mut base_lines = base_data.split_inclusive(|&b| b == b'\n');
+        let mut result = Vec::with_capacity(target_len);
+        while let Some(line) = lines.next() {
+            let Some(body) = line.strip_suffix(b"\n") else {
+                return Err(anyhow!("Invalid line delta: unterminated command"));
+            };
+            let (&command, argument) = body.split_first()
+                .ok_or_else(|| anyhow!("Invalid line delta: empty command"))?;
+            match command {
+                b'=' | b'-' => {
+                    let count: usize = std::str::from_utf8(argument).ok()
+                        .and_then(|count| count.parse().ok())
+                        .ok_or_else(|| anyhow!("Invalid line delta: bad line count"))?;
+                    for _ in 0..count {
+                        let base_line = base_lines.next()
+                            .ok_or_else(|| anyhow!("Line delta refers past the end of the base file"))?;
+                        if command == b'=' {
+                            if result.len() + base_line.len() > target_len {
+                                return Err(anyhow!("Delta data exceeds the recorded target length"));
+                            }
+                            result.extend_from_slice(base_line);
+                        }
+                    }
+                }
+                b'+' => {
+                    let mut inserted = &line[1..];
+                    // 下一行为 `\` 时插入的行在文件末尾，没有换行符
+                    if lines.next_if(|next| *next == b"\\\n").is_some() {
+                        inserted = argument;
+                    }
+                    if result.len() + inserted.len() > target_len {
+                        return Err(anyhow!("Delta data exceeds the recorded target length"));
+                    }
+                    result.extend_from_slice(inserted);
+                }
+                _ => return Err(anyhow!("Unknown line delta command: {}", command as char)),
+            }
+        }
+
+        if result.len() != target_len {
+            return Err(anyhow!("Reconstructed file size mismatch"));
+        }
+        Ok(result)
+    }
+
+    /// 创建流式应用差分的 [`DeltaApplier`]，重建的内容写到 `output`
+    pub(crate) fn applier<'a, W: Write>(&self, delta_data: &'a [u8], output: W) -> Result<DeltaApplier<'a, W>> {
+        DeltaApplier::new(delta_data, self.max_target_size, output)
+    }
+
+    /// 差分数据使用的格式，无法识别时返回 None
+    pub fn algorithm_of(delta_data: &[u8]) -> Option<DeltaAlgorithm> {
+        if delta_data.starts_with(LINE_DELTA_MAGIC) {
+            Some(DeltaAlgorithm::TextLines)
+        } else if delta_data.starts_with(DELTA_MAGIC) {
+            Some(DeltaAlgorithm::Simple)
+        } else {
+            None
+        }
+    }
+
+    /// 添加基础文件并缓存其数据
     pub fn add_base_file(&mut self, storage_id: String, data: Vec<u8>, file_type: String) {
+        self.register_base_file(storage_id.clone(), data.len() as u64, file_type);
+        self.base_files.insert(storage_id, data);
+    }
+
+    /// 登记基础文件（不缓存数据），已登记的文件保留原有引用计数
+    pub fn register_base_file(&mut self, storage_id: String, size: u64, file_type: String) {
+        let reference_count = self.delta_records.values()
+            .filter(|record| record.base_storage_id == storage_id)
+            .count() as u32;
         let info = BaseFileInfo {
-            size: data.len() as u64,
+            size,
             file_type,
             created_at: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
-            reference_count: 0,
+            reference_count,
+            perceptual_hash: None,
         };
 
-        self.base_files.insert(storage_id.clone(), data);
         self.base_file_info.insert(storage_id, info);
     }
 
+    /// 记录已登记基础文件的图片感知哈希
+    pub fn set_perceptual_hash(&mut self, storage_id: &str, perceptual_hash: u64) {
+        if let Some(info) = self.base_file_info.get_mut(storage_id) {
+            info.perceptual_hash = Some(perceptual_hash);
+        }
+    }
+
+    /// 登记差分文件，并增加其基础文件的引用计数
+    pub fn register_delta(&mut self, storage_id: String, record: DeltaRecord) {
+        self.increment_reference(&record.base_storage_id);
+        if let Some(previous) = self.delta_records.insert(storage_id, record) {
+            self.decrement_reference(&previous.base_storage_id);
+        }
+    }
+
+    /// 移除差分文件记录，并减少其基础文件的引用计数
+    pub fn remove_delta(&mut self, storage_id: &str) -> Option<DeltaRecord> {
+        let record = self.delta_records.remove(storage_id)?;
+        self.decrement_reference(&record.base_storage_id);
+        Some(record)
+    }
+
+    /// 清空所有记录，用于从索引重建
+    pub fn clear(&mut self) {
+        self.base_files.clear();
+        self.base_file_info.clear();
+        self.delta_records.clear();
+    }
+
+    /// 获取基础文件信息
+    pub fn base_file_info(&self, storage_id: &str) -> Option<&BaseFileInfo> {
+        self.base_file_info.get(storage_id)
+    }
+
+    /// 无论引用计数如何都移除基础文件记录（基础文件已从索引中移除时使用）
+    pub fn forget_base_file(&mut self, storage_id: &str) {
+        self.base_files.remove(storage_id);
+        self.base_file_info.remove(storage_id);
+    }
+
     /// 移除基础文件
     pub fn remove_base_file(&mut self, storage_id: &str) -> bool {
         if let Some(info) = self.base_file_info.get(storage_id) {
@@ -377,16 +731,29 @@ impl DeltaStorage {
 
     /// 获取差分存储统计信息
     pub fn get_stats(&self) -> DeltaStats {
-        let total_base_files = self.base_files.len() as u32;
-        let total_references = self.base_file_info.values()
-            .map(|info| info.reference_count)
-            .sum::<u32>();
+        let total_delta_files = self.delta_records.len() as u32;
+
+        let average_similarity = if self.delta_records.is_empty() {
+            0.0
+        } else {
+            self.delta_records.values()
+                .map(|record| record.similarity_score)
+                .sum::<f32>() / total_delta_files as f32
+        };
+
+        let original_size: u64 = self.delta_records.values().map(|r| r.original_size).sum();
+        let delta_size: u64 = self.delta_records.values().map(|r| r.delta_size).sum();
+        let storage_savings = if original_size == 0 {
+            0.0
+        } else {
+            (1.0 - delta_size as f64 / original_size as f64).max(0.0) as f32
+        };
 
         DeltaStats {
-            total_base_files,
-            total_delta_files: total_references,
-            average_similarity: 0.0, // TODO: 计算平均相似度
-            storage_savings: 0.0,    // TODO: 计算存储节省
+            total_base_files: self.base_file_info.len() as u32,
+            total_delta_files,
+            average_similarity,
+            storage_savings,
         }
     }
 
@@ -399,8 +766,243 @@ impl DeltaStorage {
     }
 }
 
+/// 流式应用差分：基础文件的内容依次写入，重建的内容写到 `output`
+///
+/// 两种差分格式都按顺序读取基础文件，因此基础文件和重建结果都不需要整体放在内存中，
+/// 只有差分数据本身保留在内存里。写完基础文件后调用 [`DeltaApplier::finish`] 检查长度，
+/// 结果与 [`DeltaStorage::apply_delta`] 相同
+pub(crate) struct DeltaApplier<'a, W: Write> {
+    delta: &'a [u8],
+    /// 下一条指令在差分数据中的位置
+    pos: usize,
+    /// 是否为按行差分，此时 `op` 以行计数，否则以字节计数
+    lines: bool,
+    base_len: u64,
+    target_len: u64,
+    /// 指令已经用到的基础文件位置（简单差分）
+    base_pos: u64,
+    /// 已写入的基础文件字节数
+    base_seen: u64,
+    written: u64,
+    /// 当前指令对基础文件剩余部分的处理
+    op: BaseOp,
+    /// 基础文件的当前行只读到一部分（按行差分）
+    in_line: bool,
+    output: W,
+}
+
+/// 对基础文件接下来若干字节（或行）的处理
+#[derive(Clone, Copy)]
+enum BaseOp {
+    Copy(u64),
+    Skip(u64),
+}
+
+impl<'a, W: Write> DeltaApplier<'a, W> {
+    fn new(delta: &'a [u8], max_target_size: u64, output: W) -> Result<Self> {
+        let lines = delta.starts_with(LINE_DELTA_MAGIC);
+        let (base_len, target_len, pos) = if lines {
+            let header_len = delta.iter().position(|&b| b == b'\n').map_or(delta.len(), |i| i + 1);
+            let mut fields = std::str::from_utf8(&delta[..header_len])
+                .map_err(|_| anyhow!("Invalid line delta header"))?
+                .split_whitespace()
+                .skip(1)
+                .map(|field| field.parse::<u64>());
+            let (Some(Ok(base_len)), Some(Ok(target_len)), None) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(anyhow!("Invalid line delta header"));
+            };
+            (base_len, target_len, header_len)
+        } else {
+            if delta.len() < DELTA_HEADER_LEN {
+                return Err(anyhow!("Invalid delta data: too short"));
+            }
+            if &delta[..DELTA_MAGIC.len()] != DELTA_MAGIC {
+                return Err(anyhow!("Invalid delta data: wrong header"));
+            }
+            let read_u64 = |at: usize| u64::from_le_bytes(
+                delta[at..at + 8].try_into().expect("slice has 8 bytes")
+            );
+            (read_u64(DELTA_MAGIC.len()), read_u64(DELTA_MAGIC.len() + 8), DELTA_HEADER_LEN)
+        };
+        if target_len > max_target_size {
+            return Err(anyhow!(
+                "Delta target of {} bytes exceeds the limit of {} bytes",
+                target_len, max_target_size
+            ));
+        }
+
+        Ok(Self {
+            delta,
+            pos,
+            lines,
+            base_len,
+            target_len,
+            base_pos: 0,
+            base_seen: 0,
+            written: 0,
+            op: BaseOp::Skip(0),
+            in_line: false,
+            output,
+        })
+    }
+
+    /// 写入基础文件的下一段内容
+    fn feed(&mut self, mut base: &[u8]) -> Result<()> {
+        self.base_seen += base.len() as u64;
+        if self.base_seen > self.base_len {
+            return Err(anyhow!("Base data length mismatch"));
+        }
+        while !base.is_empty() {
+            let (copy, remaining) = match self.op {
+                BaseOp::Copy(n) => (true, n),
+                BaseOp::Skip(n) => (false, n),
+            };
+            if remaining == 0 {
+                match self.next_op()? {
+                    Some(op) => self.op = op,
+                    // 指令已经用完，剩余的基础文件内容不再需要
+                    None => return Ok(()),
+                }
+                continue;
+            }
+
+            // 本次处理的字节数和用掉的行数（或字节数）
+            let (take, used) = if self.lines {
+                match base.iter().position(|&b| b == b'\n') {
+                    Some(i) => (i + 1, 1),
+                    None => (base.len(), 0),
+                }
+            } else {
+                let take = remaining.min(base.len() as u64);
+                (take as usize, take)
+            };
+            self.in_line = self.lines && used == 0;
+            if copy {
+                self.emit(&base[..take])?;
+            }
+            self.op = if copy { BaseOp::Copy(remaining - used) } else { BaseOp::Skip(remaining - used) };
+            base = &base[take..];
+        }
+        Ok(())
+    }
+
+    /// 写出重建的内容，超过记录的目标长度时返回错误
+    fn emit(&mut self, data: &[u8]) -> Result<()> {
+        if self.written + data.len() as u64 > self.target_len {
+            return Err(anyhow!("Delta data exceeds the recorded target length"));
+        }
+        self.output.write_all(data)?;
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    /// 解析下一条读取基础文件的指令，途中的插入指令直接写出；指令用完时返回 None
+    fn next_op(&mut self) -> Result<Option<BaseOp>> {
+        let delta = self.delta;
+        if self.lines {
+            while self.pos < delta.len() {
+                let end = delta[self.pos..].iter().position(|&b| b == b'\n')
+                    .ok_or_else(|| anyhow!("Invalid line delta: unterminated command"))?;
+                let line = &delta[self.pos..self.pos + end + 1];
+                self.pos += end + 1;
+                let (&command, argument) = line[..end].split_first()
+                    .ok_or_else(|| anyhow!("Invalid line delta: empty command"))?;
+                match command {
+                    b'=' | b'-' => {
+                        let count: u64 = std::str::from_utf8(argument).ok()
+                            .and_then(|count| count.parse().ok())
+                            .ok_or_else(|| anyhow!("Invalid line delta: bad line count"))?;
+                        return Ok(Some(if command == b'=' { BaseOp::Copy(count) } else { BaseOp::Skip(count) }));
+                    }
+                    b'+' => {
+                        let mut inserted = &line[1..];
+                        // 下一行为 `\` 时插入的行在文件末尾，没有换行符
+                        if delta[self.pos..].starts_with(b"\\\n") {
+                            self.pos += 2;
+                            inserted = argument;
+                        }
+                        self.emit(inserted)?;
+                    }
+                    _ => return Err(anyhow!("Unknown line delta command: {}", command as char)),
+                }
+            }
+            return Ok(None);
+        }
+
+        let Some(&command) = delta.get(self.pos) else {
+            return Ok(None);
+        };
+        let len = delta.get(self.pos + 1..self.pos + 5)
+            .map(|len| u32::from_le_bytes(len.try_into().expect("slice has 4 bytes")) as u64);
+        self.pos += 5;
+        match (command, len) {
+            (0x01, Some(len)) => {
+                if self.base_pos + len > self.base_len {
+                    return Err(anyhow!("COPY command out of bounds"));
+                }
+                self.base_pos += len;
+                Ok(Some(BaseOp::Copy(len)))
+            }
+            (0x02, Some(len)) => {
+                let inserted = delta.get(self.pos..self.pos + len as usize)
+                    .ok_or_else(|| anyhow!("INSERT command out of bounds"))?;
+                self.pos += len as usize;
+                self.emit(inserted)?;
+                // 插入的内容替换了基础文件中相同位置的字节
+                self.base_pos += len;
+                Ok(Some(BaseOp::Skip(len)))
+            }
+            (0x01, None) => Err(anyhow!("Invalid COPY command")),
+            (0x02, None) => Err(anyhow!("Invalid INSERT command")),
+            _ => Err(anyhow!("Unknown delta command: {}", command)),
+        }
+    }
+
+    /// 基础文件写完后执行剩余的指令并检查长度，返回 `output`
+    pub(crate) fn finish(mut self) -> Result<W> {
+        if self.base_seen != self.base_len {
+            return Err(anyhow!("Base data length mismatch"));
+        }
+        // 基础文件不以换行符结尾时，最后读到一部分的行也算一行
+        if self.in_line {
+            self.op = match self.op {
+                BaseOp::Copy(n) => BaseOp::Copy(n - 1),
+                BaseOp::Skip(n) => BaseOp::Skip(n - 1),
+            };
+        }
+        loop {
+            match self.op {
+                BaseOp::Copy(n) | BaseOp::Skip(n) if n > 0 && self.lines => {
+                    return Err(anyhow!("Line delta refers past the end of the base file"));
+                }
+                // 简单差分的 COPY 已经按头部记录的长度检查过，这里只剩越过基础文件末尾的插入
+                _ => {}
+            }
+            match self.next_op()? {
+                Some(op) => self.op = op,
+                None => break,
+            }
+        }
+        if self.written != self.target_len {
+            return Err(anyhow!("Reconstructed file size mismatch"));
+        }
+        Ok(self.output)
+    }
+}
+
+impl<W: Write> Write for DeltaApplier<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.feed(buf).map_err(std::io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
 /// 差分存储统计信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeltaStats {
     /// 基础文件数量
     pub total_base_files: u32,
@@ -408,14 +1010,274 @@ pub struct DeltaStats {
     pub total_delta_files: u32,
     /// 平均相似度
     pub average_similarity: f32,
-    /// 存储空间节省率
+    /// 存储空间节省率：差分文件相对其原始大小节省的比例（0.0-1.0）
     pub storage_savings: f32,
 }
 
+/// UTF-8 BOM
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 写出累积的 COPY 指令，超过单条指令上限时拆分
+fn push_copy(delta: &mut Vec<u8>, pending: &mut u64) {
+    while *pending > 0 {
+        let len = (*pending).min(u32::MAX as u64);
+        delta.push(0x01);
+        delta.extend_from_slice(&(len as u32).to_le_bytes());
+        *pending -= len;
+    }
+}
+
+/// 前缀中没有 NUL 字节的内容视为文本（与 git 的判断方式相同）
+fn looks_like_text(data: &[u8]) -> bool {
+    !data[..data.len().min(TEXT_SNIFF_LEN)].contains(&0)
+}
+
+/// 按行生成差分，增删的行数超过 `MAX_LINE_EDITS` 时返回 None
+///
+/// 格式是一行头部 `STOWR_LINE_DELTA_V1 <基础文件长度> <目标文件长度>`，之后每行一条指令：
+///
+/// - `=<n>`：复制基础文件接下来的 n 行
+/// - `-<n>`：跳过基础文件接下来的 n 行
+/// - `+<行内容>`：插入一行（原样保留，包括换行符）
+/// - `\`：上一个插入的行在文件末尾，没有换行符
+fn create_line_delta(base_data: &[u8], target_data: &[u8]) -> Option<Vec<u8>> {
+    let base_lines: Vec<&[u8]> = base_data.split_inclusive(|&b| b == b'\n').collect();
+    let target_lines: Vec<&[u8]> = target_data.split_inclusive(|&b| b == b'\n').collect();
+    let edits = diff_lines(&base_lines, &target_lines, MAX_LINE_EDITS)?;
+
+    let mut delta = Vec::new();
+    delta.extend_from_slice(LINE_DELTA_MAGIC);
+    delta.extend_from_slice(format!(" {} {}\n", base_data.len(), target_data.len()).as_bytes());
+    let mut target_pos = 0;
+    for edit in edits {
+        match edit {
+            LineEdit::Equal(count) => {
+                delta.extend_from_slice(format!("={}\n", count).as_bytes());
+                target_pos += count;
+            }
+            LineEdit::Delete(count) => delta.extend_from_slice(format!("-{}\n", count).as_bytes()),
+            LineEdit::Insert(count) => {
+                for line in &target_lines[target_pos..target_pos + count] {
+                    delta.push(b'+');
+                    delta.extend_from_slice(line);
+                    if !line.ends_with(b"\n") {
+                        delta.extend_from_slice(b"\n\\\n");
+                    }
+                }
+                target_pos += count;
+            }
+        }
+    }
+    Some(delta)
+}
+
+/// 连续的同类行操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineEdit {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Myers 差分，返回把 `base` 变为 `target` 的最短编辑序列；增删行数超过 `max_edits` 时返回 None
+fn diff_lines<'a>(base: &[&'a [u8]], target: &[&'a [u8]], max_edits: usize) -> Option<Vec<LineEdit>> {
+    // 相同内容的行映射为相同的编号，之后只比较编号
+    let mut ids: HashMap<&'a [u8], u32> = HashMap::new();
+    let mut intern = |lines: &[&'a [u8]]| -> Vec<u32> {
+        lines.iter().map(|&line| {
+            let next = ids.len() as u32;
+            *ids.entry(line).or_insert(next)
+        }).collect()
+    };
+    let a = intern(base);
+    let b = intern(target);
+
+    // 去掉相同的前缀和后缀，只对中间部分运行 Myers
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut edits = Vec::new();
+    push_edit(&mut edits, LineEdit::Equal(prefix));
+    for edit in myers(a_mid, b_mid, max_edits)? {
+        push_edit(&mut edits, edit);
+    }
+    push_edit(&mut edits, LineEdit::Equal(suffix));
+    Some(edits)
+}
+
+/// 合并相邻的同类操作
+fn push_edit(edits: &mut Vec<LineEdit>, edit: LineEdit) {
+    let count = match edit {
+        LineEdit::Equal(n) | LineEdit::Delete(n) | LineEdit::Insert(n) => n,
+    };
+    if count == 0 {
+        return;
+    }
+    match (edits.last_mut(), edit) {
+        (Some(LineEdit::Equal(n)), LineEdit::Equal(m))
+        | (Some(LineEdit::Delete(n)), LineEdit::Delete(m))
+        | (Some(LineEdit::Insert(n)), LineEdit::Insert(m)) => *n += m,
+        _ => edits.push(edit),
+    }
+}
+
+/// 按单行输出编辑序列，保存每一轮的 V 数组用于回溯
+fn myers(a: &[u32], b: &[u32], max_edits: usize) -> Option<Vec<LineEdit>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let limit = (a.len() + b.len()).min(max_edits) as isize;
+    // V[k] 为第 k 条对角线上到达的最远 x，下标偏移 limit + 1
+    let offset = limit + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // 第 d 轮开始前 V 在 [-d, d] 范围内的值
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut found = None;
+    'search: for d in 0..=limit {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'search;
+            }
+        }
+    }
+    found?;
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let row = &trace[d as usize];
+        let at = |k: isize| row[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(LineEdit::Equal(1));
+            x -= 1;
+            y -= 1;
+        }
+        edits.push(if x == prev_x { LineEdit::Insert(1) } else { LineEdit::Delete(1) });
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.extend(std::iter::repeat_n(LineEdit::Equal(1), x as usize));
+    edits.reverse();
+    Some(edits)
+}
+
+/// 文本规范化记录，提取时据此还原原始字节
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TextNormalization {
+    /// 是否去掉了开头的 UTF-8 BOM
+    #[serde(default)]
+    pub bom: bool,
+    /// 是否将 CRLF 转换为了 LF
+    #[serde(default)]
+    pub crlf: bool,
+}
+
+impl TextNormalization {
+    /// 规范化文本内容，使只有 BOM 或换行符不同的文件内容一致
+    ///
+    /// 只有全部换行都是 CRLF 时才转换换行符，保证可以精确还原。
+    /// 内容不是 UTF-8 文本或无需处理时返回 None
+    pub fn normalize(content: &[u8]) -> Option<(Vec<u8>, Self)> {
+        if std::str::from_utf8(content).is_err() {
+            return None;
+        }
+
+        let bom = content.starts_with(UTF8_BOM);
+        let body = if bom { &content[UTF8_BOM.len()..] } else { content };
+
+        let mut has_newline = false;
+        let mut all_crlf = true;
+        for (i, &byte) in body.iter().enumerate() {
+            if byte == b'\n' {
+                has_newline = true;
+                if i == 0 || body[i - 1] != b'\r' {
+                    all_crlf = false;
+                    break;
+                }
+            }
+        }
+        let crlf = has_newline && all_crlf;
+
+        if !bom && !crlf {
+            return None;
+        }
+
+        let normalized = if crlf {
+            let mut normalized = Vec::with_capacity(body.len());
+            for (i, &byte) in body.iter().enumerate() {
+                if byte == b'\r' && body.get(i + 1) == Some(&b'\n') {
+                    continue;
+                }
+                normalized.push(byte);
+            }
+            normalized
+        } else {
+            body.to_vec()
+        };
+
+        Some((normalized, Self { bom, crlf }))
+    }
+
+    /// 还原规范化前的原始字节
+    pub fn restore(&self, content: &[u8]) -> Vec<u8> {
+        let mut restored = Vec::with_capacity(content.len() + UTF8_BOM.len());
+        if self.bom {
+            restored.extend_from_slice(UTF8_BOM);
+        }
+        if self.crlf {
+            for &byte in content {
+                if byte == b'\n' {
+                    restored.push(b'\r');
+                }
+                restored.push(byte);
+            }
+        } else {
+            restored.extend_from_slice(content);
+        }
+        restored
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_text_normalization_round_trip() {
+        let original = b"\xEF\xBB\xBFline one\r\nline two\r\r\n";
+        let (normalized, transform) = TextNormalization::normalize(original).unwrap();
+        assert_eq!(normalized, b"line one\nline two\r\n");
+        assert_eq!(transform, TextNormalization { bom: true, crlf: true });
+        assert_eq!(transform.restore(&normalized), original);
+
+        // 混合换行无法精确还原，只去掉 BOM
+        let mixed = b"\xEF\xBB\xBFa\r\nb\n";
+        let (normalized, transform) = TextNormalization::normalize(mixed).unwrap();
+        assert!(!transform.crlf);
+        assert_eq!(transform.restore(&normalized), mixed);
+
+        assert!(TextNormalization::normalize(b"plain\n").is_none());
+        assert!(TextNormalization::normalize(b"\xFF\xFE\r\n").is_none());
+    }
+
     #[test]
     fn test_similarity_calculation() {
         let delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::Simple);
@@ -430,7 +1292,7 @@ mod tests {
         
         // 部分相似
         let partial_similarity = delta_storage.calculate_similarity(data1, data3);
-        assert!(partial_similarity >= 0.0 && partial_similarity <= 1.0, "Similarity should be between 0.0 and 1.0, got: {}", partial_similarity);
+        assert!((0.0..=1.0).contains(&partial_similarity), "Similarity should be between 0.0 and 1.0, got: {}", partial_similarity);
         
         // 测试更相似的字符串
         let similar_data1 = b"Hello World Test";
@@ -458,6 +1320,116 @@ mod tests {
         assert_eq!(reconstructed, target_data);
     }
 
+    #[test]
+    fn test_text_lines_delta() {
+        let delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::TextLines);
+        let base = b"fn main() {\n    let a = 1;\n    let b = 2;\n    println!(\"{}\", a + b);\n}\n";
+        let target = b"fn main() {\n    let a = 1;\n    let c = 3;\n    let b = 2;\n    println!(\"{}\", a + b);\n}";
+
+        let delta = delta_storage.create_delta(base, target).unwrap();
+        assert_eq!(DeltaStorage::algorithm_of(&delta), Some(DeltaAlgorithm::TextLines));
+        let text = String::from_utf8(delta.clone()).unwrap();
+        assert_eq!(text.lines().skip(1).collect::<Vec<_>>(), vec!["=2", "+    let c = 3;", "=2", "-1", "+}", "\\"]);
+        assert_eq!(delta_storage.apply_delta(base, &delta).unwrap(), target);
+
+        // 二进制内容使用简单差分
+        let binary = delta_storage.create_delta(b"a\0b", b"a\0c").unwrap();
+        assert_eq!(DeltaStorage::algorithm_of(&binary), Some(DeltaAlgorithm::Simple));
+        assert_eq!(delta_storage.apply_delta(b"a\0b", &binary).unwrap(), b"a\0c");
+
+        let header = format!("STOWR_LINE_DELTA_V1 {} 5\n", base.len());
+        for body in ["=99\n", "+abc\n=1\n", "\\\n", "=1", "?\n"] {
+            let malformed = format!("{}{}", header, body);
+            assert!(delta_storage.apply_delta(base, malformed.as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_stats_and_lazy_base_lookup() {
+        let mut delta_storage = DeltaStorage::new(0.5, DeltaAlgorithm::Simple);
+        delta_storage.register_base_file("base".to_string(), 11, "txt".to_string());
+        delta_storage.register_delta("d1".to_string(), DeltaRecord {
+            base_storage_id: "base".to_string(),
+            similarity_score: 0.8,
+            original_size: 100,
+            delta_size: 20,
+        });
+        delta_storage.register_delta("d2".to_string(), DeltaRecord {
+            base_storage_id: "base".to_string(),
+            similarity_score: 0.6,
+            original_size: 100,
+            delta_size: 40,
+        });
+
+        let stats = delta_storage.get_stats();
+        assert_eq!(stats.total_base_files, 1);
+        assert_eq!(stats.total_delta_files, 2);
+        assert!((stats.average_similarity - 0.7).abs() < 1e-6);
+        assert!((stats.storage_savings - 0.7).abs() < 1e-6);
+        assert_eq!(delta_storage.base_file_info("base").unwrap().reference_count, 2);
+
+        // 基础文件数据按需加载
+        let found = delta_storage.find_best_base_with(b"Hello World", "txt", |id| {
+            assert_eq!(id, "base");
+            Some(b"Hello World".to_vec())
+        }).unwrap();
+        assert_eq!(found.base_storage_id, "base");
+        assert_eq!(found.similarity_score, 1.0);
+        assert!(delta_storage.find_best_base(b"Hello World", "txt").is_none());
+
+        delta_storage.remove_delta("d1");
+        assert_eq!(delta_storage.base_file_info("base").unwrap().reference_count, 1);
+        assert!(!delta_storage.remove_base_file("base"));
+    }
+
+    #[test]
+    fn test_candidate_filter_skips_loading() {
+        let mut delta_storage = DeltaStorage::new(0.5, DeltaAlgorithm::Simple);
+        delta_storage.set_candidate_filter(2.0, true);
+        delta_storage.register_base_file("same".to_string(), 11, "txt".to_string());
+        delta_storage.register_base_file("other_type".to_string(), 11, "bin".to_string());
+        delta_storage.register_base_file("too_large".to_string(), 23, "txt".to_string());
+        delta_storage.register_base_file("too_small".to_string(), 5, "txt".to_string());
+
+        let mut loaded = Vec::new();
+        let found = delta_storage.find_best_base_with(b"Hello World", "txt", |id| {
+            loaded.push(id.to_string());
+            Some(b"Hello World".to_vec())
+        }).unwrap();
+        assert_eq!(found.base_storage_id, "same");
+        assert_eq!(loaded, vec!["same"]);
+
+        assert!(delta_storage.is_candidate(10, "txt", 20, "txt"));
+        assert!(delta_storage.is_candidate(20, "txt", 10, "txt"));
+        assert!(!delta_storage.is_candidate(0, "txt", 10, "txt"));
+
+        // 不限制时所有候选都会比较
+        delta_storage.set_candidate_filter(0.0, false);
+        assert!(delta_storage.is_candidate(1, "txt", 1000, "bin"));
+    }
+
+    #[test]
+    fn test_image_candidates_shortlisted_by_perceptual_hash() {
+        let mut delta_storage = DeltaStorage::new(0.5, DeltaAlgorithm::Simple);
+        for (id, hash) in [("far", 0xffff_0000_0000_0000u64), ("near", 0b111), ("nearest", 0b1)] {
+            delta_storage.register_base_file(id.to_string(), 11, "png".to_string());
+            delta_storage.set_perceptual_hash(id, hash);
+        }
+        delta_storage.register_base_file("unhashed".to_string(), 11, "png".to_string());
+
+        // 只读取距离不超过上限的基础文件，由近到远
+        let mut loaded = Vec::new();
+        let found = delta_storage.find_best_image_base_with(b"Hello World", "png", 0, 4, |id| {
+            loaded.push(id.to_string());
+            Some(b"Hello World".to_vec())
+        }).unwrap();
+        assert_eq!(found.base_storage_id, "nearest");
+        assert_eq!(loaded, vec!["nearest", "near"]);
+
+        // 字节相似度仍需达到阈值
+        assert!(delta_storage.find_best_image_base_with(b"Hello World", "png", 0, 4, |_| Some(b"zzzzzzzzzzz".to_vec())).is_none());
+    }
+
     #[test]
     fn test_file_type_inference() {
         use std::path::Path;
@@ -466,4 +1438,154 @@ mod tests {
         assert_eq!(DeltaStorage::infer_file_type(Path::new("image.png")), "png");
         assert_eq!(DeltaStorage::infer_file_type(Path::new("noext")), "unknown");
     }
+
+    #[test]
+    fn test_apply_delta_rejects_malformed_data() {
+        let delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::Simple);
+        let base = b"base content";
+        let mut delta = delta_storage.create_delta(base, b"base CONTENT!").unwrap();
+
+        // 头部不完整
+        for len in 0..DELTA_HEADER_LEN {
+            assert!(delta_storage.apply_delta(base, &delta[..len]).is_err());
+        }
+
+        // 目标长度超过上限时在分配之前拒绝
+        let mut huge = delta.clone();
+        huge[DELTA_MAGIC.len() + 8..DELTA_HEADER_LEN].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(delta_storage.apply_delta(base, &huge).is_err());
+
+        // 指令写出的内容超过记录的目标长度
+        delta.extend_from_slice(&[0x02, 1, 0, 0, 0, b'x']);
+        assert!(delta_storage.apply_delta(base, &delta).is_err());
+
+        let mut limited = DeltaStorage::new(0.7, DeltaAlgorithm::Simple);
+        limited.set_max_target_size(4);
+        assert!(limited.create_delta(base, b"too long").is_err());
+    }
+
+    mod prop {
+        use super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn delta_round_trip(
+                base in proptest::collection::vec(any::<u8>(), 0..512),
+                target in proptest::collection::vec(any::<u8>(), 0..512),
+            ) {
+                let delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::Simple);
+                let delta = delta_storage.create_delta(&base, &target).unwrap();
+                prop_assert_eq!(delta_storage.apply_delta(&base, &delta).unwrap(), target);
+            }
+
+            #[test]
+            fn delta_round_trip_with_edits(
+                base in proptest::collection::vec(any::<u8>(), 1..512),
+                edits in proptest::collection::vec((any::<usize>(), any::<u8>()), 0..16),
+                tail in proptest::collection::vec(any::<u8>(), 0..32),
+            ) {
+                // 相似的文件会交替产生 COPY 和 INSERT 指令
+                let mut target = base.clone();
+                for (at, byte) in edits {
+                    let at = at % target.len();
+                    target[at] = byte;
+                }
+                target.truncate(target.len() - tail.len().min(target.len() / 2));
+                target.extend_from_slice(&tail);
+
+                let delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::Simple);
+                let delta = delta_storage.create_delta(&base, &target).unwrap();
+                prop_assert_eq!(delta_storage.apply_delta(&base, &delta).unwrap(), target);
+            }
+
+            #[test]
+            fn line_delta_round_trip(
+                base in proptest::collection::vec("[abc]{0,3}\n?", 0..40),
+                target in proptest::collection::vec("[abc]{0,3}\n?", 0..40),
+            ) {
+                let (base, target) = (base.concat().into_bytes(), target.concat().into_bytes());
+                let delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::TextLines);
+                let delta = delta_storage.create_delta(&base, &target).unwrap();
+                prop_assert_eq!(DeltaStorage::algorithm_of(&delta), Some(DeltaAlgorithm::TextLines));
+                prop_assert_eq!(delta_storage.apply_delta(&base, &delta).unwrap(), target);
+            }
+
+            #[test]
+            fn streaming_delta_round_trip(
+                base in proptest::collection::vec(any::<u8>(), 0..2048),
+                edits in proptest::collection::vec((any::<usize>(), any::<u8>()), 0..4),
+                tail in proptest::collection::vec(any::<u8>(), 0..300),
+                block_size in 1usize..200,
+            ) {
+                let mut target = base.clone();
+                for (at, byte) in edits {
+                    if !target.is_empty() {
+                        let at = at % target.len();
+                        target[at] = byte;
+                    }
+                }
+                target.extend_from_slice(&tail);
+
+                let delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::Simple);
+                let base_sig = BlockSignature::from_bytes(&base, block_size);
+                let (delta, target_sig) = delta_storage.create_delta_streaming(&base_sig, &target[..]).unwrap();
+                prop_assert_eq!(target_sig, BlockSignature::from_bytes(&target, block_size));
+                prop_assert_eq!(delta_storage.apply_delta(&base, &delta).unwrap(), target);
+            }
+
+            #[test]
+            fn applier_matches_apply_delta(
+                base in proptest::collection::vec("[ab]{0,3}\n?", 0..20),
+                target in proptest::collection::vec("[ab]{0,3}\n?", 0..20),
+                lines in any::<bool>(),
+                corrupt in proptest::option::of((any::<usize>(), any::<u8>())),
+                chunk in 1usize..8,
+            ) {
+                // 基础文件分成小段写入，结果（包括是否出错）与一次性应用相同
+                let (base, target) = (base.concat().into_bytes(), target.concat().into_bytes());
+                let algorithm = if lines { DeltaAlgorithm::TextLines } else { DeltaAlgorithm::Simple };
+                let delta_storage = DeltaStorage::new(0.7, algorithm);
+                let mut delta = delta_storage.create_delta(&base, &target).unwrap();
+                if let Some((at, byte)) = corrupt {
+                    let at = at % delta.len();
+                    delta[at] = byte;
+                }
+
+                let streamed = delta_storage.applier(&delta, Vec::new()).and_then(|mut applier| {
+                    for piece in base.chunks(chunk) {
+                        applier.write_all(piece)?;
+                    }
+                    applier.finish()
+                });
+                match delta_storage.apply_delta(&base, &delta) {
+                    Ok(expected) => prop_assert_eq!(streamed.unwrap(), expected),
+                    Err(_) => prop_assert!(streamed.is_err()),
+                }
+            }
+
+            #[test]
+            fn apply_delta_never_panics(
+                base in proptest::collection::vec(any::<u8>(), 0..64),
+                body in proptest::collection::vec(any::<u8>(), 0..256),
+                target_len in any::<u64>(),
+                valid_header in any::<bool>(),
+            ) {
+                // 随机指令流，头部可以是合法的，以便覆盖指令解析
+                let mut delta = Vec::new();
+                if valid_header {
+                    delta.extend_from_slice(DELTA_MAGIC);
+                    delta.extend_from_slice(&(base.len() as u64).to_le_bytes());
+                    delta.extend_from_slice(&(target_len % 1024).to_le_bytes());
+                }
+                delta.extend_from_slice(&body);
+
+                let mut delta_storage = DeltaStorage::new(0.7, DeltaAlgorithm::Simple);
+                delta_storage.set_max_target_size(1 << 20);
+                if let Ok(result) = delta_storage.apply_delta(&base, &delta) {
+                    prop_assert_eq!(result.len() as u64, target_len % 1024);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// stowr-core 的可识别错误
+///
+/// 这些错误同样以 `anyhow::Error` 返回，调用方可以通过
+/// `err.downcast_ref::<StowrError>()` 判断具体类型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StowrError {
+    /// 操作预计占用的内存超过 `Config::max_memory_bytes`
+    MemoryLimitExceeded {
+        /// 预计需要的内存（字节）
+        required: u64,
+        /// 配置的上限（字节）
+        limit: u64,
+    },
+    /// 文件被内容过滤器拒绝存储
+    Rejected {
+        path: PathBuf,
+        /// 拒绝该文件的过滤器名称
+        filter: String,
+        reason: String,
+    },
+    /// 要删除的文件仍被其他条目依赖
+    HasDependents {
+        path: PathBuf,
+        /// 依赖该文件的条目路径
+        dependents: Vec<PathBuf>,
+    },
+    /// 操作超过配置的超时时间，已产生的中间结果已回滚
+    Timeout {
+        /// 操作名称（store、extract 等）
+        operation: String,
+        path: PathBuf,
+        /// 配置的超时时间（毫秒）
+        limit_ms: u64,
+    },
+    /// 目标磁盘的可用空间不足，操作在写入前被中止
+    InsufficientSpace {
+        path: PathBuf,
+        /// 需要的空间，包含 `Config::min_free_bytes` 保留的部分（字节）
+        required: u64,
+        /// 当前可用空间（字节）
+        available: u64,
+    },
+    /// 条目已固定，批量删除和自动维护操作不能移除它
+    Pinned {
+        path: PathBuf,
+    },
+    /// 读取的内容与条目记录的哈希不一致，存储文件可能已损坏
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    /// 解压输出超过 `Config::max_decompressed_bytes` 或 `Config::max_compression_ratio` 的限制
+    DecompressionLimitExceeded {
+        /// 生效的输出上限（字节）
+        limit: u64,
+    },
+    /// 共享令牌格式错误、签名不符，或共享密钥已轮换
+    InvalidShareToken,
+    /// 共享令牌已过期
+    ShareExpired {
+        /// 过期时间（RFC 3339）
+        expired_at: String,
+    },
+}
+
+impl fmt::Display for StowrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StowrError::MemoryLimitExceeded { required, limit } => write!(
+                f,
+                "Operation needs about {} bytes of memory, exceeding the configured limit of {} bytes; \
+                 lower the compression level or raise compression.max_memory",
+                required, limit
+            ),
+            StowrError::Rejected { path, filter, reason } => write!(
+                f,
+                "File rejected by filter '{}': {} ({})",
+                filter, path.display(), reason
+            ),
+            StowrError::HasDependents { path, dependents } => write!(
+                f,
+                "Cannot delete {}: {} other stored file(s) depend on it; use cascade or promote",
+                path.display(), dependents.len()
+            ),
+            StowrError::Timeout { operation, path, limit_ms } => write!(
+                f,
+                "Operation '{}' timed out after {} ms: {}",
+                operation, limit_ms, path.display()
+            ),
+            StowrError::InsufficientSpace { path, required, available } => write!(
+                f,
+                "Not enough free space to write {}: needs {} bytes but only {} bytes are available",
+                path.display(), required, available
+            ),
+            StowrError::Pinned { path } => write!(
+                f,
+                "File is pinned and cannot be removed by bulk or automated operations: {}; unpin it first",
+                path.display()
+            ),
+            StowrError::ChecksumMismatch { path, expected, actual } => write!(
+                f,
+                "Content of {} does not match its recorded hash (expected {}, got {}); the stored file may be corrupted",
+                path.display(), expected, actual
+            ),
+            StowrError::DecompressionLimitExceeded { limit } => write!(
+                f,
+                "Decompressed output exceeds the limit of {} bytes; the stored file may be corrupted or malicious \
+                 (raise compression.max_output or compression.max_ratio if it is legitimate)",
+                limit
+            ),
+            StowrError::InvalidShareToken => write!(f, "Share token is invalid or has been revoked"),
+            StowrError::ShareExpired { expired_at } => write!(f, "Share token expired at {}", expired_at),
+        }
+    }
+}
+
+impl std::error::Error for StowrError {}
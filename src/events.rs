@@ -0,0 +1,145 @@
+//! 仓库变更事件
+//!
+//! [`StorageManager::subscribe`](crate::StorageManager::subscribe) 返回一个通道接收端，之后每次修改索引的操作
+//! 都会发送对应的 [`StowrEvent`]，界面可以据此更新文件列表而不必轮询 `list_files`。
+//! 事务中的事件推迟到提交时发送，回滚的事务不发送事件。接收端被丢弃后，对应的订阅在下一次发送时移除。
+//! 事件可以序列化为带 `kind` 字段的 JSON，便于转发给前端。
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::activity::Operation;
+use crate::index::FileEntry;
+
+/// 一次仓库变更
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StowrEvent {
+    /// 新增或覆盖了条目
+    Stored {
+        #[serde(with = "crate::paths::serde_path")]
+        path: PathBuf,
+        entry_id: String,
+        /// 原始大小
+        size: u64,
+    },
+    /// 条目已提取，从索引中移除
+    Extracted {
+        #[serde(with = "crate::paths::serde_path")]
+        path: PathBuf,
+        entry_id: String,
+    },
+    /// 条目已删除
+    Deleted {
+        #[serde(with = "crate::paths::serde_path")]
+        path: PathBuf,
+        entry_id: String,
+    },
+    /// 条目改名或移动到其他目录
+    Renamed {
+        entry_id: String,
+        #[serde(with = "crate::paths::serde_path")]
+        old_path: PathBuf,
+        #[serde(with = "crate::paths::serde_path")]
+        new_path: PathBuf,
+    },
+    /// 条目的元数据（备注、集合、固定、过期时间）已修改
+    Updated {
+        #[serde(with = "crate::paths::serde_path")]
+        path: PathBuf,
+        entry_id: String,
+    },
+    /// 维护操作改写了多个条目（去重合并、gc、应用备份、迁移等），需要重新读取文件列表
+    Reloaded {
+        /// 操作名称，如 `gc`
+        operation: String,
+    },
+}
+
+impl StowrEvent {
+    /// 与活动记录对应的事件
+    pub(crate) fn from_activity(operation: Operation, entry: &FileEntry) -> Self {
+        let path = entry.original_path.clone();
+        let entry_id = entry.id.clone();
+        match operation {
+            Operation::Store => StowrEvent::Stored { path, entry_id, size: entry.file_size },
+            Operation::Extract => StowrEvent::Extracted { path, entry_id },
+            Operation::Delete => StowrEvent::Deleted { path, entry_id },
+        }
+    }
+
+    /// 元数据修改事件
+    pub(crate) fn updated(entry: &FileEntry) -> Self {
+        StowrEvent::Updated { path: entry.original_path.clone(), entry_id: entry.id.clone() }
+    }
+
+    /// 批量改写事件
+    pub(crate) fn reloaded(operation: &str) -> Self {
+        StowrEvent::Reloaded { operation: operation.to_string() }
+    }
+}
+
+/// 事件订阅者列表
+#[derive(Default)]
+pub(crate) struct EventBus {
+    subscribers: Mutex<Vec<Sender<StowrEvent>>>,
+}
+
+impl EventBus {
+    /// 新增订阅，只接收之后发送的事件
+    pub fn subscribe(&self) -> Receiver<StowrEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(sender);
+        receiver
+    }
+
+    /// 是否有订阅者，没有时调用方可以不构造事件
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.lock().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+
+    /// 按顺序发送给所有订阅者，移除接收端已丢弃的订阅
+    pub fn publish(&self, events: &[StowrEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.retain(|sender| events.iter().all(|event| sender.send(event.clone()).is_ok()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_bus_fans_out_and_drops_closed_receivers() {
+        let bus = EventBus::default();
+        assert!(!bus.has_subscribers());
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+
+        let events = vec![
+            StowrEvent::reloaded("gc"),
+            StowrEvent::Renamed {
+                entry_id: "id".to_string(),
+                old_path: PathBuf::from("a.txt"),
+                new_path: PathBuf::from("docs/a.txt"),
+            },
+        ];
+        bus.publish(&events);
+        assert_eq!(first.try_iter().collect::<Vec<_>>(), events);
+        assert_eq!(second.try_iter().collect::<Vec<_>>(), events);
+
+        drop(second);
+        bus.publish(&events[..1]);
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 1);
+        assert_eq!(first.try_iter().count(), 1);
+
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json, serde_json::json!({ "kind": "reloaded", "operation": "gc" }));
+        assert_eq!(serde_json::from_value::<StowrEvent>(json).unwrap(), events[0]);
+    }
+}
@@ -0,0 +1,214 @@
+//! 通过外部命令压缩的编解码器
+//!
+//! [`ExternalCodec`] 把数据写入外部程序（如 `zpaq`、`precomp`）的标准输入，从标准输出读取结果，
+//! 作为 [`Compressor`] 注册后即可用 `CompressionAlgorithm::Custom(id)` 选择。
+//! 适合特定格式有专用预处理器、希望与内置压缩器对比效果的场景。
+//!
+//! 命令不经过 shell，参数中的 `{level}` 替换为压缩级别。
+//! 可以限制运行时间和输出大小，超时或超限时终止子进程。
+
+use anyhow::{Context, Result, anyhow};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::compression::Compressor;
+use crate::error::StowrError;
+
+/// 错误信息中保留的标准错误输出长度
+const MAX_STDERR_LEN: usize = 4096;
+/// 等待子进程结束的轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// 通过外部命令压缩和解压的编解码器
+#[derive(Debug, Clone)]
+pub struct ExternalCodec {
+    id: String,
+    extension: String,
+    compress_command: Vec<String>,
+    decompress_command: Vec<String>,
+    timeout: Option<Duration>,
+    max_output: u64,
+}
+
+impl ExternalCodec {
+    /// 创建编解码器，命令的第一项是程序，其余是参数
+    pub fn new(
+        id: impl Into<String>,
+        extension: impl Into<String>,
+        compress_command: Vec<String>,
+        decompress_command: Vec<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            extension: extension.into(),
+            compress_command,
+            decompress_command,
+            timeout: None,
+            max_output: 0,
+        }
+    }
+
+    /// 单次调用的最长运行时间，超时后终止子进程并返回错误
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 单次调用的输出大小上限（字节），0 表示不限制
+    pub fn with_max_output(mut self, max_output: u64) -> Self {
+        self.max_output = max_output;
+        self
+    }
+
+    fn effective_limit(&self, max_output: u64) -> u64 {
+        match self.max_output {
+            0 => max_output,
+            limit => limit.min(max_output),
+        }
+    }
+
+    /// 运行命令，输出超过 `max_output` 时返回 `StowrError::DecompressionLimitExceeded`
+    fn run(&self, command: &[String], level: Option<u32>, input: &[u8], max_output: u64) -> Result<Vec<u8>> {
+        let (program, args) = command.split_first()
+            .ok_or_else(|| anyhow!("External codec '{}' has an empty command", self.id))?;
+        let args = args.iter().map(|arg| match level {
+            Some(level) => arg.replace("{level}", &level.to_string()),
+            None => arg.clone(),
+        });
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start external codec '{}': {}", self.id, program))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let over_limit = AtomicBool::new(false);
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+
+        let (status, output, errors, timed_out) = std::thread::scope(|scope| {
+            scope.spawn(move || {
+                // 子进程不读完输入就退出时写入会失败，结果由退出状态决定
+                let _ = stdin.write_all(input);
+            });
+            let output = scope.spawn(|| -> io::Result<Vec<u8>> {
+                let mut output = Vec::new();
+                (&mut stdout).take(max_output.saturating_add(1)).read_to_end(&mut output)?;
+                if output.len() as u64 > max_output {
+                    over_limit.store(true, Ordering::Relaxed);
+                }
+                Ok(output)
+            });
+            let errors = scope.spawn(move || {
+                let mut errors = Vec::new();
+                let _ = (&mut stderr).take(MAX_STDERR_LEN as u64).read_to_end(&mut errors);
+                // 超出部分继续读完丢弃，提前关闭管道会让子进程写入失败或被 SIGPIPE 终止
+                let _ = io::copy(&mut stderr, &mut io::sink());
+                errors
+            });
+
+            let mut timed_out = false;
+            let status = loop {
+                if let Some(status) = child.try_wait()? {
+                    break status;
+                }
+                timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                if timed_out || over_limit.load(Ordering::Relaxed) {
+                    let _ = child.kill();
+                    break child.wait()?;
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            };
+            let output = output.join().expect("stdout reader panicked");
+            let errors = errors.join().expect("stderr reader panicked");
+            io::Result::Ok((status, output, errors, timed_out))
+        }).with_context(|| format!("External codec '{}' failed", self.id))?;
+
+        if timed_out {
+            return Err(anyhow!("External codec '{}' timed out after {:?}", self.id, self.timeout.unwrap_or_default()));
+        }
+        if over_limit.load(Ordering::Relaxed) {
+            return Err(StowrError::DecompressionLimitExceeded { limit: max_output }.into());
+        }
+        if !status.success() {
+            return Err(anyhow!(
+                "External codec '{}' exited with {}: {}",
+                self.id, status, String::from_utf8_lossy(&errors).trim()
+            ));
+        }
+        output.with_context(|| format!("Failed to read output of external codec '{}'", self.id))
+    }
+}
+
+impl Compressor for ExternalCodec {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn file_extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn compress(&self, data: &[u8], level: u32) -> Result<Vec<u8>> {
+        self.run(&self.compress_command, Some(level), data, self.effective_limit(u64::MAX))
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        self.run(&self.decompress_command, None, data, self.effective_limit(u64::MAX))
+    }
+
+    fn decompress_limited(&self, data: &[u8], max_output: u64) -> Result<Vec<u8>> {
+        self.run(&self.decompress_command, None, data, self.effective_limit(max_output))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_external_codec_round_trip_and_limits() {
+        let gzip = ExternalCodec::new("ext-gzip", "egz", command(&["gzip", "-c", "-{level}"]), command(&["gzip", "-dc"]))
+            .with_timeout(Duration::from_secs(30));
+        let data = b"external codec data ".repeat(1000);
+        let compressed = gzip.compress(&data, 6).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(gzip.decompress(&compressed).unwrap(), data);
+
+        let err = gzip.decompress_limited(&compressed, 100).unwrap_err();
+        assert_eq!(err.downcast_ref::<StowrError>(), Some(&StowrError::DecompressionLimitExceeded { limit: 100 }));
+        assert!(gzip.clone().with_max_output(100).decompress(&compressed).is_err());
+
+        // 非零退出码带上标准错误输出
+        let err = gzip.decompress(b"not gzip").unwrap_err();
+        assert!(err.to_string().contains("exited with"));
+
+        let slow = ExternalCodec::new("slow", "slow", command(&["sleep", "5"]), command(&["sleep", "5"]))
+            .with_timeout(Duration::from_millis(50));
+        let started = Instant::now();
+        assert!(slow.compress(b"data", 0).unwrap_err().to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let missing = ExternalCodec::new("missing", "x", command(&["stowr-no-such-program"]), Vec::new());
+        assert!(missing.compress(b"data", 0).is_err());
+        assert!(missing.decompress(b"data").is_err());
+    }
+
+    #[test]
+    fn test_external_codec_drains_verbose_stderr() {
+        // 标准错误输出超过保留的长度时继续读完，子进程的写入不会失败
+        let noisy = command(&["sh", "-c", "head -c 100000 /dev/zero >&2 && cat"]);
+        let codec = ExternalCodec::new("noisy", "noisy", noisy.clone(), noisy)
+            .with_timeout(Duration::from_secs(30));
+        assert_eq!(codec.compress(b"payload", 0).unwrap(), b"payload");
+    }
+}
@@ -0,0 +1,136 @@
+//! 从 HTTP(S) 地址获取内容，需要启用 `http` feature
+//!
+//! 响应体完整读入内存后交给存储流程，不写临时文件，也不是流式处理：去重、差分和压缩都需要完整内容，
+//! 存储期间的内存占用是响应体大小的数倍。读取时按 `FetchOptions::max_bytes` 限制大小，
+//! 服务器声明的长度或实际读到的数据超过上限时立即中止。更大的文件应先下载到本地再用 `store_file` 存储。
+
+use anyhow::{Context, Result, anyhow};
+use std::io::Read;
+use std::time::Duration;
+
+/// [`StorageManager::store_from_url`](crate::StorageManager::store_from_url) 的选项
+#[derive(Debug, Clone)]
+pub struct FetchOptions {
+    /// 响应体的大小上限（字节），默认 64 MiB，0 表示不限制
+    ///
+    /// 响应体会完整保存在内存中，提高上限前应确认可用内存足够
+    pub max_bytes: u64,
+    /// 整个请求（包括读取响应体）的超时时间，None 表示不限制
+    pub timeout: Option<Duration>,
+    /// 附加的请求头，如认证信息
+    pub headers: Vec<(String, String)>,
+    /// 逻辑路径已存储时替换原有条目，否则返回错误
+    pub replace: bool,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024 * 1024,
+            timeout: Some(Duration::from_secs(300)),
+            headers: Vec::new(),
+            replace: false,
+        }
+    }
+}
+
+/// 获取到的内容
+pub(crate) struct Fetched {
+    pub content: Vec<u8>,
+    /// 响应头 `Last-Modified` 对应的 Unix 纳秒，作为条目的源文件修改时间
+    pub last_modified: Option<i64>,
+}
+
+/// 请求 `url` 并读取完整的响应体，非 2xx 状态返回错误
+pub(crate) fn fetch(url: &str, options: &FetchOptions) -> Result<Fetched> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(options.timeout)
+        .user_agent(concat!("stowr-core/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let mut request = client.get(url);
+    for (name, value) in &options.headers {
+        request = request.header(name, value);
+    }
+    let response = request.send()
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!("Failed to fetch {}: HTTP {}", url, status));
+    }
+
+    let limit = if options.max_bytes == 0 { u64::MAX } else { options.max_bytes };
+    if let Some(length) = response.content_length().filter(|&length| length > limit) {
+        return Err(anyhow!("Remote resource is {} bytes, exceeding the limit of {} bytes: {}", length, limit, url));
+    }
+    let last_modified = response.headers()
+        .get(reqwest::header::LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| chrono::DateTime::parse_from_rfc2822(value).ok())
+        .and_then(|time| time.timestamp_nanos_opt());
+
+    let mut content = Vec::new();
+    response.take(limit.saturating_add(1)).read_to_end(&mut content)
+        .with_context(|| format!("Failed to read response body: {}", url))?;
+    if content.len() as u64 > limit {
+        return Err(anyhow!("Remote resource exceeds the limit of {} bytes: {}", limit, url));
+    }
+    Ok(Fetched { content, last_modified })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, StorageManager, create_index};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    /// 依次用给定的状态行和响应体应答请求，返回服务地址
+    fn serve(responses: Vec<(&'static str, &'static [u8])>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for ((status, body), stream) in responses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nLast-Modified: Wed, 01 May 2024 12:00:00 GMT\r\nConnection: close\r\n\r\n",
+                    status, body.len()
+                ).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_store_from_url() {
+        let body: &'static [u8] = b"remote asset content";
+        let url = serve(vec![("200 OK", body), ("404 Not Found", b""), ("200 OK", body)]);
+        let dir = TempDir::new().unwrap();
+        let config = Config { storage_path: dir.path().join("storage"), ..Config::default() };
+        let mut manager = StorageManager::new(config.clone(), create_index(&config).unwrap());
+        let logical = Path::new("assets/logo.txt");
+
+        let entry = manager.store_from_url(&url, logical, &FetchOptions::default()).unwrap().into_entry();
+        assert_eq!(entry.source_mtime, Some(1_714_564_800_000_000_000));
+        assert_eq!(manager.read_file(logical).unwrap(), body);
+
+        // 已存储的路径需要显式替换，不会发出请求
+        assert!(manager.store_from_url(&url, logical, &FetchOptions::default()).is_err());
+        let replace = FetchOptions { replace: true, ..FetchOptions::default() };
+        assert!(manager.store_from_url(&url, logical, &replace).unwrap_err().to_string().contains("404"));
+        assert_eq!(manager.read_file(logical).unwrap(), body);
+
+        let limited = FetchOptions { max_bytes: 4, ..replace };
+        assert!(manager.store_from_url(&url, logical, &limited).unwrap_err().to_string().contains("limit"));
+        assert_eq!(manager.list_files().unwrap().len(), 1);
+    }
+}
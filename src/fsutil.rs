@@ -0,0 +1,341 @@
+//! 文件读写工具
+//!
+//! 所有最终文件（存储文件、提取结果、索引）都先写入临时文件，
+//! 成功后再重命名到目标路径，避免中断时留下被误认为有效的半成品。
+//! 因为文件只会被整体替换、不会原地改写，较大的存储文件可以安全地用内存映射读取。
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::error::StowrError;
+
+/// 临时文件扩展名
+const TEMP_EXTENSION: &str = "stowr-tmp";
+
+/// 原子地写入文件：先写临时文件，再重命名到目标路径
+///
+/// 提供 `temp_dir` 且与目标位于同一文件系统时在其中创建临时文件，
+/// 否则在目标所在目录创建，保证重命名不跨文件系统。
+/// `sync` 为 true 时在重命名前 fsync 临时文件，重命名后 fsync 目标所在目录
+pub fn atomic_write(path: &Path, data: &[u8], temp_dir: Option<&Path>, sync: bool) -> Result<()> {
+    let parent = path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    if let Some(temp_dir) = temp_dir {
+        fs::create_dir_all(temp_dir)
+            .context("Failed to create temporary directory")?;
+        if same_filesystem(temp_dir, parent) {
+            let temp = temp_path(temp_dir, path);
+            write_temp(&temp, sync, |file| write_data(file, data, &temp))?;
+            if fs::rename(&temp, path).is_ok() {
+                return finish(parent, sync);
+            }
+            // 无法跨目录重命名时退回到目标目录
+            let _ = fs::remove_file(&temp);
+        }
+    }
+
+    let temp = temp_path(parent, path);
+    write_temp(&temp, sync, |file| write_data(file, data, &temp))?;
+    rename_into_place(&temp, path)?;
+    finish(parent, sync)
+}
+
+/// 原子地写入由 `write` 逐步产生的内容，内容不必全部放在内存中
+///
+/// 临时文件的位置与 [`atomic_write`] 相同。`write` 只调用一次，返回错误时删除临时文件，
+/// 目标路径保持不变；临时目录中的文件无法重命名到目标时复制到目标所在目录后再重命名
+pub fn atomic_write_with(
+    path: &Path,
+    temp_dir: Option<&Path>,
+    sync: bool,
+    write: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    let parent = path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let dir = match temp_dir {
+        Some(temp_dir) => {
+            fs::create_dir_all(temp_dir)
+                .context("Failed to create temporary directory")?;
+            if same_filesystem(temp_dir, parent) { temp_dir } else { parent }
+        }
+        None => parent,
+    };
+    let mut temp = temp_path(dir, path);
+    write_temp(&temp, sync, |file| {
+        let mut writer = std::io::BufWriter::new(file);
+        write(&mut writer)?;
+        Ok(writer.flush()?)
+    })?;
+
+    if dir != parent {
+        if fs::rename(&temp, path).is_ok() {
+            return finish(parent, sync);
+        }
+        // 无法跨目录重命名时复制到目标目录
+        let fallback = temp_path(parent, path);
+        let copied = fs::copy(&temp, &fallback)
+            .and_then(|_| if sync { fs::File::open(&fallback)?.sync_all() } else { Ok(()) });
+        let _ = fs::remove_file(&temp);
+        if let Err(e) = copied {
+            let _ = fs::remove_file(&fallback);
+            return Err(e).with_context(|| format!("Failed to write temporary file: {}", fallback.display()));
+        }
+        temp = fallback;
+    }
+    rename_into_place(&temp, path)?;
+    finish(parent, sync)
+}
+
+fn write_data(file: &mut fs::File, data: &[u8], temp: &Path) -> Result<()> {
+    file.write_all(data)
+        .with_context(|| format!("Failed to write temporary file: {}", temp.display()))
+}
+
+/// 将临时文件重命名到目标路径，失败时删除临时文件
+fn rename_into_place(temp: &Path, path: &Path) -> Result<()> {
+    fs::rename(temp, path)
+        .inspect_err(|_| {
+            let _ = fs::remove_file(temp);
+        })
+        .with_context(|| format!("Failed to move temporary file into place: {}", path.display()))
+}
+
+/// 将目录项的变化（创建、重命名、删除）同步到磁盘
+#[cfg(unix)]
+pub fn sync_dir(dir: &Path) -> Result<()> {
+    fs::File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| format!("Failed to sync directory: {}", dir.display()))
+}
+
+/// 将目录项的变化（创建、重命名、删除）同步到磁盘
+#[cfg(not(unix))]
+pub fn sync_dir(_dir: &Path) -> Result<()> {
+    // Windows 无法打开目录句柄进行同步，重命名本身由文件系统日志保证
+    Ok(())
+}
+
+fn finish(parent: &Path, sync: bool) -> Result<()> {
+    if sync {
+        sync_dir(parent)?;
+    }
+    Ok(())
+}
+
+/// 写入前检查目标所在磁盘是否有足够的可用空间
+///
+/// 需要 `required` 字节并额外保留 `reserve` 字节，不足时返回 `StowrError::InsufficientSpace`；
+/// 无法获取可用空间时不做限制
+pub fn ensure_free_space(target: &Path, required: u64, reserve: u64) -> Result<()> {
+    // 目标及其上级目录可能尚未创建，检查最近的已存在目录
+    let Some(dir) = target.ancestors().skip(1).find(|dir| dir.is_dir()) else {
+        return Ok(());
+    };
+    let Ok(available) = fs2::available_space(dir) else {
+        return Ok(());
+    };
+    let required = required.saturating_add(reserve);
+    if available < required {
+        return Err(StowrError::InsufficientSpace {
+            path: target.to_path_buf(),
+            required,
+            available,
+        }.into());
+    }
+    Ok(())
+}
+
+/// 目录是否可写：创建并立即删除一个临时文件
+pub fn probe_writable(dir: &Path) -> bool {
+    let probe = temp_path(dir, Path::new("probe"));
+    let writable = fs::File::create(&probe).is_ok();
+    let _ = fs::remove_file(&probe);
+    writable
+}
+
+/// 读取的文件内容：内存映射或读入内存的副本
+pub(crate) enum FileData {
+    Mapped(memmap2::Mmap),
+    Owned(Vec<u8>),
+}
+
+impl std::ops::Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Mapped(map) => map,
+            FileData::Owned(data) => data,
+        }
+    }
+}
+
+/// 读取整个文件，不小于 `mmap_threshold` 字节时使用只读内存映射，0 表示总是读入内存
+///
+/// 映射失败（如文件系统不支持）时退回普通读取
+pub(crate) fn read_file(path: &Path, mmap_threshold: u64) -> std::io::Result<FileData> {
+    if mmap_threshold > 0 {
+        let file = fs::File::open(path)?;
+        if file.metadata()?.len() >= mmap_threshold {
+            // SAFETY: 本库对已有存储文件的写入（包括校验失败后的还原和应用备份）都通过
+            // `atomic_write`/`atomic_write_with` 整体替换，重命名后旧映射仍指向原来的文件，
+            // 映射期间内容不会被本库改写或截断；其他进程直接改写存储目录不在保证范围内
+            if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+                return Ok(FileData::Mapped(map));
+            }
+        }
+    }
+    fs::read(path).map(FileData::Owned)
+}
+
+/// 是否为写入中断留下的临时文件
+pub fn is_temp_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == TEMP_EXTENSION)
+}
+
+/// 临时文件名中保留的目标文件名前缀的最大字节数
+///
+/// 文件名通常限制为 255 字节，目标文件名接近上限时加上 UUID 和扩展名会超出
+const TEMP_NAME_PREFIX_LEN: usize = 64;
+
+fn temp_path(dir: &Path, target: &Path) -> PathBuf {
+    let mut name = target.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if name.len() > TEMP_NAME_PREFIX_LEN {
+        let end = (0..=TEMP_NAME_PREFIX_LEN).rev().find(|&i| name.is_char_boundary(i)).unwrap_or(0);
+        name.truncate(end);
+    }
+    dir.join(format!(".{}.{}.{}", name, Uuid::new_v4().simple(), TEMP_EXTENSION))
+}
+
+/// 创建临时文件并调用 `write` 写入内容，失败时删除临时文件
+///
+/// `write` 返回的错误原样传出，便于调用方区分超时、校验失败等非 I/O 错误
+fn write_temp(temp: &Path, sync: bool, write: impl FnOnce(&mut fs::File) -> Result<()>) -> Result<()> {
+    let mut file = fs::File::create(temp)
+        .with_context(|| format!("Failed to write temporary file: {}", temp.display()))?;
+    let result = write(&mut file).and_then(|_| {
+        if sync {
+            file.sync_all()
+                .with_context(|| format!("Failed to write temporary file: {}", temp.display()))?;
+        }
+        Ok(())
+    });
+    if result.is_err() {
+        drop(file);
+        let _ = fs::remove_file(temp);
+    }
+    result
+}
+
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_a: &Path, _b: &Path) -> bool {
+    // 无法直接比较时直接尝试重命名，失败会退回到目标目录
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_file_maps_large_files_only() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("blob");
+        fs::write(&path, vec![7u8; 4096]).unwrap();
+
+        let mapped = read_file(&path, 1024).unwrap();
+        assert!(matches!(mapped, FileData::Mapped(_)));
+        assert_eq!(&mapped[..], &vec![7u8; 4096][..]);
+        assert!(matches!(read_file(&path, 8192).unwrap(), FileData::Owned(_)));
+        assert!(matches!(read_file(&path, 0).unwrap(), FileData::Owned(_)));
+
+        // 整体替换后旧映射的内容不变（Windows 上不能替换仍被映射的文件）
+        #[cfg(unix)]
+        {
+            atomic_write(&path, b"replaced", None, false).unwrap();
+            assert_eq!(mapped.len(), 4096);
+        }
+        drop(mapped);
+        #[cfg(not(unix))]
+        atomic_write(&path, b"replaced", None, false).unwrap();
+        assert_eq!(&read_file(&path, 1024).unwrap()[..], b"replaced");
+        assert!(read_file(&dir.path().join("missing"), 1024).is_err());
+    }
+
+    fn leftovers(dir: &Path) -> usize {
+        fs::read_dir(dir).unwrap()
+            .filter(|e| is_temp_file(&e.as_ref().unwrap().path()))
+            .count()
+    }
+
+    #[test]
+    fn test_atomic_write_replaces_target() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("out.bin");
+        fs::write(&target, b"old").unwrap();
+
+        atomic_write(&target, b"new", None, true).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert_eq!(leftovers(dir.path()), 0);
+
+        // 接近文件名长度上限的目标，临时文件名不会超出上限
+        let long = dir.path().join(format!("{}.bin", "é".repeat(120)));
+        atomic_write(&long, b"long", None, false).unwrap();
+        assert_eq!(fs::read(&long).unwrap(), b"long");
+        assert!(temp_path(dir.path(), &long).file_name().unwrap().len() < 128);
+    }
+
+    #[test]
+    fn test_atomic_write_uses_temp_dir() {
+        let dir = TempDir::new().unwrap();
+        let temp_dir = dir.path().join("tmp");
+        let target = dir.path().join("out.bin");
+
+        atomic_write(&target, b"data", Some(&temp_dir), false).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"data");
+        assert!(temp_dir.exists());
+        assert_eq!(leftovers(&temp_dir), 0);
+
+        // 目标目录不存在时报错且不留下临时文件
+        assert!(atomic_write(&dir.path().join("missing/out.bin"), b"x", Some(&temp_dir), false).is_err());
+        assert_eq!(leftovers(&temp_dir), 0);
+    }
+
+    #[test]
+    fn test_atomic_write_with_discards_failed_writes() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("out.bin");
+        fs::write(&target, b"old").unwrap();
+
+        atomic_write_with(&target, None, true, |output| Ok(output.write_all(b"streamed")?)).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"streamed");
+
+        // 写入过程中出错时目标保持不变，错误原样传出
+        let err = atomic_write_with(&target, None, false, |output| {
+            output.write_all(b"partial")?;
+            Err(StowrError::InvalidShareToken.into())
+        }).unwrap_err();
+        assert!(matches!(err.downcast_ref::<StowrError>(), Some(StowrError::InvalidShareToken)));
+        assert_eq!(fs::read(&target).unwrap(), b"streamed");
+        assert_eq!(leftovers(dir.path()), 0);
+    }
+}
@@ -0,0 +1,164 @@
+//! 以 FUSE 挂载存储库的只读视图，需要启用 `fuse` feature（仅 Unix）
+//!
+//! 把 [`ReadOnlyView`] 接到 `fuser` 上：目录树和属性来自视图，读取文件时按需解压并使用视图的缓存。
+//! 挂载为只读，文件和目录属于发起请求的用户；修改存储库需要卸载后通过 `StorageManager` 进行。
+//! 挂载使用系统的 `fusermount`，不需要在编译时链接 libfuse。
+
+use anyhow::{Context, Result};
+use fuser::{FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request};
+use std::ffi::OsStr;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::view::{NodeAttr, NodeKind, ReadOnlyView};
+
+/// 内核缓存属性和目录项的时间，视图在挂载期间不会变化
+const TTL: Duration = Duration::from_secs(60);
+
+/// 属性中报告的块大小
+const BLOCK_SIZE: u32 = 4096;
+
+/// [`ReadOnlyView`] 的 FUSE 适配
+pub struct ViewFilesystem {
+    view: ReadOnlyView,
+}
+
+impl ViewFilesystem {
+    pub fn new(view: ReadOnlyView) -> Self {
+        Self { view }
+    }
+
+    /// 被挂载的视图
+    pub fn view(&self) -> &ReadOnlyView {
+        &self.view
+    }
+}
+
+/// 在 `mountpoint` 以只读方式挂载视图，阻塞直到被卸载（如 `fusermount -u`）
+pub fn mount(view: ReadOnlyView, mountpoint: &Path) -> Result<()> {
+    let options = [
+        MountOption::RO,
+        MountOption::FSName("stowr".to_string()),
+        MountOption::Subtype("stowr".to_string()),
+        MountOption::DefaultPermissions,
+    ];
+    fuser::mount2(ViewFilesystem::new(view), mountpoint, &options)
+        .with_context(|| format!("Failed to mount repository view at {}", mountpoint.display()))
+}
+
+/// 视图节点属性转换为 FUSE 属性：目录 0555，文件 0444，没有修改时间时使用 Unix 纪元
+fn file_attr(attr: &NodeAttr, uid: u32, gid: u32) -> FileAttr {
+    let modified = attr.modified.unwrap_or(UNIX_EPOCH);
+    let (kind, perm, nlink) = match attr.kind {
+        NodeKind::Directory => (FileType::Directory, 0o555, 2),
+        NodeKind::File => (FileType::RegularFile, 0o444, 1),
+    };
+    FileAttr {
+        ino: attr.ino,
+        size: attr.size,
+        blocks: attr.size.div_ceil(512),
+        atime: modified,
+        mtime: modified,
+        ctime: modified,
+        crtime: modified,
+        kind,
+        perm,
+        nlink,
+        uid,
+        gid,
+        rdev: 0,
+        blksize: BLOCK_SIZE,
+        flags: 0,
+    }
+}
+
+fn file_type(kind: NodeKind) -> FileType {
+    match kind {
+        NodeKind::Directory => FileType::Directory,
+        NodeKind::File => FileType::RegularFile,
+    }
+}
+
+impl Filesystem for ViewFilesystem {
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.view.lookup(parent, name) {
+            Some(attr) => reply.entry(&TTL, &file_attr(&attr, req.uid(), req.gid()), 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.view.getattr(ino) {
+            Some(attr) => reply.attr(&TTL, &file_attr(&attr, req.uid(), req.gid())),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    // 视图按 inode 缓存解码后的内容，大文件解码一次后按偏移读取，不需要按打开的句柄区分
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Ok(offset) = u64::try_from(offset) else {
+            return reply.error(libc::EINVAL);
+        };
+        match self.view.getattr(ino) {
+            None => reply.error(libc::ENOENT),
+            Some(attr) if attr.kind == NodeKind::Directory => reply.error(libc::EISDIR),
+            Some(_) => match self.view.read(ino, offset, size as usize) {
+                Ok(data) => reply.data(&data),
+                Err(e) => {
+                    crate::output::warning!("Warning: Failed to read inode {}: {}", ino, e);
+                    reply.error(libc::EIO);
+                }
+            },
+        }
+    }
+
+    fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        let Some(children) = self.view.readdir(ino) else {
+            return reply.error(if self.view.getattr(ino).is_some() { libc::ENOTDIR } else { libc::ENOENT });
+        };
+
+        // 视图不记录父目录，`..` 的 inode 由内核自行解析
+        let entries = [
+            (ino, FileType::Directory, OsStr::new(".")),
+            (ReadOnlyView::ROOT, FileType::Directory, OsStr::new("..")),
+        ].into_iter()
+            .chain(children.iter().map(|(name, attr)| (attr.ino, file_type(attr.kind), name.as_os_str())));
+        // 每一项的 offset 是下一项的位置，内核在下次调用时传回
+        for (i, (child, kind, name)) in entries.enumerate().skip(usize::try_from(offset).unwrap_or(0)) {
+            if reply.add(child, i as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_file_attr_is_read_only() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let file = NodeAttr { ino: 5, kind: NodeKind::File, size: 1000, modified: Some(modified) };
+        let attr = file_attr(&file, 1000, 100);
+        assert_eq!((attr.ino, attr.size, attr.blocks), (5, 1000, 2));
+        assert_eq!((attr.kind, attr.perm, attr.nlink), (FileType::RegularFile, 0o444, 1));
+        assert_eq!((attr.uid, attr.gid, attr.mtime), (1000, 100, modified));
+
+        let dir = NodeAttr { ino: ReadOnlyView::ROOT, kind: NodeKind::Directory, size: 0, modified: None };
+        let attr = file_attr(&dir, 0, 0);
+        assert_eq!((attr.kind, attr.perm, attr.mtime), (FileType::Directory, 0o555, SystemTime::UNIX_EPOCH));
+    }
+}
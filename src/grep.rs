@@ -0,0 +1,221 @@
+//! 在已存储的文本文件中搜索内容
+//!
+//! [`StorageManager::grep`](crate::StorageManager::grep) 按路径模式筛选条目，边解压边按行匹配正则表达式，
+//! 内存中只保留当前行。超过大小上限的条目不会读取，开头含有 NUL 字节的内容视为二进制文件并跳过；
+//! 内容相同的条目只搜索一次。
+
+use anyhow::{Context, Result};
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// 判断二进制内容时检查的开头字节数
+const BINARY_PEEK_LEN: usize = 8 * 1024;
+
+/// 搜索选项
+#[derive(Debug, Clone)]
+pub struct GrepOptions {
+    /// 只搜索原始大小不超过该值（字节）的条目，0 表示不限制
+    pub max_file_size: u64,
+    /// 每个条目最多返回的匹配行数，0 表示不限制
+    pub max_matches_per_file: usize,
+    /// 并行解压的线程数，0 表示使用 `Config::multithread`
+    pub parallelism: usize,
+    /// 忽略大小写
+    pub ignore_case: bool,
+    /// 同时搜索二进制内容
+    pub include_binary: bool,
+}
+
+impl Default for GrepOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: 16 * 1024 * 1024,
+            max_matches_per_file: 0,
+            parallelism: 0,
+            ignore_case: false,
+            include_binary: false,
+        }
+    }
+}
+
+/// 一个匹配行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrepMatch {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    /// 行号，从 1 开始
+    pub line_number: usize,
+    /// 去掉行尾换行符的行内容，非 UTF-8 字节按替换字符显示
+    pub line: String,
+}
+
+/// 编译搜索模式
+pub(crate) fn build_regex(pattern: &str, options: &GrepOptions) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .with_context(|| format!("Invalid search pattern: {}", pattern))
+}
+
+/// 在内容中逐行搜索，返回 (行号, 行内容)；二进制内容在未要求时返回空列表
+pub(crate) fn search_lines(content: &[u8], regex: &Regex, options: &GrepOptions) -> Vec<(usize, String)> {
+    let mut searcher = LineSearcher::new(regex, options);
+    searcher.consume(content);
+    searcher.finish()
+}
+
+/// 逐行搜索写入的内容，结果与 [`search_lines`] 相同
+///
+/// 只保留尚未结束的一行，以及判断是否为二进制内容之前的开头部分，适合边解压边搜索
+pub(crate) struct LineSearcher<'a> {
+    regex: &'a Regex,
+    include_binary: bool,
+    limit: usize,
+    /// 尚未结束的行；判断是否为二进制内容之前保存开头的内容
+    pending: Vec<u8>,
+    /// 是否已判断过二进制内容
+    checked: bool,
+    /// 内容为二进制且未要求搜索二进制内容
+    skipped: bool,
+    line_number: usize,
+    matches: Vec<(usize, String)>,
+}
+
+impl<'a> LineSearcher<'a> {
+    pub(crate) fn new(regex: &'a Regex, options: &GrepOptions) -> Self {
+        Self {
+            regex,
+            include_binary: options.include_binary,
+            limit: if options.max_matches_per_file == 0 { usize::MAX } else { options.max_matches_per_file },
+            pending: Vec::new(),
+            checked: false,
+            skipped: false,
+            line_number: 0,
+            matches: Vec::new(),
+        }
+    }
+
+    fn consume(&mut self, data: &[u8]) {
+        if self.skipped || self.matches.len() >= self.limit {
+            return;
+        }
+        if !self.checked {
+            self.pending.extend_from_slice(data);
+            if self.pending.len() >= BINARY_PEEK_LEN {
+                self.check_binary();
+                let pending = std::mem::take(&mut self.pending);
+                self.consume(&pending);
+            }
+            return;
+        }
+        let mut rest = data;
+        while let Some(end) = rest.iter().position(|&b| b == b'\n') {
+            if self.pending.is_empty() {
+                self.search_line(&rest[..end]);
+            } else {
+                self.pending.extend_from_slice(&rest[..end]);
+                let mut line = std::mem::take(&mut self.pending);
+                self.search_line(&line);
+                line.clear();
+                self.pending = line;
+            }
+            rest = &rest[end + 1..];
+        }
+        self.pending.extend_from_slice(rest);
+    }
+
+    fn check_binary(&mut self) {
+        self.checked = true;
+        self.skipped = !self.include_binary && self.pending[..self.pending.len().min(BINARY_PEEK_LEN)].contains(&0);
+    }
+
+    fn search_line(&mut self, line: &[u8]) {
+        self.line_number += 1;
+        if self.matches.len() < self.limit && self.regex.is_match(line) {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            self.matches.push((self.line_number, String::from_utf8_lossy(line).into_owned()));
+        }
+    }
+
+    /// 搜索最后一行（可能为空）并返回所有匹配
+    pub(crate) fn finish(mut self) -> Vec<(usize, String)> {
+        if !self.checked {
+            self.check_binary();
+            let pending = std::mem::take(&mut self.pending);
+            self.consume(&pending);
+        }
+        if self.skipped {
+            return Vec::new();
+        }
+        let last = std::mem::take(&mut self.pending);
+        self.search_line(&last);
+        self.matches
+    }
+}
+
+impl Write for LineSearcher<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.consume(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_lines_limits_and_skips_binary() {
+        let content = b"fn main() {\r\n    println!(\"TODO\");\n}\n// todo: tests\n";
+        let options = GrepOptions::default();
+        let regex = build_regex("todo", &options).unwrap();
+        assert_eq!(search_lines(content, &regex, &options), vec![(4, "// todo: tests".to_string())]);
+
+        let ignore_case = GrepOptions { ignore_case: true, max_matches_per_file: 1, ..GrepOptions::default() };
+        let regex = build_regex("todo", &ignore_case).unwrap();
+        assert_eq!(search_lines(content, &regex, &ignore_case), vec![(2, "    println!(\"TODO\");".to_string())]);
+
+        let binary = b"\x00\x01todo";
+        assert!(search_lines(binary, &regex, &ignore_case).is_empty());
+        let include_binary = GrepOptions { include_binary: true, ..ignore_case };
+        assert_eq!(search_lines(binary, &regex, &include_binary).len(), 1);
+        assert!(build_regex("(", &options).is_err());
+    }
+
+    #[test]
+    fn test_line_searcher_matches_whole_content_search() {
+        let mut content = Vec::new();
+        for i in 0..5000 {
+            content.extend_from_slice(format!("line {} {}\r\n", i, if i % 7 == 0 { "todo" } else { "done" }).as_bytes());
+        }
+        content.extend_from_slice(b"last todo");
+        let options = GrepOptions { max_matches_per_file: 100, ..GrepOptions::default() };
+        let regex = build_regex("todo", &options).unwrap();
+        let expected = search_lines(&content, &regex, &options);
+        assert_eq!(expected.len(), 100);
+
+        // 任意切分写入的结果都与整体搜索相同
+        for chunk in [1, 7, 4096, content.len()] {
+            let mut searcher = LineSearcher::new(&regex, &options);
+            for part in content.chunks(chunk) {
+                searcher.write_all(part).unwrap();
+            }
+            assert_eq!(searcher.finish(), expected);
+        }
+
+        // NUL 字节出现在第一次写入之后但仍在开头范围内时同样视为二进制内容
+        let mut binary = b"todo\n".to_vec();
+        binary.extend_from_slice(b"\x00todo\n");
+        let mut searcher = LineSearcher::new(&regex, &options);
+        for part in binary.chunks(3) {
+            searcher.write_all(part).unwrap();
+        }
+        assert!(searcher.finish().is_empty());
+    }
+}
@@ -1,19 +1,28 @@
 use anyhow::{Context, Result};
 use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use chrono;
 
+use crate::backend::StorageTier;
 use crate::config::{Config, IndexMode, CompressionAlgorithm, DeltaAlgorithm};
+use crate::crypto::{self, EncryptionKey, KeyProvider};
 use crate::dedup::DedupInfo;
-use crate::delta::DeltaInfo;
+use crate::fsutil;
+use crate::delta::{DeltaInfo, TextNormalization};
+use crate::precompress::Precompression;
+use crate::recompress::StreamEncoding;
+use crate::paths::{decode_path, encode_path, ENCODED_PATH_MARKER};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
     pub id: String,
+    #[serde(with = "crate::paths::serde_path")]
     pub original_path: PathBuf,
+    #[serde(with = "crate::paths::serde_path")]
     pub stored_path: PathBuf,
     pub file_size: u64,
     pub compressed_size: u64,
@@ -37,6 +46,66 @@ pub struct FileEntry {
     pub similarity_score: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub delta_algorithm: Option<DeltaAlgorithm>,
+    // 加密相关字段
+    /// 包装数据密钥所用主密钥的标识
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// 被主密钥包装的数据密钥（十六进制）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrapped_key: Option<String>,
+    /// 存储时源文件的修改时间（Unix 纳秒），用于快速检测变更
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_mtime: Option<i64>,
+    /// 存储前对文本内容所做的规范化，提取时据此还原
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_normalization: Option<TextNormalization>,
+    /// 存储前解开的 gzip/zstd 压缩流，提取时据此重新压缩
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_encoding: Option<StreamEncoding>,
+    /// 预压缩过滤器替换的编码片段，提取时据此重新编码
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precompression: Option<Precompression>,
+    /// 条目所属的集合，如按项目归档的一组文件
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// 过期时间（RFC 3339，UTC，精确到秒），过期后由 `StorageManager::run_maintenance` 删除
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    /// 图片的感知哈希（十六进制），启用 `image` feature 时计算，用于筛选差分候选
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perceptual_hash: Option<String>,
+    /// 用户填写的备注，如存档原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 已固定的条目不会被批量删除和自动维护操作移除
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// 存储文件所在的层级
+    #[serde(default, skip_serializing_if = "StorageTier::is_hot")]
+    pub tier: StorageTier,
+    /// 最近一次读取内容的时间（RFC 3339），从未读取时为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_accessed: Option<String>,
+    /// 内联存储的内容（未压缩），仅 `tier` 为 `Inline` 时存在；JSON 索引中保存为十六进制
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_hex_bytes")]
+    pub inline_data: Option<Vec<u8>>,
+}
+
+mod serde_hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match data {
+            Some(data) => serializer.serialize_str(&crate::crypto::to_hex(data)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|hex| crate::crypto::from_hex(&hex).map_err(serde::de::Error::custom))
+            .transpose()
+    }
 }
 
 impl FileEntry {
@@ -65,6 +134,20 @@ impl FileEntry {
             base_storage_id: None,
             similarity_score: None,
             delta_algorithm: None,
+            key_id: None,
+            wrapped_key: None,
+            source_mtime: None,
+            text_normalization: None,
+            stream_encoding: None,
+            precompression: None,
+            collection: None,
+            expires_at: None,
+            perceptual_hash: None,
+            description: None,
+            pinned: false,
+            tier: StorageTier::Hot,
+            last_accessed: None,
+            inline_data: None,
         }
     }
 
@@ -95,6 +178,11 @@ impl FileEntry {
         self.is_delta.unwrap_or(false)
     }
 
+    /// 存储文件是否已加密
+    pub fn is_encrypted(&self) -> bool {
+        self.wrapped_key.is_some()
+    }
+
     /// 获取实际存储大小（考虑引用文件）
     pub fn get_actual_storage_size(&self) -> u64 {
         if self.is_reference_file() {
@@ -105,7 +193,186 @@ impl FileEntry {
     }
 }
 
-pub trait IndexStore {
+/// 索引的汇总信息
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexSummary {
+    /// 条目数量
+    pub count: usize,
+    /// 原始内容总大小
+    pub logical_bytes: u64,
+    /// 存储文件实际占用的总大小（引用条目不计入）
+    pub physical_bytes: u64,
+}
+
+impl IndexSummary {
+    /// 计入一个条目
+    pub(crate) fn include(&mut self, entry: &FileEntry) {
+        self.count += 1;
+        self.logical_bytes += entry.file_size;
+        self.physical_bytes += entry.get_actual_storage_size();
+    }
+
+    /// 移除一个条目
+    fn exclude(&mut self, entry: &FileEntry) {
+        self.count = self.count.saturating_sub(1);
+        self.logical_bytes = self.logical_bytes.saturating_sub(entry.file_size);
+        self.physical_bytes = self.physical_bytes.saturating_sub(entry.get_actual_storage_size());
+    }
+
+    fn add(&mut self, other: &IndexSummary) {
+        self.count += other.count;
+        self.logical_bytes += other.logical_bytes;
+        self.physical_bytes += other.physical_bytes;
+    }
+
+    pub(crate) fn from_entries<'a>(entries: impl IntoIterator<Item = &'a FileEntry>) -> Self {
+        let mut summary = Self::default();
+        for entry in entries {
+            summary.include(entry);
+        }
+        summary
+    }
+}
+
+/// 列出条目时的排序方式，均为升序，相同时按路径排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EntryOrder {
+    /// 原始路径（按路径字符串逐字节比较）
+    #[default]
+    Path,
+    /// 存储时间
+    CreatedAt,
+    /// 原始大小
+    Size,
+}
+
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
+impl EntryOrder {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "path" => Ok(EntryOrder::Path),
+            "created_at" => Ok(EntryOrder::CreatedAt),
+            "size" => Ok(EntryOrder::Size),
+            _ => Err(anyhow::anyhow!("Invalid entry order. Valid values: path, created_at, size")),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            EntryOrder::Path => "path".to_string(),
+            EntryOrder::CreatedAt => "created_at".to_string(),
+            EntryOrder::Size => "size".to_string(),
+        }
+    }
+
+    /// 对应的 SQL 排序子句
+    fn sql(&self) -> &'static str {
+        match self {
+            EntryOrder::Path => "original_path",
+            EntryOrder::CreatedAt => "created_at, original_path",
+            EntryOrder::Size => "file_size, original_path",
+        }
+    }
+
+    /// 按排序方式原地排序，与 SQLite 后端的顺序一致
+    fn sort(&self, entries: &mut [FileEntry]) {
+        entries.sort_by_cached_key(|entry| {
+            let path = encode_path(&entry.original_path);
+            match self {
+                EntryOrder::Path => (String::new(), 0, path),
+                EntryOrder::CreatedAt => (entry.created_at.clone(), 0, path),
+                EntryOrder::Size => (String::new(), entry.file_size, path),
+            }
+        });
+    }
+}
+
+/// 大小分布中的一个区间，包含 `min`，不包含 `max`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeBucket {
+    pub min: u64,
+    /// None 表示没有上限
+    pub max: Option<u64>,
+    pub summary: IndexSummary,
+}
+
+/// 按区间边界生成空的大小分布，边界必须严格递增
+fn empty_buckets(bounds: &[u64]) -> Result<Vec<SizeBucket>> {
+    if bounds.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(anyhow::anyhow!("Histogram bucket bounds must be strictly increasing"));
+    }
+    let mins = std::iter::once(0).chain(bounds.iter().copied());
+    let maxes = bounds.iter().copied().map(Some).chain(std::iter::once(None));
+    Ok(mins.zip(maxes)
+        .map(|(min, max)| SizeBucket { min, max, summary: IndexSummary::default() })
+        .collect())
+}
+
+/// 目录视图中的一个子目录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeDirectory {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    /// 该目录下（含所有子目录）全部条目的汇总
+    pub summary: IndexSummary,
+}
+
+/// 目录视图：某个目录下的直接子目录和直接文件，均按路径排序
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TreeListing {
+    #[serde(with = "crate::paths::serde_path")]
+    pub path: PathBuf,
+    /// 该目录下全部条目的汇总
+    pub summary: IndexSummary,
+    pub directories: Vec<TreeDirectory>,
+    pub files: Vec<FileEntry>,
+}
+
+impl TreeListing {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            ..Self::default()
+        }
+    }
+
+    /// 计入一个条目，不在目录下的条目被忽略
+    fn include(&mut self, entry: FileEntry, directories: &mut BTreeMap<PathBuf, IndexSummary>) {
+        let Ok(rest) = entry.original_path.strip_prefix(&self.path) else {
+            return;
+        };
+        let mut components = rest.components();
+        let Some(first) = components.next() else {
+            return;
+        };
+        self.summary.include(&entry);
+        if components.next().is_some() {
+            directories.entry(self.path.join(first)).or_default().include(&entry);
+        } else {
+            self.files.push(entry);
+        }
+    }
+
+    fn from_entries(path: &Path, entries: Vec<FileEntry>) -> Self {
+        let mut listing = Self::new(path);
+        let mut directories = BTreeMap::new();
+        for entry in entries {
+            listing.include(entry, &mut directories);
+        }
+        listing.finish(directories)
+    }
+
+    fn finish(mut self, directories: BTreeMap<PathBuf, IndexSummary>) -> Self {
+        self.directories = directories.into_iter()
+            .map(|(path, summary)| TreeDirectory { path, summary })
+            .collect();
+        self.files.sort_by(|a, b| a.original_path.cmp(&b.original_path));
+        self
+    }
+}
+
+/// 索引后端，需要是 `Send`，使 `StorageManager` 可以交给工作线程（见 [`IngestQueue`](crate::IngestQueue)）
+pub trait IndexStore: Send {
     fn add_file(&mut self, entry: FileEntry) -> Result<()>;
     fn get_file(&self, original_path: &Path) -> Result<Option<FileEntry>>;
     fn remove_file(&mut self, original_path: &Path) -> Result<Option<FileEntry>>;
@@ -113,44 +380,448 @@ pub trait IndexStore {
     fn rename_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()>;
     fn move_file(&mut self, original_path: &Path, new_path: &Path) -> Result<()>;
     fn count(&self) -> Result<usize>;
+
+    /// 逐个返回所有条目，不把全部条目收集到一个 Vec 中，顺序不确定
+    ///
+    /// 迭代期间持有索引的只读借用，适合导出或扫描大型仓库
+    fn entries(&self) -> Result<Box<dyn Iterator<Item = Result<FileEntry>> + '_>> {
+        Ok(Box::new(self.list_files()?.into_iter().map(Ok)))
+    }
+
+    /// 条目数量和总大小，由后端直接计算，不需要列出全部条目
+    fn summary(&self) -> Result<IndexSummary> {
+        Ok(IndexSummary::from_entries(&self.list_files()?))
+    }
+
+    /// 列出目录下的直接子目录（含汇总）和直接文件
+    fn tree(&self, prefix: &Path) -> Result<TreeListing> {
+        Ok(TreeListing::from_entries(prefix, self.list_files()?))
+    }
+
+    /// 按指定方式排序列出所有条目，多次调用的顺序保持一致，适合分页
+    fn list_files_ordered(&self, order: EntryOrder) -> Result<Vec<FileEntry>> {
+        let mut entries = self.list_files()?;
+        order.sort(&mut entries);
+        Ok(entries)
+    }
+
+    /// 原始大小最大的 `n` 个条目，从大到小排列
+    fn largest_entries(&self, n: usize) -> Result<Vec<FileEntry>> {
+        let mut entries = self.list_files()?;
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.file_size));
+        entries.truncate(n);
+        Ok(entries)
+    }
+
+    /// 按原始大小统计条目分布
+    ///
+    /// `bounds` 是严格递增的区间边界，返回 `bounds.len() + 1` 个区间：
+    /// `[0, b0)`、`[b0, b1)`……`[bn, ∞)`
+    fn size_histogram(&self, bounds: &[u64]) -> Result<Vec<SizeBucket>> {
+        let mut buckets = empty_buckets(bounds)?;
+        for entry in self.list_files()? {
+            let index = bounds.partition_point(|&bound| bound <= entry.file_size);
+            buckets[index].summary.include(&entry);
+        }
+        Ok(buckets)
+    }
+
+    /// 在 `within` 之内过期的条目（包括已过期的），按过期时间排列，相同时按路径排列
+    fn expiring_within(&self, within: std::time::Duration) -> Result<Vec<FileEntry>> {
+        let cutoff = expiry_cutoff(within);
+        let mut entries: Vec<FileEntry> = self.list_files()?
+            .into_iter()
+            .filter(|entry| entry.expires_at.as_ref().is_some_and(|expires_at| *expires_at <= cutoff))
+            .collect();
+        entries.sort_by_cached_key(|entry| (entry.expires_at.clone(), encode_path(&entry.original_path)));
+        Ok(entries)
+    }
+
+    /// 按条目ID查找
+    fn get_file_by_id(&self, id: &str) -> Result<Option<FileEntry>> {
+        Ok(self.list_files()?.into_iter().find(|entry| entry.id == id))
+    }
+
+    /// 写入多个条目（覆盖同路径的条目），后端在一次写入中完成
+    fn add_files(&mut self, entries: Vec<FileEntry>) -> Result<()> {
+        for entry in entries {
+            self.add_file(entry)?;
+        }
+        Ok(())
+    }
+
+    /// 用给定条目整体替换索引内容（用于事务回滚）
+    fn restore(&mut self, entries: Vec<FileEntry>) -> Result<()> {
+        for entry in self.list_files()? {
+            self.remove_file(&entry.original_path)?;
+        }
+        for entry in entries {
+            self.add_file(entry)?;
+        }
+        Ok(())
+    }
+
+    /// 更换索引加密密钥（None 表示解密为明文）
+    fn set_encryption_key(&mut self, _key: Option<EncryptionKey>) -> Result<()> {
+        Err(anyhow::anyhow!("This index backend does not support encryption"))
+    }
+
+    /// 整理索引的磁盘存储，回收删除条目后留下的空间
+    fn compact(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 快速检查索引状态，不列出全部条目
+    fn health(&self) -> Result<IndexHealth> {
+        Ok(IndexHealth {
+            entry_count: self.count()?,
+            ..IndexHealth::default()
+        })
+    }
+}
+
+/// 索引的健康状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexHealth {
+    pub entry_count: usize,
+    /// 是否有其他连接持有写锁
+    pub locked: bool,
+    /// 追加日志中尚未合并到快照的记录数
+    pub pending_journal_entries: usize,
+}
+
+/// 索引在存储目录中可能使用的全部文件
+const INDEX_DISK_FILES: &[&str] = &[
+    INDEX_FILE,
+    COMPRESSED_INDEX_FILE,
+    INDEX_LOG_FILE,
+    SQLITE_INDEX_FILE,
+    "index.db-journal",
+    "index.db-wal",
+];
+
+/// 文件名是否为索引使用的文件
+/// 可记录的最晚过期时间，年份保持四位数
+const MAX_EXPIRY: &str = "9999-12-31T23:59:59Z";
+
+/// 过期时间的存储格式：UTC、精确到秒、以 `Z` 结尾，按字符串比较即按时间比较
+///
+/// 晚于 9999 年的时间记为 [`MAX_EXPIRY`]
+pub(crate) fn format_expiry(time: chrono::DateTime<chrono::Utc>) -> String {
+    let formatted = time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    if formatted.len() == MAX_EXPIRY.len() && !formatted.starts_with('-') {
+        formatted
+    } else if time.timestamp() > 0 {
+        MAX_EXPIRY.to_string()
+    } else {
+        "0000-01-01T00:00:00Z".to_string()
+    }
+}
+
+/// 距现在 `within` 之后的时间，超出可表示的范围时取最晚的过期时间
+fn expiry_cutoff(within: std::time::Duration) -> String {
+    chrono::Duration::from_std(within).ok()
+        .and_then(|within| chrono::Utc::now().checked_add_signed(within))
+        .map(format_expiry)
+        .unwrap_or_else(|| MAX_EXPIRY.to_string())
+}
+
+pub(crate) fn is_index_file(name: &str) -> bool {
+    INDEX_DISK_FILES.contains(&name)
+}
+
+/// 索引文件占用的磁盘空间
+pub(crate) fn index_disk_usage(storage_path: &Path) -> u64 {
+    INDEX_DISK_FILES.iter()
+        .filter_map(|name| fs::metadata(storage_path.join(name)).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// 明文 JSON 索引文件
+const INDEX_FILE: &str = "index.json";
+/// zstd 压缩的索引快照
+const COMPRESSED_INDEX_FILE: &str = "index.json.zst";
+/// 压缩索引的追加日志
+const INDEX_LOG_FILE: &str = "index.log";
+/// SQLite 索引数据库
+const SQLITE_INDEX_FILE: &str = "index.db";
+/// SQLite 等待其他连接释放锁的时间（rusqlite 的默认值）
+const SQLITE_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// 日志记录数达到该值（且不少于条目数）时合并到快照
+const COMPACT_MIN_RECORDS: usize = 1024;
+/// 索引快照的 zstd 压缩级别
+const INDEX_ZSTD_LEVEL: i32 = 3;
+/// SQLite 索引逐个返回条目时每次查询的条目数
+const SQLITE_ENTRY_BATCH: usize = 256;
+
+/// 追加日志中的一条修改记录
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum LogRecord {
+    Put {
+        entry: Box<FileEntry>,
+    },
+    Remove {
+        #[serde(with = "crate::paths::serde_path")]
+        path: PathBuf,
+    },
 }
 
 pub struct JsonIndex {
-    index_path: PathBuf,
+    storage_path: PathBuf,
     entries: HashMap<PathBuf, FileEntry>,
+    /// 随增删条目增量维护的汇总信息
+    totals: IndexSummary,
+    /// 索引加密密钥，设置后索引以密文形式保存
+    key: Option<EncryptionKey>,
+    /// 是否使用压缩快照加追加日志的格式
+    compressed: bool,
+    /// 追加日志中尚未合并的记录数
+    log_records: usize,
+    /// 保存后是否 fsync
+    sync: bool,
 }
 
 impl JsonIndex {
     pub fn new(storage_path: &Path) -> Result<Self> {
-        let index_path = storage_path.join("index.json");
-        let entries = if index_path.exists() {
-            let content = fs::read_to_string(&index_path)
+        Self::with_key(storage_path, None)
+    }
+
+    /// 打开（可选）加密的 JSON 索引
+    ///
+    /// 提供密钥时，已有的明文索引会立即以密文重写；
+    /// 未提供密钥却遇到加密索引时返回错误
+    pub fn with_key(storage_path: &Path, key: Option<EncryptionKey>) -> Result<Self> {
+        Self::open(storage_path, key, false)
+    }
+
+    /// 打开 JSON 索引，`compressed` 指定保存格式
+    ///
+    /// 压缩格式将索引保存为 zstd 压缩的快照 `index.json.zst`，
+    /// 每次修改只向 `index.log` 追加一条记录，日志足够长时再合并到快照，
+    /// 因此单次修改的保存开销不随条目数增长。
+    /// 已有索引的格式与 `compressed` 不一致时会在打开时自动转换。
+    pub fn open(storage_path: &Path, key: Option<EncryptionKey>, compressed: bool) -> Result<Self> {
+        let plain_path = storage_path.join(INDEX_FILE);
+        let snapshot_path = storage_path.join(COMPRESSED_INDEX_FILE);
+        let needs_rewrite;
+        let mut log_records = 0;
+
+        let entries = if snapshot_path.exists() {
+            let data = fs::read(&snapshot_path)
+                .context("Failed to read index file")?;
+            let (data, was_plain) = Self::decode(&data, key.as_ref())?;
+            let content = zstd::decode_all(&data[..])
+                .context("Failed to decompress index file")?;
+            let mut entries = Self::parse_entries(content)?;
+            log_records = Self::replay_log(&storage_path.join(INDEX_LOG_FILE), &mut entries, key.as_ref())?;
+            needs_rewrite = !compressed || (was_plain && key.is_some());
+            entries
+        } else if plain_path.exists() {
+            let data = fs::read(&plain_path)
                 .context("Failed to read index file")?;
-            serde_json::from_str(&content)
-                .unwrap_or_else(|_| HashMap::new())
+            let (content, was_plain) = Self::decode(&data, key.as_ref())?;
+            needs_rewrite = compressed || (was_plain && key.is_some());
+            Self::parse_entries(content)?
         } else {
-            HashMap::new()
+            // 新建的压缩索引在首次合并前只有追加日志
+            let mut entries = HashMap::new();
+            log_records = Self::replay_log(&storage_path.join(INDEX_LOG_FILE), &mut entries, key.as_ref())?;
+            needs_rewrite = !compressed && log_records > 0;
+            entries
         };
 
-        Ok(Self {
-            index_path,
+        let mut index = Self {
+            storage_path: storage_path.to_path_buf(),
+            totals: IndexSummary::from_entries(entries.values()),
             entries,
-        })
+            key,
+            compressed,
+            log_records,
+            sync: false,
+        };
+
+        // 启用加密或切换格式后立即迁移旧的索引
+        if needs_rewrite {
+            index.save()?;
+        }
+
+        Ok(index)
+    }
+
+    /// 设置保存索引后是否 fsync
+    pub fn with_sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// 按需解密索引数据，返回内容以及数据原本是否为明文
+    fn decode(data: &[u8], key: Option<&EncryptionKey>) -> Result<(Vec<u8>, bool)> {
+        if crypto::is_encrypted(data) {
+            let key = key
+                .ok_or_else(|| anyhow::anyhow!("Index is encrypted but no key was provided"))?;
+            let content = crypto::decrypt(key, data)
+                .context("Failed to decrypt index file")?;
+            Ok((content, false))
+        } else {
+            Ok((data.to_vec(), true))
+        }
+    }
+
+    fn encode(&self, content: Vec<u8>) -> Result<Vec<u8>> {
+        match &self.key {
+            Some(key) => crypto::encrypt(key, &content),
+            None => Ok(content),
+        }
+    }
+
+    fn parse_entries(content: Vec<u8>) -> Result<HashMap<PathBuf, FileEntry>> {
+        let content = String::from_utf8(content)
+            .context("Index file is not valid UTF-8")?;
+        // 空文件视为空索引；其他无法解析的内容返回错误，避免以空索引覆盖原有文件
+        if content.trim().is_empty() {
+            return Ok(HashMap::new());
+        }
+        // 键使用 encode_path 编码，旧索引中的 UTF-8 路径键可直接解码
+        let raw: HashMap<String, FileEntry> = serde_json::from_str(&content)
+            .context("Failed to parse index file")?;
+        raw.into_iter()
+            .map(|(key, entry)| Ok((decode_path(&key)?, entry)))
+            .collect::<Result<HashMap<_, _>>>()
+    }
+
+    /// 重放追加日志，返回有效记录数
+    ///
+    /// 日志格式为若干条 `长度(u32 LE) + 记录`。写入中断留下的不完整尾部会被忽略
+    fn replay_log(
+        log_path: &Path,
+        entries: &mut HashMap<PathBuf, FileEntry>,
+        key: Option<&EncryptionKey>,
+    ) -> Result<usize> {
+        if !log_path.exists() {
+            return Ok(0);
+        }
+        let data = fs::read(log_path)
+            .context("Failed to read index log")?;
+
+        let mut offset = 0;
+        let mut count = 0;
+        while offset + 4 <= data.len() {
+            let len = u32::from_le_bytes(data[offset..offset + 4].try_into()?) as usize;
+            let Some(record) = data.get(offset + 4..offset + 4 + len) else {
+                crate::output::warning!("Warning: Ignoring truncated record at the end of the index log");
+                break;
+            };
+            let (record, _) = Self::decode(record, key)?;
+            match serde_json::from_slice(&record).context("Failed to parse index log record")? {
+                LogRecord::Put { entry } => {
+                    entries.insert(entry.original_path.clone(), *entry);
+                }
+                LogRecord::Remove { path } => {
+                    entries.remove(&path);
+                }
+            }
+            offset += 4 + len;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// 写入完整索引，并清理其他格式的旧文件
+    fn save(&mut self) -> Result<()> {
+        let raw: HashMap<String, &FileEntry> = self.entries.iter()
+            .map(|(path, entry)| (encode_path(path), entry))
+            .collect();
+        let plain_path = self.storage_path.join(INDEX_FILE);
+        let snapshot_path = self.storage_path.join(COMPRESSED_INDEX_FILE);
+        let log_path = self.storage_path.join(INDEX_LOG_FILE);
+
+        if self.compressed {
+            let content = serde_json::to_vec(&raw)
+                .context("Failed to serialize index")?;
+            let content = zstd::encode_all(&content[..], INDEX_ZSTD_LEVEL)
+                .context("Failed to compress index")?;
+            fsutil::atomic_write(&snapshot_path, &self.encode(content)?, None, self.sync)
+                .context("Failed to write index file")?;
+            for stale in [&log_path, &plain_path] {
+                if stale.exists() {
+                    fs::remove_file(stale)
+                        .context("Failed to remove stale index file")?;
+                }
+            }
+            self.log_records = 0;
+        } else {
+            let content = serde_json::to_string_pretty(&raw)
+                .context("Failed to serialize index")?;
+            fsutil::atomic_write(&plain_path, &self.encode(content.into_bytes())?, None, self.sync)
+                .context("Failed to write index file")?;
+            for stale in [&snapshot_path, &log_path] {
+                if stale.exists() {
+                    fs::remove_file(stale)
+                        .context("Failed to remove stale index file")?;
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn save(&self) -> Result<()> {
-        let content = serde_json::to_string_pretty(&self.entries)
-            .context("Failed to serialize index")?;
-        fs::write(&self.index_path, content)
-            .context("Failed to write index file")?;
+    /// 保存一组修改：压缩格式下追加到日志，否则写入完整索引
+    fn persist(&mut self, records: Vec<LogRecord>) -> Result<()> {
+        if !self.compressed {
+            return self.save();
+        }
+
+        let mut data = Vec::new();
+        for record in &records {
+            let record = serde_json::to_vec(record)
+                .context("Failed to serialize index log record")?;
+            let record = self.encode(record)?;
+            let len = u32::try_from(record.len())
+                .map_err(|_| anyhow::anyhow!("Index log record too large"))?;
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(&record);
+        }
+
+        let mut log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.storage_path.join(INDEX_LOG_FILE))
+            .context("Failed to open index log")?;
+        std::io::Write::write_all(&mut log, &data)
+            .context("Failed to append to index log")?;
+        if self.sync {
+            log.sync_data()
+                .context("Failed to sync index log")?;
+        }
+        self.log_records += records.len();
+
+        if self.log_records >= COMPACT_MIN_RECORDS.max(self.entries.len()) {
+            self.save()?;
+        }
         Ok(())
     }
 }
 
 impl IndexStore for JsonIndex {
     fn add_file(&mut self, entry: FileEntry) -> Result<()> {
-        self.entries.insert(entry.original_path.clone(), entry);
-        self.save()
+        self.totals.include(&entry);
+        if let Some(old) = self.entries.insert(entry.original_path.clone(), entry.clone()) {
+            self.totals.exclude(&old);
+        }
+        self.persist(vec![LogRecord::Put { entry: Box::new(entry) }])
+    }
+
+    fn add_files(&mut self, entries: Vec<FileEntry>) -> Result<()> {
+        let mut records = Vec::with_capacity(entries.len());
+        for entry in entries {
+            self.totals.include(&entry);
+            if let Some(old) = self.entries.insert(entry.original_path.clone(), entry.clone()) {
+                self.totals.exclude(&old);
+            }
+            records.push(LogRecord::Put { entry: Box::new(entry) });
+        }
+        self.persist(records)
     }
 
     fn get_file(&self, original_path: &Path) -> Result<Option<FileEntry>> {
@@ -159,7 +830,10 @@ impl IndexStore for JsonIndex {
 
     fn remove_file(&mut self, original_path: &Path) -> Result<Option<FileEntry>> {
         let entry = self.entries.remove(original_path);
-        self.save()?;
+        if let Some(entry) = &entry {
+            self.totals.exclude(entry);
+            self.persist(vec![LogRecord::Remove { path: original_path.to_path_buf() }])?;
+        }
         Ok(entry)
     }
 
@@ -167,27 +841,159 @@ impl IndexStore for JsonIndex {
         Ok(self.entries.values().cloned().collect())
     }
 
+    fn entries(&self) -> Result<Box<dyn Iterator<Item = Result<FileEntry>> + '_>> {
+        Ok(Box::new(self.entries.values().cloned().map(Ok)))
+    }
+
     fn rename_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()> {
         if let Some(mut entry) = self.entries.remove(old_path) {
             entry.original_path = new_path.to_path_buf();
-            self.entries.insert(new_path.to_path_buf(), entry);
-            self.save()?;
+            if let Some(replaced) = self.entries.insert(new_path.to_path_buf(), entry.clone()) {
+                self.totals.exclude(&replaced);
+            }
+            self.persist(vec![
+                LogRecord::Remove { path: old_path.to_path_buf() },
+                LogRecord::Put { entry: Box::new(entry) },
+            ])?;
         }
         Ok(())
     }
 
     fn move_file(&mut self, original_path: &Path, new_path: &Path) -> Result<()> {
-        if let Some(mut entry) = self.entries.remove(original_path) {
-            entry.original_path = new_path.to_path_buf();
-            self.entries.insert(new_path.to_path_buf(), entry);
-            self.save()?;
-        }
-        Ok(())
+        self.rename_file(original_path, new_path)
     }
 
     fn count(&self) -> Result<usize> {
         Ok(self.entries.len())
     }
+
+    fn summary(&self) -> Result<IndexSummary> {
+        Ok(self.totals)
+    }
+
+    fn restore(&mut self, entries: Vec<FileEntry>) -> Result<()> {
+        self.entries = entries.into_iter()
+            .map(|entry| (entry.original_path.clone(), entry))
+            .collect();
+        self.totals = IndexSummary::from_entries(self.entries.values());
+        self.save()
+    }
+
+    fn set_encryption_key(&mut self, key: Option<EncryptionKey>) -> Result<()> {
+        self.key = key;
+        self.save()
+    }
+
+    /// 将追加日志合并到快照，并重写为紧凑的完整索引
+    fn compact(&mut self) -> Result<()> {
+        self.save()
+    }
+
+    fn health(&self) -> Result<IndexHealth> {
+        Ok(IndexHealth {
+            entry_count: self.entries.len(),
+            locked: false,
+            pending_journal_entries: self.log_records,
+        })
+    }
+}
+
+/// SQLite 索引的一次结构迁移
+///
+/// 新增列或索引时在 `SQLITE_MIGRATIONS` 末尾追加迁移，版本号递增，已发布的迁移不再修改
+struct Migration {
+    version: u32,
+    description: &'static str,
+    apply: fn(&Connection) -> Result<()>,
+}
+
+const SQLITE_MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "files table", apply: migrate_files_table },
+    Migration { version: 2, description: "file size index", apply: migrate_file_size_index },
+    Migration { version: 3, description: "inline data column", apply: migrate_inline_data },
+    Migration { version: 4, description: "stream encoding column", apply: migrate_stream_encoding },
+    Migration { version: 5, description: "precompression column", apply: migrate_precompression },
+    Migration { version: 6, description: "collection column", apply: migrate_collection },
+    Migration { version: 7, description: "expiry column", apply: migrate_expires_at },
+    Migration { version: 8, description: "perceptual hash column", apply: migrate_perceptual_hash },
+];
+
+/// 创建文件表；引入结构版本之前创建的数据库在这里补充缺少的列
+fn migrate_files_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS files (
+            original_path TEXT PRIMARY KEY,
+            id TEXT NOT NULL,
+            stored_path TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            compressed_size INTEGER NOT NULL,
+            created_at TEXT NOT NULL,
+            compression_algorithm TEXT NOT NULL DEFAULT 'gzip',
+            hash TEXT,
+            is_reference INTEGER DEFAULT 0,
+            original_storage_id TEXT,
+            ref_count INTEGER DEFAULT 1,
+            is_delta INTEGER DEFAULT 0,
+            base_storage_id TEXT,
+            similarity_score REAL,
+            delta_algorithm TEXT,
+            key_id TEXT,
+            wrapped_key TEXT,
+            source_mtime INTEGER,
+            text_normalization TEXT,
+            description TEXT,
+            pinned INTEGER NOT NULL DEFAULT 0,
+            tier TEXT,
+            last_accessed TEXT
+        )",
+        [],
+    )?;
+
+    // 旧数据库中缺少的列
+    SqliteIndex::ensure_column(conn, "key_id", "TEXT")?;
+    SqliteIndex::ensure_column(conn, "wrapped_key", "TEXT")?;
+    SqliteIndex::ensure_column(conn, "source_mtime", "INTEGER")?;
+    SqliteIndex::ensure_column(conn, "text_normalization", "TEXT")?;
+    SqliteIndex::ensure_column(conn, "description", "TEXT")?;
+    SqliteIndex::ensure_column(conn, "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+    SqliteIndex::ensure_column(conn, "tier", "TEXT")?;
+    SqliteIndex::ensure_column(conn, "last_accessed", "TEXT")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_files_id ON files(id)", [])?;
+
+    Ok(())
+}
+
+fn migrate_file_size_index(conn: &Connection) -> Result<()> {
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_files_size ON files(file_size)", [])?;
+    Ok(())
+}
+
+fn migrate_inline_data(conn: &Connection) -> Result<()> {
+    SqliteIndex::ensure_column(conn, "inline_data", "BLOB")
+}
+
+fn migrate_stream_encoding(conn: &Connection) -> Result<()> {
+    SqliteIndex::ensure_column(conn, "stream_encoding", "TEXT")
+}
+
+fn migrate_precompression(conn: &Connection) -> Result<()> {
+    SqliteIndex::ensure_column(conn, "precompression", "TEXT")
+}
+
+fn migrate_collection(conn: &Connection) -> Result<()> {
+    SqliteIndex::ensure_column(conn, "collection", "TEXT")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_files_collection ON files(collection)", [])?;
+    Ok(())
+}
+
+fn migrate_expires_at(conn: &Connection) -> Result<()> {
+    SqliteIndex::ensure_column(conn, "expires_at", "TEXT")?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_files_expires_at ON files(expires_at)", [])?;
+    Ok(())
+}
+
+fn migrate_perceptual_hash(conn: &Connection) -> Result<()> {
+    SqliteIndex::ensure_column(conn, "perceptual_hash", "TEXT")
 }
 
 pub struct SqliteIndex {
@@ -196,32 +1002,249 @@ pub struct SqliteIndex {
 
 impl SqliteIndex {
     pub fn new(storage_path: &Path) -> Result<Self> {
-        let db_path = storage_path.join("index.db");
-        let conn = Connection::open(db_path)
+        Self::with_key(storage_path, None)
+    }
+
+    /// 打开（可选）加密的 SQLite 索引
+    ///
+    /// 加密依赖 SQLCipher，需要启用 `sqlcipher` feature
+    pub fn with_key(storage_path: &Path, key: Option<&EncryptionKey>) -> Result<Self> {
+        let db_path = storage_path.join(SQLITE_INDEX_FILE);
+        let mut conn = Connection::open(db_path)
             .context("Failed to open SQLite database")?;
 
+        if let Some(key) = key {
+            Self::apply_key(&conn, key)?;
+        }
+
+        Self::run_migrations(&mut conn)?;
+
+        Ok(Self { conn })
+    }
+
+    /// 设置提交事务时的同步级别
+    ///
+    /// 启用时使用 `synchronous = FULL`，每次提交都 fsync。不启用时只有数据库处于 WAL 模式才降为
+    /// `NORMAL`（断电时可能丢失最近的提交，但不会损坏数据库）；默认的回滚日志模式下 `NORMAL`
+    /// 在断电时可能损坏数据库，因此仍然使用 `FULL`
+    pub fn set_sync(&self, sync: bool) -> Result<()> {
+        let journal_mode: String = self.conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .context("Failed to query SQLite journal mode")?;
+        let level = if sync || !journal_mode.eq_ignore_ascii_case("wal") { "FULL" } else { "NORMAL" };
+        self.conn.execute_batch(&format!("PRAGMA synchronous = {};", level))
+            .context("Failed to set SQLite synchronous mode")
+    }
+
+    /// 当前数据库的结构版本，没有执行过迁移时为 0
+    pub fn schema_version(&self) -> Result<u32> {
+        Self::current_schema_version(&self.conn)
+    }
+
+    fn current_schema_version(conn: &Connection) -> Result<u32> {
+        let version: Option<u32> = conn.query_row(
+            "SELECT MAX(version) FROM schema_version",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(version.unwrap_or(0))
+    }
+
+    /// 按版本顺序执行尚未执行的迁移
+    ///
+    /// 每个迁移在单独的写事务中执行并记录到 `schema_version` 表，中途失败时已完成的迁移保留，
+    /// 下次打开时从失败的迁移继续。数据库版本比当前程序支持的版本新时拒绝打开
+    fn run_migrations(conn: &mut Connection) -> Result<()> {
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS files (
-                original_path TEXT PRIMARY KEY,
-                id TEXT NOT NULL,
-                stored_path TEXT NOT NULL,
-                file_size INTEGER NOT NULL,
-                compressed_size INTEGER NOT NULL,
-                created_at TEXT NOT NULL,
-                compression_algorithm TEXT NOT NULL DEFAULT 'gzip',
-                hash TEXT,
-                is_reference INTEGER DEFAULT 0,
-                original_storage_id TEXT,
-                ref_count INTEGER DEFAULT 1,
-                is_delta INTEGER DEFAULT 0,
-                base_storage_id TEXT,
-                similarity_score REAL,
-                delta_algorithm TEXT
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                applied_at TEXT NOT NULL
             )",
             [],
         )?;
 
-        Ok(Self { conn })
+        let latest = SQLITE_MIGRATIONS.last().map_or(0, |m| m.version);
+        let current = Self::current_schema_version(conn)?;
+        if current > latest {
+            return Err(anyhow::anyhow!(
+                "Index schema version {} is newer than the supported version {}; upgrade stowr-core",
+                current, latest
+            ));
+        }
+
+        for migration in SQLITE_MIGRATIONS.iter().filter(|m| m.version > current) {
+            let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+            // 其他进程可能已经执行了这个迁移
+            if Self::current_schema_version(&tx)? >= migration.version {
+                continue;
+            }
+            (migration.apply)(&tx).with_context(|| format!(
+                "Failed to migrate index schema to version {} ({})",
+                migration.version, migration.description
+            ))?;
+            tx.execute(
+                "INSERT INTO schema_version (version, applied_at) VALUES (?1, ?2)",
+                rusqlite::params![migration.version, chrono::Utc::now().to_rfc3339()],
+            )?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    /// 为旧数据库补充缺少的列
+    fn ensure_column(conn: &Connection, name: &str, declaration: &str) -> Result<()> {
+        let mut stmt = conn.prepare("PRAGMA table_info(files)")?;
+        let exists = stmt.query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .any(|column| column == name);
+        if !exists {
+            conn.execute(&format!("ALTER TABLE files ADD COLUMN {} {}", name, declaration), [])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlcipher")]
+impl SqliteIndex {
+    fn apply_key(conn: &Connection, key: &EncryptionKey) -> Result<()> {
+        let hex = crypto::to_hex(key.as_bytes());
+        conn.execute_batch(&format!("PRAGMA key = \"x'{}'\";", hex))
+            .context("Failed to set SQLCipher key")?;
+        // 密钥错误时首次读取才会失败，这里主动验证
+        conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+            .context("Failed to open encrypted index: wrong key or unencrypted database")?;
+        Ok(())
+    }
+
+    fn rekey(conn: &Connection, key: Option<&EncryptionKey>) -> Result<()> {
+        let hex = key.map(|k| crypto::to_hex(k.as_bytes())).unwrap_or_default();
+        let statement = if hex.is_empty() {
+            "PRAGMA rekey = '';".to_string()
+        } else {
+            format!("PRAGMA rekey = \"x'{}'\";", hex)
+        };
+        conn.execute_batch(&statement)
+            .context("Failed to rekey SQLCipher database")
+    }
+}
+
+#[cfg(not(feature = "sqlcipher"))]
+impl SqliteIndex {
+    fn apply_key(_conn: &Connection, _key: &EncryptionKey) -> Result<()> {
+        Err(anyhow::anyhow!(
+            "Encrypted SQLite index requires stowr-core to be built with the `sqlcipher` feature"
+        ))
+    }
+
+    fn rekey(_conn: &Connection, _key: Option<&EncryptionKey>) -> Result<()> {
+        Err(anyhow::anyhow!(
+            "Encrypted SQLite index requires stowr-core to be built with the `sqlcipher` feature"
+        ))
+    }
+}
+
+/// 查询条目时使用的列，顺序与 `SqliteIndex::row_to_entry` 一致
+const SQLITE_ENTRY_COLUMNS: &str = "original_path, id, stored_path, file_size, compressed_size, created_at,
+                    compression_algorithm, hash, is_reference, original_storage_id, ref_count,
+                    is_delta, base_storage_id, similarity_score, delta_algorithm,
+                    key_id, wrapped_key, source_mtime, text_normalization, description, pinned,
+                    tier, last_accessed, inline_data, stream_encoding, precompression, collection,
+                    expires_at, perceptual_hash";
+
+impl SqliteIndex {
+    /// 将查询结果行转换为文件条目
+    ///
+    /// 路径列保存的是 `encode_path` 的结果，非 UTF-8 路径也能无损还原
+    fn row_to_entry(row: &rusqlite::Row) -> rusqlite::Result<FileEntry> {
+        Ok(FileEntry {
+            original_path: Self::path_column(row, 0)?,
+            id: row.get(1)?,
+            stored_path: Self::path_column(row, 2)?,
+            file_size: row.get(3)?,
+            compressed_size: row.get(4)?,
+            created_at: row.get(5)?,
+            compression_algorithm: CompressionAlgorithm::from_id(&row.get::<_, String>(6)?),
+            hash: row.get(7)?,
+            is_reference: row.get::<_, Option<i32>>(8)?.map(|i| i != 0),
+            original_storage_id: row.get(9)?,
+            ref_count: row.get(10)?,
+            is_delta: row.get::<_, Option<i32>>(11)?.map(|i| i != 0),
+            base_storage_id: row.get(12)?,
+            similarity_score: row.get(13)?,
+            delta_algorithm: row.get::<_, Option<String>>(14)?
+                .map(|s| s.parse())
+                .transpose()
+                .map_err(|_| rusqlite::Error::InvalidColumnType(14, "delta_algorithm".to_string(), rusqlite::types::Type::Text))?,
+            key_id: row.get(15)?,
+            wrapped_key: row.get(16)?,
+            source_mtime: row.get(17)?,
+            text_normalization: row.get::<_, Option<String>>(18)?
+                .and_then(|s| serde_json::from_str(&s).ok()),
+            description: row.get(19)?,
+            pinned: row.get::<_, i32>(20)? != 0,
+            tier: row.get::<_, Option<String>>(21)?
+                .map(|s| StorageTier::from_str(&s))
+                .transpose()
+                .map_err(|_| rusqlite::Error::InvalidColumnType(21, "tier".to_string(), rusqlite::types::Type::Text))?
+                .unwrap_or_default(),
+            last_accessed: row.get(22)?,
+            inline_data: row.get(23)?,
+            stream_encoding: row.get::<_, Option<String>>(24)?
+                .and_then(|s| serde_json::from_str(&s).ok()),
+            precompression: row.get::<_, Option<String>>(25)?
+                .and_then(|s| serde_json::from_str(&s).ok()),
+            collection: row.get(26)?,
+            expires_at: row.get(27)?,
+            perceptual_hash: row.get(28)?,
+        })
+    }
+
+    fn path_column(row: &rusqlite::Row, index: usize) -> rusqlite::Result<PathBuf> {
+        let encoded: String = row.get(index)?;
+        decode_path(&encoded)
+            .map_err(|_| rusqlite::Error::InvalidColumnType(index, "path".to_string(), rusqlite::types::Type::Text))
+    }
+}
+
+/// [`SqliteIndex`] 的条目迭代器
+struct SqliteEntries<'a> {
+    conn: &'a Connection,
+    batch: std::collections::VecDeque<FileEntry>,
+    /// 已返回的最后一个条目的编码路径
+    after: Option<String>,
+    done: bool,
+}
+
+impl SqliteEntries<'_> {
+    fn fetch(&mut self) -> Result<()> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM files WHERE ?1 IS NULL OR original_path > ?1 ORDER BY original_path LIMIT ?2",
+            SQLITE_ENTRY_COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            rusqlite::params![self.after, SQLITE_ENTRY_BATCH as i64],
+            SqliteIndex::row_to_entry,
+        )?;
+        for row in rows {
+            self.batch.push_back(row?);
+        }
+        self.done = self.batch.len() < SQLITE_ENTRY_BATCH;
+        self.after = self.batch.back().map(|entry| encode_path(&entry.original_path));
+        Ok(())
+    }
+}
+
+impl Iterator for SqliteEntries<'_> {
+    type Item = Result<FileEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.batch.is_empty() && !self.done {
+            if let Err(e) = self.fetch() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.batch.pop_front().map(Ok)
     }
 }
 
@@ -231,12 +1254,15 @@ impl IndexStore for SqliteIndex {
             "INSERT OR REPLACE INTO files (
                 original_path, id, stored_path, file_size, compressed_size, created_at,
                 compression_algorithm, hash, is_reference, original_storage_id, ref_count,
-                is_delta, base_storage_id, similarity_score, delta_algorithm
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                is_delta, base_storage_id, similarity_score, delta_algorithm,
+                key_id, wrapped_key, source_mtime, text_normalization, description, pinned,
+                tier, last_accessed, inline_data, stream_encoding, precompression, collection,
+                expires_at, perceptual_hash
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29)",
             rusqlite::params![
-                entry.original_path.to_string_lossy(),
+                encode_path(&entry.original_path),
                 entry.id,
-                entry.stored_path.to_string_lossy(),
+                encode_path(&entry.stored_path),
                 entry.file_size,
                 entry.compressed_size,
                 entry.created_at,
@@ -248,45 +1274,194 @@ impl IndexStore for SqliteIndex {
                 entry.is_delta.map(|b| if b { 1 } else { 0 }),
                 entry.base_storage_id,
                 entry.similarity_score,
-                entry.delta_algorithm.as_ref().map(|a| a.to_string())
+                entry.delta_algorithm.as_ref().map(|a| a.to_string()),
+                entry.key_id,
+                entry.wrapped_key,
+                entry.source_mtime,
+                entry.text_normalization.map(|t| serde_json::to_string(&t)).transpose()?,
+                entry.description,
+                entry.pinned as i32,
+                (!entry.tier.is_hot()).then(|| entry.tier.to_string()),
+                entry.last_accessed,
+                entry.inline_data,
+                entry.stream_encoding.as_ref().map(serde_json::to_string).transpose()?,
+                entry.precompression.as_ref().map(serde_json::to_string).transpose()?,
+                entry.collection,
+                entry.expires_at,
+                entry.perceptual_hash
             ],
         )?;
         Ok(())
     }
 
     fn get_file(&self, original_path: &Path) -> Result<Option<FileEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files WHERE original_path = ?1",
+            SQLITE_ENTRY_COLUMNS
+        ))?;
+
+        let entry = stmt.query_row([encode_path(original_path)], Self::row_to_entry)
+            .optional()?;
+
+        Ok(entry)
+    }
+
+    fn get_file_by_id(&self, id: &str) -> Result<Option<FileEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files WHERE id = ?1",
+            SQLITE_ENTRY_COLUMNS
+        ))?;
+
+        let entry = stmt.query_row([id], Self::row_to_entry)
+            .optional()?;
+
+        Ok(entry)
+    }
+
+    fn list_files_ordered(&self, order: EntryOrder) -> Result<Vec<FileEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files ORDER BY {}",
+            SQLITE_ENTRY_COLUMNS,
+            order.sql()
+        ))?;
+
+        let entries = stmt.query_map([], Self::row_to_entry)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    fn expiring_within(&self, within: std::time::Duration) -> Result<Vec<FileEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files WHERE expires_at IS NOT NULL AND expires_at <= ?1
+             ORDER BY expires_at, original_path",
+            SQLITE_ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt.query_map([expiry_cutoff(within)], Self::row_to_entry)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    fn largest_entries(&self, n: usize) -> Result<Vec<FileEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files ORDER BY file_size DESC LIMIT ?1",
+            SQLITE_ENTRY_COLUMNS
+        ))?;
+
+        let entries = stmt.query_map([n.min(i64::MAX as usize) as i64], Self::row_to_entry)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    fn size_histogram(&self, bounds: &[u64]) -> Result<Vec<SizeBucket>> {
+        let mut buckets = empty_buckets(bounds)?;
+        // 区间序号：第一个大于文件大小的边界
+        let bucket = (0..bounds.len())
+            .map(|i| format!("WHEN file_size < ?{} THEN {}", i + 1, i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let bucket = match bucket.is_empty() {
+            true => "0".to_string(),
+            false => format!("CASE {} ELSE {} END", bucket, bounds.len()),
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} AS bucket,
+                    COUNT(*),
+                    SUM(file_size),
+                    SUM(CASE WHEN is_reference = 1 THEN 0 ELSE compressed_size END)
+             FROM files GROUP BY bucket",
+            bucket
+        ))?;
+        let params = bounds.iter().map(|&bound| bound.min(i64::MAX as u64) as i64);
+        let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| Ok((
+            row.get::<_, i64>(0)? as usize,
+            IndexSummary {
+                count: row.get::<_, i64>(1)? as usize,
+                logical_bytes: row.get::<_, i64>(2)? as u64,
+                physical_bytes: row.get::<_, i64>(3)? as u64,
+            },
+        )))?;
+        for row in rows {
+            let (index, summary) = row?;
+            buckets[index].summary = summary;
+        }
+        Ok(buckets)
+    }
+
+    /// 通过主键范围查询目录下的条目，子目录在 SQL 中分组汇总，只读取直接文件的完整条目
+    fn tree(&self, prefix: &Path) -> Result<TreeListing> {
+        let separator = std::path::MAIN_SEPARATOR;
+        let base = match prefix.to_str() {
+            Some(s) if !s.is_empty() && !s.starts_with(ENCODED_PATH_MARKER) => {
+                let mut base = s.to_string();
+                if !base.ends_with(separator) {
+                    base.push(separator);
+                }
+                base
+            }
+            // 相对根和无法按文本比较的路径逐条处理
+            _ => return Ok(TreeListing::from_entries(prefix, self.list_files()?)),
+        };
+        // 以分隔符结尾的前缀的上界：把结尾的分隔符换成下一个字符
+        let upper = format!("{}{}", &base[..base.len() - separator.len_utf8()], char::from(separator as u8 + 1));
+        let rest_start = base.chars().count() as i64 + 1;
+        let separator = separator.to_string();
+
+        let mut listing = TreeListing::new(prefix);
+        let mut directories = BTreeMap::new();
+
         let mut stmt = self.conn.prepare(
-            "SELECT id, stored_path, file_size, compressed_size, created_at,
-                    compression_algorithm, hash, is_reference, original_storage_id, ref_count,
-                    is_delta, base_storage_id, similarity_score, delta_algorithm
-             FROM files WHERE original_path = ?1"
+            "SELECT substr(rest, 1, instr(rest, ?3) - 1) AS child,
+                    COUNT(*),
+                    SUM(file_size),
+                    SUM(CASE WHEN is_reference = 1 THEN 0 ELSE compressed_size END)
+             FROM (SELECT substr(original_path, ?4) AS rest, file_size, compressed_size, is_reference
+                   FROM files WHERE original_path >= ?1 AND original_path < ?2)
+             WHERE instr(rest, ?3) > 0
+             GROUP BY child",
+        )?;
+        let rows = stmt.query_map(
+            rusqlite::params![base, upper, separator, rest_start],
+            |row| Ok((
+                row.get::<_, String>(0)?,
+                IndexSummary {
+                    count: row.get::<_, i64>(1)? as usize,
+                    logical_bytes: row.get::<_, i64>(2)? as u64,
+                    physical_bytes: row.get::<_, i64>(3)? as u64,
+                },
+            )),
         )?;
+        for row in rows {
+            let (child, summary) = row?;
+            listing.summary.add(&summary);
+            directories.insert(prefix.join(child), summary);
+        }
 
-        let entry = stmt.query_row([original_path.to_string_lossy()], |row| {
-            Ok(FileEntry {
-                id: row.get(0)?,
-                original_path: original_path.to_path_buf(),
-                stored_path: PathBuf::from(row.get::<_, String>(1)?),
-                file_size: row.get(2)?,
-                compressed_size: row.get(3)?,
-                created_at: row.get(4)?,
-                compression_algorithm: row.get::<_, String>(5)?.parse()
-                    .map_err(|_| rusqlite::Error::InvalidColumnType(5, "compression_algorithm".to_string(), rusqlite::types::Type::Text))?,
-                hash: row.get(6)?,
-                is_reference: row.get::<_, Option<i32>>(7)?.map(|i| i != 0),
-                original_storage_id: row.get(8)?,
-                ref_count: row.get(9)?,
-                is_delta: row.get::<_, Option<i32>>(10)?.map(|i| i != 0),
-                base_storage_id: row.get(11)?,
-                similarity_score: row.get(12)?,
-                delta_algorithm: row.get::<_, Option<String>>(13)?
-                    .map(|s| s.parse())
-                    .transpose()
-                    .map_err(|_| rusqlite::Error::InvalidColumnType(13, "delta_algorithm".to_string(), rusqlite::types::Type::Text))?,
-            })
-        }).optional()?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files
+             WHERE original_path >= ?1 AND original_path < ?2 AND instr(substr(original_path, ?4), ?3) = 0",
+            SQLITE_ENTRY_COLUMNS
+        ))?;
+        let files = stmt.query_map(rusqlite::params![base, upper, separator, rest_start], Self::row_to_entry)?
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(entry)
+        // 非 UTF-8 路径以编码形式保存，不在上面的范围内
+        let marker = ENCODED_PATH_MARKER.to_string();
+        let marker_end = char::from(ENCODED_PATH_MARKER as u8 + 1).to_string();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files WHERE original_path >= ?1 AND original_path < ?2",
+            SQLITE_ENTRY_COLUMNS
+        ))?;
+        let encoded = stmt.query_map([marker, marker_end], Self::row_to_entry)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for entry in files.into_iter().chain(encoded) {
+            listing.include(entry, &mut directories);
+        }
+        Ok(listing.finish(directories))
     }
 
     fn remove_file(&mut self, original_path: &Path) -> Result<Option<FileEntry>> {
@@ -294,53 +1469,40 @@ impl IndexStore for SqliteIndex {
         if entry.is_some() {
             self.conn.execute(
                 "DELETE FROM files WHERE original_path = ?1",
-                [original_path.to_string_lossy()],
+                [encode_path(original_path)],
             )?;
         }
         Ok(entry)
     }
 
     fn list_files(&self) -> Result<Vec<FileEntry>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT original_path, id, stored_path, file_size, compressed_size, created_at,
-                    compression_algorithm, hash, is_reference, original_storage_id, ref_count,
-                    is_delta, base_storage_id, similarity_score, delta_algorithm
-             FROM files"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM files",
+            SQLITE_ENTRY_COLUMNS
+        ))?;
 
-        let entries = stmt.query_map([], |row| {
-            Ok(FileEntry {
-                original_path: PathBuf::from(row.get::<_, String>(0)?),
-                id: row.get(1)?,
-                stored_path: PathBuf::from(row.get::<_, String>(2)?),
-                file_size: row.get(3)?,
-                compressed_size: row.get(4)?,
-                created_at: row.get(5)?,
-                compression_algorithm: row.get::<_, String>(6)?.parse()
-                    .map_err(|_| rusqlite::Error::InvalidColumnType(6, "compression_algorithm".to_string(), rusqlite::types::Type::Text))?,
-                hash: row.get(7)?,
-                is_reference: row.get::<_, Option<i32>>(8)?.map(|i| i != 0),
-                original_storage_id: row.get(9)?,
-                ref_count: row.get(10)?,
-                is_delta: row.get::<_, Option<i32>>(11)?.map(|i| i != 0),
-                base_storage_id: row.get(12)?,
-                similarity_score: row.get(13)?,
-                delta_algorithm: row.get::<_, Option<String>>(14)?
-                    .map(|s| s.parse())
-                    .transpose()
-                    .map_err(|_| rusqlite::Error::InvalidColumnType(14, "delta_algorithm".to_string(), rusqlite::types::Type::Text))?,
-            })
-        })?.collect::<Result<Vec<_>, _>>()?;
+        let entries = stmt.query_map([], Self::row_to_entry)?
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(entries)
     }
 
+    /// 按主键分批查询，每批从上一批最后一个路径之后开始，内存中最多保留一批条目
+    fn entries(&self) -> Result<Box<dyn Iterator<Item = Result<FileEntry>> + '_>> {
+        Ok(Box::new(SqliteEntries {
+            conn: &self.conn,
+            batch: std::collections::VecDeque::new(),
+            after: None,
+            done: false,
+        }))
+    }
+
     fn rename_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()> {
         self.conn.execute(
             "UPDATE files SET original_path = ?1 WHERE original_path = ?2",
             rusqlite::params![
-                new_path.to_string_lossy(),
-                old_path.to_string_lossy()
+                encode_path(new_path),
+                encode_path(old_path)
             ],
         )?;
         Ok(())
@@ -350,27 +1512,122 @@ impl IndexStore for SqliteIndex {
         self.conn.execute(
             "UPDATE files SET original_path = ?1 WHERE original_path = ?2",
             rusqlite::params![
-                new_path.to_string_lossy(),
-                original_path.to_string_lossy()
+                encode_path(new_path),
+                encode_path(original_path)
             ],
         )?;
         Ok(())
     }
 
+    fn summary(&self) -> Result<IndexSummary> {
+        let (count, logical, physical): (i64, i64, i64) = self.conn.query_row(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(file_size), 0),
+                    COALESCE(SUM(CASE WHEN is_reference = 1 THEN 0 ELSE compressed_size END), 0)
+             FROM files",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        Ok(IndexSummary {
+            count: count as usize,
+            logical_bytes: logical as u64,
+            physical_bytes: physical as u64,
+        })
+    }
+
+    fn add_files(&mut self, entries: Vec<FileEntry>) -> Result<()> {
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let result = entries.into_iter().try_for_each(|entry| self.add_file(entry));
+
+        match result {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+
+    fn restore(&mut self, entries: Vec<FileEntry>) -> Result<()> {
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        let result = (|| -> Result<()> {
+            self.conn.execute("DELETE FROM files", [])?;
+            for entry in entries {
+                self.add_file(entry)?;
+            }
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+
     fn count(&self) -> Result<usize> {
         let mut stmt = self.conn.prepare("SELECT COUNT(*) FROM files")?;
         let count: i64 = stmt.query_row([], |row| row.get(0))?;
         Ok(count as usize)
     }
+
+    fn set_encryption_key(&mut self, key: Option<EncryptionKey>) -> Result<()> {
+        Self::rekey(&self.conn, key.as_ref())
+    }
+
+    fn compact(&mut self) -> Result<()> {
+        self.conn.execute_batch("VACUUM;")
+            .context("Failed to vacuum SQLite index")
+    }
+
+    /// 尝试立即获取写锁来判断数据库是否被其他连接锁定，不等待锁释放
+    fn health(&self) -> Result<IndexHealth> {
+        let entry_count = self.count()?;
+        self.conn.busy_timeout(std::time::Duration::ZERO)?;
+        let probe = self.conn.execute_batch("BEGIN IMMEDIATE; ROLLBACK;");
+        self.conn.busy_timeout(SQLITE_BUSY_TIMEOUT)?;
+        let locked = match probe {
+            Ok(()) => false,
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::DatabaseBusy => true,
+            Err(e) => return Err(e).context("Failed to probe SQLite lock"),
+        };
+        Ok(IndexHealth { entry_count, locked, pending_journal_entries: 0 })
+    }
 }
 
 pub fn create_index(config: &Config) -> Result<Box<dyn IndexStore>> {
+    create_index_with_key(config, None)
+}
+
+/// 使用密钥提供者创建索引
+///
+/// 当 `config.encrypt_index` 启用时，索引使用提供者的主密钥加密
+pub fn create_index_with_key(
+    config: &Config,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+) -> Result<Box<dyn IndexStore>> {
     fs::create_dir_all(&config.storage_path)?;
 
+    let key = if config.encrypt_index {
+        let provider = key_provider
+            .ok_or_else(|| anyhow::anyhow!("Index encryption is enabled but no key provider was given"))?;
+        Some(provider.master_key()?)
+    } else {
+        None
+    };
+
     let mode = match &config.index_mode {
         IndexMode::Auto => {
             // 尝试读取现有的索引来决定使用哪种模式
-            let json_index = JsonIndex::new(&config.storage_path)?;
+            let json_index = JsonIndex::open(&config.storage_path, key.clone(), config.compress_index)?;
             let count = json_index.count()?;
             if count >= 1000 {
                 IndexMode::Sqlite
@@ -383,10 +1640,256 @@ pub fn create_index(config: &Config) -> Result<Box<dyn IndexStore>> {
 
     match mode {
         IndexMode::Json | IndexMode::Auto => {
-            Ok(Box::new(JsonIndex::new(&config.storage_path)?))
+            let index = JsonIndex::open(&config.storage_path, key, config.compress_index)?
+                .with_sync(config.durability.sync_index());
+            Ok(Box::new(index))
         }
         IndexMode::Sqlite => {
-            Ok(Box::new(SqliteIndex::new(&config.storage_path)?))
+            let index = SqliteIndex::with_key(&config.storage_path, key.as_ref())?;
+            index.set_sync(config.durability.sync_index())?;
+            Ok(Box::new(index))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(path: &str) -> FileEntry {
+        FileEntry::new(
+            path.to_string(),
+            PathBuf::from(path),
+            PathBuf::from(format!("{}.gz", path)),
+            10,
+            5,
+            CompressionAlgorithm::Gzip,
+        )
+    }
+
+    #[test]
+    fn test_compressed_index_log_and_migration() {
+        let dir = TempDir::new().unwrap();
+        let mut plain = JsonIndex::new(dir.path()).unwrap();
+        plain.add_file(entry("a")).unwrap();
+
+        // 打开时转换为压缩格式，之后的修改只追加日志
+        let mut index = JsonIndex::open(dir.path(), None, true).unwrap();
+        assert!(!dir.path().join(INDEX_FILE).exists());
+        let snapshot = fs::read(dir.path().join(COMPRESSED_INDEX_FILE)).unwrap();
+        index.add_file(entry("b")).unwrap();
+        index.rename_file(Path::new("a"), Path::new("c")).unwrap();
+        index.remove_file(Path::new("b")).unwrap();
+        assert_eq!(fs::read(dir.path().join(COMPRESSED_INDEX_FILE)).unwrap(), snapshot);
+        assert_eq!(index.log_records, 4);
+
+        // 写入中断留下的不完整记录被忽略
+        let log_path = dir.path().join(INDEX_LOG_FILE);
+        let mut log = fs::read(&log_path).unwrap();
+        log.extend_from_slice(&100u32.to_le_bytes());
+        log.extend_from_slice(b"{\"op\"");
+        fs::write(&log_path, log).unwrap();
+
+        let reopened = JsonIndex::open(dir.path(), None, true).unwrap();
+        let paths: Vec<PathBuf> = reopened.list_files().unwrap().into_iter().map(|e| e.original_path).collect();
+        assert_eq!(paths, vec![PathBuf::from("c")]);
+        assert_eq!(reopened.summary().unwrap().count, 1);
+
+        // 关闭压缩后恢复为明文 JSON
+        let plain = JsonIndex::open(dir.path(), None, false).unwrap();
+        assert_eq!(plain.count().unwrap(), 1);
+        assert!(dir.path().join(INDEX_FILE).exists());
+        assert!(!dir.path().join(COMPRESSED_INDEX_FILE).exists());
+        assert!(!log_path.exists());
+    }
+
+    #[test]
+    fn test_unparseable_index_is_not_overwritten() {
+        let dir = TempDir::new().unwrap();
+        let mut plain = JsonIndex::new(dir.path()).unwrap();
+        plain.add_file(entry("a")).unwrap();
+        let plain_path = dir.path().join(INDEX_FILE);
+        fs::write(&plain_path, b"{\"a\": ").unwrap();
+
+        // 解析失败时返回错误，不转换格式，也不删除原文件
+        assert!(JsonIndex::open(dir.path(), None, true).is_err());
+        assert_eq!(fs::read(&plain_path).unwrap(), b"{\"a\": ");
+        assert!(!dir.path().join(COMPRESSED_INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn test_compressed_index_compacts_log() {
+        let dir = TempDir::new().unwrap();
+        let key = EncryptionKey::generate();
+        let mut index = JsonIndex::open(dir.path(), Some(key.clone()), true).unwrap();
+        for i in 0..COMPACT_MIN_RECORDS + 1 {
+            index.add_file(entry(&format!("f{}", i))).unwrap();
+        }
+        assert_eq!(index.log_records, 1);
+
+        assert!(JsonIndex::open(dir.path(), None, true).is_err());
+        let reopened = JsonIndex::open(dir.path(), Some(key), true).unwrap();
+        assert_eq!(reopened.count().unwrap(), COMPACT_MIN_RECORDS + 1);
+    }
+
+    #[test]
+    fn test_durability_sets_sqlite_synchronous() {
+        let dir = TempDir::new().unwrap();
+        let index = SqliteIndex::new(dir.path()).unwrap();
+        let synchronous = |index: &SqliteIndex| -> i64 {
+            index.conn.query_row("PRAGMA synchronous", [], |row| row.get(0)).unwrap()
+        };
+
+        index.set_sync(true).unwrap();
+        assert_eq!(synchronous(&index), 2);
+        // 回滚日志模式下不降低同步级别，WAL 模式下才使用 NORMAL
+        index.set_sync(false).unwrap();
+        assert_eq!(synchronous(&index), 2);
+        index.conn.execute_batch("PRAGMA journal_mode = WAL;").unwrap();
+        index.set_sync(false).unwrap();
+        assert_eq!(synchronous(&index), 1);
+
+        // JSON 索引同步保存后内容不变
+        let config = Config {
+            storage_path: dir.path().join("json"),
+            index_mode: IndexMode::Json,
+            compress_index: true,
+            durability: crate::config::Durability::BlobAndIndex,
+            ..Config::default()
+        };
+        let mut json = create_index(&config).unwrap();
+        json.add_file(entry("a")).unwrap();
+        assert_eq!(create_index(&config).unwrap().count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_sqlite_migrations_upgrade_legacy_database() {
+        let dir = TempDir::new().unwrap();
+        // 引入结构版本之前的数据库：没有 schema_version 表，缺少后来新增的列
+        let legacy = Connection::open(dir.path().join(SQLITE_INDEX_FILE)).unwrap();
+        legacy.execute_batch(
+            "CREATE TABLE files (
+                original_path TEXT PRIMARY KEY, id TEXT NOT NULL, stored_path TEXT NOT NULL,
+                file_size INTEGER NOT NULL, compressed_size INTEGER NOT NULL, created_at TEXT NOT NULL,
+                compression_algorithm TEXT NOT NULL DEFAULT 'gzip', hash TEXT,
+                is_reference INTEGER DEFAULT 0, original_storage_id TEXT, ref_count INTEGER DEFAULT 1,
+                is_delta INTEGER DEFAULT 0, base_storage_id TEXT, similarity_score REAL, delta_algorithm TEXT
+            );
+            INSERT INTO files (original_path, id, stored_path, file_size, compressed_size, created_at)
+                VALUES ('a', 'a', 'a.gz', 10, 5, '2024-01-01T00:00:00+00:00');",
+        ).unwrap();
+        drop(legacy);
+
+        let latest = SQLITE_MIGRATIONS.last().unwrap().version;
+        let mut index = SqliteIndex::new(dir.path()).unwrap();
+        assert_eq!(index.schema_version().unwrap(), latest);
+        assert!(!index.get_file(Path::new("a")).unwrap().unwrap().pinned);
+        index.add_file(entry("b")).unwrap();
+        drop(index);
+
+        // 再次打开不重复执行迁移
+        let index = SqliteIndex::new(dir.path()).unwrap();
+        let applied: i64 = index.conn
+            .query_row("SELECT count(*) FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(applied, latest as i64);
+        assert_eq!(index.count().unwrap(), 2);
+
+        // 更新版本的程序创建的数据库拒绝打开
+        index.conn.execute(
+            "INSERT INTO schema_version (version, applied_at) VALUES (?1, '')",
+            [latest + 1],
+        ).unwrap();
+        drop(index);
+        assert!(SqliteIndex::new(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_entries_iterates_all_backends() {
+        let dir = TempDir::new().unwrap();
+        let json_dir = dir.path().join("json");
+        fs::create_dir_all(&json_dir).unwrap();
+        let backends: Vec<Box<dyn IndexStore>> = vec![
+            Box::new(JsonIndex::new(&json_dir).unwrap()),
+            Box::new(SqliteIndex::new(dir.path()).unwrap()),
+        ];
+        for mut index in backends {
+            assert_eq!(index.entries().unwrap().count(), 0);
+            // 超过一批的条目，包括路径恰好落在批次边界上的情况
+            for i in 0..SQLITE_ENTRY_BATCH * 2 + 1 {
+                index.add_file(entry(&format!("dir/f{:04}", i))).unwrap();
+            }
+
+            let mut paths: Vec<PathBuf> = index.entries().unwrap()
+                .map(|entry| entry.unwrap().original_path)
+                .collect();
+            paths.sort();
+            let mut expected: Vec<PathBuf> = index.list_files().unwrap().into_iter()
+                .map(|entry| entry.original_path)
+                .collect();
+            expected.sort();
+            assert_eq!(paths.len(), SQLITE_ENTRY_BATCH * 2 + 1);
+            assert_eq!(paths, expected);
+            assert_eq!(index.entries().unwrap().take(3).count(), 3);
+        }
+    }
+
+    #[test]
+    fn test_add_files_replaces_entries_in_one_write() {
+        let dir = TempDir::new().unwrap();
+        let json_dir = dir.path().join("json");
+        fs::create_dir_all(&json_dir).unwrap();
+        let backends: Vec<Box<dyn IndexStore>> = vec![
+            Box::new(JsonIndex::open(&json_dir, None, true).unwrap()),
+            Box::new(SqliteIndex::new(dir.path()).unwrap()),
+        ];
+        for mut index in backends {
+            index.add_file(entry("a")).unwrap();
+            let mut replaced = entry("a");
+            replaced.file_size = 30;
+            index.add_files(vec![replaced, entry("b"), entry("c")]).unwrap();
+            assert_eq!(index.count().unwrap(), 3);
+            assert_eq!(index.get_file(Path::new("a")).unwrap().unwrap().file_size, 30);
+            assert_eq!(index.summary().unwrap().logical_bytes, 50);
+        }
+
+        // 压缩格式的 JSON 索引把整批修改作为日志记录追加，重新打开后可以回放
+        let reopened = JsonIndex::open(&json_dir, None, true).unwrap();
+        assert_eq!(reopened.count().unwrap(), 3);
+        assert_eq!(reopened.log_records, 4);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = TempDir::new().unwrap();
+        let path = PathBuf::from(OsStr::from_bytes(b"/data/caf\xe9-\xff.bin"));
+        let mut stored = entry("id");
+        stored.original_path = path.clone();
+
+        // 明文 JSON、压缩快照加追加日志、SQLite 重新打开后都能按原始字节找回
+        let json_dir = dir.path().join("json");
+        let log_dir = dir.path().join("log");
+        for subdir in [&json_dir, &log_dir] {
+            fs::create_dir_all(subdir).unwrap();
+        }
+        let open: Vec<Box<dyn Fn() -> Box<dyn IndexStore>>> = vec![
+            Box::new(|| Box::new(JsonIndex::open(&json_dir, None, false).unwrap())),
+            Box::new(|| Box::new(JsonIndex::open(&log_dir, None, true).unwrap())),
+            Box::new(|| Box::new(SqliteIndex::new(dir.path()).unwrap())),
+        ];
+        for open in open {
+            open().add_file(stored.clone()).unwrap();
+
+            let index = open();
+            let found = index.get_file(&path).unwrap().unwrap();
+            assert_eq!(found.original_path.as_os_str().as_bytes(), path.as_os_str().as_bytes());
+            let listed: Vec<PathBuf> = index.list_files().unwrap().into_iter().map(|e| e.original_path).collect();
+            assert_eq!(listed, vec![path.clone()]);
         }
     }
 }
@@ -0,0 +1,283 @@
+//! 多线程批量存储队列
+//!
+//! [`IngestQueue`] 持有存储管理器和固定数量的工作线程。生产者可以从任意线程提交文件路径或
+//! 读取器，队列有容量上限：排队的任务已满时 [`push`](IngestQueue::push) 阻塞、
+//! [`try_push`](IngestQueue::try_push) 立即把任务退回，服务端可以据此返回 503 等响应，
+//! 不会无限制地在内存中积压上传内容。每个提交返回一个 [`IngestHandle`]，用于等待该项的存储结果。
+//!
+//! 读取器的内容在工作线程中读入内存，读取期间不占用存储管理器；去重、差分和写入索引
+//! 仍按顺序逐项执行，与单线程调用 [`StorageManager::store_file`] 的结果一致。
+
+use anyhow::{Result, anyhow};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+use crate::storage::{StorageManager, StoreOutcome};
+
+/// 提交给队列的内容
+pub enum IngestSource {
+    /// 存储文件，规则同 [`StorageManager::store_file`]
+    Path { path: PathBuf, delete_source: bool },
+    /// 读取全部内容后以 `logical_path` 存储，规则同 [`StorageManager::store_bytes`]
+    Reader { logical_path: PathBuf, reader: Box<dyn Read + Send> },
+}
+
+impl std::fmt::Debug for IngestSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IngestSource::Path { path, delete_source } => f.debug_struct("Path")
+                .field("path", path)
+                .field("delete_source", delete_source)
+                .finish(),
+            IngestSource::Reader { logical_path, .. } => f.debug_struct("Reader")
+                .field("logical_path", logical_path)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// 队列参数
+#[derive(Debug, Clone, Copy)]
+pub struct IngestOptions {
+    /// 工作线程数，至少为 1
+    pub workers: usize,
+    /// 等待处理的任务上限，至少为 1
+    pub capacity: usize,
+    /// 读取器内容的大小上限（字节），0 表示不限制
+    pub max_reader_bytes: u64,
+}
+
+impl Default for IngestOptions {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            capacity: 64,
+            max_reader_bytes: 0,
+        }
+    }
+}
+
+struct Task {
+    source: IngestSource,
+    reply: mpsc::Sender<Result<StoreOutcome>>,
+}
+
+/// 单个提交的存储结果
+#[derive(Debug)]
+pub struct IngestHandle {
+    result: Receiver<Result<StoreOutcome>>,
+}
+
+impl IngestHandle {
+    /// 等待该项处理完成
+    pub fn wait(self) -> Result<StoreOutcome> {
+        self.result.recv()
+            .unwrap_or_else(|_| Err(anyhow!("Ingest worker stopped before finishing the item")))
+    }
+
+    /// 已处理完成时返回结果，否则返回 None
+    pub fn try_result(&self) -> Option<Result<StoreOutcome>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(anyhow!("Ingest worker stopped before finishing the item"))),
+        }
+    }
+}
+
+/// 有容量上限、由工作线程池消费的存储队列
+pub struct IngestQueue {
+    sender: Option<SyncSender<Task>>,
+    workers: Vec<JoinHandle<()>>,
+    manager: Arc<Mutex<StorageManager>>,
+}
+
+impl IngestQueue {
+    /// 接管存储管理器并启动工作线程，[`finish`](Self::finish) 后取回
+    pub fn new(manager: StorageManager, options: IngestOptions) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Task>(options.capacity.max(1));
+        let receiver = Arc::new(Mutex::new(receiver));
+        let manager = Arc::new(Mutex::new(manager));
+        let workers = (0..options.workers.max(1))
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let manager = Arc::clone(&manager);
+                std::thread::spawn(move || {
+                    loop {
+                        // 只在接收时持有锁，处理任务期间其他线程可以继续接收
+                        let task = lock(&receiver).recv();
+                        let Ok(task) = task else {
+                            break;
+                        };
+                        let result = process(&manager, task.source, options.max_reader_bytes);
+                        let _ = task.reply.send(result);
+                    }
+                })
+            })
+            .collect();
+        Self { sender: Some(sender), workers, manager }
+    }
+
+    /// 提交内容，队列已满时阻塞到有空位
+    pub fn push(&self, source: IngestSource) -> Result<IngestHandle> {
+        let (task, handle) = Self::task(source);
+        self.sender()?.send(task)
+            .map_err(|_| anyhow!("Ingest queue has stopped"))?;
+        Ok(handle)
+    }
+
+    /// 提交内容，队列已满时不阻塞，把内容原样退回
+    pub fn try_push(&self, source: IngestSource) -> std::result::Result<IngestHandle, IngestSource> {
+        let (task, handle) = Self::task(source);
+        let Ok(sender) = self.sender() else {
+            return Err(task.source);
+        };
+        match sender.try_send(task) {
+            Ok(()) => Ok(handle),
+            Err(TrySendError::Full(task) | TrySendError::Disconnected(task)) => Err(task.source),
+        }
+    }
+
+    /// 提交文件路径，见 [`push`](Self::push)
+    pub fn push_path(&self, path: impl Into<PathBuf>, delete_source: bool) -> Result<IngestHandle> {
+        self.push(IngestSource::Path { path: path.into(), delete_source })
+    }
+
+    /// 提交读取器，见 [`push`](Self::push)
+    pub fn push_reader(&self, logical_path: impl Into<PathBuf>, reader: impl Read + Send + 'static) -> Result<IngestHandle> {
+        self.push(IngestSource::Reader { logical_path: logical_path.into(), reader: Box::new(reader) })
+    }
+
+    /// 在两项存储之间访问存储管理器，例如查询索引
+    pub fn with_manager<T>(&self, f: impl FnOnce(&mut StorageManager) -> T) -> T {
+        f(&mut lock(&self.manager))
+    }
+
+    /// 停止接收新内容，处理完已提交的内容后取回存储管理器
+    pub fn finish(mut self) -> Result<StorageManager> {
+        self.shutdown();
+        let manager = Arc::clone(&self.manager);
+        drop(self);
+        let manager = Arc::try_unwrap(manager)
+            .map_err(|_| anyhow!("Storage manager is still in use by an ingest worker"))?;
+        Ok(manager.into_inner().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn task(source: IngestSource) -> (Task, IngestHandle) {
+        let (reply, result) = mpsc::channel();
+        (Task { source, reply }, IngestHandle { result })
+    }
+
+    fn sender(&self) -> Result<&SyncSender<Task>> {
+        self.sender.as_ref().ok_or_else(|| anyhow!("Ingest queue has stopped"))
+    }
+
+    fn shutdown(&mut self) {
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for IngestQueue {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn process(manager: &Mutex<StorageManager>, source: IngestSource, max_reader_bytes: u64) -> Result<StoreOutcome> {
+    match source {
+        IngestSource::Path { path, delete_source } => lock(manager).store_file(&path, delete_source),
+        IngestSource::Reader { logical_path, reader } => {
+            // 读取期间不占用存储管理器
+            let limit = if max_reader_bytes == 0 { u64::MAX } else { max_reader_bytes };
+            let mut content = Vec::new();
+            reader.take(limit.saturating_add(1)).read_to_end(&mut content)?;
+            if content.len() as u64 > limit {
+                return Err(anyhow!("Content exceeds the limit of {} bytes: {}", limit, logical_path.display()));
+            }
+            lock(manager).store_bytes(&logical_path, content)
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::index::create_index;
+    use std::fs;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    /// 收到信号后才返回内容的读取器，用于占住工作线程
+    struct GatedReader {
+        gate: Receiver<()>,
+        content: Option<Cursor<Vec<u8>>>,
+    }
+
+    impl Read for GatedReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.content.is_none() {
+                let _ = self.gate.recv();
+                self.content = Some(Cursor::new(b"gated".to_vec()));
+            }
+            self.content.as_mut().unwrap().read(buf)
+        }
+    }
+
+    #[test]
+    fn test_ingest_queue_applies_backpressure_and_reports_results() {
+        let dir = TempDir::new().unwrap();
+        let config = Config { storage_path: dir.path().join("storage"), ..Config::default() };
+        let manager = StorageManager::new(config.clone(), create_index(&config).unwrap());
+
+        // 单个工作线程被读取器占住，容量为 1 的队列在第二项之后就满了
+        let queue = IngestQueue::new(manager, IngestOptions { workers: 1, capacity: 1, max_reader_bytes: 16 });
+        let (open, gate) = mpsc::channel();
+        let gated = queue.push_reader(dir.path().join("gated.txt"), GatedReader { gate, content: None }).unwrap();
+        let queued = queue.push_reader(dir.path().join("queued.txt"), Cursor::new(b"queued".to_vec())).unwrap();
+        let rejected = queue.try_push(IngestSource::Path { path: dir.path().join("later.txt"), delete_source: false });
+        assert!(matches!(rejected, Err(IngestSource::Path { .. })));
+        assert!(gated.try_result().is_none());
+        open.send(()).unwrap();
+        assert!(gated.wait().unwrap().is_new());
+        assert!(queued.wait().is_ok());
+        let manager = queue.finish().unwrap();
+
+        // 多个生产者线程同时提交，相同内容仍然去重
+        let queue = Arc::new(IngestQueue::new(manager, IngestOptions { workers: 3, capacity: 2, max_reader_bytes: 16 }));
+        let producers: Vec<_> = (0..4)
+            .map(|producer| {
+                let queue = Arc::clone(&queue);
+                let dir = dir.path().to_path_buf();
+                std::thread::spawn(move || {
+                    (0..5)
+                        .map(|i| {
+                            let path = dir.join(format!("p{}-{}.txt", producer, i));
+                            fs::write(&path, format!("content {}", i)).unwrap();
+                            queue.push_path(path, true).unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let handles: Vec<IngestHandle> = producers.into_iter().flat_map(|p| p.join().unwrap()).collect();
+        let outcomes: Vec<StoreOutcome> = handles.into_iter().map(|h| h.wait().unwrap()).collect();
+        assert_eq!(outcomes.iter().filter(|outcome| matches!(outcome, StoreOutcome::Deduplicated(_))).count(), 15);
+        let too_large = queue.push_reader(dir.path().join("big.txt"), Cursor::new(vec![0u8; 17])).unwrap();
+        assert!(too_large.wait().unwrap_err().to_string().contains("limit"));
+        assert_eq!(queue.with_manager(|manager| manager.list_files().unwrap().len()), 22);
+
+        let queue = Arc::try_unwrap(queue).ok().unwrap();
+        assert_eq!(queue.finish().unwrap().list_files().unwrap().len(), 22);
+    }
+}
@@ -0,0 +1,455 @@
+use anyhow::{Context, Result, anyhow};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::storage::StorageManager;
+
+/// 持久化任务队列文件名（位于存储目录下）
+pub const JOB_QUEUE_FILE: &str = "jobs.db";
+
+/// 任务ID
+pub type JobId = u64;
+
+/// 任务优先级，数值越大越先执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum JobPriority {
+    /// 后台批量任务
+    Background,
+    #[default]
+    Normal,
+    /// 用户交互触发的任务（例如界面上的提取请求）
+    Interactive,
+}
+
+impl JobPriority {
+    fn to_i64(self) -> i64 {
+        match self {
+            JobPriority::Background => 0,
+            JobPriority::Normal => 1,
+            JobPriority::Interactive => 2,
+        }
+    }
+}
+
+/// 任务内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Job {
+    /// 存储文件
+    Store {
+        #[serde(with = "crate::paths::serde_path")]
+        path: PathBuf,
+        delete_source: bool,
+    },
+    /// 提取文件
+    Extract {
+        #[serde(with = "crate::paths::serde_path")]
+        path: PathBuf,
+    },
+    /// 增量同步目录，见 [`StorageManager::store_directory_incremental`]
+    SyncDirectory {
+        #[serde(with = "crate::paths::serde_path")]
+        path: PathBuf,
+    },
+    /// 处理保留的基础文件，见 [`StorageManager::gc`]
+    Gc,
+    /// 例行维护，见 [`StorageManager::run_maintenance`]
+    Maintenance,
+    /// 迁移到冷层，见 [`StorageManager::tier_migrate`]
+    TierMigrate,
+}
+
+impl Job {
+    fn execute(&self, storage: &mut StorageManager) -> Result<()> {
+        match self {
+            Job::Store { path, delete_source } => storage.store_file(path, *delete_source).map(|_| ()),
+            Job::Extract { path } => storage.owe_file(path),
+            Job::SyncDirectory { path } => storage.store_directory_incremental(path).map(|_| ()),
+            Job::Gc => storage.gc().map(|_| ()),
+            Job::Maintenance => storage.run_maintenance().map(|_| ()),
+            Job::TierMigrate => storage.tier_migrate().map(|_| ()),
+        }
+    }
+}
+
+/// 任务状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
+impl JobStatus {
+    /// 任务是否已结束
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed(_) | JobStatus::Cancelled)
+    }
+}
+
+/// 队列中的任务，按优先级排序，同优先级先入先出
+#[derive(Debug)]
+struct QueuedJob {
+    id: JobId,
+    priority: JobPriority,
+    job: Job,
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for QueuedJob {}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    next_id: JobId,
+    pending: BinaryHeap<QueuedJob>,
+    statuses: HashMap<JobId, JobStatus>,
+}
+
+/// 带优先级的存储/提取任务队列
+///
+/// 队列可以通过 `Arc` 在线程间共享：界面线程入队、查询和取消任务，
+/// 持有 `StorageManager` 的工作线程反复调用 [`JobQueue::run_next`]。
+/// 每个任务执行完后都会重新选择优先级最高的任务，
+/// 因此交互式提取会插到正在进行的后台批量存储之前执行。
+#[derive(Debug, Default)]
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 加入任务，返回任务ID
+    pub fn enqueue(&self, job: Job, priority: JobPriority) -> JobId {
+        let mut state = self.lock();
+        state.next_id += 1;
+        let id = state.next_id;
+        state.pending.push(QueuedJob { id, priority, job });
+        state.statuses.insert(id, JobStatus::Queued);
+        id
+    }
+
+    /// 查询任务状态，未知的任务ID返回 None
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.lock().statuses.get(&id).cloned()
+    }
+
+    /// 取消尚未开始的任务，返回是否成功取消
+    pub fn cancel(&self, id: JobId) -> bool {
+        let mut state = self.lock();
+        if state.statuses.get(&id) != Some(&JobStatus::Queued) {
+            return false;
+        }
+        state.pending.retain(|job| job.id != id);
+        state.statuses.insert(id, JobStatus::Cancelled);
+        true
+    }
+
+    /// 等待执行的任务数量
+    pub fn pending_count(&self) -> usize {
+        self.lock().pending.len()
+    }
+
+    /// 清除已结束任务的状态记录
+    pub fn clear_finished(&self) {
+        self.lock().statuses.retain(|_, status| !status.is_finished());
+    }
+
+    /// 执行优先级最高的一个任务，队列为空时返回 None
+    pub fn run_next(&self, storage: &mut StorageManager) -> Option<(JobId, JobStatus)> {
+        let queued = {
+            let mut state = self.lock();
+            let queued = state.pending.pop()?;
+            state.statuses.insert(queued.id, JobStatus::Running);
+            queued
+        };
+
+        // 执行期间不持有锁，其他线程可以继续入队或取消
+        let status = match queued.job.execute(storage) {
+            Ok(()) => JobStatus::Completed,
+            Err(e) => JobStatus::Failed(e.to_string()),
+        };
+        self.lock().statuses.insert(queued.id, status.clone());
+        Some((queued.id, status))
+    }
+
+    /// 执行任务直到队列为空，返回执行的任务数
+    pub fn run_until_empty(&self, storage: &mut StorageManager) -> usize {
+        let mut count = 0;
+        while self.run_next(storage).is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 保存在存储目录 `jobs.db`（SQLite）中的任务队列，程序重启后继续执行
+///
+/// 用法与 [`JobQueue`] 相同，任务的入队、开始和结束都立即写入数据库。
+/// 打开队列时，上次退出前仍处于 `Running` 的任务（例如 gc 或同步中途崩溃）恢复为 `Queued`，
+/// 由之后的 [`run_next`](Self::run_next) 重新执行；各任务重复执行都是安全的，
+/// 已存储的文件会被跳过。已结束任务的状态保留到 [`clear_finished`](Self::clear_finished)。
+pub struct PersistentJobQueue {
+    conn: Mutex<Connection>,
+    resumed: Vec<JobId>,
+}
+
+impl PersistentJobQueue {
+    /// 打开存储目录下的任务队列，不存在时创建
+    pub fn open(storage_path: &Path) -> Result<Self> {
+        std::fs::create_dir_all(storage_path)
+            .context("Failed to create storage directory")?;
+        let conn = Connection::open(storage_path.join(JOB_QUEUE_FILE))
+            .context("Failed to open job queue")?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                priority INTEGER NOT NULL,
+                job TEXT NOT NULL,
+                state TEXT NOT NULL,
+                error TEXT,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS jobs_pending ON jobs (state, priority, id);",
+        ).context("Failed to create job queue")?;
+
+        let resumed: Vec<JobId> = conn.prepare("SELECT id FROM jobs WHERE state = 'running' ORDER BY id")?
+            .query_map([], |row| row.get::<_, i64>(0))?
+            .map(|id| id.map(|id| id as JobId))
+            .collect::<rusqlite::Result<_>>()?;
+        conn.execute("UPDATE jobs SET state = 'queued', updated_at = ?1 WHERE state = 'running'", params![now()])
+            .context("Failed to resume interrupted jobs")?;
+        Ok(Self { conn: Mutex::new(conn), resumed })
+    }
+
+    /// 打开时从中断中恢复、重新排队的任务
+    pub fn resumed(&self) -> &[JobId] {
+        &self.resumed
+    }
+
+    /// 加入任务，返回任务ID
+    pub fn enqueue(&self, job: Job, priority: JobPriority) -> Result<JobId> {
+        let data = serde_json::to_string(&job).context("Failed to serialize job")?;
+        let conn = self.lock();
+        conn.execute(
+            "INSERT INTO jobs (priority, job, state, updated_at) VALUES (?1, ?2, 'queued', ?3)",
+            params![priority.to_i64(), data, now()],
+        ).context("Failed to enqueue job")?;
+        Ok(conn.last_insert_rowid() as JobId)
+    }
+
+    /// 查询任务状态，未知的任务ID返回 None
+    pub fn status(&self, id: JobId) -> Result<Option<JobStatus>> {
+        let row: Option<(String, Option<String>)> = self.lock()
+            .query_row("SELECT state, error FROM jobs WHERE id = ?1", params![id as i64], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()?;
+        row.map(|(state, error)| parse_status(&state, error)).transpose()
+    }
+
+    /// 取消尚未开始的任务，返回是否成功取消
+    pub fn cancel(&self, id: JobId) -> Result<bool> {
+        let changed = self.lock().execute(
+            "UPDATE jobs SET state = 'cancelled', updated_at = ?2 WHERE id = ?1 AND state = 'queued'",
+            params![id as i64, now()],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// 等待执行的任务数量
+    pub fn pending_count(&self) -> Result<usize> {
+        let count: i64 = self.lock().query_row("SELECT COUNT(*) FROM jobs WHERE state = 'queued'", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// 删除已结束任务的记录，返回删除的数量
+    pub fn clear_finished(&self) -> Result<usize> {
+        Ok(self.lock().execute("DELETE FROM jobs WHERE state IN ('completed', 'failed', 'cancelled')", [])?)
+    }
+
+    /// 执行优先级最高的一个任务，队列为空时返回 None
+    pub fn run_next(&self, storage: &mut StorageManager) -> Result<Option<(JobId, JobStatus)>> {
+        let Some((id, job)) = self.claim()? else {
+            return Ok(None);
+        };
+
+        // 执行期间不持有锁，其他线程可以继续入队或取消
+        let status = match job.execute(storage) {
+            Ok(()) => JobStatus::Completed,
+            Err(e) => JobStatus::Failed(e.to_string()),
+        };
+        let (state, error) = match &status {
+            JobStatus::Failed(error) => ("failed", Some(error.as_str())),
+            _ => ("completed", None),
+        };
+        self.lock().execute(
+            "UPDATE jobs SET state = ?2, error = ?3, updated_at = ?4 WHERE id = ?1",
+            params![id as i64, state, error, now()],
+        ).context("Failed to record job status")?;
+        Ok(Some((id, status)))
+    }
+
+    /// 执行任务直到队列为空，返回执行的任务数
+    pub fn run_until_empty(&self, storage: &mut StorageManager) -> Result<usize> {
+        let mut count = 0;
+        while self.run_next(storage)?.is_some() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// 取出优先级最高的任务并标记为执行中
+    fn claim(&self) -> Result<Option<(JobId, Job)>> {
+        let conn = self.lock();
+        let Some((id, data)) = conn.query_row(
+            "SELECT id, job FROM jobs WHERE state = 'queued' ORDER BY priority DESC, id LIMIT 1",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        ).optional()? else {
+            return Ok(None);
+        };
+        conn.execute(
+            "UPDATE jobs SET state = 'running', attempts = attempts + 1, updated_at = ?2 WHERE id = ?1",
+            params![id, now()],
+        ).context("Failed to start job")?;
+        let job = serde_json::from_str(&data)
+            .with_context(|| format!("Invalid job record: {}", id))?;
+        Ok(Some((id as JobId, job)))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn parse_status(state: &str, error: Option<String>) -> Result<JobStatus> {
+    Ok(match state {
+        "queued" => JobStatus::Queued,
+        "running" => JobStatus::Running,
+        "completed" => JobStatus::Completed,
+        "failed" => JobStatus::Failed(error.unwrap_or_default()),
+        "cancelled" => JobStatus::Cancelled,
+        other => return Err(anyhow!("Unknown job state: {}", other)),
+    })
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::index::create_index;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_interactive_jobs_run_first_and_cancel() {
+        let dir = TempDir::new().unwrap();
+        let config = Config {
+            storage_path: dir.path().join("storage"),
+            ..Config::default()
+        };
+        let mut storage = StorageManager::new(config.clone(), create_index(&config).unwrap());
+
+        let wanted = dir.path().join("wanted.txt");
+        fs::write(&wanted, "wanted").unwrap();
+        storage.store_file(&wanted, true).unwrap();
+
+        let queue = JobQueue::new();
+        let mut batch = Vec::new();
+        for i in 0..3 {
+            let path = dir.path().join(format!("bulk{}.txt", i));
+            fs::write(&path, format!("bulk {}", i)).unwrap();
+            batch.push(queue.enqueue(Job::Store { path, delete_source: true }, JobPriority::Background));
+        }
+
+        // 第一个后台任务执行后，用户请求提取
+        assert_eq!(queue.run_next(&mut storage), Some((batch[0], JobStatus::Completed)));
+        let extract = queue.enqueue(Job::Extract { path: wanted.clone() }, JobPriority::Interactive);
+        assert!(queue.cancel(batch[2]));
+        assert!(!queue.cancel(batch[0]));
+
+        assert_eq!(queue.run_next(&mut storage), Some((extract, JobStatus::Completed)));
+        assert!(wanted.exists());
+        assert_eq!(queue.status(batch[1]), Some(JobStatus::Queued));
+
+        assert_eq!(queue.run_until_empty(&mut storage), 1);
+        assert_eq!(queue.status(batch[1]), Some(JobStatus::Completed));
+        assert_eq!(queue.status(batch[2]), Some(JobStatus::Cancelled));
+
+        let missing = queue.enqueue(Job::Extract { path: dir.path().join("missing") }, JobPriority::Normal);
+        queue.run_next(&mut storage);
+        assert!(matches!(queue.status(missing), Some(JobStatus::Failed(_))));
+
+        queue.clear_finished();
+        assert_eq!(queue.status(extract), None);
+    }
+
+    #[test]
+    fn test_persistent_queue_resumes_interrupted_jobs() {
+        let dir = TempDir::new().unwrap();
+        let config = Config {
+            storage_path: dir.path().join("storage"),
+            ..Config::default()
+        };
+        let mut storage = StorageManager::new(config.clone(), create_index(&config).unwrap());
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "alpha").unwrap();
+
+        let queue = PersistentJobQueue::open(&config.storage_path).unwrap();
+        let gc = queue.enqueue(Job::Gc, JobPriority::Background).unwrap();
+        let store = queue.enqueue(Job::Store { path: file.clone(), delete_source: false }, JobPriority::Interactive).unwrap();
+        let cancelled = queue.enqueue(Job::Maintenance, JobPriority::Normal).unwrap();
+        assert!(queue.cancel(cancelled).unwrap());
+
+        // 取出任务后模拟崩溃：任务停留在执行中，重新打开后恢复排队
+        assert_eq!(queue.claim().unwrap().map(|(id, _)| id), Some(store));
+        assert_eq!(queue.status(store).unwrap(), Some(JobStatus::Running));
+        drop(queue);
+
+        let queue = PersistentJobQueue::open(&config.storage_path).unwrap();
+        assert_eq!(queue.resumed(), &[store]);
+        assert_eq!(queue.pending_count().unwrap(), 2);
+        assert_eq!(queue.run_next(&mut storage).unwrap(), Some((store, JobStatus::Completed)));
+        assert!(storage.get_file(&file).unwrap().is_some());
+        assert_eq!(queue.run_until_empty(&mut storage).unwrap(), 1);
+        assert_eq!(queue.status(gc).unwrap(), Some(JobStatus::Completed));
+        assert_eq!(queue.status(cancelled).unwrap(), Some(JobStatus::Cancelled));
+
+        assert_eq!(queue.clear_finished().unwrap(), 3);
+        assert_eq!(queue.status(gc).unwrap(), None);
+    }
+}
@@ -40,16 +40,99 @@
 //! - System utilities
 
 pub mod config;
+pub mod output;
+pub mod compression;
+pub mod crypto;
 pub mod storage;
 pub mod index;
 pub mod dedup;
 pub mod delta;
+pub mod paths;
+pub mod error;
+pub mod throttle;
+pub mod filter;
+pub mod repository;
+pub mod repo_set;
+pub mod package;
+pub mod bundle;
+pub mod backup;
+pub mod merkle;
+pub mod signing;
+pub mod grep;
+pub mod content_index;
+pub mod phash;
+pub mod bloom;
+pub mod jobs;
+pub mod deadline;
+pub mod fsutil;
+pub mod patterns;
+pub mod rewrite;
+pub mod scan_cache;
+pub mod backend;
+pub mod activity;
+pub mod events;
+pub mod verify;
+pub mod parity;
+pub mod container;
+pub mod manifest;
+pub mod signature;
+pub mod marker;
+pub mod stub;
+pub mod view;
+pub mod external;
+pub mod recompress;
+pub mod precompress;
+pub mod share;
+pub mod scoped;
+pub mod ingest;
+#[cfg(feature = "http")]
+pub mod fetch;
+#[cfg(all(feature = "fuse", unix))]
+pub mod fuse;
+#[cfg(all(feature = "projfs", windows))]
+pub mod projfs;
 
-pub use config::{Config, IndexMode, CompressionAlgorithm, DeltaAlgorithm};
-pub use storage::StorageManager;
-pub use index::{FileEntry, IndexStore, create_index};
-pub use dedup::{ContentDeduplicator, DedupInfo, DedupStats};
-pub use delta::{DeltaStorage, DeltaInfo, SimilarityMatch, DeltaStats};
+pub use config::{BlobExtension, Config, IndexMode, CompressionAlgorithm, DeltaAlgorithm, Durability, Verbosity};
+pub use storage::{BrokenEntry, BrokenReason, CaseDuplicateGroup, CompactReport, ConvergeReport, DeleteMode, DiskUsage, FileStatus, GcReport, HealthReport, MaintenanceReport, MirrorReport, OnConflict, Remediation, RepairReport, ScrubReport, StartupReport, StorageManager, StoreOutcome, TierReport, Transaction};
+pub use error::StowrError;
+pub use throttle::{ByteBudget, IoThrottle};
+pub use filter::{BatchReport, ContentFilter, FilterDecision, SecretPatternFilter};
+pub use compression::{Compressor, CompressorRegistry, DecompressionLimits, SizeEstimate};
+pub use index::{EntryOrder, FileEntry, IndexHealth, IndexStore, IndexSummary, SizeBucket, TreeDirectory, TreeListing, create_index, create_index_with_key};
+pub use crypto::{EncryptionKey, KdfParams, KeyProvider, StaticKeyProvider};
+pub use repository::{Repository, RepositoryManifest};
+pub use repo_set::{RepoSet, RouteRule};
+pub use package::PackageMetadata;
+pub use bundle::BundleMetadata;
+pub use backup::{BackupManifest, BackupReport, BackupSince};
+pub use merkle::{MerkleProof, MerkleTree, ProofStep};
+pub use signing::{IndexSignature, SignatureReport};
+pub use grep::{GrepMatch, GrepOptions};
+pub use content_index::ContentHit;
+pub use container::{BlobEncryption, BlobHeader};
+pub use manifest::{Manifest, ManifestVars};
+pub use signature::BlockSignature;
+pub use marker::SourceMarker;
+pub use stub::Stub;
+pub use view::{NodeAttr, NodeKind, ReadOnlyView};
+pub use external::ExternalCodec;
+pub use bloom::BloomFilter;
+pub use patterns::{Matcher, PatternSet};
+pub use rewrite::{PathRewrite, PathRule};
+pub use activity::{ActivityRecord, Operation};
+pub use events::StowrEvent;
+pub use verify::VerifyStats;
+pub use backend::{DirectoryBackend, StorageBackend, StorageTier};
+pub use jobs::{Job, JobId, JobPriority, JobQueue, JobStatus, PersistentJobQueue};
+pub use dedup::{ContentDeduplicator, DedupInfo, DedupStats, EntryDedupInfo};
+pub use delta::{DeltaStorage, DeltaInfo, DeltaRecord, SimilarityMatch, DeltaStats};
+pub use recompress::StreamEncoding;
+pub use precompress::{EmlPrecompressor, Precompression, Precompressor, SegmentKind, ZipPrecompressor};
+pub use share::ShareClaims;
+pub use scoped::{Admin, ReadOnly, ScopedManager, StoreOnly};
+pub use ingest::{IngestHandle, IngestOptions, IngestQueue, IngestSource};
+#[cfg(feature = "http")]
+pub use fetch::FetchOptions;
 
 // Re-export commonly used types
 pub use anyhow::Result;
@@ -0,0 +1,216 @@
+//! 可移植的归档清单
+//!
+//! 在列表文件格式（每行一个路径或通配符模式，`!` 开头为排除，`#` 开头为注释）的基础上增加：
+//!
+//! - 变量替换：`${HOME}`、`${PROJECT}` 等，先查找调用方设置的变量，再查找环境变量，
+//!   `${HOME}` 在 Windows 上退回到 `USERPROFILE`；`$$` 表示字面的 `$`
+//! - 按操作系统的条件段：`[os:windows]`、`[os:linux,macos]`、`[os:unix]` 之后的行只在匹配的系统上生效，
+//!   `[all]` 结束条件段
+//!
+//! 团队可以共享同一份清单，在不同机器和系统上展开为各自的路径。
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// 展开后的清单
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// 包含的路径或通配符模式，按清单中的顺序
+    pub includes: Vec<String>,
+    /// 排除模式（已去掉 `!` 前缀）
+    pub excludes: Vec<String>,
+}
+
+/// 展开清单时使用的变量和目标系统
+#[derive(Debug, Clone)]
+pub struct ManifestVars {
+    vars: HashMap<String, String>,
+    os: String,
+    use_env: bool,
+}
+
+impl ManifestVars {
+    /// 当前系统，未设置的变量从环境变量读取
+    pub fn new() -> Self {
+        Self {
+            vars: HashMap::new(),
+            os: std::env::consts::OS.to_string(),
+            use_env: true,
+        }
+    }
+
+    /// 设置变量，优先于同名的环境变量
+    pub fn set(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.vars.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// 按指定系统（`std::env::consts::OS` 的取值，如 `linux`、`windows`）选择条件段
+    pub fn for_os(mut self, os: &str) -> Self {
+        self.os = os.to_lowercase();
+        self
+    }
+
+    /// 不读取环境变量，只使用显式设置的变量
+    pub fn without_env(mut self) -> Self {
+        self.use_env = false;
+        self
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.vars.get(name) {
+            return Some(value.clone());
+        }
+        if !self.use_env {
+            return None;
+        }
+        std::env::var(name).ok().or_else(|| match name {
+            "HOME" => std::env::var("USERPROFILE").ok(),
+            _ => None,
+        })
+    }
+
+    /// 系统名或系统族（`windows` / `unix`）是否匹配
+    fn matches_os(&self, name: &str) -> bool {
+        let family = if self.os == "windows" { "windows" } else { "unix" };
+        name == self.os || name == family
+    }
+}
+
+impl Default for ManifestVars {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Manifest {
+    /// 读取并展开清单文件
+    pub fn load(path: &Path, vars: &ManifestVars) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+        Self::parse(&content, vars)
+            .with_context(|| format!("Invalid manifest: {}", path.display()))
+    }
+
+    /// 展开清单内容；未定义的变量和无法识别的条件段返回带行号的错误
+    pub fn parse(content: &str, vars: &ManifestVars) -> Result<Self> {
+        let mut manifest = Self::default();
+        let mut active = true;
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(section) = parse_section(line) {
+                active = match section {
+                    Section::All => true,
+                    Section::Os(names) => names.iter().any(|name| vars.matches_os(name)),
+                };
+                continue;
+            }
+            // 其他系统的条件段不展开，其中可以引用只在该系统上存在的变量
+            if !active {
+                continue;
+            }
+
+            let (target, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (&mut manifest.excludes, pattern),
+                None => (&mut manifest.includes, line),
+            };
+            let expanded = substitute(pattern, vars)
+                .with_context(|| format!("Line {}", number + 1))?;
+            target.push(expanded);
+        }
+        Ok(manifest)
+    }
+}
+
+enum Section {
+    All,
+    Os(Vec<String>),
+}
+
+/// 只有 `[all]` 和 `[os:...]` 是条件段，其他以 `[` 开头的行（如 `[a-z]*.txt`）仍是模式
+fn parse_section(line: &str) -> Option<Section> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim().to_lowercase();
+    if inner == "all" {
+        return Some(Section::All);
+    }
+    let names = inner.strip_prefix("os:")?;
+    Some(Section::Os(
+        names.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect(),
+    ))
+}
+
+fn substitute(pattern: &str, vars: &ManifestVars) -> Result<String> {
+    let mut result = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = after.strip_prefix('{') {
+            let end = after.find('}')
+                .ok_or_else(|| anyhow!("Unterminated variable reference in '{}'", pattern))?;
+            let name = &after[..end];
+            let value = vars.lookup(name)
+                .ok_or_else(|| anyhow!("Undefined variable ${{{}}}", name))?;
+            result.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            // 单独的 `$`（如 Windows 管理共享 `C$`）保持原样
+            result.push('$');
+            rest = after;
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r"
+# 团队共享的清单
+${PROJECT}/docs/**
+!${PROJECT}/**/*.tmp
+
+[os:windows]
+${APPDATA}\Tool\settings.json
+[os:linux,macos]
+${HOME}/.toolrc
+[all]
+cost$$report.txt
+C$/share
+[a-z]*.log
+";
+
+    #[test]
+    fn test_parse_substitutes_and_selects_sections() {
+        let vars = ManifestVars::new()
+            .without_env()
+            .set("PROJECT", "/work/app")
+            .set("HOME", "/home/alice");
+
+        let linux = Manifest::parse(MANIFEST, &vars.clone().for_os("linux")).unwrap();
+        assert_eq!(linux.includes, vec![
+            "/work/app/docs/**", "/home/alice/.toolrc", "cost$report.txt", "C$/share", "[a-z]*.log",
+        ]);
+        assert_eq!(linux.excludes, vec!["/work/app/**/*.tmp"]);
+
+        // Windows 段引用的 APPDATA 只在 Windows 上需要定义
+        assert!(Manifest::parse(MANIFEST, &vars.clone().for_os("windows")).is_err());
+        let windows = Manifest::parse(MANIFEST, &vars.set("APPDATA", r"C:\Users\alice\AppData").for_os("windows")).unwrap();
+        assert_eq!(windows.includes[1], r"C:\Users\alice\AppData\Tool\settings.json");
+
+        let err = Manifest::parse("${MISSING}/x", &ManifestVars::new().without_env()).unwrap_err();
+        assert!(format!("{:#}", err).contains("Line 1"));
+        assert!(Manifest::parse("${UNCLOSED", &ManifestVars::new()).is_err());
+    }
+}
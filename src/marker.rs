@@ -0,0 +1,160 @@
+//! 源文件上的 stowr 标记
+//!
+//! 存储后保留源文件时，可以在源文件的扩展属性（Unix 上的 `user.stowr.marker`）或
+//! NTFS 备用数据流（`<文件>:stowr.marker`）中写入条目ID和内容哈希。
+//! 文件被移动（重命名）后标记随文件一起移动，再次存储时只要大小和修改时间与条目记录一致，
+//! 就可以直接使用标记中的哈希，不需要重新读取文件。
+//!
+//! 标记只是提示：文件系统不支持时静默跳过，标记指向的条目不存在或已变化时按普通方式存储。
+
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+/// 扩展属性名
+#[cfg(unix)]
+const XATTR_NAME: &str = "user.stowr.marker";
+/// NTFS 备用数据流名
+#[cfg(windows)]
+const STREAM_NAME: &str = "stowr.marker";
+/// 标记内容的长度上限，超过时视为无效
+const MAX_MARKER_LEN: usize = 1024;
+
+/// 写入源文件的标记
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMarker {
+    /// 存储时创建的条目ID
+    pub entry_id: String,
+    /// 存储时记录的内容哈希
+    pub hash: String,
+}
+
+impl SourceMarker {
+    /// 写入标记，已有的标记会被覆盖
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let data = serde_json::to_vec(self)?;
+        write_raw(path, &data)
+    }
+
+    /// 读取标记，没有标记或内容无法解析时返回 None
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        Ok(read_raw(path)?
+            .filter(|data| data.len() <= MAX_MARKER_LEN)
+            .and_then(|data| serde_json::from_slice(&data).ok()))
+    }
+
+    /// 删除标记，没有标记时不报错
+    pub fn remove(path: &Path) -> io::Result<()> {
+        remove_raw(path)
+    }
+}
+
+#[cfg(unix)]
+fn write_raw(path: &Path, data: &[u8]) -> io::Result<()> {
+    xattr::set(path, XATTR_NAME, data)
+}
+
+#[cfg(unix)]
+fn read_raw(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match xattr::get(path, XATTR_NAME) {
+        Ok(data) => Ok(data),
+        // 不支持扩展属性的文件系统上视为没有标记
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(unix)]
+fn remove_raw(path: &Path) -> io::Result<()> {
+    if read_raw(path)?.is_some() {
+        xattr::remove(path, XATTR_NAME)?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn stream_path(path: &Path) -> std::path::PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(":");
+    name.push(STREAM_NAME);
+    name.into()
+}
+
+/// 执行修改备用数据流的操作后还原文件的修改时间和访问时间
+///
+/// 写入或删除备用数据流会更新文件本身的修改时间，导致之后按大小和修改时间判断文件未变化时失配
+#[cfg(windows)]
+fn preserving_times(path: &Path, modify: impl FnOnce() -> io::Result<()>) -> io::Result<()> {
+    let metadata = std::fs::metadata(path)?;
+    modify()?;
+    let mut times = std::fs::FileTimes::new().set_modified(metadata.modified()?);
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    std::fs::OpenOptions::new().write(true).open(path)?.set_times(times)
+}
+
+#[cfg(windows)]
+fn write_raw(path: &Path, data: &[u8]) -> io::Result<()> {
+    preserving_times(path, || std::fs::write(stream_path(path), data))
+}
+
+#[cfg(windows)]
+fn read_raw(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match std::fs::read(stream_path(path)) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(windows)]
+fn remove_raw(path: &Path) -> io::Result<()> {
+    if read_raw(path)?.is_none() {
+        return Ok(());
+    }
+    preserving_times(path, || std::fs::remove_file(stream_path(path)))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn write_raw(_path: &Path, _data: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "source markers are not supported on this platform"))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_raw(_path: &Path) -> io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn remove_raw(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_marker_keeps_modified_time() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("source.txt");
+        std::fs::write(&path, "content").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+
+        let marker = SourceMarker { entry_id: "id".to_string(), hash: "abc".to_string() };
+        match marker.write(&path) {
+            // 不支持扩展属性的文件系统上跳过
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => return,
+            result => result.unwrap(),
+        }
+        assert_eq!(SourceMarker::read(&path).unwrap(), Some(marker));
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), modified);
+        SourceMarker::remove(&path).unwrap();
+        assert_eq!(SourceMarker::read(&path).unwrap(), None);
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), modified);
+    }
+}
@@ -0,0 +1,227 @@
+//! 仓库完整性清单（Merkle 树）
+//!
+//! 叶子为 `SHA256(0x00 || 编码后的路径 || 0x00 || 内容哈希)`，按编码后的路径排序；
+//! 内部节点为 `SHA256(0x01 || 左 || 右)`，某层节点数为奇数时最后一个节点直接升到上一层。
+//! 空仓库的根为 `SHA256("")`。
+//!
+//! 两个仓库的根相同即内容一致，同步前只需比较根；把根记录在仓库之外，之后可以用
+//! [`MerkleProof`] 证明某个条目属于当时的仓库，发现索引被篡改。
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+
+use crate::crypto;
+use crate::index::FileEntry;
+use crate::paths;
+
+type Node = [u8; 32];
+
+fn leaf_node(encoded_path: &str, hash: &str) -> Node {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(encoded_path.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(hash.as_bytes());
+    hasher.finalize().into()
+}
+
+fn inner_node(left: &Node, right: &Node) -> Node {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// 由索引条目构建的 Merkle 树
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    /// 按编码后的路径排序的 (路径, 内容哈希)
+    leaves: Vec<(String, String)>,
+    /// 自底向上的各层节点，最后一层只有根
+    levels: Vec<Vec<Node>>,
+}
+
+impl MerkleTree {
+    /// 由条目构建，缺少哈希的旧条目以空字符串参与计算
+    pub fn from_entries<'a>(entries: impl IntoIterator<Item = &'a FileEntry>) -> Self {
+        let mut leaves: Vec<(String, String)> = entries.into_iter()
+            .map(|entry| (paths::encode_path(&entry.original_path), entry.hash.clone().unwrap_or_default()))
+            .collect();
+        leaves.sort();
+
+        let mut levels = vec![leaves.iter().map(|(path, hash)| leaf_node(path, hash)).collect::<Vec<_>>()];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels.last().unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => inner_node(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Self { leaves, levels }
+    }
+
+    /// 根哈希的十六进制表示
+    pub fn root_hash(&self) -> String {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => crypto::to_hex(root),
+            None => crypto::to_hex(&Sha256::digest(b"")),
+        }
+    }
+
+    /// 条目数
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// 与另一棵树相比新增、缺少或内容不同的路径，按路径排序；根相同时直接返回空列表
+    pub fn diff(&self, other: &MerkleTree) -> Vec<PathBuf> {
+        if self.root_hash() == other.root_hash() {
+            return Vec::new();
+        }
+        let (mut ours, mut theirs) = (self.leaves.iter().peekable(), other.leaves.iter().peekable());
+        let mut changed = Vec::new();
+        loop {
+            let path = match (ours.peek(), theirs.peek()) {
+                (None, None) => break,
+                (Some((path, _)), None) => { ours.next(); path }
+                (None, Some((path, _))) => { theirs.next(); path }
+                (Some((a, a_hash)), Some((b, b_hash))) => match a.cmp(b) {
+                    Ordering::Less => { ours.next(); a }
+                    Ordering::Greater => { theirs.next(); b }
+                    Ordering::Equal => {
+                        ours.next();
+                        theirs.next();
+                        if a_hash == b_hash {
+                            continue;
+                        }
+                        a
+                    }
+                },
+            };
+            changed.push(paths::decode_path(path).unwrap_or_else(|_| PathBuf::from(path)));
+        }
+        changed
+    }
+
+    /// 生成证明某条目属于这棵树的路径证明，路径不在树中时返回 None
+    pub fn proof(&self, file_path: &Path) -> Option<MerkleProof> {
+        let encoded = paths::encode_path(file_path);
+        let leaf = self.leaves.binary_search_by(|(path, _)| path.cmp(&encoded)).ok()?;
+        let mut index = leaf;
+        let mut siblings = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if let Some(node) = level.get(sibling) {
+                siblings.push(ProofStep { hash: crypto::to_hex(node), left: sibling < index });
+            }
+            index /= 2;
+        }
+        Some(MerkleProof {
+            original_path: file_path.to_path_buf(),
+            hash: self.leaves[leaf].1.clone(),
+            siblings,
+        })
+    }
+}
+
+/// 证明路径上的一个兄弟节点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub hash: String,
+    /// 兄弟节点是否在左侧
+    pub left: bool,
+}
+
+/// 条目属于某个根的证明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    #[serde(with = "crate::paths::serde_path")]
+    pub original_path: PathBuf,
+    /// 条目的内容哈希
+    pub hash: String,
+    pub siblings: Vec<ProofStep>,
+}
+
+impl MerkleProof {
+    /// 检查证明能否推出给定的根哈希
+    pub fn verify(&self, root_hash: &str) -> bool {
+        let mut node = leaf_node(&paths::encode_path(&self.original_path), &self.hash);
+        for step in &self.siblings {
+            let Ok(sibling) = crypto::from_hex(&step.hash) else {
+                return false;
+            };
+            let Ok(sibling) = Node::try_from(sibling.as_slice()) else {
+                return false;
+            };
+            node = if step.left { inner_node(&sibling, &node) } else { inner_node(&node, &sibling) };
+        }
+        crypto::to_hex(&node) == root_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, hash: &str) -> FileEntry {
+        let mut entry = FileEntry::new(
+            path.to_string(),
+            PathBuf::from(path),
+            PathBuf::from("blob"),
+            1,
+            1,
+            crate::config::CompressionAlgorithm::Gzip,
+        );
+        entry.hash = Some(hash.to_string());
+        entry
+    }
+
+    #[test]
+    fn test_merkle_root_diff_and_proofs() {
+        let entries: Vec<FileEntry> = ["a.txt", "b.txt", "c/d.txt", "e.txt", "f.txt"]
+            .iter()
+            .map(|path| entry(path, &format!("hash-{}", path)))
+            .collect();
+        let tree = MerkleTree::from_entries(&entries);
+        assert_eq!(tree.len(), 5);
+
+        // 根与条目顺序无关
+        let reversed = MerkleTree::from_entries(entries.iter().rev());
+        assert_eq!(tree.root_hash(), reversed.root_hash());
+        assert!(tree.diff(&reversed).is_empty());
+
+        let mut changed = entries.clone();
+        changed[1].hash = Some("other".to_string());
+        changed.remove(3);
+        changed.push(entry("g.txt", "hash-g"));
+        let changed = MerkleTree::from_entries(&changed);
+        assert_ne!(tree.root_hash(), changed.root_hash());
+        assert_eq!(changed.diff(&tree), vec![PathBuf::from("b.txt"), PathBuf::from("e.txt"), PathBuf::from("g.txt")]);
+
+        let root = tree.root_hash();
+        for e in &entries {
+            let proof = tree.proof(&e.original_path).unwrap();
+            assert!(proof.verify(&root));
+            assert!(!proof.verify(&changed.root_hash()));
+        }
+        let mut forged = tree.proof(Path::new("b.txt")).unwrap();
+        forged.hash = "other".to_string();
+        assert!(!forged.verify(&root));
+        assert!(tree.proof(Path::new("missing.txt")).is_none());
+
+        let empty = MerkleTree::from_entries(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.root_hash(), crypto::to_hex(&Sha256::digest(b"")));
+    }
+}
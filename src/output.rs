@@ -0,0 +1,84 @@
+//! 库内提示信息的输出级别
+//!
+//! 存储、提取等操作的进度和警告通过本模块的宏输出，低于当前级别的信息直接丢弃。
+//! 默认不输出任何信息，避免作为库使用时干扰调用方自己的输出（如 CLI 的 JSON 结果）。
+//! 索引、模式匹配等模块没有配置可读，因此级别是进程级的，对同一进程中的所有 `StorageManager` 生效。
+//! `StorageManager::new` 不会修改级别，避免打开一个存储库就改变其他存储库的输出；
+//! 应用在启动时调用 [`set_verbosity`]，例如传入 `Config::verbosity`。
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::config::Verbosity;
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Silent as u8);
+
+/// 设置进程内的输出级别
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// 当前的输出级别
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Silent,
+        1 => Verbosity::Warn,
+        2 => Verbosity::Info,
+        _ => Verbosity::Debug,
+    }
+}
+
+/// 当前级别是否输出 `level` 级别的信息
+pub fn enabled(level: Verbosity) -> bool {
+    level != Verbosity::Silent && verbosity() >= level
+}
+
+/// 警告和错误，输出到标准错误
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if $crate::output::enabled($crate::config::Verbosity::Warn) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// 操作结果和进度，输出到标准输出
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::output::enabled($crate::config::Verbosity::Info) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// 压缩率、相似度等细节，输出到标准输出
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::output::enabled($crate::config::Verbosity::Debug) {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {debug, info, warning};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_levels() {
+        let previous = verbosity();
+        set_verbosity(Verbosity::Info);
+        assert!(enabled(Verbosity::Warn) && enabled(Verbosity::Info));
+        assert!(!enabled(Verbosity::Debug));
+
+        set_verbosity(Verbosity::Silent);
+        assert!(!enabled(Verbosity::Warn));
+        assert!(!enabled(Verbosity::Silent));
+        set_verbosity(previous);
+
+        assert_eq!(Verbosity::from_str("debug").unwrap(), Verbosity::Debug);
+        assert_eq!(Verbosity::from_str(&Verbosity::Warn.to_string()).unwrap(), Verbosity::Warn);
+        assert!(Verbosity::from_str("loud").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! 可移植的单条目包（`.stowrpkg`）
+//!
+//! 文件格式：魔数(8) + 元数据长度(u32 LE) + 元数据 JSON + 压缩后的完整内容。
+//! 包中不包含差分或引用关系，可以在任意仓库中导入。
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::CompressionAlgorithm;
+use crate::fsutil;
+
+/// 包文件头部魔数
+const PACKAGE_MAGIC: &[u8; 8] = b"STWRPKG1";
+/// 当前包格式版本
+pub const FORMAT_VERSION: u32 = 1;
+/// 包文件扩展名
+pub const PACKAGE_EXTENSION: &str = "stowrpkg";
+
+/// 包元数据
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageMetadata {
+    pub format_version: u32,
+    #[serde(with = "crate::paths::serde_path")]
+    pub original_path: PathBuf,
+    /// 原始内容大小
+    pub file_size: u64,
+    /// 原始内容的 SHA256
+    pub hash: String,
+    /// 内容使用的压缩算法
+    pub compression_algorithm: CompressionAlgorithm,
+    /// 条目在原仓库中的创建时间
+    pub created_at: String,
+    /// 条目的备注
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 条目所属的集合
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+/// 写入包文件
+pub fn write_package(dest: &Path, metadata: &PackageMetadata, payload: &[u8]) -> Result<()> {
+    let metadata_json = serde_json::to_vec(metadata)
+        .context("Failed to serialize package metadata")?;
+    let metadata_len = u32::try_from(metadata_json.len())
+        .map_err(|_| anyhow!("Package metadata too large"))?;
+
+    let mut data = Vec::with_capacity(PACKAGE_MAGIC.len() + 4 + metadata_json.len() + payload.len());
+    data.extend_from_slice(PACKAGE_MAGIC);
+    data.extend_from_slice(&metadata_len.to_le_bytes());
+    data.extend_from_slice(&metadata_json);
+    data.extend_from_slice(payload);
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create package directory")?;
+    }
+    fsutil::atomic_write(dest, &data, None, false)
+        .context("Failed to write package")
+}
+
+/// 读取包文件，返回元数据和压缩后的内容
+pub fn read_package(src: &Path) -> Result<(PackageMetadata, Vec<u8>)> {
+    let data = fs::read(src)
+        .with_context(|| format!("Failed to read package: {}", src.display()))?;
+
+    let header_len = PACKAGE_MAGIC.len() + 4;
+    if data.len() < header_len || !data.starts_with(PACKAGE_MAGIC) {
+        return Err(anyhow!("Not a stowr package: {}", src.display()));
+    }
+
+    let metadata_len = u32::from_le_bytes(data[PACKAGE_MAGIC.len()..header_len].try_into()?) as usize;
+    let metadata_end = header_len.checked_add(metadata_len)
+        .filter(|end| *end <= data.len())
+        .ok_or_else(|| anyhow!("Truncated stowr package: {}", src.display()))?;
+
+    let metadata: PackageMetadata = serde_json::from_slice(&data[header_len..metadata_end])
+        .context("Failed to parse package metadata")?;
+    if metadata.format_version > FORMAT_VERSION {
+        return Err(anyhow!("Unsupported package format version: {}", metadata.format_version));
+    }
+
+    Ok((metadata, data[metadata_end..].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_package_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let dest = dir.path().join("item.stowrpkg");
+        let metadata = PackageMetadata {
+            format_version: FORMAT_VERSION,
+            original_path: PathBuf::from("docs/readme.md"),
+            file_size: 5,
+            hash: "abc".to_string(),
+            compression_algorithm: CompressionAlgorithm::Lz4,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            description: Some("release notes".to_string()),
+            collection: None,
+        };
+
+        write_package(&dest, &metadata, b"payload").unwrap();
+        let (read_metadata, payload) = read_package(&dest).unwrap();
+        assert_eq!(read_metadata, metadata);
+        assert_eq!(payload, b"payload");
+    }
+
+    #[test]
+    fn test_rejects_invalid_package() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("bad.stowrpkg");
+
+        fs::write(&path, b"not a package").unwrap();
+        assert!(read_package(&path).is_err());
+
+        let mut truncated = PACKAGE_MAGIC.to_vec();
+        truncated.extend_from_slice(&100u32.to_le_bytes());
+        fs::write(&path, truncated).unwrap();
+        assert!(read_package(&path).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! 存储文件的纠删码校验数据
+//!
+//! 启用 `Config::parity_shards` 后，每个存储文件旁边会写入一个 `.parity` 文件：
+//! 存储文件按 10 个数据分片计算 Reed-Solomon 校验分片，并记录每个分片的哈希。
+//! `StorageManager::repair` 通过分片哈希定位损坏的分片，损坏的分片数不超过校验分片数时
+//! 可以重建存储文件。校验数据针对写入磁盘的字节计算（已压缩，启用加密时已加密）。
+
+use anyhow::{anyhow, Result};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// 校验文件扩展名，追加在存储文件名之后
+pub const PARITY_EXTENSION: &str = "parity";
+/// 每个存储文件拆分的数据分片数
+pub const DATA_SHARDS: usize = 10;
+/// 校验分片数的上限
+pub const MAX_PARITY_SHARDS: usize = DATA_SHARDS;
+
+const MAGIC: &[u8; 8] = b"STOWRPAR";
+const FORMAT_VERSION: u8 = 1;
+const HASH_LEN: usize = 32;
+/// magic + 版本 + 数据分片数 + 校验分片数 + 存储文件长度 + 分片长度
+const HEADER_LEN: usize = 8 + 1 + 1 + 1 + 8 + 8;
+
+/// 存储文件对应的校验文件路径
+pub fn parity_path(stored_path: &Path) -> PathBuf {
+    let mut name = stored_path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(PARITY_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// 计算存储文件的校验数据
+pub fn encode(blob: &[u8], parity_shards: usize) -> Result<Vec<u8>> {
+    if parity_shards == 0 || parity_shards > MAX_PARITY_SHARDS {
+        return Err(anyhow!("Parity shard count must be between 1 and {}", MAX_PARITY_SHARDS));
+    }
+    let shard_len = blob.len().div_ceil(DATA_SHARDS).max(1);
+    let mut shards = split_shards(blob, shard_len);
+    shards.resize(DATA_SHARDS + parity_shards, vec![0; shard_len]);
+    ReedSolomon::new(DATA_SHARDS, parity_shards)?.encode(&mut shards)?;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + shards.len() * HASH_LEN + parity_shards * shard_len);
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    out.push(DATA_SHARDS as u8);
+    out.push(parity_shards as u8);
+    out.extend_from_slice(&(blob.len() as u64).to_le_bytes());
+    out.extend_from_slice(&(shard_len as u64).to_le_bytes());
+    for shard in &shards {
+        out.extend_from_slice(&Sha256::digest(shard));
+    }
+    for shard in &shards[DATA_SHARDS..] {
+        out.extend_from_slice(shard);
+    }
+    Ok(out)
+}
+
+/// 检查存储文件，内容完好时返回 None，可以修复时返回修复后的内容
+///
+/// 损坏的分片（包括校验文件自身损坏的校验分片）超过校验分片数时返回错误
+pub fn repair(blob: &[u8], parity: &[u8]) -> Result<Option<Vec<u8>>> {
+    let header = Header::parse(parity)?;
+    let hashes_start = HEADER_LEN;
+    let parity_start = hashes_start + header.total_shards() * HASH_LEN;
+    let hash_of = |i: usize| &parity[hashes_start + i * HASH_LEN..hashes_start + (i + 1) * HASH_LEN];
+
+    // 长度变化的存储文件按记录的长度补齐或截断后再逐个分片比较
+    let mut padded = blob[..blob.len().min(header.blob_len)].to_vec();
+    padded.resize(header.blob_len, 0);
+    let mut shards: Vec<Option<Vec<u8>>> = split_shards(&padded, header.shard_len)
+        .into_iter()
+        .chain(parity[parity_start..].chunks(header.shard_len).map(<[u8]>::to_vec))
+        .enumerate()
+        .map(|(i, shard)| (Sha256::digest(&shard).as_slice() == hash_of(i)).then_some(shard))
+        .collect();
+
+    let data_intact = shards[..header.data_shards].iter().all(Option::is_some);
+    if data_intact && blob.len() == header.blob_len {
+        return Ok(None);
+    }
+
+    let damaged = shards.iter().filter(|shard| shard.is_none()).count();
+    if damaged > header.parity_shards {
+        return Err(anyhow!(
+            "{} of {} shards are damaged, at most {} can be repaired",
+            damaged, header.total_shards(), header.parity_shards
+        ));
+    }
+    ReedSolomon::new(header.data_shards, header.parity_shards)?.reconstruct_data(&mut shards)?;
+
+    let mut repaired: Vec<u8> = shards.into_iter()
+        .take(header.data_shards)
+        .flat_map(|shard| shard.unwrap_or_default())
+        .collect();
+    repaired.truncate(header.blob_len);
+    Ok(Some(repaired))
+}
+
+struct Header {
+    data_shards: usize,
+    parity_shards: usize,
+    blob_len: usize,
+    shard_len: usize,
+}
+
+impl Header {
+    /// 解析并校验文件头，分片数和分片长度必须与文件长度一致，不信任头部中的数值
+    fn parse(parity: &[u8]) -> Result<Self> {
+        if parity.len() < HEADER_LEN || &parity[..8] != MAGIC {
+            return Err(anyhow!("Not a stowr parity file"));
+        }
+        if parity[8] != FORMAT_VERSION {
+            return Err(anyhow!("Unsupported parity file version: {}", parity[8]));
+        }
+        let read_u64 = |at: usize| {
+            usize::try_from(u64::from_le_bytes(parity[at..at + 8].try_into().expect("slice has 8 bytes"))).ok()
+        };
+        let corrupted = || anyhow!("Parity file header is corrupted");
+        let header = Self {
+            data_shards: parity[9] as usize,
+            parity_shards: parity[10] as usize,
+            blob_len: read_u64(11).ok_or_else(corrupted)?,
+            shard_len: read_u64(19).ok_or_else(corrupted)?,
+        };
+        // 数据分片按 `DATA_SHARDS` 拆分，其他数量的文件不是本库写入的
+        if header.data_shards != DATA_SHARDS
+            || header.parity_shards == 0
+            || header.parity_shards > MAX_PARITY_SHARDS
+            || header.shard_len == 0
+            || header.data_shards.checked_mul(header.shard_len).is_none_or(|capacity| header.blob_len > capacity)
+        {
+            return Err(corrupted());
+        }
+        let expected_len = header.parity_shards.checked_mul(header.shard_len)
+            .and_then(|len| len.checked_add(HEADER_LEN + header.total_shards() * HASH_LEN));
+        if expected_len != Some(parity.len()) {
+            return Err(anyhow!("Parity file has an unexpected length"));
+        }
+        Ok(header)
+    }
+
+    fn total_shards(&self) -> usize {
+        self.data_shards + self.parity_shards
+    }
+}
+
+/// 按分片长度拆分，最后一个分片补零；总分片数固定为 `DATA_SHARDS`
+fn split_shards(data: &[u8], shard_len: usize) -> Vec<Vec<u8>> {
+    (0..DATA_SHARDS)
+        .map(|i| {
+            let start = (i * shard_len).min(data.len());
+            let end = ((i + 1) * shard_len).min(data.len());
+            let mut shard = data[start..end].to_vec();
+            shard.resize(shard_len, 0);
+            shard
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        (0..10_007u32).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_repair_within_parity_budget() {
+        let blob = sample();
+        let parity = encode(&blob, 2).unwrap();
+        assert_eq!(repair(&blob, &parity).unwrap(), None);
+
+        // 两个不同分片内的损坏可以修复
+        let mut damaged = blob.clone();
+        damaged[5] ^= 0xff;
+        damaged[9_000] ^= 0xff;
+        assert_eq!(repair(&damaged, &parity).unwrap(), Some(blob.clone()));
+
+        // 截断同样按损坏的分片处理
+        assert_eq!(repair(&blob[..9_500], &parity).unwrap(), Some(blob.clone()));
+
+        // 三个分片损坏超过校验分片数
+        damaged[4_000] ^= 0xff;
+        assert!(repair(&damaged, &parity).is_err());
+    }
+
+    #[test]
+    fn test_damaged_parity_file_and_small_blobs() {
+        let blob = sample();
+        let mut parity = encode(&blob, 1).unwrap();
+        let last = parity.len() - 1;
+        parity[last] ^= 0xff;
+        // 只有校验分片损坏时存储文件视为完好
+        assert_eq!(repair(&blob, &parity).unwrap(), None);
+        assert!(repair(&blob, b"garbage").is_err());
+
+        for blob in [&b""[..], b"x", b"short blob"] {
+            let parity = encode(blob, 3).unwrap();
+            assert_eq!(repair(blob, &parity).unwrap(), None);
+        }
+        assert!(encode(&blob, 0).is_err());
+    }
+
+    #[test]
+    fn test_rejects_inconsistent_headers() {
+        let blob = sample();
+        let parity = encode(&blob, 2).unwrap();
+        let with = |at: usize, bytes: &[u8]| {
+            let mut parity = parity.clone();
+            parity[at..at + bytes.len()].copy_from_slice(bytes);
+            parity
+        };
+        // 分片数与写入时不同、分片长度溢出或与文件长度不符时都返回错误而不是越界或溢出
+        assert!(repair(&blob, &with(9, &[12])).is_err());
+        assert!(repair(&blob, &with(10, &[200])).is_err());
+        assert!(repair(&blob, &with(19, &u64::MAX.to_le_bytes())).is_err());
+        assert!(repair(&blob, &with(19, &(1u64 << 61).to_le_bytes())).is_err());
+        assert!(repair(&blob, &with(11, &u64::MAX.to_le_bytes())).is_err());
+        assert!(repair(&blob, &parity[..parity.len() - 1]).is_err());
+        assert_eq!(repair(&blob, &parity).unwrap(), None);
+    }
+}
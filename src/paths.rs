@@ -0,0 +1,418 @@
+//! 路径处理工具
+//!
+//! Windows 上超过 MAX_PATH（260 字符）的路径以及 UNC 共享路径需要使用
+//! `\\?\` 扩展长度前缀才能被文件系统 API 正确处理。这里统一负责：
+//! - 文件系统操作前将路径转换为扩展长度形式（仅 Windows）
+//! - 索引键统一使用去掉扩展前缀的用户可见形式，保证往返一致
+
+use anyhow::{anyhow, Result};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+pub(crate) const ENCODED_PATH_MARKER: char = '\0';
+const VERBATIM_PREFIX: &str = r"\\?\";
+const VERBATIM_UNC_PREFIX: &str = r"\\?\UNC\";
+const DEVICE_PREFIX: &str = r"\\.\";
+
+/// 将路径转换为文件系统操作使用的形式
+///
+/// 在 Windows 上会转换为 `\\?\` 扩展长度路径，其他平台原样返回
+pub fn fs_path(path: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        match absolute.to_str() {
+            Some(s) => PathBuf::from(to_extended_length(s)),
+            None => absolute,
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        path.to_path_buf()
+    }
+}
+
+/// 生成索引键
+///
+/// 去掉扩展长度前缀，使 `\\?\C:\a` 与 `C:\a`、`\\?\UNC\srv\share` 与
+/// `\\srv\share` 对应同一个索引条目
+pub fn index_key(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(s) if s.starts_with(VERBATIM_PREFIX) => PathBuf::from(strip_extended_prefix(s)),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// 将 Windows 路径字符串转换为扩展长度形式
+///
+/// - `C:\dir\file` -> `\\?\C:\dir\file`
+/// - `\\server\share\file` -> `\\?\UNC\server\share\file`
+/// - 已带 `\\?\` 或 `\\.\` 前缀的路径保持不变
+/// - 相对路径和驱动器相对路径（`C:file`）无法扩展，保持不变
+///
+/// 扩展长度路径会跳过系统的路径规范化，因此这里会同时处理 `/`、`.` 和 `..`
+pub fn to_extended_length(path: &str) -> String {
+    if path.starts_with(VERBATIM_PREFIX) || path.starts_with(DEVICE_PREFIX) {
+        return path.to_string();
+    }
+
+    let unified = path.replace('/', "\\");
+
+    if let Some(rest) = unified.strip_prefix(r"\\") {
+        // UNC 路径：前两个组件是服务器和共享名，不参与 `..` 回退
+        let mut parts = rest.splitn(3, '\\');
+        let server = parts.next().unwrap_or_default();
+        let share = parts.next().unwrap_or_default();
+        if server.is_empty() || share.is_empty() {
+            return path.to_string();
+        }
+        let tail = normalize_components(parts.next().unwrap_or_default());
+        let mut result = format!("{}{}\\{}", VERBATIM_UNC_PREFIX, server, share);
+        if !tail.is_empty() {
+            result.push('\\');
+            result.push_str(&tail);
+        }
+        return result;
+    }
+
+    if is_drive_absolute(&unified) {
+        let (drive, rest) = unified.split_at(2);
+        return format!("{}{}\\{}", VERBATIM_PREFIX, drive, normalize_components(rest));
+    }
+
+    path.to_string()
+}
+
+/// 去掉扩展长度前缀，还原为普通 Windows 路径
+pub fn strip_extended_prefix(path: &str) -> String {
+    if let Some(rest) = path.strip_prefix(VERBATIM_UNC_PREFIX) {
+        format!(r"\\{}", rest)
+    } else if let Some(rest) = path.strip_prefix(VERBATIM_PREFIX) {
+        rest.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// 将路径编码为可无损存储在索引中的字符串
+///
+/// 合法 UTF-8 路径原样保存，与旧索引格式完全兼容；非 UTF-8 路径
+/// （Linux 上的任意字节文件名、Windows 上的孤立代理项）保存为
+/// `\0` 前缀加十六进制的原始字节（Unix 为 OS 字节，Windows 为 WTF-8）。
+/// `\0` 在任何平台的路径中都不合法，因此不会与普通路径混淆
+pub fn encode_path(path: &Path) -> String {
+    match path.to_str() {
+        Some(s) if !s.starts_with(ENCODED_PATH_MARKER) => s.to_string(),
+        _ => {
+            let bytes = os_str_to_bytes(path.as_os_str());
+            let mut encoded = String::with_capacity(1 + bytes.len() * 2);
+            encoded.push(ENCODED_PATH_MARKER);
+            for byte in bytes {
+                encoded.push_str(&format!("{:02x}", byte));
+            }
+            encoded
+        }
+    }
+}
+
+/// 解码由 [`encode_path`] 生成的字符串
+pub fn decode_path(encoded: &str) -> Result<PathBuf> {
+    let Some(hex) = encoded.strip_prefix(ENCODED_PATH_MARKER) else {
+        return Ok(PathBuf::from(encoded));
+    };
+
+    // 编码后的路径可能来自导入的包或 JSON，按字节解码，格式不对时返回错误
+    let bytes = crate::crypto::from_hex(hex)
+        .map_err(|e| anyhow!("Invalid encoded path: {}", e))?;
+
+    Ok(PathBuf::from(bytes_to_os_string(bytes)?))
+}
+
+#[cfg(unix)]
+fn os_str_to_bytes(s: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    s.as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn bytes_to_os_string(bytes: Vec<u8>) -> Result<OsString> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(OsString::from_vec(bytes))
+}
+
+#[cfg(windows)]
+fn os_str_to_bytes(s: &OsStr) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+    wtf8_encode(&s.encode_wide().collect::<Vec<u16>>())
+}
+
+#[cfg(windows)]
+fn bytes_to_os_string(bytes: Vec<u8>) -> Result<OsString> {
+    use std::os::windows::ffi::OsStringExt;
+    Ok(OsString::from_wide(&wtf8_decode(&bytes)?))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn os_str_to_bytes(s: &OsStr) -> Vec<u8> {
+    s.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn bytes_to_os_string(bytes: Vec<u8>) -> Result<OsString> {
+    String::from_utf8(bytes)
+        .map(OsString::from)
+        .map_err(|_| anyhow!("Non UTF-8 paths are not supported on this platform"))
+}
+
+/// 将 UTF-16 码元序列编码为 WTF-8（允许孤立代理项的 UTF-8 超集）
+#[cfg_attr(not(windows), allow(dead_code))]
+fn wtf8_encode(units: &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(units.len() * 3);
+    for decoded in char::decode_utf16(units.iter().copied()) {
+        match decoded {
+            Ok(c) => {
+                let mut buf = [0u8; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            Err(e) => {
+                // 孤立代理项按三字节 UTF-8 形式编码
+                let unit = e.unpaired_surrogate();
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+/// 将 WTF-8 字节解码为 UTF-16 码元序列
+#[cfg_attr(not(windows), allow(dead_code))]
+fn wtf8_decode(bytes: &[u8]) -> Result<Vec<u16>> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let first = bytes[i];
+        let (code_point, len) = match first {
+            0x00..=0x7F => (first as u32, 1),
+            0xC0..=0xDF => (first as u32 & 0x1F, 2),
+            0xE0..=0xEF => (first as u32 & 0x0F, 3),
+            0xF0..=0xF7 => (first as u32 & 0x07, 4),
+            _ => return Err(anyhow!("Invalid WTF-8 sequence")),
+        };
+        if i + len > bytes.len() {
+            return Err(anyhow!("Truncated WTF-8 sequence"));
+        }
+        let mut code_point = code_point;
+        for &b in &bytes[i + 1..i + len] {
+            if b & 0xC0 != 0x80 {
+                return Err(anyhow!("Invalid WTF-8 continuation byte"));
+            }
+            code_point = (code_point << 6) | (b as u32 & 0x3F);
+        }
+        if code_point >= 0x10000 {
+            let c = code_point - 0x10000;
+            units.push(0xD800 | (c >> 10) as u16);
+            units.push(0xDC00 | (c & 0x3FF) as u16);
+        } else {
+            units.push(code_point as u16);
+        }
+        i += len;
+    }
+    Ok(units)
+}
+
+/// 用于 `#[serde(with = "...")]` 的路径序列化，保证非 UTF-8 路径无损往返
+pub mod serde_path {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::path::{Path, PathBuf};
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::encode_path(path))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        super::decode_path(&encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+/// 用于 `#[serde(with = "...")]` 的路径列表序列化，每个路径按 [`serde_path`] 编码
+pub mod serde_paths {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::path::PathBuf;
+
+    pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| super::encode_path(path)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|encoded| super::decode_path(encoded).map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
+/// 用于 `#[serde(with = "...")]` 的（路径, 说明）列表序列化，如批量操作的失败原因
+pub mod serde_path_pairs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::path::PathBuf;
+
+    pub fn serialize<S: Serializer>(pairs: &[(PathBuf, String)], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(pairs.iter().map(|(path, text)| (super::encode_path(path), text)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(PathBuf, String)>, D::Error> {
+        Vec::<(String, String)>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(encoded, text)| Ok((super::decode_path(&encoded).map_err(serde::de::Error::custom)?, text)))
+            .collect()
+    }
+}
+
+/// 比较路径时忽略大小写和 Unicode 规范化形式（NFC/NFD）差异的键
+///
+/// 逐个路径组件按 Unicode 的规范无大小写匹配规则处理：NFD 分解、转为小写、再次 NFD 分解，
+/// 因此 macOS 导入的 NFD 文件名与 Windows、Linux 上的 NFC 文件名得到相同的键。
+/// 不是合法 UTF-8 的组件按原始字节比较，不会被有损转换合并到其他路径上
+pub fn fold_key(path: &Path) -> OsString {
+    path.components()
+        .map(|component| {
+            let name = component.as_os_str();
+            match name.to_str() {
+                Some(name) => OsString::from(name.nfd().flat_map(char::to_lowercase).collect::<String>().nfd().collect::<String>()),
+                None => name.to_os_string(),
+            }
+        })
+        .collect::<PathBuf>()
+        .into_os_string()
+}
+
+/// 是否为带盘符的绝对路径（如 `C:\`）
+fn is_drive_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\'
+}
+
+/// 规范化路径组件：去除空组件和 `.`，处理 `..`
+fn normalize_components(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            c => components.push(c),
+        }
+    }
+    components.join("\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_key_ignores_case_and_normalization() {
+        let nfc = Path::new("Photos/Café/Résumé.PDF");
+        let nfd = Path::new("photos/cafe\u{301}/re\u{301}sume\u{301}.pdf");
+        assert_eq!(fold_key(nfc), fold_key(nfd));
+        assert_ne!(fold_key(nfc), fold_key(Path::new("photos/cafe/resume.pdf")));
+        // 拉丁字母以外的预组合字符同样按规范分解比较
+        assert_eq!(fold_key(Path::new("\u{1f71}θήνα/한글")), fold_key(Path::new("\u{3b1}\u{301}θη\u{301}να/\u{1112}\u{1161}\u{11ab}\u{1100}\u{1173}\u{11af}")));
+        assert_eq!(fold_key(Path::new("ΆΘΉΝΑ")), fold_key(Path::new("άθήνα")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fold_key_keeps_non_utf8_components_exact() {
+        use std::os::unix::ffi::OsStrExt;
+        let invalid = Path::new(OsStr::from_bytes(b"Docs/caf\xe9.txt"));
+        let other = Path::new(OsStr::from_bytes(b"docs/caf\xc9.txt"));
+        assert_ne!(fold_key(invalid), fold_key(other));
+        assert_ne!(fold_key(invalid), fold_key(Path::new("docs/caf\u{fffd}.txt")));
+        assert_eq!(fold_key(invalid), fold_key(Path::new(OsStr::from_bytes(b"DOCS/caf\xe9.txt"))));
+    }
+
+    #[test]
+    fn test_drive_path_round_trip() {
+        let original = r"C:\Users\alice\file.txt";
+        let extended = to_extended_length(original);
+        assert_eq!(extended, r"\\?\C:\Users\alice\file.txt");
+        assert_eq!(strip_extended_prefix(&extended), original);
+    }
+
+    #[test]
+    fn test_unc_path_round_trip() {
+        let original = r"\\server\share\dir\file.txt";
+        let extended = to_extended_length(original);
+        assert_eq!(extended, r"\\?\UNC\server\share\dir\file.txt");
+        assert_eq!(strip_extended_prefix(&extended), original);
+    }
+
+    #[test]
+    fn test_extended_path_normalization() {
+        assert_eq!(to_extended_length(r"C:/a/./b/../c.txt"), r"\\?\C:\a\c.txt");
+        assert_eq!(to_extended_length(r"\\srv\share\..\x"), r"\\?\UNC\srv\share\x");
+        // 已经是扩展长度路径的保持不变
+        assert_eq!(to_extended_length(r"\\?\C:\a"), r"\\?\C:\a");
+        assert_eq!(to_extended_length(r"\\.\pipe\name"), r"\\.\pipe\name");
+    }
+
+    #[test]
+    fn test_relative_paths_unchanged() {
+        assert_eq!(to_extended_length(r"C:file.txt"), r"C:file.txt");
+        assert_eq!(to_extended_length(r"dir\file.txt"), r"dir\file.txt");
+        assert_eq!(to_extended_length(r"\\server"), r"\\server");
+    }
+
+    #[test]
+    fn test_utf8_path_encoding_is_unchanged() {
+        let path = Path::new("目录/文件 名.txt");
+        assert_eq!(encode_path(path), "目录/文件 名.txt");
+        assert_eq!(decode_path("目录/文件 名.txt").unwrap(), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_round_trip() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"dir/caf\xe9.txt"));
+        let encoded = encode_path(path);
+        assert!(encoded.starts_with(ENCODED_PATH_MARKER));
+        assert_eq!(decode_path(&encoded).unwrap(), path);
+    }
+
+    #[test]
+    fn test_decode_path_rejects_malformed_hex() {
+        for bad in ["\0a\u{e9}b", "\0\u{e9}\u{e9}", "\0+f", "\0abc"] {
+            assert!(decode_path(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_wtf8_round_trip_with_lone_surrogate() {
+        let units = [0x0061, 0xD800, 0x0062, 0xD83D, 0xDE00];
+        let bytes = wtf8_encode(&units);
+        assert_eq!(wtf8_decode(&bytes).unwrap(), units);
+    }
+
+    #[test]
+    fn test_index_key_round_trip() {
+        let unc = Path::new(r"\\?\UNC\server\share\file.txt");
+        assert_eq!(index_key(unc), PathBuf::from(r"\\server\share\file.txt"));
+
+        let drive = Path::new(r"\\?\D:\data\file.bin");
+        assert_eq!(index_key(drive), PathBuf::from(r"D:\data\file.bin"));
+
+        let drive_relative = Path::new(r"C:notes.txt");
+        assert_eq!(index_key(drive_relative), PathBuf::from(r"C:notes.txt"));
+
+        let plain = Path::new("relative/file.txt");
+        assert_eq!(index_key(plain), PathBuf::from("relative/file.txt"));
+    }
+}
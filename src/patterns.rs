@@ -0,0 +1,323 @@
+//! 通配符模式匹配
+//!
+//! 搜索、按列表存储/提取以及排除模式统一使用 [`Matcher`]，语义如下：
+//! - `/` 和 `\` 都视为路径分隔符，模式和路径中可以混用
+//! - `*` 匹配单个路径组件内的任意字符，`?` 匹配单个字符，都不跨越分隔符
+//! - `[abc]`、`[a-z]` 匹配字符集合，`[!abc]` 或 `[^abc]` 匹配集合以外的字符
+//! - 单独作为一个组件的 `**` 匹配零个或多个目录：`a/**/b` 匹配 `a/b` 和 `a/x/y/b`，
+//!   结尾的 `a/**` 匹配 `a` 下的所有内容；不单独成组件的 `**` 与 `*` 相同
+//! - 不含分隔符的模式（如 `*.txt`）匹配任意深度下的文件名，
+//!   含分隔符的模式需要匹配完整路径
+//! - 匹配区分大小写
+
+use anyhow::{Context, Result};
+use regex::{Regex, RegexSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 路径分隔符对应的正则表达式
+const SEPARATOR: &str = r"[/\\]";
+
+/// 是否包含通配符
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// 编译后的通配符模式
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    pattern: String,
+    regex: Regex,
+}
+
+impl Matcher {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(&to_regex(pattern))
+            .with_context(|| format!("Invalid pattern: {}", pattern))?;
+        Ok(Self {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    /// 原始模式
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// 模式对应的正则表达式
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        self.regex.is_match(&path.to_string_lossy())
+    }
+
+    /// 在文件系统中展开模式，返回按路径排序的匹配文件
+    ///
+    /// 只遍历模式中第一个通配符之前的目录，且不含 `**` 时只遍历到模式的深度
+    pub fn expand(&self) -> Result<Vec<PathBuf>> {
+        let (base, rest) = split_base(&self.pattern);
+        let max_depth = if split_components(rest).any(|component| component == "**") {
+            usize::MAX
+        } else {
+            split_components(rest).count()
+        };
+
+        let mut files = Vec::new();
+        let base_path = PathBuf::from(base);
+        let start = if base.is_empty() { Path::new(".") } else { base_path.as_path() };
+        if start.is_dir() {
+            self.walk(start, &base_path, max_depth, &mut files)?;
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn walk(&self, dir: &Path, prefix: &Path, depth: usize, files: &mut Vec<PathBuf>) -> Result<()> {
+        if depth == 0 {
+            return Ok(());
+        }
+        let read_dir = fs::read_dir(crate::paths::fs_path(dir))
+            .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+        for item in read_dir {
+            let item = match item {
+                Ok(item) => item,
+                Err(e) => {
+                    crate::output::warning!("Error reading path: {}", e);
+                    continue;
+                }
+            };
+            let path = prefix.join(item.file_name());
+            let fs_entry = dir.join(item.file_name());
+            if item.file_type().is_ok_and(|t| t.is_dir()) {
+                if let Err(e) = self.walk(&fs_entry, &path, depth - 1, files) {
+                    crate::output::warning!("Error reading path: {}", e);
+                }
+            } else if fs_entry.is_file() && self.is_match(&path) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 一组模式，判断路径是否匹配其中任意一个
+///
+/// 所有模式合并为一个正则表达式集合，每个路径只需扫描一次，
+/// 不访问文件系统，适合对大量文件应用排除模式
+#[derive(Debug, Clone)]
+pub struct PatternSet {
+    set: RegexSet,
+}
+
+impl PatternSet {
+    pub fn new<I, S>(patterns: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let regexes: Vec<String> = patterns.into_iter()
+            .map(|pattern| to_regex(pattern.as_ref()))
+            .collect();
+        let set = RegexSet::new(&regexes)
+            .context("Invalid pattern set")?;
+        Ok(Self { set })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        self.set.is_match(&path.to_string_lossy())
+    }
+}
+
+/// 按分隔符拆分模式组件，忽略结尾的分隔符
+fn split_components(pattern: &str) -> impl Iterator<Item = &str> {
+    pattern.trim_end_matches(['/', '\\']).split(['/', '\\'])
+}
+
+/// 拆分出第一个通配符之前的目录部分和剩余部分
+fn split_base(pattern: &str) -> (&str, &str) {
+    let first_wildcard = pattern.find(['*', '?', '[']).unwrap_or(pattern.len());
+    match pattern[..first_wildcard].rfind(['/', '\\']) {
+        // 根目录本身作为起点
+        Some(0) => (&pattern[..1], &pattern[1..]),
+        Some(i) => (&pattern[..i], &pattern[i + 1..]),
+        None => ("", pattern),
+    }
+}
+
+/// 将模式转换为正则表达式
+fn to_regex(pattern: &str) -> String {
+    let components: Vec<&str> = split_components(pattern).collect();
+    if components.len() == 1 {
+        // 不含分隔符时匹配文件名
+        return match components[0] {
+            "**" => "^.*$".to_string(),
+            component => format!("(?:^|{}){}$", SEPARATOR, component_regex(component)),
+        };
+    }
+
+    let mut regex = String::from("^");
+    let last = components.len() - 1;
+    for (i, component) in components.iter().enumerate() {
+        if *component == "**" {
+            regex.push_str(match (i == 0, i == last) {
+                (true, _) => "(?:.*[/\\\\])?",
+                (false, true) => "[/\\\\].*",
+                (false, false) => "(?:[/\\\\].*)?",
+            });
+            continue;
+        }
+        if i > 0 && !(i == 1 && components[0] == "**") {
+            regex.push_str(SEPARATOR);
+        }
+        regex.push_str(&component_regex(component));
+    }
+    regex.push('$');
+    regex
+}
+
+/// 将单个路径组件中的通配符转换为正则表达式
+fn component_regex(component: &str) -> String {
+    let chars: Vec<char> = component.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' => {
+                while i + 1 < chars.len() && chars[i + 1] == '*' {
+                    i += 1;
+                }
+                regex.push_str(r"[^/\\]*");
+            }
+            '?' => regex.push_str(r"[^/\\]"),
+            '[' => match parse_class(&chars[i + 1..]) {
+                Some((class, len)) => {
+                    regex.push_str(&class);
+                    i += len;
+                }
+                // 没有闭合的 `[` 按普通字符处理
+                None => regex.push_str(r"\["),
+            },
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex
+}
+
+/// 解析 `[` 之后的字符集合，返回正则表达式和消耗的字符数（含 `]`）
+fn parse_class(chars: &[char]) -> Option<(String, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut class = String::from(if negated { r"[^/\\" } else { "[" });
+    let start = i;
+    while i < chars.len() {
+        let c = chars[i];
+        // 紧跟在 `[` 后的 `]` 是普通字符
+        if c == ']' && i > start {
+            class.push(']');
+            return Some((class, i + 1));
+        }
+        if c == '-' && i > start && i + 1 < chars.len() && chars[i + 1] != ']' {
+            class.push('-');
+        } else {
+            class.push_str(&regex::escape(&c.to_string()));
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Matcher::new(pattern).unwrap().is_match(Path::new(path))
+    }
+
+    #[test]
+    fn test_pattern_semantics() {
+        // 不含分隔符的模式匹配任意深度的文件名
+        assert!(matches("*.txt", "notes.txt"));
+        assert!(matches("*.txt", "/home/user/notes.txt"));
+        assert!(!matches("*.txt", "/home/user/notes.txt.bak"));
+
+        // `*` 和 `?` 不跨越分隔符
+        assert!(matches("docs/*.md", "docs/readme.md"));
+        assert!(!matches("docs/*.md", "docs/sub/readme.md"));
+        assert!(matches("file?.rs", "src/file1.rs"));
+        assert!(!matches("a?b", "a/b"));
+
+        // `**` 匹配零个或多个目录
+        assert!(matches("src/**/*.rs", "src/lib.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/lib.rs"));
+        assert!(matches("**/target/*", "target/debug"));
+        assert!(matches("**/target/*", "/work/crate/target/debug"));
+        assert!(matches("build/**", "build/out/app.exe"));
+        assert!(!matches("build/**", "build"));
+        assert!(matches("**", "anything/at/all"));
+
+        // 字符集合
+        assert!(matches("log[0-9].txt", "log7.txt"));
+        assert!(!matches("log[!0-9].txt", "log7.txt"));
+        assert!(matches("log[!0-9].txt", "logx.txt"));
+        assert!(matches("[]].txt", "].txt"));
+        assert!(matches("a[b", "a[b"));
+
+        // 正则特殊字符按字面匹配
+        assert!(matches("report (1).pdf", "report (1).pdf"));
+        assert!(!matches("a.txt", "abtxt"));
+    }
+
+    #[test]
+    fn test_windows_separators() {
+        assert!(matches(r"C:\data\*.txt", r"C:\data\a.txt"));
+        assert!(matches(r"C:\data\*.txt", "C:/data/a.txt"));
+        assert!(matches("C:/data/**/*.log", r"C:\data\logs\2024\app.log"));
+        assert!(matches(r"\\server\share\*", r"\\server\share\file"));
+        assert!(!matches(r"C:\data\*.txt", r"C:\data\sub\a.txt"));
+        assert!(matches("*.txt", r"C:\data\sub\a.txt"));
+    }
+
+    #[test]
+    fn test_pattern_set_matches_any() {
+        let set = PatternSet::new(["*.log", "**/target/**"]).unwrap();
+        assert!(set.is_match(Path::new("/work/app.log")));
+        assert!(set.is_match(Path::new(r"C:\work\target\debug\app.exe")));
+        assert!(!set.is_match(Path::new("/work/src/main.rs")));
+        assert!(PatternSet::new(Vec::<&str>::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_expand_walks_only_needed_directories() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        for file in ["top.txt", "a/one.txt", "a/b/two.txt", "a/skip.log"] {
+            fs::write(root.join(file), file).unwrap();
+        }
+
+        let expand = |pattern: &str| -> Vec<PathBuf> {
+            Matcher::new(&format!("{}/{}", root.display(), pattern)).unwrap().expand().unwrap()
+        };
+        assert_eq!(expand("*.txt"), vec![root.join("top.txt")]);
+        assert_eq!(expand("a/*.txt"), vec![root.join("a/one.txt")]);
+        assert_eq!(
+            expand("**/*.txt"),
+            vec![root.join("a/b/two.txt"), root.join("a/one.txt"), root.join("top.txt")]
+        );
+        assert!(expand("missing/*.txt").is_empty());
+    }
+}
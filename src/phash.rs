@@ -0,0 +1,80 @@
+//! 图片感知哈希
+//!
+//! 重新编码、缩放或轻微调色后的照片字节几乎完全不同，按字节窗口计算的相似度没有意义。
+//! 启用 `image` feature 后，存储图片时计算 64 位差值哈希（dHash）：缩小为 9×8 灰度图，
+//! 逐行比较相邻像素的明暗。两张图片哈希的汉明距离越小越相似，差分存储据此筛选候选基础文件。
+//! 未启用该 feature 时不计算哈希，差分候选的筛选方式不变。
+
+/// 计算感知哈希的图片扩展名（小写）
+const IMAGE_TYPES: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff"];
+
+/// 按扩展名推断的文件类型是否为图片，见 [`DeltaStorage::infer_file_type`](crate::delta::DeltaStorage::infer_file_type)
+pub fn is_image_type(file_type: &str) -> bool {
+    IMAGE_TYPES.contains(&file_type)
+}
+
+/// 两个感知哈希的汉明距离（0-64）
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// 索引中保存的十六进制形式
+pub fn to_hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+/// 解析索引中保存的十六进制形式
+pub fn from_hex(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex, 16).ok()
+}
+
+/// 计算图片内容的感知哈希，无法解码或未启用 `image` feature 时返回 None
+#[cfg(feature = "image")]
+pub fn perceptual_hash(content: &[u8]) -> Option<u64> {
+    use image::imageops::FilterType;
+
+    let image = image::load_from_memory(content).ok()?;
+    let pixels = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = pixels.get_pixel(x, y)[0] < pixels.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(brighter);
+        }
+    }
+    Some(hash)
+}
+
+/// 计算图片内容的感知哈希，无法解码或未启用 `image` feature 时返回 None
+#[cfg(not(feature = "image"))]
+pub fn perceptual_hash(_content: &[u8]) -> Option<u64> {
+    None
+}
+
+#[cfg(all(test, feature = "image"))]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    fn encode(image: &RgbImage, format: ImageFormat) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        image.write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_perceptual_hash_survives_reencoding() {
+        let gradient = RgbImage::from_fn(64, 48, |x, y| Rgb([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8]));
+        let inverted = RgbImage::from_fn(64, 48, |x, y| Rgb([255 - (x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8]));
+
+        let png = perceptual_hash(&encode(&gradient, ImageFormat::Png)).unwrap();
+        let bmp = perceptual_hash(&encode(&gradient, ImageFormat::Bmp)).unwrap();
+        let other = perceptual_hash(&encode(&inverted, ImageFormat::Png)).unwrap();
+        assert!(distance(png, bmp) <= 2);
+        assert!(distance(png, other) > 32);
+        assert_eq!(from_hex(&to_hex(png)), Some(png));
+        assert!(perceptual_hash(b"not an image").is_none());
+        assert!(is_image_type("jpeg") && !is_image_type("txt"));
+    }
+}
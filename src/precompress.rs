@@ -0,0 +1,492 @@
+//! 可逆的预压缩过滤器
+//!
+//! Office 文档（docx、xlsx、odt 等）是 zip 容器，邮件（eml）中的附件是 base64 文本，
+//! 内容只改动一处，编码后的字节就大面积变化，去重和差分几乎无效。
+//! 预压缩过滤器在存储前找出这些编码片段，存储时替换为解码后的内容，并记录每段的编码参数，
+//! 提取时重新编码，还原出逐字节相同的文件。
+//!
+//! 过滤器只负责定位片段（[`Precompressor::find_segments`]），解码、参数搜索和校验由本模块完成：
+//! 只有重新编码能得到完全相同的字节时才替换该片段，其余片段原样保留。
+//! 编码方式是内置的 [`SegmentCodec`]，条目中记录的参数足以还原，提取时不需要注册过滤器。
+//! deflate 的输出依赖压缩库版本，条目同时记录原始文件的 SHA256，还原结果不符时报错。
+
+use anyhow::{anyhow, Result};
+use flate2::bufread::DeflateDecoder;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::recompress::{check_original, deflate_into, find_deflate_level, read_limited, sha256_hex};
+
+/// 过滤器找到的片段类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// 原始 deflate 数据（zip 成员等）
+    Deflate,
+    /// 按行折行的 base64 文本（MIME 附件等），包括最后一行的换行符
+    Base64,
+}
+
+/// 片段的编码参数，提取时据此重新编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "codec", rename_all = "lowercase")]
+pub enum SegmentCodec {
+    Deflate {
+        level: u32,
+    },
+    Base64 {
+        /// 每行的字符数，最后一行可以更短
+        line_len: u32,
+        /// 换行符是否为 CRLF
+        crlf: bool,
+    },
+}
+
+impl SegmentCodec {
+    fn encode(&self, decoded: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        match self {
+            SegmentCodec::Deflate { level } => {
+                deflate_into(decoded, *level, output)?;
+            }
+            SegmentCodec::Base64 { line_len, crlf } => {
+                let encoded = base64_encode(decoded);
+                for line in encoded.as_bytes().chunks(*line_len as usize) {
+                    output.extend_from_slice(line);
+                    output.extend_from_slice(if *crlf { b"\r\n" } else { b"\n" });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 被替换为解码内容的一个片段
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Segment {
+    /// 在原始内容中的偏移
+    pub offset: u64,
+    /// 原始（编码后）长度
+    pub encoded_len: u64,
+    /// 解码后的长度
+    pub decoded_len: u64,
+    #[serde(flatten)]
+    pub codec: SegmentCodec,
+}
+
+/// 条目记录的预压缩信息
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Precompression {
+    /// 处理该文件的过滤器名称
+    pub filter: String,
+    /// 按偏移升序排列的片段
+    pub segments: Vec<Segment>,
+    /// 预压缩前原始文件的 SHA256，旧版本记录的条目没有该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_hash: Option<String>,
+}
+
+impl Precompression {
+    /// 重新编码各片段，还原预压缩前的原始字节
+    ///
+    /// 结果与记录的原始文件哈希不符（通常是压缩库版本变化）时返回错误
+    pub fn restore(&self, content: &[u8]) -> Result<Vec<u8>> {
+        let truncated = || anyhow!("Precompressed content is shorter than its segments record");
+        let mut restored = Vec::with_capacity(content.len());
+        let mut pos = 0usize;
+        for segment in &self.segments {
+            // 片段之间的内容原样保存
+            let gap = (segment.offset as usize).checked_sub(restored.len())
+                .ok_or_else(|| anyhow!("Precompression segments overlap"))?;
+            restored.extend_from_slice(content.get(pos..pos + gap).ok_or_else(truncated)?);
+            pos += gap;
+            let decoded = content.get(pos..pos + segment.decoded_len as usize).ok_or_else(truncated)?;
+            segment.codec.encode(decoded, &mut restored)?;
+            pos += decoded.len();
+            if restored.len() as u64 != segment.offset + segment.encoded_len {
+                return Err(anyhow!("Re-encoded segment at offset {} has the wrong length", segment.offset));
+            }
+        }
+        restored.extend_from_slice(&content[pos.min(content.len())..]);
+        check_original(self.original_hash.as_deref(), &restored)?;
+        Ok(restored)
+    }
+}
+
+/// 预压缩过滤器：在文件内容中定位可以解码的片段
+pub trait Precompressor: Send + Sync {
+    /// 过滤器名称，记录在条目中
+    fn name(&self) -> &str;
+
+    /// 找出内容中的编码片段，不处理该文件时返回空列表
+    ///
+    /// 片段可以有误报，无法解码或无法精确重新编码的片段会被跳过
+    fn find_segments(&self, path: &Path, data: &[u8]) -> Vec<(Range<usize>, SegmentKind)>;
+}
+
+/// 依次尝试各过滤器，第一个替换了至少一个片段的过滤器生效
+///
+/// 返回替换后的内容和还原所需的信息；解码后的总大小不超过 `max_output` 字节
+pub fn precompress(
+    precompressors: &[Arc<dyn Precompressor>],
+    path: &Path,
+    data: &[u8],
+    max_output: u64,
+) -> Option<(Vec<u8>, Precompression)> {
+    precompressors.iter().find_map(|precompressor| {
+        let mut ranges = precompressor.find_segments(path, data);
+        ranges.sort_by_key(|(range, _)| range.start);
+
+        let mut content = Vec::with_capacity(data.len());
+        let mut segments = Vec::new();
+        let mut copied = 0usize;
+        let mut budget = max_output;
+        for (range, kind) in ranges {
+            if range.start < copied || range.end > data.len() || range.is_empty() {
+                continue;
+            }
+            let encoded = &data[range.clone()];
+            let Some((decoded, codec)) = decode_segment(encoded, kind, budget) else {
+                continue;
+            };
+            budget -= decoded.len() as u64;
+            content.extend_from_slice(&data[copied..range.start]);
+            content.extend_from_slice(&decoded);
+            copied = range.end;
+            segments.push(Segment {
+                offset: range.start as u64,
+                encoded_len: encoded.len() as u64,
+                decoded_len: decoded.len() as u64,
+                codec,
+            });
+        }
+        if segments.is_empty() {
+            return None;
+        }
+        content.extend_from_slice(&data[copied..]);
+        Some((content, Precompression {
+            filter: precompressor.name().to_string(),
+            segments,
+            original_hash: Some(sha256_hex(data)),
+        }))
+    })
+}
+
+/// 解码片段并找出能精确还原的编码参数
+fn decode_segment(encoded: &[u8], kind: SegmentKind, max_output: u64) -> Option<(Vec<u8>, SegmentCodec)> {
+    let (decoded, codec) = match kind {
+        SegmentKind::Deflate => {
+            let mut decoder = DeflateDecoder::new(encoded);
+            let decoded = read_limited(&mut decoder, max_output)?;
+            // 片段必须恰好是一个完整的 deflate 流
+            if !decoder.into_inner().is_empty() {
+                return None;
+            }
+            let level = find_deflate_level(&decoded, encoded)?;
+            return Some((decoded, SegmentCodec::Deflate { level }));
+        }
+        SegmentKind::Base64 => {
+            let first_line = encoded.iter().position(|&b| b == b'\n')?;
+            let crlf = first_line > 0 && encoded[first_line - 1] == b'\r';
+            let line_len = if crlf { first_line - 1 } else { first_line };
+            let text: Vec<u8> = encoded.iter().copied().filter(|&b| b != b'\r' && b != b'\n').collect();
+            let decoded = base64_decode(&text)?;
+            if line_len == 0 || decoded.len() as u64 > max_output {
+                return None;
+            }
+            (decoded, SegmentCodec::Base64 { line_len: line_len as u32, crlf })
+        }
+    };
+    let mut reencoded = Vec::with_capacity(encoded.len());
+    codec.encode(&decoded, &mut reencoded).ok()?;
+    (reencoded == encoded).then_some((decoded, codec))
+}
+
+/// zip 容器（docx、xlsx、pptx、odt、epub、jar 等）中以 deflate 压缩的成员
+#[derive(Debug, Default)]
+pub struct ZipPrecompressor;
+
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP_EOCD_LEN: usize = 22;
+const ZIP_METHOD_DEFLATE: u16 = 8;
+const ZIP_FLAG_ENCRYPTED: u16 = 0x0001;
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+impl ZipPrecompressor {
+    /// 按中央目录列出 deflate 成员的数据范围，zip64 和加密成员不处理
+    fn deflate_members(data: &[u8]) -> Option<Vec<Range<usize>>> {
+        // 中央目录结束记录之后最多是 65535 字节的注释
+        let search_start = data.len().checked_sub(ZIP_EOCD_LEN)?;
+        let eocd = (search_start.saturating_sub(u16::MAX as usize)..=search_start)
+            .rev()
+            .find(|&pos| read_u32(data, pos) == Some(ZIP_END_OF_CENTRAL_DIRECTORY))?;
+        let entry_count = read_u16(data, eocd + 10)?;
+        let mut pos = read_u32(data, eocd + 16)? as usize;
+
+        let mut members = Vec::new();
+        for _ in 0..entry_count {
+            if read_u32(data, pos)? != ZIP_CENTRAL_HEADER {
+                return None;
+            }
+            let flags = read_u16(data, pos + 8)?;
+            let method = read_u16(data, pos + 10)?;
+            let compressed_size = read_u32(data, pos + 20)?;
+            let local_offset = read_u32(data, pos + 42)?;
+            let name_len = read_u16(data, pos + 28)? as usize;
+            let extra_len = read_u16(data, pos + 30)? as usize;
+            let comment_len = read_u16(data, pos + 32)? as usize;
+            pos += 46 + name_len + extra_len + comment_len;
+
+            if method != ZIP_METHOD_DEFLATE || flags & ZIP_FLAG_ENCRYPTED != 0
+                || compressed_size == u32::MAX || local_offset == u32::MAX
+            {
+                continue;
+            }
+            let local = local_offset as usize;
+            if read_u32(data, local) != Some(ZIP_LOCAL_HEADER) {
+                continue;
+            }
+            let start = local + 30 + read_u16(data, local + 26)? as usize + read_u16(data, local + 28)? as usize;
+            members.push(start..start + compressed_size as usize);
+        }
+        Some(members)
+    }
+}
+
+impl Precompressor for ZipPrecompressor {
+    fn name(&self) -> &str {
+        "zip"
+    }
+
+    fn find_segments(&self, _path: &Path, data: &[u8]) -> Vec<(Range<usize>, SegmentKind)> {
+        if read_u32(data, 0) != Some(ZIP_LOCAL_HEADER) {
+            return Vec::new();
+        }
+        Self::deflate_members(data)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|range| (range, SegmentKind::Deflate))
+            .collect()
+    }
+}
+
+/// 邮件（eml、mht、mbox）中 `Content-Transfer-Encoding: base64` 的正文和附件
+#[derive(Debug, Default)]
+pub struct EmlPrecompressor;
+
+const EML_EXTENSIONS: [&str; 4] = ["eml", "mht", "mhtml", "mbox"];
+
+/// 按行切分，返回每行（不含换行符）的范围和包括换行符的结束位置
+fn lines(data: &[u8]) -> impl Iterator<Item = (Range<usize>, usize)> + '_ {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        if start >= data.len() {
+            return None;
+        }
+        let line_start = start;
+        let (content_end, next) = match data[start..].iter().position(|&b| b == b'\n') {
+            Some(i) => {
+                let newline = start + i;
+                let content_end = if newline > start && data[newline - 1] == b'\r' { newline - 1 } else { newline };
+                (content_end, newline + 1)
+            }
+            None => (data.len(), data.len()),
+        };
+        start = next;
+        Some((line_start..content_end, next))
+    })
+}
+
+fn is_base64_line(line: &[u8]) -> bool {
+    !line.is_empty() && line.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/' || b == b'=')
+}
+
+impl Precompressor for EmlPrecompressor {
+    fn name(&self) -> &str {
+        "eml"
+    }
+
+    fn find_segments(&self, path: &Path, data: &[u8]) -> Vec<(Range<usize>, SegmentKind)> {
+        let is_mail = path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| EML_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)));
+        if !is_mail {
+            return Vec::new();
+        }
+
+        let mut segments = Vec::new();
+        // 遇到 base64 声明后等待头部结束的空行，再把随后连续的 base64 行作为一个片段
+        let mut pending = false;
+        let mut segment: Option<Range<usize>> = None;
+        for (line, next) in lines(data) {
+            let text = &data[line.clone()];
+            if let Some(current) = &mut segment {
+                if is_base64_line(text) {
+                    current.end = next;
+                    continue;
+                }
+                segments.push((segment.take().unwrap(), SegmentKind::Base64));
+            }
+            if pending && text.is_empty() {
+                pending = false;
+                segment = Some(next..next);
+            } else if let Some(value) = text.to_ascii_lowercase().strip_prefix(b"content-transfer-encoding:") {
+                pending = value.trim_ascii() == b"base64";
+            }
+        }
+        segments.extend(segment.map(|range| (range, SegmentKind::Base64)));
+        segments.retain(|(range, _)| !range.is_empty());
+        segments
+    }
+}
+
+/// 默认启用的预压缩过滤器
+pub fn builtin_precompressors() -> Vec<Arc<dyn Precompressor>> {
+    vec![Arc::new(ZipPrecompressor), Arc::new(EmlPrecompressor)]
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// 解码带填充的标准 base64，格式不正确时返回 None
+fn base64_decode(text: &[u8]) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(text.len() / 4 * 3);
+    let chunks = text.chunks(4).count();
+    for (index, chunk) in text.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&b| b == b'=').count();
+        if padding > 2 || (padding > 0 && index + 1 != chunks) {
+            return None;
+        }
+        let mut n = 0u32;
+        for &b in &chunk[..4 - padding] {
+            let value = BASE64_ALPHABET.iter().position(|&c| c == b)? as u32;
+            n = n << 6 | value;
+        }
+        n <<= 6 * padding as u32;
+        decoded.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造只含 deflate 成员的最小 zip 文件
+    fn build_zip(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = Vec::new();
+        let mut central = Vec::new();
+        for (name, content) in members {
+            let compressed = deflate_into(content, 6, Vec::new()).unwrap();
+            let offset = zip.len() as u32;
+            let mut crc = flate2::Crc::new();
+            crc.update(content);
+            let fields = |zip: &mut Vec<u8>| {
+                zip.extend_from_slice(&[20, 0, 0, 0, 8, 0, 0, 0, 0, 0]);
+                zip.extend_from_slice(&crc.sum().to_le_bytes());
+                zip.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+                zip.extend_from_slice(&(content.len() as u32).to_le_bytes());
+                zip.extend_from_slice(&(name.len() as u16).to_le_bytes());
+                zip.extend_from_slice(&0u16.to_le_bytes());
+            };
+            zip.extend_from_slice(&ZIP_LOCAL_HEADER.to_le_bytes());
+            fields(&mut zip);
+            zip.extend_from_slice(name.as_bytes());
+            zip.extend_from_slice(&compressed);
+
+            central.extend_from_slice(&ZIP_CENTRAL_HEADER.to_le_bytes());
+            central.extend_from_slice(&20u16.to_le_bytes());
+            fields(&mut central);
+            // 注释长度、磁盘号、内部属性和外部属性
+            central.extend_from_slice(&[0; 10]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let central_offset = zip.len() as u32;
+        zip.extend_from_slice(&central);
+        zip.extend_from_slice(&ZIP_END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        zip.extend_from_slice(&[0; 4]);
+        zip.extend_from_slice(&(members.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(members.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&central_offset.to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes());
+        zip
+    }
+
+    #[test]
+    fn test_precompress_round_trip() {
+        let builtins = builtin_precompressors();
+        let document = "<w:p>paragraph</w:p>".repeat(500);
+        let zip = build_zip(&[("word/document.xml", document.as_bytes()), ("word/styles.xml", b"<styles/>")]);
+        let (content, precompression) = precompress(&builtins, Path::new("report.docx"), &zip, u64::MAX).unwrap();
+        assert_eq!(precompression.filter, "zip");
+        assert_eq!(precompression.segments.len(), 2);
+        assert!(content.windows(document.len()).any(|window| window == document.as_bytes()));
+        assert_eq!(precompression.restore(&content).unwrap(), zip);
+        let json = serde_json::to_string(&precompression).unwrap();
+        assert_eq!(serde_json::from_str::<Precompression>(&json).unwrap(), precompression);
+        // 解码后超过上限的成员保持原样
+        let (content, limited) = precompress(&builtins, Path::new("report.docx"), &zip, 100).unwrap();
+        assert_eq!(limited.segments.len(), 1);
+        assert_eq!(limited.restore(&content).unwrap(), zip);
+        // 重新编码的结果与原始文件哈希不符时报错，旧版本的条目没有哈希时不校验
+        let mut changed = limited.clone();
+        changed.original_hash = Some(crate::recompress::sha256_hex(b"another build"));
+        assert!(changed.restore(&content).unwrap_err().to_string().contains("does not match the original bytes"));
+        changed.original_hash = None;
+        assert_eq!(changed.restore(&content).unwrap(), zip);
+
+        let attachment: Vec<u8> = (0..3000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let encoded = base64_encode(&attachment);
+        let mut mail = b"From: a@example.com\r\nContent-Type: application/pdf\r\nContent-Transfer-Encoding: base64\r\n\r\n".to_vec();
+        for line in encoded.as_bytes().chunks(76) {
+            mail.extend_from_slice(line);
+            mail.extend_from_slice(b"\r\n");
+        }
+        mail.extend_from_slice(b"--boundary--\r\n");
+        let (content, precompression) = precompress(&builtins, Path::new("mail.EML"), &mail, u64::MAX).unwrap();
+        assert_eq!(precompression.segments[0].codec, SegmentCodec::Base64 { line_len: 76, crlf: true });
+        assert!(content.windows(attachment.len()).any(|window| window == attachment));
+        assert_eq!(precompression.restore(&content).unwrap(), mail);
+        assert!(precompress(&builtins, Path::new("mail.txt"), &mail, u64::MAX).is_none());
+
+        // 行长不一致的 base64 无法精确还原，不做替换
+        let mut irregular = mail.clone();
+        let body = irregular.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        irregular.insert(body + 10, b'\n');
+        irregular.insert(body + 10, b'\r');
+        assert!(precompress(&builtins, Path::new("mail.eml"), &irregular, u64::MAX).is_none());
+
+        assert_eq!(base64_decode(base64_encode(b"ab").as_bytes()).unwrap(), b"ab");
+        assert!(base64_decode(b"YQ==YQ==").is_none());
+        assert_eq!(base64_encode(b"hello"), "aGVsbG8=");
+    }
+}
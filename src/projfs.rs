@@ -0,0 +1,427 @@
+//! 以 Windows ProjFS（Projected File System）投影存储库的只读视图，需要启用 `projfs` feature（仅 Windows）
+//!
+//! 虚拟化根目录中的内容按需由 [`ReadOnlyView`] 提供：枚举目录时读取 `readdir`，首次访问路径时通过
+//! `resolve` 写入占位符，读取文件内容时通过 `read` 解压并填充（hydrate）。ProjFS 的路径不区分大小写，
+//! 精确查找失败时逐级按 `PrjFileNameCompare` 比较。
+//! 删除、重命名、创建硬链接和修改已投影的路径会被拒绝；在根目录中新建的文件只保存在本地磁盘上，不写入存储库。
+//! 需要在 Windows 可选功能中启用 Client-ProjFS。
+
+use anyhow::{Context, Result, anyhow};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::ffi::{c_void, OsStr, OsString};
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use windows_sys::core::{GUID, HRESULT, PCWSTR};
+use windows_sys::Win32::Foundation::{
+    E_FAIL, E_INVALIDARG, E_OUTOFMEMORY, ERROR_ACCESS_DENIED, ERROR_DIRECTORY, ERROR_FILE_NOT_FOUND, ERROR_READ_FAULT, S_OK,
+};
+use windows_sys::Win32::Storage::FileSystem::FILE_ATTRIBUTE_READONLY;
+use windows_sys::Win32::Storage::ProjectedFileSystem::{
+    PrjAllocateAlignedBuffer, PrjFileNameCompare, PrjFileNameMatch, PrjFillDirEntryBuffer, PrjFreeAlignedBuffer,
+    PrjMarkDirectoryAsPlaceholder, PrjStartVirtualizing, PrjStopVirtualizing, PrjWriteFileData, PrjWritePlaceholderInfo,
+    PRJ_CALLBACKS, PRJ_CALLBACK_DATA, PRJ_CB_DATA_FLAG_ENUM_RESTART_SCAN, PRJ_DIR_ENTRY_BUFFER_HANDLE,
+    PRJ_FILE_BASIC_INFO, PRJ_NAMESPACE_VIRTUALIZATION_CONTEXT, PRJ_NOTIFICATION, PRJ_NOTIFICATION_MAPPING,
+    PRJ_NOTIFICATION_PARAMETERS, PRJ_NOTIFY_FILE_PRE_CONVERT_TO_FULL, PRJ_NOTIFY_PRE_DELETE, PRJ_NOTIFY_PRE_RENAME,
+    PRJ_NOTIFY_PRE_SET_HARDLINK, PRJ_PLACEHOLDER_INFO, PRJ_STARTVIRTUALIZING_OPTIONS,
+};
+
+use crate::view::{NodeAttr, NodeKind, ReadOnlyView};
+
+/// 填充文件时每次写入的最大字节数，是常见扇区大小的整数倍
+const WRITE_CHUNK: usize = 1024 * 1024;
+
+/// 1601-01-01 到 1970-01-01 之间的 100 纳秒间隔数
+const FILETIME_UNIX_EPOCH: i64 = 116_444_736_000_000_000;
+
+/// 正在进行的目录枚举
+struct Enumeration {
+    /// 按 `PrjFileNameCompare` 排序的子项，名称以 NUL 结尾
+    entries: Vec<(Vec<u16>, NodeAttr)>,
+    /// 第一次调用（或重新扫描）时的搜索表达式，None 表示全部匹配
+    pattern: Option<Vec<u16>>,
+    started: bool,
+    next: usize,
+}
+
+/// 回调共享的状态，ProjFS 在线程池中调用回调
+struct Provider {
+    /// 视图内部的存储管理器不能跨线程共享，读取按顺序进行
+    view: Mutex<ReadOnlyView>,
+    enumerations: Mutex<HashMap<u128, Enumeration>>,
+}
+
+impl Provider {
+    fn view(&self) -> Result<MutexGuard<'_, ReadOnlyView>, HRESULT> {
+        self.view.lock().map_err(|_| E_FAIL)
+    }
+}
+
+/// 正在运行的投影，drop 时停止虚拟化
+///
+/// 已经填充的文件在停止后仍留在根目录中，下次启动时直接使用；
+/// 存储库内容变化后需要清空根目录再重新启动，否则看到的是旧内容。
+pub struct Projection {
+    context: PRJ_NAMESPACE_VIRTUALIZATION_CONTEXT,
+    provider: *mut Provider,
+    root: PathBuf,
+}
+
+// 虚拟化句柄可以在任意线程上停止，回调只通过 Provider 内的锁访问视图
+unsafe impl Send for Projection {}
+
+impl Projection {
+    /// 虚拟化根目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 停止投影
+    pub fn stop(self) {}
+}
+
+impl Drop for Projection {
+    fn drop(&mut self) {
+        // SAFETY: 停止虚拟化后不会再有回调使用 provider
+        unsafe {
+            PrjStopVirtualizing(self.context);
+            drop(Box::from_raw(self.provider));
+        }
+    }
+}
+
+/// 在 `root`（不存在时创建）开始投影视图，返回的 [`Projection`] 被 drop 时停止
+pub fn start(view: ReadOnlyView, root: &Path) -> Result<Projection> {
+    std::fs::create_dir_all(root)
+        .with_context(|| format!("Failed to create virtualization root {}", root.display()))?;
+    let root = root.canonicalize()
+        .with_context(|| format!("Failed to resolve virtualization root {}", root.display()))?;
+    let root_name = wide(root.as_os_str());
+    let instance_id = instance_id(&root);
+
+    // 根目录已经标记过时再次标记会失败；实例ID由路径决定，只在启动也失败时报告
+    // SAFETY: 路径以 NUL 结尾，ID 在调用期间有效
+    let marked = unsafe { PrjMarkDirectoryAsPlaceholder(root_name.as_ptr(), std::ptr::null(), std::ptr::null(), &instance_id) };
+
+    let callbacks = PRJ_CALLBACKS {
+        StartDirectoryEnumerationCallback: Some(start_enumeration),
+        EndDirectoryEnumerationCallback: Some(end_enumeration),
+        GetDirectoryEnumerationCallback: Some(get_enumeration),
+        GetPlaceholderInfoCallback: Some(get_placeholder_info),
+        GetFileDataCallback: Some(get_file_data),
+        NotificationCallback: Some(notify),
+        ..Default::default()
+    };
+    // 空的通知根表示整个虚拟化根目录
+    let notification_root = [0u16];
+    let mut mappings = [PRJ_NOTIFICATION_MAPPING {
+        NotificationBitMask: PRJ_NOTIFY_PRE_DELETE | PRJ_NOTIFY_PRE_RENAME
+            | PRJ_NOTIFY_PRE_SET_HARDLINK | PRJ_NOTIFY_FILE_PRE_CONVERT_TO_FULL,
+        NotificationRoot: notification_root.as_ptr(),
+    }];
+    let options = PRJ_STARTVIRTUALIZING_OPTIONS {
+        NotificationMappings: mappings.as_mut_ptr(),
+        NotificationMappingsCount: mappings.len() as u32,
+        ..Default::default()
+    };
+
+    let provider = Box::into_raw(Box::new(Provider {
+        view: Mutex::new(view),
+        enumerations: Mutex::new(HashMap::new()),
+    }));
+    let mut context: PRJ_NAMESPACE_VIRTUALIZATION_CONTEXT = std::ptr::null_mut();
+    // SAFETY: 回调表和选项在调用期间有效，provider 在 Projection 被 drop 前一直有效
+    let started = unsafe {
+        PrjStartVirtualizing(root_name.as_ptr(), &callbacks, provider as *const c_void, &options, &mut context)
+    };
+    if started < 0 {
+        // SAFETY: 启动失败时不会有回调
+        drop(unsafe { Box::from_raw(provider) });
+        return Err(if marked < 0 {
+            anyhow!("Failed to mark {} as a ProjFS virtualization root: HRESULT {:#010x}", root.display(), marked)
+        } else {
+            anyhow!("Failed to start ProjFS virtualization at {}: HRESULT {:#010x}", root.display(), started)
+        });
+    }
+
+    Ok(Projection { context, provider, root })
+}
+
+/// 由根目录路径得到固定的实例ID，重新启动时沿用根目录上已有的标记
+fn instance_id(root: &Path) -> GUID {
+    let digest = Sha256::digest(root.as_os_str().as_encoded_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    GUID::from_u128(u128::from_be_bytes(bytes))
+}
+
+fn guid_key(guid: &GUID) -> u128 {
+    (guid.data1 as u128) << 96 | (guid.data2 as u128) << 80 | (guid.data3 as u128) << 64 | u64::from_be_bytes(guid.data4) as u128
+}
+
+fn hresult_from_win32(error: u32) -> HRESULT {
+    ((error & 0xffff) | 0x8007_0000) as HRESULT
+}
+
+fn wide(name: &OsStr) -> Vec<u16> {
+    name.encode_wide().chain(std::iter::once(0)).collect()
+}
+
+/// 读取以 NUL 结尾的宽字符串，空指针视为空字符串
+unsafe fn from_wide(ptr: PCWSTR) -> OsString {
+    if ptr.is_null() {
+        return OsString::new();
+    }
+    let mut len = 0;
+    while *ptr.add(len) != 0 {
+        len += 1;
+    }
+    OsString::from_wide(std::slice::from_raw_parts(ptr, len))
+}
+
+/// SystemTime 转为 FILETIME（1601 年起的 100 纳秒间隔）
+fn filetime(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => FILETIME_UNIX_EPOCH + (after.as_nanos() / 100) as i64,
+        Err(e) => FILETIME_UNIX_EPOCH - (e.duration().as_nanos() / 100) as i64,
+    }
+}
+
+/// 视图节点属性转为 ProjFS 的基本信息：文件只读，没有修改时间时由系统使用当前时间
+fn basic_info(attr: &NodeAttr) -> PRJ_FILE_BASIC_INFO {
+    let time = attr.modified.map(filetime).unwrap_or(0);
+    let is_directory = attr.kind == NodeKind::Directory;
+    PRJ_FILE_BASIC_INFO {
+        IsDirectory: is_directory,
+        FileSize: if is_directory { 0 } else { attr.size as i64 },
+        CreationTime: time,
+        LastAccessTime: time,
+        LastWriteTime: time,
+        ChangeTime: time,
+        FileAttributes: if is_directory { 0 } else { FILE_ATTRIBUTE_READONLY },
+    }
+}
+
+/// 按相对于根目录的路径查找：先用 `resolve` 精确查找，找不到时不区分大小写逐级比较
+fn resolve(view: &ReadOnlyView, path: &Path) -> Option<NodeAttr> {
+    if let Some(attr) = view.resolve(path) {
+        return Some(attr);
+    }
+    let mut attr = view.getattr(ReadOnlyView::ROOT)?;
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        let name = wide(name);
+        attr = view.readdir(attr.ino)?
+            .into_iter()
+            // SAFETY: 两个名称都以 NUL 结尾
+            .find(|(child, _)| unsafe { PrjFileNameCompare(wide(child).as_ptr(), name.as_ptr()) } == 0)?
+            .1;
+    }
+    Some(attr)
+}
+
+/// 回调中的共享状态和请求的相对路径
+unsafe fn request<'a>(data: *const PRJ_CALLBACK_DATA) -> (&'a PRJ_CALLBACK_DATA, &'a Provider, PathBuf) {
+    let data = &*data;
+    let provider = &*(data.InstanceContext as *const Provider);
+    (data, provider, PathBuf::from(from_wide(data.FilePathName)))
+}
+
+unsafe extern "system" fn start_enumeration(data: *const PRJ_CALLBACK_DATA, id: *const GUID) -> HRESULT {
+    let (_, provider, path) = request(data);
+    let children = {
+        let view = match provider.view() {
+            Ok(view) => view,
+            Err(hr) => return hr,
+        };
+        let Some(dir) = resolve(&view, &path) else {
+            return hresult_from_win32(ERROR_FILE_NOT_FOUND);
+        };
+        match view.readdir(dir.ino) {
+            Some(children) => children,
+            None => return hresult_from_win32(ERROR_DIRECTORY),
+        }
+    };
+
+    // ProjFS 要求按它的文件名顺序返回目录项
+    let mut entries: Vec<(Vec<u16>, NodeAttr)> = children.into_iter()
+        .map(|(name, attr)| (wide(&name), attr))
+        .collect();
+    entries.sort_by(|(a, _), (b, _)| PrjFileNameCompare(a.as_ptr(), b.as_ptr()).cmp(&0));
+
+    let Ok(mut enumerations) = provider.enumerations.lock() else {
+        return E_FAIL;
+    };
+    enumerations.insert(guid_key(&*id), Enumeration { entries, pattern: None, started: false, next: 0 });
+    S_OK
+}
+
+unsafe extern "system" fn end_enumeration(data: *const PRJ_CALLBACK_DATA, id: *const GUID) -> HRESULT {
+    let (_, provider, _) = request(data);
+    let Ok(mut enumerations) = provider.enumerations.lock() else {
+        return E_FAIL;
+    };
+    enumerations.remove(&guid_key(&*id));
+    S_OK
+}
+
+unsafe extern "system" fn get_enumeration(
+    data: *const PRJ_CALLBACK_DATA,
+    id: *const GUID,
+    search: PCWSTR,
+    buffer: PRJ_DIR_ENTRY_BUFFER_HANDLE,
+) -> HRESULT {
+    let (data, provider, _) = request(data);
+    let Ok(mut enumerations) = provider.enumerations.lock() else {
+        return E_FAIL;
+    };
+    let Some(enumeration) = enumerations.get_mut(&guid_key(&*id)) else {
+        return E_INVALIDARG;
+    };
+
+    // 搜索表达式只在第一次调用和重新扫描时生效，之后的调用沿用
+    if !enumeration.started || data.Flags & PRJ_CB_DATA_FLAG_ENUM_RESTART_SCAN != 0 {
+        let pattern = from_wide(search);
+        enumeration.pattern = (!pattern.is_empty()).then(|| wide(&pattern));
+        enumeration.started = true;
+        enumeration.next = 0;
+    }
+
+    let mut added = false;
+    while let Some((name, attr)) = enumeration.entries.get(enumeration.next) {
+        let matches = enumeration.pattern.as_ref()
+            .is_none_or(|pattern| PrjFileNameMatch(name.as_ptr(), pattern.as_ptr()));
+        if matches {
+            let hr = PrjFillDirEntryBuffer(name.as_ptr(), &basic_info(attr), buffer);
+            if hr < 0 {
+                // 缓冲区已满，下次调用从这一项继续；一项都放不下时报告错误
+                return if added { S_OK } else { hr };
+            }
+            added = true;
+        }
+        enumeration.next += 1;
+    }
+    S_OK
+}
+
+unsafe extern "system" fn get_placeholder_info(data: *const PRJ_CALLBACK_DATA) -> HRESULT {
+    let (data, provider, path) = request(data);
+    let attr = match provider.view() {
+        Ok(view) => resolve(&view, &path),
+        Err(hr) => return hr,
+    };
+    let Some(attr) = attr else {
+        return hresult_from_win32(ERROR_FILE_NOT_FOUND);
+    };
+    let info = PRJ_PLACEHOLDER_INFO { FileBasicInfo: basic_info(&attr), ..Default::default() };
+    PrjWritePlaceholderInfo(data.NamespaceVirtualizationContext, data.FilePathName, &info, std::mem::size_of::<PRJ_PLACEHOLDER_INFO>() as u32)
+}
+
+unsafe extern "system" fn get_file_data(data: *const PRJ_CALLBACK_DATA, offset: u64, length: u32) -> HRESULT {
+    let (data, provider, path) = request(data);
+    let attr = match provider.view() {
+        Ok(view) => resolve(&view, &path),
+        Err(hr) => return hr,
+    };
+    let Some(attr) = attr.filter(|attr| attr.kind == NodeKind::File) else {
+        return hresult_from_win32(ERROR_FILE_NOT_FOUND);
+    };
+    if length == 0 {
+        return S_OK;
+    }
+
+    let capacity = WRITE_CHUNK.min(length as usize);
+    let buffer = PrjAllocateAlignedBuffer(data.NamespaceVirtualizationContext, capacity);
+    if buffer.is_null() {
+        return E_OUTOFMEMORY;
+    }
+
+    // 分块读取并写入，每块之间释放视图锁，让其他回调有机会执行；
+    // 超过视图缓存容量的文件只在第一块时解码到临时文件，之后的块按偏移读取，不会每块都重新解压
+    let end = offset + length as u64;
+    let mut position = offset;
+    let mut result = S_OK;
+    while position < end {
+        let size = capacity.min((end - position) as usize);
+        let chunk = match provider.view().map(|view| view.read(attr.ino, position, size)) {
+            Ok(Ok(chunk)) => chunk,
+            Ok(Err(e)) => {
+                crate::output::warning!("Warning: Failed to read {}: {}", path.display(), e);
+                result = hresult_from_win32(ERROR_READ_FAULT);
+                break;
+            }
+            Err(hr) => {
+                result = hr;
+                break;
+            }
+        };
+        if chunk.is_empty() {
+            break;
+        }
+        std::ptr::copy_nonoverlapping(chunk.as_ptr(), buffer.cast::<u8>(), chunk.len());
+        result = PrjWriteFileData(data.NamespaceVirtualizationContext, &data.DataStreamId, buffer, position, chunk.len() as u32);
+        if result < 0 {
+            break;
+        }
+        position += chunk.len() as u64;
+    }
+
+    PrjFreeAlignedBuffer(buffer);
+    result
+}
+
+/// 只注册了删除、重命名、创建硬链接和转为完整文件之前的通知，对投影出的路径一律拒绝
+unsafe extern "system" fn notify(
+    data: *const PRJ_CALLBACK_DATA,
+    _is_directory: bool,
+    _notification: PRJ_NOTIFICATION,
+    _destination: PCWSTR,
+    _parameters: *mut PRJ_NOTIFICATION_PARAMETERS,
+) -> HRESULT {
+    let (_, provider, path) = request(data);
+    let projected = match provider.view() {
+        Ok(view) => resolve(&view, &path).is_some(),
+        Err(hr) => return hr,
+    };
+    if projected {
+        hresult_from_win32(ERROR_ACCESS_DENIED)
+    } else {
+        S_OK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_basic_info_is_read_only() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1);
+        let file = NodeAttr { ino: 2, kind: NodeKind::File, size: 10, modified: Some(modified) };
+        let info = basic_info(&file);
+        assert!(!info.IsDirectory);
+        assert_eq!(info.FileSize, 10);
+        assert_eq!(info.LastWriteTime, FILETIME_UNIX_EPOCH + 10_000_000);
+        assert_eq!(info.FileAttributes, FILE_ATTRIBUTE_READONLY);
+
+        let dir = NodeAttr { ino: ReadOnlyView::ROOT, kind: NodeKind::Directory, size: 0, modified: None };
+        let info = basic_info(&dir);
+        assert!(info.IsDirectory);
+        assert_eq!((info.LastWriteTime, info.FileAttributes), (0, 0));
+    }
+
+    #[test]
+    fn test_instance_id_depends_only_on_root() {
+        let a = instance_id(Path::new(r"C:\stowr\view"));
+        let b = instance_id(Path::new(r"C:\stowr\view"));
+        let c = instance_id(Path::new(r"C:\stowr\other"));
+        assert_eq!(guid_key(&a), guid_key(&b));
+        assert_ne!(guid_key(&a), guid_key(&c));
+        assert_eq!(GUID::from_u128(guid_key(&a)).data4, a.data4);
+    }
+}
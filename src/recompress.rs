@@ -0,0 +1,328 @@
+//! 已压缩输入的解包与还原
+//!
+//! `.log.gz` 等文件本身就是 gzip/zstd 压缩流，内容只差几行压缩结果也完全不同，差分无法生效，
+//! 再次压缩也几乎没有收益。启用 `unwrap_compressed` 后存储前解开压缩流，存储其中的原始内容，
+//! 并记录重新压缩所需的参数，提取时据此还原出逐字节相同的文件。
+//!
+//! 只有用记录的参数重新压缩能得到与输入完全相同的字节时才会解包。这依赖压缩器的实现：
+//! 由相同压缩库（gzip 为 miniz_oxide，zstd 为 libzstd）生成的文件通常可以还原，
+//! 其他工具生成的文件原样存储，不影响正确性。
+//! 压缩库升级后同样的参数可能压缩出不同的字节，因此还记录解包前原始字节的 SHA256，
+//! 还原结果不符时报错，而不是静默返回不同的文件。
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use flate2::bufread::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+const GZIP_MAGIC: [u8; 3] = [0x1f, 0x8b, 0x08];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// gzip 头之后的 CRC32 和原始大小
+const GZIP_TRAILER_LEN: usize = 8;
+
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+const GZIP_RESERVED: u8 = 0xe0;
+
+/// 依次尝试的压缩级别，常用级别在前，尽早找到匹配
+const DEFLATE_LEVELS: [u32; 10] = [6, 9, 1, 2, 3, 4, 5, 7, 8, 0];
+const ZSTD_LEVELS: [i32; 19] = [3, 1, 2, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19];
+
+/// 解包前的压缩流格式，提取时据此重新压缩
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum StreamEncoding {
+    /// 单成员 gzip 文件
+    Gzip {
+        /// 原样保留的 gzip 头，包括文件名、修改时间等字段
+        #[serde(with = "serde_hex")]
+        header: Vec<u8>,
+        /// 重现 deflate 数据所用的压缩级别
+        level: u32,
+        /// 解包前原始字节的 SHA256，旧版本记录的条目没有该字段
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        original_hash: Option<String>,
+    },
+    /// 单帧 zstd 文件
+    Zstd {
+        level: i32,
+        /// 帧头中是否记录了内容大小
+        content_size: bool,
+        /// 是否带内容校验和
+        checksum: bool,
+        /// 解包前原始字节的 SHA256，旧版本记录的条目没有该字段
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        original_hash: Option<String>,
+    },
+}
+
+impl StreamEncoding {
+    /// 解开 gzip 或 zstd 压缩流，返回其中的原始内容和还原所需的参数
+    ///
+    /// 内容不是压缩流、解压后超过 `max_output` 字节，或无法重新压缩出相同的字节时返回 None
+    pub fn strip(data: &[u8], max_output: u64) -> Option<(Vec<u8>, Self)> {
+        let (content, mut encoding) = if data.starts_with(&GZIP_MAGIC) {
+            strip_gzip(data, max_output)?
+        } else if data.starts_with(&ZSTD_MAGIC) {
+            strip_zstd(data, max_output)?
+        } else {
+            return None;
+        };
+        let (StreamEncoding::Gzip { original_hash, .. } | StreamEncoding::Zstd { original_hash, .. }) = &mut encoding;
+        *original_hash = Some(sha256_hex(data));
+        Some((content, encoding))
+    }
+
+    /// 重新压缩，还原解包前的原始字节
+    ///
+    /// 结果与记录的原始字节哈希不符（通常是压缩库版本变化）时返回错误
+    pub fn restore(&self, content: &[u8]) -> Result<Vec<u8>> {
+        let mut restored = Vec::new();
+        self.encode(content, &mut restored)?;
+        let (StreamEncoding::Gzip { original_hash, .. } | StreamEncoding::Zstd { original_hash, .. }) = self;
+        check_original(original_hash.as_deref(), &restored)?;
+        Ok(restored)
+    }
+
+    fn encode<W: Write>(&self, content: &[u8], writer: &mut W) -> io::Result<()> {
+        match self {
+            StreamEncoding::Gzip { header, level, .. } => {
+                writer.write_all(header)?;
+                deflate_into(content, *level, &mut *writer)?;
+                let mut crc = Crc::new();
+                crc.update(content);
+                writer.write_all(&crc.sum().to_le_bytes())?;
+                // ISIZE 是原始大小对 2^32 取模
+                writer.write_all(&(content.len() as u32).to_le_bytes())
+            }
+            StreamEncoding::Zstd { level, content_size, checksum, .. } => {
+                let mut encoder = zstd::stream::write::Encoder::new(writer, *level)?;
+                encoder.include_checksum(*checksum)?;
+                encoder.include_contentsize(*content_size)?;
+                if *content_size {
+                    encoder.set_pledged_src_size(Some(content.len() as u64))?;
+                }
+                encoder.write_all(content)?;
+                encoder.finish()?;
+                Ok(())
+            }
+        }
+    }
+
+    /// 按当前参数重新压缩是否得到 `expected`，出现第一个不同的字节时即停止压缩
+    fn reproduces(&self, content: &[u8], expected: &[u8]) -> bool {
+        reproduces(expected, |writer| self.encode(content, writer))
+    }
+}
+
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    crate::crypto::to_hex(&Sha256::digest(data))
+}
+
+/// 校验重新编码的结果与记录的原始字节哈希一致，没有记录哈希时不校验
+pub(crate) fn check_original(expected: Option<&str>, restored: &[u8]) -> Result<()> {
+    match expected {
+        Some(expected) if sha256_hex(restored) != expected => Err(anyhow!(
+            "Re-encoded content does not match the original bytes (expected SHA256 {}); \
+             the compression library may have changed since the file was stored",
+            expected
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// 以指定级别压缩为原始 deflate 数据（不带 zlib/gzip 头）
+pub(crate) fn deflate_into<W: Write>(content: &[u8], level: u32, writer: W) -> io::Result<W> {
+    let mut encoder = DeflateEncoder::new(writer, Compression::new(level));
+    encoder.write_all(content)?;
+    encoder.finish()
+}
+
+/// 找出能把 `content` 重新压缩为 `expected`（原始 deflate 数据）的压缩级别
+pub(crate) fn find_deflate_level(content: &[u8], expected: &[u8]) -> Option<u32> {
+    DEFLATE_LEVELS.iter()
+        .copied()
+        .find(|&level| reproduces(expected, |writer| deflate_into(content, level, writer).map(|_| ())))
+}
+
+/// `encode` 写出的数据是否恰好为 `expected`，出现第一个不同的字节时即停止
+fn reproduces(expected: &[u8], encode: impl FnOnce(&mut MatchWriter) -> io::Result<()>) -> bool {
+    let mut writer = MatchWriter { expected, pos: 0 };
+    encode(&mut writer).is_ok() && writer.pos == expected.len()
+}
+
+/// 将写入的数据与期望的字节比较，不一致时返回错误，使压缩器尽早放弃
+struct MatchWriter<'a> {
+    expected: &'a [u8],
+    pos: usize,
+}
+
+impl Write for MatchWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let end = self.pos + buf.len();
+        if self.expected.get(self.pos..end) != Some(buf) {
+            return Err(io::Error::other("recompressed output differs"));
+        }
+        self.pos = end;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// gzip 头的长度，格式不支持时返回 None
+fn gzip_header_len(data: &[u8]) -> Option<usize> {
+    let flags = *data.get(3)?;
+    if flags & GZIP_RESERVED != 0 {
+        return None;
+    }
+    let mut pos = 10;
+    if flags & GZIP_FEXTRA != 0 {
+        let extra_len = u16::from_le_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+        pos += 2 + extra_len;
+    }
+    for flag in [GZIP_FNAME, GZIP_FCOMMENT] {
+        if flags & flag != 0 {
+            pos += data.get(pos..)?.iter().position(|&b| b == 0)? + 1;
+        }
+    }
+    if flags & GZIP_FHCRC != 0 {
+        pos += 2;
+    }
+    (pos <= data.len()).then_some(pos)
+}
+
+/// 读取最多 `max_output` 字节，超过时返回 None
+pub(crate) fn read_limited(reader: impl Read, max_output: u64) -> Option<Vec<u8>> {
+    let mut content = Vec::new();
+    reader.take(max_output.saturating_add(1)).read_to_end(&mut content).ok()?;
+    (content.len() as u64 <= max_output).then_some(content)
+}
+
+fn strip_gzip(data: &[u8], max_output: u64) -> Option<(Vec<u8>, StreamEncoding)> {
+    let header_len = gzip_header_len(data)?;
+    let mut decoder = DeflateDecoder::new(&data[header_len..]);
+    let content = read_limited(&mut decoder, max_output)?;
+    // 多成员文件或带填充数据时 deflate 流之后不止一个尾部
+    if decoder.into_inner().len() != GZIP_TRAILER_LEN {
+        return None;
+    }
+
+    let header = data[..header_len].to_vec();
+    DEFLATE_LEVELS.iter()
+        .map(|&level| StreamEncoding::Gzip { header: header.clone(), level, original_hash: None })
+        .find(|encoding| encoding.reproduces(&content, data))
+        .map(|encoding| (content, encoding))
+}
+
+fn strip_zstd(data: &[u8], max_output: u64) -> Option<(Vec<u8>, StreamEncoding)> {
+    // 只处理单帧文件
+    if zstd::zstd_safe::find_frame_compressed_size(data).ok()? != data.len() {
+        return None;
+    }
+    let descriptor = *data.get(ZSTD_MAGIC.len())?;
+    // 使用字典压缩的帧无法单独还原
+    if descriptor & 0x03 != 0 {
+        return None;
+    }
+    let content_size = descriptor >> 6 != 0 || descriptor & 0x20 != 0;
+    let checksum = descriptor & 0x04 != 0;
+
+    let content = read_limited(zstd::stream::read::Decoder::new(data).ok()?, max_output)?;
+    ZSTD_LEVELS.iter()
+        .map(|&level| StreamEncoding::Zstd { level, content_size, checksum, original_hash: None })
+        .find(|encoding| encoding.reproduces(&content, data))
+        .map(|encoding| (content, encoding))
+}
+
+mod serde_hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::crypto::to_hex(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        crate::crypto::from_hex(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::GzBuilder;
+
+    #[test]
+    fn test_strip_and_restore_compressed_streams() {
+        let content: Vec<u8> = (0..2000).flat_map(|i| format!("line {} of the log\n", i).into_bytes()).collect();
+
+        let mut gzip = GzBuilder::new()
+            .filename("app.log")
+            .mtime(1_700_000_000)
+            .write(Vec::new(), Compression::best());
+        gzip.write_all(&content).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let (stripped, encoding) = StreamEncoding::strip(&gzip, u64::MAX).unwrap();
+        assert_eq!(stripped, content);
+        assert!(matches!(&encoding, StreamEncoding::Gzip { header, .. } if header.ends_with(b"app.log\0")));
+        assert_eq!(encoding.restore(&stripped).unwrap(), gzip);
+        let json = serde_json::to_string(&encoding).unwrap();
+        assert_eq!(serde_json::from_str::<StreamEncoding>(&json).unwrap(), encoding);
+
+        let zstd = zstd::encode_all(&content[..], 7).unwrap();
+        let (stripped, encoding) = StreamEncoding::strip(&zstd, u64::MAX).unwrap();
+        assert_eq!(stripped, content);
+        assert_eq!(encoding.restore(&stripped).unwrap(), zstd);
+
+        // 超过解压上限、多成员、尾部有多余数据或不是压缩流时不解包
+        assert!(StreamEncoding::strip(&gzip, 100).is_none());
+        let mut concatenated = gzip.clone();
+        concatenated.extend_from_slice(&gzip);
+        assert!(StreamEncoding::strip(&concatenated, u64::MAX).is_none());
+        let mut padded = zstd.clone();
+        padded.push(0);
+        assert!(StreamEncoding::strip(&padded, u64::MAX).is_none());
+        assert!(StreamEncoding::strip(&content, u64::MAX).is_none());
+
+        // 无法重现的压缩流（这里篡改了 deflate 数据的分块方式）原样存储
+        let mut reencoded = gzip[..gzip_header_len(&gzip).unwrap()].to_vec();
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        for chunk in content.chunks(100) {
+            encoder.write_all(chunk).unwrap();
+            encoder.flush().unwrap();
+        }
+        reencoded.extend_from_slice(&encoder.finish().unwrap());
+        reencoded.extend_from_slice(&gzip[gzip.len() - GZIP_TRAILER_LEN..]);
+        assert!(StreamEncoding::strip(&reencoded, u64::MAX).is_none());
+    }
+
+    #[test]
+    fn test_restore_checks_original_hash() {
+        let content = b"log line\n".repeat(100);
+        let zstd = zstd::encode_all(&content[..], 3).unwrap();
+        let (stripped, encoding) = StreamEncoding::strip(&zstd, u64::MAX).unwrap();
+        let StreamEncoding::Zstd { level, content_size, checksum, original_hash } = encoding.clone() else {
+            panic!("expected zstd encoding");
+        };
+        assert_eq!(original_hash, Some(sha256_hex(&zstd)));
+
+        // 模拟压缩库升级后重新压缩出不同的字节：记录的哈希与重新压缩的结果对不上
+        let mut other = zstd.clone();
+        other.push(0);
+        let changed = StreamEncoding::Zstd { level, content_size, checksum, original_hash: Some(sha256_hex(&other)) };
+        assert!(changed.restore(&stripped).unwrap_err().to_string().contains("does not match the original bytes"));
+
+        // 旧版本记录的条目没有哈希，仍按参数还原
+        let legacy: StreamEncoding = serde_json::from_str(
+            &format!(r#"{{"format":"zstd","level":{},"content_size":{},"checksum":{}}}"#, level, content_size, checksum),
+        ).unwrap();
+        assert_eq!(legacy.restore(&stripped).unwrap(), zstd);
+    }
+}
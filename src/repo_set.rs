@@ -0,0 +1,242 @@
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::index::{FileEntry, create_index};
+use crate::paths;
+use crate::storage::StorageManager;
+
+/// 路由规则：路径位于 `prefix` 之下的文件存入 `repository`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteRule {
+    pub prefix: PathBuf,
+    pub repository: String,
+}
+
+/// 多仓库管理器
+///
+/// 同时打开多个仓库（例如每个项目一个），按路径前缀把存储请求路由到对应仓库，
+/// 提取和查询时在所有仓库中查找。多条规则匹配时使用最长的前缀，
+/// 没有规则匹配时使用默认仓库。
+#[derive(Default)]
+pub struct RepoSet {
+    repositories: Vec<(String, StorageManager)>,
+    rules: Vec<RouteRule>,
+    default_repository: Option<String>,
+}
+
+impl RepoSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按配置打开仓库并加入集合
+    pub fn open(&mut self, name: &str, config: Config) -> Result<()> {
+        let index = create_index(&config)?;
+        self.add_repository(name, StorageManager::new(config, index))
+    }
+
+    /// 加入已打开的仓库（例如加密仓库）
+    pub fn add_repository(&mut self, name: &str, storage: StorageManager) -> Result<()> {
+        if self.get(name).is_some() {
+            return Err(anyhow!("Repository already exists: {}", name));
+        }
+        self.repositories.push((name.to_string(), storage));
+        Ok(())
+    }
+
+    /// 移除仓库及指向它的路由规则
+    pub fn remove_repository(&mut self, name: &str) -> Option<StorageManager> {
+        let position = self.repositories.iter().position(|(n, _)| n == name)?;
+        self.rules.retain(|rule| rule.repository != name);
+        if self.default_repository.as_deref() == Some(name) {
+            self.default_repository = None;
+        }
+        Some(self.repositories.remove(position).1)
+    }
+
+    /// 添加路由规则
+    pub fn add_route(&mut self, prefix: impl AsRef<Path>, repository: &str) -> Result<()> {
+        self.require(repository)?;
+        self.rules.push(RouteRule {
+            prefix: paths::index_key(prefix.as_ref()),
+            repository: repository.to_string(),
+        });
+        Ok(())
+    }
+
+    /// 设置没有规则匹配时使用的仓库
+    pub fn set_default(&mut self, repository: &str) -> Result<()> {
+        self.require(repository)?;
+        self.default_repository = Some(repository.to_string());
+        Ok(())
+    }
+
+    /// 所有路由规则
+    pub fn rules(&self) -> &[RouteRule] {
+        &self.rules
+    }
+
+    /// 所有仓库名称
+    pub fn names(&self) -> Vec<&str> {
+        self.repositories.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StorageManager> {
+        self.repositories.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, storage)| storage)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut StorageManager> {
+        self.repositories.iter_mut()
+            .find(|(n, _)| n == name)
+            .map(|(_, storage)| storage)
+    }
+
+    /// 根据路由规则确定路径应存入的仓库
+    pub fn route(&self, file_path: &Path) -> Result<&str> {
+        let file_path = paths::index_key(file_path);
+        self.rules.iter()
+            .filter(|rule| file_path.starts_with(&rule.prefix))
+            .max_by_key(|rule| rule.prefix.components().count())
+            .map(|rule| rule.repository.as_str())
+            .or(self.default_repository.as_deref())
+            .ok_or_else(|| anyhow!("No repository configured for path: {}", file_path.display()))
+    }
+
+    /// 查找当前存储了该路径的仓库
+    ///
+    /// 优先检查路由到的仓库，再依次检查其余仓库，
+    /// 以便找到路由规则修改前存入的文件
+    pub fn locate(&self, file_path: &Path) -> Result<Option<&str>> {
+        let routed = self.route(file_path).ok();
+        if let Some(name) = routed {
+            if self.require(name)?.get_file(file_path)?.is_some() {
+                return Ok(Some(name));
+            }
+        }
+
+        for (name, storage) in &self.repositories {
+            if Some(name.as_str()) != routed && storage.get_file(file_path)?.is_some() {
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 存储文件到路由到的仓库，返回仓库名称
+    pub fn store_file(&mut self, file_path: &Path, delete_source: bool) -> Result<String> {
+        let name = self.route(file_path)?.to_string();
+        self.require_mut(&name)?.store_file(file_path, delete_source)?;
+        Ok(name)
+    }
+
+    /// 从存储了该文件的仓库提取文件
+    pub fn owe_file(&mut self, file_path: &Path) -> Result<()> {
+        let name = self.locate(file_path)?
+            .ok_or_else(|| anyhow!("File not found in any repository: {}", file_path.display()))?
+            .to_string();
+        self.require_mut(&name)?.owe_file(file_path)
+    }
+
+    /// 获取文件条目及其所在仓库
+    pub fn get_file(&self, file_path: &Path) -> Result<Option<(String, FileEntry)>> {
+        match self.locate(file_path)? {
+            Some(name) => {
+                let entry = self.require(name)?.get_file(file_path)?;
+                Ok(entry.map(|entry| (name.to_string(), entry)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 列出所有仓库中的文件
+    pub fn list_files(&self) -> Result<Vec<(String, FileEntry)>> {
+        let mut files = Vec::new();
+        for (name, storage) in &self.repositories {
+            files.extend(storage.list_files()?.into_iter().map(|entry| (name.clone(), entry)));
+        }
+        Ok(files)
+    }
+
+    /// 在所有仓库中搜索文件
+    pub fn search_files(&self, pattern: &str) -> Result<Vec<(String, FileEntry)>> {
+        let mut files = Vec::new();
+        for (name, storage) in &self.repositories {
+            files.extend(storage.search_files(pattern)?.into_iter().map(|entry| (name.clone(), entry)));
+        }
+        Ok(files)
+    }
+
+    fn require(&self, name: &str) -> Result<&StorageManager> {
+        self.get(name).ok_or_else(|| anyhow!("Unknown repository: {}", name))
+    }
+
+    fn require_mut(&mut self, name: &str) -> Result<&mut StorageManager> {
+        self.get_mut(name).ok_or_else(|| anyhow!("Unknown repository: {}", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn repo_config(dir: &TempDir, name: &str) -> Config {
+        Config {
+            storage_path: dir.path().join(name),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_routes_by_longest_prefix() {
+        let dir = TempDir::new().unwrap();
+        let mut set = RepoSet::new();
+        set.open("general", repo_config(&dir, "general")).unwrap();
+        set.open("game", repo_config(&dir, "game")).unwrap();
+        set.open("textures", repo_config(&dir, "textures")).unwrap();
+        set.set_default("general").unwrap();
+        set.add_route(dir.path().join("game"), "game").unwrap();
+        set.add_route(dir.path().join("game").join("textures"), "textures").unwrap();
+
+        assert_eq!(set.route(&dir.path().join("notes.txt")).unwrap(), "general");
+        assert_eq!(set.route(&dir.path().join("game/main.lua")).unwrap(), "game");
+        assert_eq!(set.route(&dir.path().join("game/textures/a.png")).unwrap(), "textures");
+        // 只按完整路径组件匹配
+        assert_eq!(set.route(&dir.path().join("gamedata/x")).unwrap(), "general");
+        assert!(set.add_route("/x", "missing").is_err());
+    }
+
+    #[test]
+    fn test_store_list_and_owe_across_repositories() {
+        let dir = TempDir::new().unwrap();
+        let mut set = RepoSet::new();
+        set.open("a", repo_config(&dir, "repo_a")).unwrap();
+        set.open("b", repo_config(&dir, "repo_b")).unwrap();
+        set.add_route(dir.path().join("work_a"), "a").unwrap();
+        set.add_route(dir.path().join("work_b"), "b").unwrap();
+
+        let file_a = dir.path().join("work_a").join("one.txt");
+        let file_b = dir.path().join("work_b").join("two.txt");
+        for file in [&file_a, &file_b] {
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, file.display().to_string()).unwrap();
+        }
+
+        assert_eq!(set.store_file(&file_a, true).unwrap(), "a");
+        assert_eq!(set.store_file(&file_b, true).unwrap(), "b");
+        assert!(set.store_file(&dir.path().join("elsewhere.txt"), false).is_err());
+
+        let mut names: Vec<String> = set.list_files().unwrap().into_iter().map(|(name, _)| name).collect();
+        names.sort();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(set.search_files("*two*").unwrap()[0].0, "b");
+
+        set.owe_file(&file_b).unwrap();
+        assert!(file_b.exists());
+        assert_eq!(set.list_files().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::crypto::{self, EncryptionKey, KdfParams, KeyProvider, StaticKeyProvider};
+use crate::fsutil;
+use crate::index::create_index_with_key;
+use crate::storage::StorageManager;
+
+/// 仓库清单文件名（位于存储目录下）
+const MANIFEST_FILE: &str = "repository.json";
+/// 当前清单格式版本
+const MANIFEST_VERSION: u32 = 1;
+/// 口令校验数据的明文
+const VERIFIER_PLAINTEXT: &[u8] = b"stowr-repository";
+
+/// 仓库清单
+///
+/// 保存口令派生参数和校验数据，本身不包含任何密钥
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryManifest {
+    pub version: u32,
+    /// Argon2id 派生参数
+    pub kdf: KdfParams,
+    /// 用主密钥加密的校验数据（十六进制），用于判断口令是否正确
+    pub verifier: String,
+}
+
+/// 口令保护的仓库
+///
+/// 主密钥由口令通过 Argon2id 派生，索引和存储文件都使用该密钥加密
+pub struct Repository {
+    storage: StorageManager,
+    manifest: RepositoryManifest,
+}
+
+impl Repository {
+    /// 使用口令初始化新仓库
+    pub fn init_with_password(config: Config, password: &str) -> Result<Self> {
+        Self::init_with_kdf(config, password, KdfParams::new())
+    }
+
+    /// 使用口令和指定派生参数初始化新仓库
+    pub fn init_with_kdf(config: Config, password: &str, kdf: KdfParams) -> Result<Self> {
+        let manifest_path = Self::manifest_path(&config.storage_path);
+        if manifest_path.exists() {
+            return Err(anyhow!("Repository already initialized: {}", config.storage_path.display()));
+        }
+
+        let key = kdf.derive_key(password)?;
+        let manifest = RepositoryManifest {
+            version: MANIFEST_VERSION,
+            verifier: crypto::to_hex(&crypto::encrypt(&key, VERIFIER_PLAINTEXT)?),
+            kdf,
+        };
+
+        fs::create_dir_all(&config.storage_path)
+            .context("Failed to create storage directory")?;
+        let json = serde_json::to_string_pretty(&manifest)
+            .context("Failed to serialize repository manifest")?;
+        fsutil::atomic_write(&manifest_path, json.as_bytes(), None, false)
+            .context("Failed to write repository manifest")?;
+
+        Self::open_with_key(config, manifest, key)
+    }
+
+    /// 使用口令打开已有仓库
+    pub fn open_with_password(config: Config, password: &str) -> Result<Self> {
+        let manifest_path = Self::manifest_path(&config.storage_path);
+        let content = fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Repository not initialized: {}", config.storage_path.display()))?;
+        let manifest: RepositoryManifest = serde_json::from_str(&content)
+            .context("Failed to parse repository manifest")?;
+        if manifest.version > MANIFEST_VERSION {
+            return Err(anyhow!("Unsupported repository manifest version: {}", manifest.version));
+        }
+
+        let key = manifest.kdf.derive_key(password)?;
+        let verifier = crypto::from_hex(&manifest.verifier)?;
+        if crypto::decrypt(&key, &verifier).is_err() {
+            return Err(anyhow!("Incorrect repository password"));
+        }
+
+        Self::open_with_key(config, manifest, key)
+    }
+
+    /// 存储目录是否已初始化为口令保护的仓库
+    pub fn is_initialized(storage_path: &Path) -> bool {
+        Self::manifest_path(storage_path).exists()
+    }
+
+    fn open_with_key(mut config: Config, manifest: RepositoryManifest, key: EncryptionKey) -> Result<Self> {
+        config.encrypt_index = true;
+        config.encrypt_blobs = true;
+
+        let provider: Arc<dyn KeyProvider> = Arc::new(StaticKeyProvider::new(Self::key_id(&manifest.kdf), key));
+        let index = create_index_with_key(&config, Some(provider.clone()))?;
+        let mut storage = StorageManager::new(config, index);
+        storage.set_key_provider(provider);
+
+        Ok(Self { storage, manifest })
+    }
+
+    /// 密钥标识由盐值决定，修改口令后会随之改变
+    fn key_id(kdf: &KdfParams) -> String {
+        // 盐值来自可被编辑的清单文件，按字符截取，非 ASCII 内容不会切在字符中间
+        format!("argon2id-{}", kdf.salt.chars().take(16).collect::<String>())
+    }
+
+    fn manifest_path(storage_path: &Path) -> PathBuf {
+        storage_path.join(MANIFEST_FILE)
+    }
+
+    /// 仓库清单
+    pub fn manifest(&self) -> &RepositoryManifest {
+        &self.manifest
+    }
+
+    /// 存储管理器
+    pub fn storage(&self) -> &StorageManager {
+        &self.storage
+    }
+
+    /// 可变的存储管理器
+    pub fn storage_mut(&mut self) -> &mut StorageManager {
+        &mut self.storage
+    }
+
+    /// 取出存储管理器
+    pub fn into_storage(self) -> StorageManager {
+        self.storage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn test_config(dir: &TempDir) -> Config {
+        Config {
+            storage_path: dir.path().join("storage"),
+            ..Config::default()
+        }
+    }
+
+    fn fast_kdf() -> KdfParams {
+        KdfParams::with_cost(64, 1, 1)
+    }
+
+    #[test]
+    fn test_password_repository_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, b"private notes").unwrap();
+
+        let mut repo = Repository::init_with_kdf(test_config(&dir), "hunter2", fast_kdf()).unwrap();
+        repo.storage_mut().store_file(&file, true).unwrap();
+        drop(repo);
+
+        assert!(Repository::open_with_password(test_config(&dir), "wrong").is_err());
+
+        let mut repo = Repository::open_with_password(test_config(&dir), "hunter2").unwrap();
+        assert_eq!(repo.storage().list_files().unwrap().len(), 1);
+        repo.storage_mut().owe_file(&file).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), b"private notes");
+    }
+
+    #[test]
+    fn test_init_twice_fails() {
+        let dir = TempDir::new().unwrap();
+        Repository::init_with_kdf(test_config(&dir), "pw", fast_kdf()).unwrap();
+
+        assert!(Repository::is_initialized(&test_config(&dir).storage_path));
+        assert!(Repository::init_with_kdf(test_config(&dir), "pw", fast_kdf()).is_err());
+    }
+
+    #[test]
+    fn test_key_id_handles_non_ascii_salt() {
+        let mut kdf = fast_kdf();
+        kdf.salt = "ééééééééééééééééé".to_string();
+        assert_eq!(Repository::key_id(&kdf), format!("argon2id-{}", "é".repeat(16)));
+    }
+}
@@ -0,0 +1,196 @@
+//! 提取时的路径重写
+//!
+//! 存储库在一台机器上创建（如 `/home/alice/...`），在另一台机器上恢复
+//! （如 `C:\Users\bob\...`）时，按规则把索引中的原始路径映射为新的输出路径。
+//! 规则按顺序尝试，第一条匹配的规则生效，都不匹配时使用原始路径。
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 配置中规则字符串的分隔符：`FROM=>TO`
+const RULE_SEPARATOR: &str = "=>";
+/// 正则规则的前缀：`regex:PATTERN=>REPLACEMENT`
+const REGEX_PREFIX: &str = "regex:";
+
+/// 单条路径重写规则
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PathRule {
+    /// 前缀替换：按路径组件匹配（`/home/al` 不匹配 `/home/alice`），`/` 和 `\` 视为相同，
+    /// 区分大小写。剩余部分的分隔符转换为 `to` 中使用的分隔符
+    Prefix { from: String, to: String },
+    /// 正则替换：对完整路径执行一次替换，`replacement` 中可以用 `$1`、`${name}` 引用捕获组
+    Regex { pattern: String, replacement: String },
+}
+
+#[allow(clippy::should_implement_trait, clippy::inherent_to_string)]
+impl PathRule {
+    /// 解析 `FROM=>TO` 或 `regex:PATTERN=>REPLACEMENT` 格式的规则
+    pub fn from_str(s: &str) -> Result<Self> {
+        let (left, right) = s.split_once(RULE_SEPARATOR)
+            .ok_or_else(|| anyhow!("Invalid path rewrite rule '{}'. Expected FROM=>TO or regex:PATTERN=>REPLACEMENT", s))?;
+        let rule = match left.strip_prefix(REGEX_PREFIX) {
+            Some(pattern) => PathRule::Regex {
+                pattern: pattern.to_string(),
+                replacement: right.to_string(),
+            },
+            None => PathRule::Prefix {
+                from: left.to_string(),
+                to: right.to_string(),
+            },
+        };
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    pub fn to_string(&self) -> String {
+        match self {
+            PathRule::Prefix { from, to } => format!("{}{}{}", from, RULE_SEPARATOR, to),
+            PathRule::Regex { pattern, replacement } => {
+                format!("{}{}{}{}", REGEX_PREFIX, pattern, RULE_SEPARATOR, replacement)
+            }
+        }
+    }
+
+    /// 检查规则是否有效（前缀非空、正则可编译）
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            PathRule::Prefix { from, .. } if from.is_empty() => {
+                Err(anyhow!("Path rewrite prefix must not be empty"))
+            }
+            PathRule::Prefix { .. } => Ok(()),
+            PathRule::Regex { pattern, .. } => Regex::new(pattern)
+                .map(|_| ())
+                .map_err(|e| anyhow!("Invalid path rewrite pattern '{}': {}", pattern, e)),
+        }
+    }
+}
+
+/// 编译后的一组路径重写规则
+#[derive(Debug, Clone, Default)]
+pub struct PathRewrite {
+    rules: Vec<CompiledRule>,
+}
+
+#[derive(Debug, Clone)]
+enum CompiledRule {
+    Prefix { from: Vec<String>, to: String },
+    Regex { regex: Regex, replacement: String },
+}
+
+impl PathRewrite {
+    pub fn new(rules: &[PathRule]) -> Result<Self> {
+        let rules = rules.iter()
+            .map(|rule| {
+                rule.validate()?;
+                Ok(match rule {
+                    PathRule::Prefix { from, to } => CompiledRule::Prefix {
+                        from: split_components(from).map(str::to_string).collect(),
+                        to: to.clone(),
+                    },
+                    PathRule::Regex { pattern, replacement } => CompiledRule::Regex {
+                        regex: Regex::new(pattern)?,
+                        replacement: replacement.clone(),
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 返回原始路径对应的输出路径
+    pub fn apply(&self, path: &Path) -> PathBuf {
+        let original = path.to_string_lossy();
+        for rule in &self.rules {
+            match rule {
+                CompiledRule::Prefix { from, to } => {
+                    if let Some(rest) = strip_prefix(&original, from) {
+                        return PathBuf::from(join(to, &rest));
+                    }
+                }
+                CompiledRule::Regex { regex, replacement } => {
+                    if regex.is_match(&original) {
+                        return PathBuf::from(regex.replace(&original, replacement.as_str()).into_owned());
+                    }
+                }
+            }
+        }
+        path.to_path_buf()
+    }
+}
+
+/// 按分隔符拆分路径组件，忽略结尾的分隔符（根目录 `/` 拆分为一个空组件）
+fn split_components(path: &str) -> impl Iterator<Item = &str> {
+    path.trim_end_matches(['/', '\\']).split(['/', '\\'])
+}
+
+/// 路径以 `prefix` 的组件开头时返回剩余组件
+fn strip_prefix<'a>(path: &'a str, prefix: &[String]) -> Option<Vec<&'a str>> {
+    let mut components = path.split(['/', '\\']);
+    for expected in prefix {
+        if components.next()? != expected {
+            return None;
+        }
+    }
+    Some(components.filter(|component| !component.is_empty()).collect())
+}
+
+/// 用 `to` 中的分隔符拼接剩余组件
+fn join(to: &str, rest: &[&str]) -> String {
+    if rest.is_empty() {
+        return to.to_string();
+    }
+    let separator = match (to.contains('\\'), to.contains('/')) {
+        (true, false) => "\\",
+        (false, true) => "/",
+        _ => std::path::MAIN_SEPARATOR_STR,
+    };
+    format!("{}{}{}", to.trim_end_matches(['/', '\\']), separator, rest.join(separator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(rules: &[&str], path: &str) -> PathBuf {
+        let rules: Vec<PathRule> = rules.iter().map(|r| PathRule::from_str(r).unwrap()).collect();
+        PathRewrite::new(&rules).unwrap().apply(Path::new(path))
+    }
+
+    #[test]
+    fn test_prefix_rules_match_whole_components() {
+        let rules = [r"/home/alice=>C:\Users\bob"];
+        assert_eq!(rewrite(&rules, "/home/alice/docs/a.txt"), PathBuf::from(r"C:\Users\bob\docs\a.txt"));
+        assert_eq!(rewrite(&rules, "/home/alice"), PathBuf::from(r"C:\Users\bob"));
+        assert_eq!(rewrite(&rules, "/home/alicex/a.txt"), PathBuf::from("/home/alicex/a.txt"));
+
+        // 分隔符不区分方向，结尾分隔符被忽略
+        let rules = [r"C:\data\=>/mnt/data"];
+        assert_eq!(rewrite(&rules, "C:/data/x/y.bin"), PathBuf::from("/mnt/data/x/y.bin"));
+        assert_eq!(rewrite(&["/=>/restore"], "/etc/hosts"), PathBuf::from("/restore/etc/hosts"));
+    }
+
+    #[test]
+    fn test_regex_rules_and_order() {
+        let rules = [r"regex:^/home/([^/]+)/=>/backup/$1/", "/home=>/other"];
+        assert_eq!(rewrite(&rules, "/home/carol/notes.md"), PathBuf::from("/backup/carol/notes.md"));
+        // 第一条规则不匹配时尝试下一条
+        assert_eq!(rewrite(&rules, "/home"), PathBuf::from("/other"));
+        assert_eq!(rewrite(&rules, "/srv/x"), PathBuf::from("/srv/x"));
+    }
+
+    #[test]
+    fn test_rule_parsing() {
+        let rule = PathRule::from_str("regex:^a(.*)=>b$1").unwrap();
+        assert_eq!(PathRule::from_str(&rule.to_string()).unwrap(), rule);
+        assert!(PathRule::from_str("no separator").is_err());
+        assert!(PathRule::from_str("regex:(=>x").is_err());
+        assert!(PathRule::from_str("=>x").is_err());
+    }
+}
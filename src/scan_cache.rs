@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fsutil;
+use crate::paths::{decode_path, encode_path};
+
+/// 扫描缓存文件名
+pub const SCAN_CACHE_FILE: &str = "scan_cache.json";
+
+/// 上次增量存储时记录的源文件状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanRecord {
+    pub size: u64,
+    /// 修改时间（纳秒）
+    pub mtime: i64,
+    /// 源文件内容哈希
+    pub hash: String,
+    /// 存储该内容的条目ID，条目被替换或删除后缓存记录失效
+    pub entry_id: String,
+}
+
+/// 增量存储使用的扫描缓存
+///
+/// 大小和修改时间都与记录一致的文件视为未变化，不需要重新读取和计算哈希
+#[derive(Debug, Default)]
+pub struct ScanCache {
+    records: HashMap<PathBuf, ScanRecord>,
+    dirty: bool,
+}
+
+impl ScanCache {
+    /// 从存储目录加载缓存，文件不存在或损坏时返回空缓存
+    pub fn load(storage_path: &Path) -> Self {
+        let raw: HashMap<String, ScanRecord> = fs::read(storage_path.join(SCAN_CACHE_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        let records = raw.into_iter()
+            .filter_map(|(path, record)| Some((decode_path(&path).ok()?, record)))
+            .collect();
+        Self { records, dirty: false }
+    }
+
+    /// 有修改时保存到存储目录
+    pub fn save(&mut self, storage_path: &Path) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let raw: HashMap<String, &ScanRecord> = self.records.iter()
+            .map(|(path, record)| (encode_path(path), record))
+            .collect();
+        let content = serde_json::to_vec(&raw)
+            .context("Failed to serialize scan cache")?;
+        fsutil::atomic_write(&storage_path.join(SCAN_CACHE_FILE), &content, None, false)
+            .context("Failed to write scan cache")?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&ScanRecord> {
+        self.records.get(path)
+    }
+
+    pub fn insert(&mut self, path: PathBuf, record: ScanRecord) {
+        if self.records.get(&path) != Some(&record) {
+            self.records.insert(path, record);
+            self.dirty = true;
+        }
+    }
+
+    /// 删除目录下已不存在于 `seen` 中的记录
+    pub fn retain_under(&mut self, dir: &Path, seen: &std::collections::HashSet<PathBuf>) {
+        let before = self.records.len();
+        self.records.retain(|path, _| !path.starts_with(dir) || seen.contains(path));
+        self.dirty |= self.records.len() != before;
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
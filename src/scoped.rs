@@ -0,0 +1,403 @@
+//! 按角色限制可用操作的存储管理器包装
+//!
+//! [`ScopedManager`] 的类型参数决定能调用哪些操作，越权调用在编译期就会报错：
+//!
+//! - [`ReadOnly`]：查询、读取、导出和校验，不修改存储
+//! - [`StoreOnly`]：在只读操作之外可以存储新内容和修改元数据（备注、集合、固定），不会移除已存储的内容
+//! - [`Admin`]：全部操作，包括删除、提取、重命名、维护，以及会替换已有条目的增量存储和 URL 存储
+//!
+//! 嵌入方可以只把受限的句柄交给上传、浏览等代码路径，避免误删数据。角色只能收窄，不能放宽：
+//!
+//! ```compile_fail
+//! use stowr_core::{Config, DeleteMode, ScopedManager, StorageManager, create_index};
+//! use stowr_core::scoped::ReadOnly;
+//! use std::path::Path;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let config = Config::default();
+//! let manager = StorageManager::new(config.clone(), create_index(&config)?);
+//! let mut viewer = ScopedManager::<ReadOnly>::new(manager);
+//! viewer.delete_file(Path::new("report.pdf"), DeleteMode::Refuse)?; // 只读句柄没有删除操作
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! 增量存储会用变化后的内容替换已有条目，同样只有管理员句柄可用：
+//!
+//! ```compile_fail
+//! use stowr_core::{Config, ScopedManager, StorageManager, create_index};
+//! use stowr_core::scoped::StoreOnly;
+//! use std::path::Path;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let config = Config::default();
+//! let manager = StorageManager::new(config.clone(), create_index(&config)?);
+//! let mut uploader = ScopedManager::<StoreOnly>::new(manager);
+//! uploader.store_directory_incremental(Path::new("reports"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use crate::backup::{BackupReport, BackupSince};
+use crate::events::StowrEvent;
+use crate::filter::BatchReport;
+use crate::index::{EntryOrder, FileEntry, IndexSummary, TreeListing};
+use crate::storage::{DeleteMode, GcReport, MaintenanceReport, OnConflict, ScrubReport, StorageManager, StoreOutcome, Transaction};
+
+mod sealed {
+    pub trait Sealed {
+        /// 是否可以写入存储库，只读句柄不记录读取时间
+        const WRITABLE: bool = true;
+    }
+}
+
+/// 角色标记，只能使用本模块定义的三种角色
+pub trait Role: sealed::Sealed {}
+
+/// 可以存储新内容和修改元数据的角色
+pub trait CanStore: Role {}
+
+/// 只读角色
+#[derive(Debug, Clone, Copy)]
+pub struct ReadOnly;
+/// 只能新增内容的角色
+#[derive(Debug, Clone, Copy)]
+pub struct StoreOnly;
+/// 拥有全部操作的角色
+#[derive(Debug, Clone, Copy)]
+pub struct Admin;
+
+impl sealed::Sealed for ReadOnly {
+    const WRITABLE: bool = false;
+}
+impl sealed::Sealed for StoreOnly {}
+impl sealed::Sealed for Admin {}
+impl Role for ReadOnly {}
+impl Role for StoreOnly {}
+impl Role for Admin {}
+impl CanStore for StoreOnly {}
+impl CanStore for Admin {}
+
+/// 只暴露角色 `R` 允许的操作的存储管理器，各操作的语义与 [`StorageManager`] 相同
+pub struct ScopedManager<R: Role> {
+    manager: StorageManager,
+    _role: PhantomData<R>,
+}
+
+impl<R: Role> ScopedManager<R> {
+    pub fn new(mut manager: StorageManager) -> Self {
+        if !R::WRITABLE {
+            manager.set_read_only();
+        }
+        Self { manager, _role: PhantomData }
+    }
+
+    /// 收窄为只读句柄
+    pub fn into_read_only(self) -> ScopedManager<ReadOnly> {
+        ScopedManager::new(self.manager)
+    }
+
+    pub fn get_file(&self, file_path: &Path) -> Result<Option<FileEntry>> {
+        self.manager.get_file(file_path)
+    }
+
+    pub fn get_entry_by_id(&self, id: &str) -> Result<Option<FileEntry>> {
+        self.manager.get_entry_by_id(id)
+    }
+
+    pub fn list_files(&self) -> Result<Vec<FileEntry>> {
+        self.manager.list_files()
+    }
+
+    pub fn list_files_ordered(&self, order: EntryOrder) -> Result<Vec<FileEntry>> {
+        self.manager.list_files_ordered(order)
+    }
+
+    pub fn search_files(&self, pattern: &str) -> Result<Vec<FileEntry>> {
+        self.manager.search_files(pattern)
+    }
+
+    pub fn grep(&self, pattern: &str, glob_filter: Option<&str>, options: &crate::grep::GrepOptions) -> Result<Vec<crate::grep::GrepMatch>> {
+        self.manager.grep(pattern, glob_filter, options)
+    }
+
+    pub fn search_content(&self, query: &str, limit: usize) -> Result<Vec<crate::content_index::ContentHit>> {
+        self.manager.search_content(query, limit)
+    }
+
+    pub fn search_descriptions(&self, query: &str) -> Result<Vec<FileEntry>> {
+        self.manager.search_descriptions(query)
+    }
+
+    pub fn tree(&self, prefix: &Path) -> Result<TreeListing> {
+        self.manager.tree(prefix)
+    }
+
+    pub fn summary(&self) -> Result<IndexSummary> {
+        self.manager.summary()
+    }
+
+    pub fn root_hash(&self) -> Result<String> {
+        self.manager.root_hash()
+    }
+
+    pub fn merkle_proof(&self, file_path: &Path) -> Result<crate::merkle::MerkleProof> {
+        self.manager.merkle_proof(file_path)
+    }
+
+    pub fn verify_signatures_with(&self, public_key: &str) -> Result<crate::signing::SignatureReport> {
+        self.manager.verify_signatures_with(public_key)
+    }
+
+    pub fn collections(&self) -> Result<BTreeMap<String, IndexSummary>> {
+        self.manager.collections()
+    }
+
+    pub fn collection_entries(&self, collection: &str) -> Result<Vec<FileEntry>> {
+        self.manager.collection_entries(collection)
+    }
+
+    pub fn expiring_within(&self, within: std::time::Duration) -> Result<Vec<FileEntry>> {
+        self.manager.expiring_within(within)
+    }
+
+    pub fn read_file(&self, file_path: &Path) -> Result<Vec<u8>> {
+        self.manager.read_file(file_path)
+    }
+
+    pub fn resolve_share<W: std::io::Write>(&self, token: &str, writer: W) -> Result<FileEntry> {
+        self.manager.resolve_share(token, writer)
+    }
+
+    pub fn export_entry(&self, file_path: &Path, dest: &Path) -> Result<()> {
+        self.manager.export_entry(file_path, dest)
+    }
+
+    pub fn export_collection(&self, collection: &str, dest_dir: &Path) -> Result<BatchReport> {
+        self.manager.export_collection(collection, dest_dir)
+    }
+
+    pub fn export_bundle(&self, file_paths: &[PathBuf], dest: &Path) -> Result<BatchReport> {
+        self.manager.export_bundle(file_paths, dest)
+    }
+
+    pub fn backup_since(&self, since: &BackupSince, dest: &Path) -> Result<BackupReport> {
+        self.manager.backup_since(since, dest)
+    }
+
+    pub fn scrub(&self, resume_token: Option<&str>) -> Result<ScrubReport> {
+        self.manager.scrub(resume_token)
+    }
+
+    pub fn subscribe(&self) -> std::sync::mpsc::Receiver<StowrEvent> {
+        self.manager.subscribe()
+    }
+}
+
+impl<R: CanStore> ScopedManager<R> {
+    /// 收窄为只能新增内容的句柄
+    pub fn into_store_only(self) -> ScopedManager<StoreOnly> {
+        ScopedManager::new(self.manager)
+    }
+
+    pub fn store_file(&mut self, file_path: &Path, delete_source: bool) -> Result<StoreOutcome> {
+        self.manager.store_file(file_path, delete_source)
+    }
+
+    pub fn store_bytes(&mut self, file_path: &Path, content: Vec<u8>) -> Result<StoreOutcome> {
+        self.manager.store_bytes(file_path, content)
+    }
+
+    pub fn import_entry(&mut self, src: &Path) -> Result<PathBuf> {
+        self.manager.import_entry(src)
+    }
+
+    pub fn import_bundle(&mut self, src: &Path) -> Result<BatchReport> {
+        self.manager.import_bundle(src)
+    }
+
+    pub fn set_description(&mut self, file_path: &Path, text: &str) -> Result<()> {
+        self.manager.set_description(file_path, text)
+    }
+
+    pub fn set_collection(&mut self, file_path: &Path, collection: Option<&str>) -> Result<()> {
+        self.manager.set_collection(file_path, collection)
+    }
+
+    pub fn rebuild_content_index(&self) -> Result<usize> {
+        self.manager.rebuild_content_index()
+    }
+
+    pub fn sign_index(&self) -> Result<crate::signing::IndexSignature> {
+        self.manager.sign_index()
+    }
+
+    pub fn pin(&mut self, file_path: &Path) -> Result<()> {
+        self.manager.pin(file_path)
+    }
+
+    pub fn create_share(&self, file_path: &Path, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Result<String> {
+        self.manager.create_share(file_path, expires_at)
+    }
+}
+
+impl ScopedManager<Admin> {
+    /// 内容变化的文件会替换原有条目，因此只对管理员开放
+    pub fn store_directory_incremental(&mut self, dir: &Path) -> Result<BatchReport> {
+        self.manager.store_directory_incremental(dir)
+    }
+
+    pub fn store_directory_into(&mut self, dir: &Path, collection: &str) -> Result<BatchReport> {
+        self.manager.store_directory_into(dir, collection)
+    }
+
+    /// `options.replace` 会替换已有条目，因此只对管理员开放
+    #[cfg(feature = "http")]
+    pub fn store_from_url(&mut self, url: &str, logical_path: &Path, options: &crate::fetch::FetchOptions) -> Result<StoreOutcome> {
+        self.manager.store_from_url(url, logical_path, options)
+    }
+
+    pub fn owe_file(&mut self, file_path: &Path) -> Result<()> {
+        self.manager.owe_file(file_path)
+    }
+
+    pub fn owe_collection(&mut self, collection: &str) -> Result<BatchReport> {
+        self.manager.owe_collection(collection)
+    }
+
+    pub fn extract_by_id(&mut self, id: &str, dest: &Path) -> Result<()> {
+        self.manager.extract_by_id(id, dest)
+    }
+
+    pub fn delete_file(&mut self, file_path: &Path, mode: DeleteMode) -> Result<()> {
+        self.manager.delete_file(file_path, mode)
+    }
+
+    pub fn remove_by_id(&mut self, id: &str, mode: DeleteMode) -> Result<()> {
+        self.manager.remove_by_id(id, mode)
+    }
+
+    pub fn delete_matching<F>(&mut self, pattern: &str, confirm: F) -> Result<BatchReport>
+    where
+        F: Fn(&[FileEntry]) -> bool,
+    {
+        self.manager.delete_matching(pattern, confirm)
+    }
+
+    pub fn delete_collection<F>(&mut self, collection: &str, confirm: F) -> Result<BatchReport>
+    where
+        F: Fn(&[FileEntry]) -> bool,
+    {
+        self.manager.delete_collection(collection, confirm)
+    }
+
+    pub fn rename_file(&mut self, old_path: &Path, new_path: &Path, on_conflict: OnConflict) -> Result<()> {
+        self.manager.rename_file(old_path, new_path, on_conflict)
+    }
+
+    pub fn move_file(&mut self, file_path: &Path, new_location: &Path, on_conflict: OnConflict) -> Result<()> {
+        self.manager.move_file(file_path, new_location, on_conflict)
+    }
+
+    pub fn move_and_extract(&mut self, file_path: &Path, new_dir: &Path) -> Result<PathBuf> {
+        self.manager.move_and_extract(file_path, new_dir)
+    }
+
+    pub fn unpin(&mut self, file_path: &Path) -> Result<()> {
+        self.manager.unpin(file_path)
+    }
+
+    pub fn set_expiry(&mut self, file_path: &Path, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Result<()> {
+        self.manager.set_expiry(file_path, expires_at)
+    }
+
+    pub fn set_collection_expiry(&mut self, collection: &str, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Result<usize> {
+        self.manager.set_collection_expiry(collection, expires_at)
+    }
+
+    pub fn revoke_all_shares(&self) -> Result<()> {
+        self.manager.revoke_all_shares()
+    }
+
+    pub fn run_maintenance(&mut self) -> Result<MaintenanceReport> {
+        self.manager.run_maintenance()
+    }
+
+    pub fn gc(&mut self) -> Result<GcReport> {
+        self.manager.gc()
+    }
+
+    pub fn apply_backup(&mut self, src: &Path) -> Result<BackupReport> {
+        self.manager.apply_backup(src)
+    }
+
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<T>,
+    {
+        self.manager.transaction(f)
+    }
+
+    /// 其余未包装的操作
+    pub fn manager_mut(&mut self) -> &mut StorageManager {
+        &mut self.manager
+    }
+
+    /// 取回内部的存储管理器
+    pub fn into_inner(self) -> StorageManager {
+        self.manager
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, create_index};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scoped_roles_narrow_operations() {
+        let dir = TempDir::new().unwrap();
+        let config = Config { storage_path: dir.path().join("storage"), ..Config::default() };
+        let manager = StorageManager::new(config.clone(), create_index(&config).unwrap());
+        let file = dir.path().join("upload.txt");
+        fs::write(&file, "uploaded").unwrap();
+
+        let mut admin = ScopedManager::<Admin>::new(manager);
+        admin.store_file(&file, true).unwrap();
+        admin.set_description(&file, "first upload").unwrap();
+
+        let mut uploader = admin.into_store_only();
+        uploader.store_bytes(&dir.path().join("note.txt"), b"note".to_vec()).unwrap();
+        assert_eq!(uploader.list_files().unwrap().len(), 2);
+
+        let viewer = uploader.into_read_only();
+        assert_eq!(viewer.read_file(&file).unwrap(), b"uploaded");
+        assert_eq!(viewer.search_descriptions("upload").unwrap().len(), 1);
+        assert!(viewer.scrub(None).unwrap().corrupted.is_empty());
+    }
+
+    #[test]
+    fn test_store_only_cannot_replace_stored_versions() {
+        let dir = TempDir::new().unwrap();
+        let config = Config { storage_path: dir.path().join("storage"), ..Config::default() };
+        let manager = StorageManager::new(config.clone(), create_index(&config).unwrap());
+        let file = dir.path().join("report.txt");
+        fs::write(&file, "version 1").unwrap();
+
+        let mut uploader = ScopedManager::<StoreOnly>::new(manager);
+        uploader.store_file(&file, false).unwrap();
+        fs::write(&file, "version 2").unwrap();
+        assert!(matches!(uploader.store_file(&file, false).unwrap(), StoreOutcome::AlreadyStored(_)));
+        assert!(uploader.store_bytes(&file, b"version 3".to_vec()).is_err());
+
+        assert_eq!(uploader.read_file(&file).unwrap(), b"version 1");
+        assert_eq!(uploader.list_files().unwrap().len(), 1);
+    }
+}
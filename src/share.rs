@@ -0,0 +1,141 @@
+//! 共享令牌
+//!
+//! 令牌由条目ID、内容哈希和过期时间组成，用仓库的共享密钥（存储目录下的 `share.key`）
+//! 做 HMAC-SHA256 签名，格式为 `<声明的十六进制>.<签名的十六进制>`，可以直接放在下载链接中。
+//! 令牌不需要在服务端保存；轮换共享密钥会使之前签发的所有令牌失效。
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs;
+use std::path::Path;
+
+use crate::crypto;
+use crate::error::StowrError;
+use crate::fsutil;
+
+/// 共享密钥文件名（位于存储目录下）
+pub const SHARE_KEY_FILE: &str = "share.key";
+const SHARE_KEY_LEN: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 令牌中签名的声明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareClaims {
+    /// 共享的条目ID，条目重命名或移动后令牌仍然有效
+    pub entry_id: String,
+    /// 签发时的内容哈希，条目内容改变后令牌失效
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// 过期时间（Unix 秒），None 表示永不过期
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+impl ShareClaims {
+    /// 声明在当前时间是否已过期
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now().timestamp())
+    }
+}
+
+/// 仓库的共享密钥
+pub(crate) struct ShareKey([u8; SHARE_KEY_LEN]);
+
+impl ShareKey {
+    /// 读取存储目录下的共享密钥，不存在时生成并保存
+    pub fn load_or_create(storage_path: &Path) -> Result<Self> {
+        match fs::read_to_string(storage_path.join(SHARE_KEY_FILE)) {
+            Ok(hex) => {
+                let bytes = crypto::from_hex(hex.trim())?;
+                let key = bytes.try_into()
+                    .map_err(|_| anyhow::anyhow!("Invalid share key file: {}", SHARE_KEY_FILE))?;
+                Ok(Self(key))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::rotate(storage_path),
+            Err(e) => Err(e).context("Failed to read share key"),
+        }
+    }
+
+    /// 生成新的共享密钥并替换原有密钥
+    pub fn rotate(storage_path: &Path) -> Result<Self> {
+        let mut key = [0u8; SHARE_KEY_LEN];
+        key.copy_from_slice(&crypto::random_bytes(SHARE_KEY_LEN));
+        fs::create_dir_all(storage_path)
+            .context("Failed to create storage directory")?;
+        fsutil::atomic_write(&storage_path.join(SHARE_KEY_FILE), crypto::to_hex(&key).as_bytes(), None, true)
+            .context("Failed to write share key")?;
+        Ok(Self(key))
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+
+    /// 签发令牌
+    pub fn sign(&self, claims: &ShareClaims) -> Result<String> {
+        let payload = serde_json::to_vec(claims)
+            .context("Failed to serialize share claims")?;
+        let signature = self.mac(&payload).finalize().into_bytes();
+        Ok(format!("{}.{}", crypto::to_hex(&payload), crypto::to_hex(&signature)))
+    }
+
+    /// 校验令牌签名并返回其中的声明，不检查是否过期
+    ///
+    /// 格式错误或签名不符时返回 `StowrError::InvalidShareToken`
+    pub fn verify(&self, token: &str) -> Result<ShareClaims> {
+        let (payload, signature) = token.trim().split_once('.')
+            .ok_or(StowrError::InvalidShareToken)?;
+        // 令牌来自外部，解码前确认两部分都是非空的 ASCII 十六进制
+        let is_hex = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_hexdigit());
+        if !is_hex(payload) || !is_hex(signature) {
+            return Err(StowrError::InvalidShareToken.into());
+        }
+        let payload = crypto::from_hex(payload).map_err(|_| StowrError::InvalidShareToken)?;
+        let signature = crypto::from_hex(signature).map_err(|_| StowrError::InvalidShareToken)?;
+        self.mac(&payload).verify_slice(&signature)
+            .map_err(|_| StowrError::InvalidShareToken)?;
+        Ok(serde_json::from_slice(&payload).map_err(|_| StowrError::InvalidShareToken)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_share_tokens_are_signed_and_rotatable() {
+        let dir = TempDir::new().unwrap();
+        let key = ShareKey::load_or_create(dir.path()).unwrap();
+        let claims = ShareClaims { entry_id: "id-1".to_string(), hash: Some("abc".to_string()), expires_at: None };
+        let token = key.sign(&claims).unwrap();
+        assert_eq!(key.verify(&token).unwrap(), claims);
+        assert_eq!(ShareKey::load_or_create(dir.path()).unwrap().verify(&token).unwrap(), claims);
+
+        // 篡改声明或签名都无法通过校验
+        let (payload, signature) = token.split_once('.').unwrap();
+        let forged = ShareClaims { entry_id: "id-2".to_string(), ..claims.clone() };
+        let forged_payload = crypto::to_hex(&serde_json::to_vec(&forged).unwrap());
+        for bad in [format!("{}.{}", forged_payload, signature), format!("{}.00", payload), "garbage".to_string()] {
+            let err = key.verify(&bad).unwrap_err();
+            assert_eq!(err.downcast_ref::<StowrError>(), Some(&StowrError::InvalidShareToken));
+        }
+
+        // 非 ASCII 或带符号的令牌直接拒绝，不会在解码时 panic
+        for bad in ["a\u{e9}b.00", "00.\u{e9}\u{e9}", "+f.00", format!("{}.+{}", payload, &signature[1..]).as_str(), ".", "00."] {
+            let err = key.verify(bad).unwrap_err();
+            assert_eq!(err.downcast_ref::<StowrError>(), Some(&StowrError::InvalidShareToken));
+        }
+
+        assert!(ShareClaims { expires_at: Some(0), ..claims.clone() }.is_expired());
+        assert!(!claims.is_expired());
+
+        let rotated = ShareKey::rotate(dir.path()).unwrap();
+        assert!(rotated.verify(&token).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! 大文件的分块签名
+//!
+//! 对大于 `Config::delta_streaming_threshold` 的基础文件，在存储文件旁写入 `.sig` 文件，
+//! 记录每个固定大小分块的 SHA256。存储新的大文件时按块流式计算签名，与已记录的签名比较来选择差分基础文件，
+//! 再按签名生成差分，不需要把候选文件解压到内存。
+//!
+//! 差分格式按位置对齐（见 `DeltaStorage::create_delta`），因此只比较相同位置的分块。
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::crypto;
+
+/// 签名文件扩展名，追加在存储文件名之后
+pub const SIGNATURE_EXTENSION: &str = "sig";
+/// 默认分块大小
+pub const BLOCK_SIZE: usize = 64 * 1024;
+
+const MAGIC: &[u8; 8] = b"STOWRSIG";
+const FORMAT_VERSION: u8 = 1;
+const HASH_LEN: usize = 32;
+/// magic + 版本 + 分块大小 + 内容长度 + 内容哈希
+const HEADER_LEN: usize = 8 + 1 + 4 + 8 + HASH_LEN;
+
+/// 存储文件对应的签名文件路径
+pub fn signature_path(stored_path: &Path) -> PathBuf {
+    let mut name = stored_path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// 内容的分块签名
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSignature {
+    block_size: usize,
+    total_len: u64,
+    content_hash: [u8; HASH_LEN],
+    blocks: Vec<[u8; HASH_LEN]>,
+}
+
+impl BlockSignature {
+    /// 按块读取并计算签名，内存占用与分块大小相关，与内容大小无关
+    pub fn from_reader(mut reader: impl Read, block_size: usize) -> io::Result<Self> {
+        let mut buffer = vec![0u8; block_size];
+        let mut builder = SignatureBuilder::new(block_size);
+        loop {
+            let len = read_block(&mut reader, &mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            builder.add_block(&buffer[..len]);
+        }
+        Ok(builder.finish())
+    }
+
+    pub fn from_bytes(data: &[u8], block_size: usize) -> Self {
+        Self::from_reader(data, block_size).expect("reading from a slice cannot fail")
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// 内容总长度
+    pub fn total_len(&self) -> u64 {
+        self.total_len
+    }
+
+    /// 完整内容的 SHA256，与 `ContentDeduplicator::calculate_hash` 一致
+    pub fn content_hash(&self) -> String {
+        crypto::to_hex(&self.content_hash)
+    }
+
+    /// 第 `index` 个分块的哈希
+    pub fn block(&self, index: usize) -> Option<&[u8; HASH_LEN]> {
+        self.blocks.get(index)
+    }
+
+    /// 相同位置上内容相同的分块所占比例（0.0-1.0），分块大小不同时为 0
+    pub fn similarity(&self, other: &BlockSignature) -> f32 {
+        if self.block_size != other.block_size {
+            return 0.0;
+        }
+        let total = self.blocks.len().max(other.blocks.len());
+        if total == 0 {
+            return 1.0;
+        }
+        let matching = self.blocks.iter().zip(&other.blocks).filter(|(a, b)| a == b).count();
+        matching as f32 / total as f32
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.blocks.len() * HASH_LEN);
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&(self.block_size as u32).to_le_bytes());
+        out.extend_from_slice(&self.total_len.to_le_bytes());
+        out.extend_from_slice(&self.content_hash);
+        for block in &self.blocks {
+            out.extend_from_slice(block);
+        }
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        if data.len() < HEADER_LEN || &data[..8] != MAGIC {
+            return Err(anyhow!("Not a stowr signature file"));
+        }
+        if data[8] != FORMAT_VERSION {
+            return Err(anyhow!("Unsupported signature file version: {}", data[8]));
+        }
+        let block_size = u32::from_le_bytes(data[9..13].try_into().expect("slice has 4 bytes")) as usize;
+        let total_len = u64::from_le_bytes(data[13..21].try_into().expect("slice has 8 bytes"));
+        let content_hash = data[21..HEADER_LEN].try_into().expect("slice has 32 bytes");
+
+        let body = &data[HEADER_LEN..];
+        let expected_blocks = if block_size == 0 { None } else { Some(total_len.div_ceil(block_size as u64)) };
+        if expected_blocks != Some((body.len() / HASH_LEN) as u64) || !body.len().is_multiple_of(HASH_LEN) {
+            return Err(anyhow!("Signature file is corrupted"));
+        }
+        Ok(Self {
+            block_size,
+            total_len,
+            content_hash,
+            blocks: body.chunks_exact(HASH_LEN)
+                .map(|chunk| chunk.try_into().expect("chunk has 32 bytes"))
+                .collect(),
+        })
+    }
+}
+
+/// 逐块累积签名，供同时需要读取分块内容的调用方使用
+pub(crate) struct SignatureBuilder {
+    signature: BlockSignature,
+    hasher: Sha256,
+}
+
+impl SignatureBuilder {
+    pub(crate) fn new(block_size: usize) -> Self {
+        Self {
+            signature: BlockSignature {
+                block_size,
+                total_len: 0,
+                content_hash: [0; HASH_LEN],
+                blocks: Vec::new(),
+            },
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// 追加一个分块，除最后一块外长度都应等于分块大小；返回该块的哈希
+    pub(crate) fn add_block(&mut self, block: &[u8]) -> [u8; HASH_LEN] {
+        self.hasher.update(block);
+        let hash: [u8; HASH_LEN] = Sha256::digest(block).into();
+        self.signature.blocks.push(hash);
+        self.signature.total_len += block.len() as u64;
+        hash
+    }
+
+    pub(crate) fn finish(mut self) -> BlockSignature {
+        self.signature.content_hash = self.hasher.finalize().into();
+        self.signature
+    }
+}
+
+/// 读满一个分块，只有到达末尾时才返回较短的长度
+pub(crate) fn read_block(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::ContentDeduplicator;
+
+    #[test]
+    fn test_signature_similarity_and_encoding() {
+        let base: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut target = base.clone();
+        target[5_000] ^= 0xff;
+
+        let base_sig = BlockSignature::from_bytes(&base, 1000);
+        let target_sig = BlockSignature::from_reader(&target[..], 1000).unwrap();
+        assert_eq!(base_sig.total_len(), 10_000);
+        assert_eq!(base_sig.content_hash(), ContentDeduplicator::calculate_hash(&base));
+        assert!((base_sig.similarity(&target_sig) - 0.9).abs() < 1e-6);
+        assert_eq!(base_sig.similarity(&BlockSignature::from_bytes(&base, 512)), 0.0);
+
+        let decoded = BlockSignature::decode(&base_sig.encode()).unwrap();
+        assert_eq!(decoded, base_sig);
+        let encoded = base_sig.encode();
+        assert!(BlockSignature::decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(BlockSignature::decode(b"garbage").is_err());
+    }
+}
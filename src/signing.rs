@@ -0,0 +1,251 @@
+//! 索引状态签名
+//!
+//! 配置 `security.signing_key`（保存 Ed25519 私钥种子十六进制的文件）后，存储管理器用它对索引的
+//! Merkle 根哈希签名，签名追加到存储目录下的 `signatures.log`（每行一条 JSON 记录）。
+//! 每条记录的签名覆盖它在日志中的序号和上一行的 SHA-256，记录前后相连。
+//! 审计方只需要公钥就能检查日志中的每条签名和这条链，并确认最近一次签名的根哈希与当前索引一致：
+//! 不经过存储管理器修改索引、改写、删除、插入或重排日志中的记录都会被发现。
+//! 只截掉日志末尾的记录不会破坏链，这时最近一次签名的根哈希与当前索引不一致，
+//! 除非索引也被回退到了被截掉之前的状态；需要防止回退时应在仓库之外另存最近一次签名的序号。
+
+use anyhow::{Context, Result, anyhow};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::crypto;
+use crate::fsutil;
+
+/// 签名日志文件名（位于存储目录下）
+pub const SIGNATURE_LOG_FILE: &str = "signatures.log";
+
+/// 对某一时刻索引状态的签名
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexSignature {
+    /// 记录在日志中的序号，从 0 开始
+    pub sequence: u64,
+    /// 日志中上一行的 SHA-256 十六进制，第一条记录为 `None`
+    pub previous: Option<String>,
+    /// 签名时索引的 Merkle 根哈希
+    pub root_hash: String,
+    /// 签名时的条目数
+    pub entries: usize,
+    /// RFC 3339 格式的签名时间
+    pub signed_at: String,
+    /// 签名公钥的十六进制
+    pub public_key: String,
+    /// 签名的十六进制
+    pub signature: String,
+}
+
+impl IndexSignature {
+    fn message(&self) -> Vec<u8> {
+        format!(
+            "stowr-index-root:v2\n{}\n{}\n{}\n{}\n{}",
+            self.sequence,
+            self.previous.as_deref().unwrap_or(""),
+            self.root_hash,
+            self.entries,
+            self.signed_at,
+        ).into_bytes()
+    }
+
+    /// 用私钥对根哈希签名，`log` 为签名日志中已有的记录，新记录接在最后一条之后
+    pub(crate) fn sign(key: &SigningKey, root_hash: String, entries: usize, log: &[LogRecord]) -> Self {
+        let mut signature = Self {
+            sequence: log.len() as u64,
+            previous: log.last().map(|record| record.digest.clone()),
+            root_hash,
+            entries,
+            signed_at: chrono::Utc::now().to_rfc3339(),
+            public_key: crypto::to_hex(key.verifying_key().as_bytes()),
+            signature: String::new(),
+        };
+        signature.signature = crypto::to_hex(&key.sign(&signature.message()).to_bytes());
+        signature
+    }
+
+    /// 检查签名是否由 `public_key` 对应的私钥生成，不信任记录中自带的公钥
+    pub fn verify(&self, public_key: &VerifyingKey) -> bool {
+        let Ok(bytes) = crypto::from_hex(&self.signature) else {
+            return false;
+        };
+        let Ok(signature) = Signature::from_slice(&bytes) else {
+            return false;
+        };
+        public_key.verify(&self.message(), &signature).is_ok()
+    }
+}
+
+/// 签名日志中的一行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LogRecord {
+    /// 行号（从 1 开始）
+    pub line: usize,
+    /// 这一行原始内容的 SHA-256 十六进制，下一条记录的 `previous` 应与之相同
+    pub digest: String,
+    /// 解析结果，无法解析时为 `None`
+    pub signature: Option<IndexSignature>,
+}
+
+/// 用公钥检查签名日志，返回签名不符或与前一行接不上的记录行号，以及最近一条有效签名的根哈希
+///
+/// 第 n 条记录（从 0 开始）的序号必须为 n、`previous` 必须为前一行的摘要，
+/// 因此删除或插入一条记录后，之后的记录都不再有效
+pub(crate) fn check_log(log: &[LogRecord], public_key: &VerifyingKey) -> (Vec<usize>, Option<String>) {
+    let mut invalid = Vec::new();
+    let mut latest_root = None;
+    for (position, record) in log.iter().enumerate() {
+        let previous = position.checked_sub(1).map(|index| log[index].digest.as_str());
+        match &record.signature {
+            Some(signature) if signature.sequence == position as u64
+                && signature.previous.as_deref() == previous
+                && signature.verify(public_key) => latest_root = Some(signature.root_hash.clone()),
+            _ => invalid.push(record.line),
+        }
+    }
+    (invalid, latest_root)
+}
+
+/// [`StorageManager::verify_signatures`](crate::StorageManager::verify_signatures) 的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureReport {
+    /// 日志中的记录数
+    pub signatures: usize,
+    /// 无法解析、签名不符或与前一行接不上的记录行号（从 1 开始）
+    pub invalid: Vec<usize>,
+    /// 最近一条有效签名的根哈希
+    pub latest_root: Option<String>,
+    /// 当前索引的根哈希
+    pub current_root: String,
+}
+
+impl SignatureReport {
+    /// 所有签名有效，且当前索引与最近一次签名时一致
+    pub fn is_valid(&self) -> bool {
+        self.invalid.is_empty() && self.latest_root.as_deref() == Some(self.current_root.as_str())
+    }
+}
+
+/// 生成新的签名私钥并写入 `path`，返回公钥的十六进制，供审计方保存
+pub fn generate_signing_key(path: &Path) -> Result<String> {
+    let seed: [u8; 32] = crypto::random_bytes(32).try_into()
+        .map_err(|_| anyhow!("Failed to generate signing key"))?;
+    let key = SigningKey::from_bytes(&seed);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context("Failed to create signing key directory")?;
+    }
+    fsutil::atomic_write(path, crypto::to_hex(&seed).as_bytes(), None, true)
+        .context("Failed to write signing key")?;
+    Ok(crypto::to_hex(key.verifying_key().as_bytes()))
+}
+
+/// 读取签名私钥文件
+pub(crate) fn load_signing_key(path: &Path) -> Result<SigningKey> {
+    let hex = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read signing key: {}", path.display()))?;
+    let seed: [u8; 32] = crypto::from_hex(hex.trim())?.try_into()
+        .map_err(|_| anyhow!("Invalid signing key file: {}", path.display()))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// 解析十六进制公钥
+pub fn parse_public_key(hex: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = crypto::from_hex(hex.trim())?.try_into()
+        .map_err(|_| anyhow!("Invalid public key length"))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| anyhow!("Invalid public key"))
+}
+
+/// 追加一条签名记录
+pub(crate) fn append(storage_path: &Path, signature: &IndexSignature) -> Result<()> {
+    let mut line = serde_json::to_vec(signature)
+        .context("Failed to serialize index signature")?;
+    line.push(b'\n');
+    let path = storage_path.join(SIGNATURE_LOG_FILE);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open signature log: {}", path.display()))?;
+    file.write_all(&line)
+        .with_context(|| format!("Failed to write signature log: {}", path.display()))
+}
+
+/// 读取签名日志中的每个非空行
+pub(crate) fn read_log(storage_path: &Path) -> Result<Vec<LogRecord>> {
+    let path = storage_path.join(SIGNATURE_LOG_FILE);
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read signature log: {}", path.display())),
+    };
+    Ok(data.split(|&b| b == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| LogRecord {
+            line: index + 1,
+            digest: crypto::to_hex(&Sha256::digest(line)),
+            signature: serde_json::from_slice(line).ok(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_index_signatures_detect_tampering() {
+        let dir = TempDir::new().unwrap();
+        let key_path = dir.path().join("keys/index.key");
+        let public_key = parse_public_key(&generate_signing_key(&key_path).unwrap()).unwrap();
+        let key = load_signing_key(&key_path).unwrap();
+
+        let signature = IndexSignature::sign(&key, "abc".to_string(), 3, &[]);
+        assert!(signature.verify(&public_key));
+        append(dir.path(), &signature).unwrap();
+        let log = read_log(dir.path()).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!((log[0].line, log[0].signature.as_ref()), (1, Some(&signature)));
+
+        // 改写根哈希或条目数后签名不再有效，其他私钥的签名也不被接受
+        assert!(!IndexSignature { root_hash: "abd".to_string(), ..signature.clone() }.verify(&public_key));
+        assert!(!IndexSignature { entries: 4, ..signature.clone() }.verify(&public_key));
+        assert!(!IndexSignature { sequence: 1, ..signature.clone() }.verify(&public_key));
+        let other = SigningKey::from_bytes(&[7u8; 32]);
+        assert!(!IndexSignature::sign(&other, "abc".to_string(), 3, &[]).verify(&public_key));
+        assert!(parse_public_key("00").is_err());
+    }
+
+    #[test]
+    fn test_signature_log_is_chained() {
+        let dir = TempDir::new().unwrap();
+        let key = SigningKey::from_bytes(&[3u8; 32]);
+        let public_key = key.verifying_key();
+        for (index, root) in ["r0", "r1", "r2"].into_iter().enumerate() {
+            let log = read_log(dir.path()).unwrap();
+            let signature = IndexSignature::sign(&key, root.to_string(), index, &log);
+            assert_eq!(signature.sequence, index as u64);
+            append(dir.path(), &signature).unwrap();
+        }
+        let log = read_log(dir.path()).unwrap();
+        assert_eq!(check_log(&log, &public_key), (vec![], Some("r2".to_string())));
+
+        // 删除中间的记录后，之后的记录与前一行接不上
+        let path = dir.path().join(SIGNATURE_LOG_FILE);
+        let lines: Vec<String> = fs::read_to_string(&path).unwrap().lines().map(str::to_string).collect();
+        fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        let log = read_log(dir.path()).unwrap();
+        assert_eq!(check_log(&log, &public_key), (vec![2], Some("r0".to_string())));
+
+        // 调换顺序同样会被发现
+        fs::write(&path, format!("{}\n{}\n{}\n", lines[1], lines[0], lines[2])).unwrap();
+        let log = read_log(dir.path()).unwrap();
+        assert_eq!(check_log(&log, &public_key).0, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,113 @@
+//! 存储后留在原位置的占位文件
+//!
+//! 启用 `Config::leave_stubs` 后，存储并删除源文件时在原位置写入 `<文件名>.stowr-stub`，
+//! 内容是可读的 JSON（条目ID、原始路径、大小和哈希），用户浏览目录时可以看到哪些文件已经存储。
+//! 将占位文件路径传给 `StorageManager::owe_file` 即可在占位文件所在位置还原文件。
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::index::FileEntry;
+
+/// 占位文件扩展名，追加在原文件名之后
+pub const STUB_EXTENSION: &str = "stowr-stub";
+/// 占位文件的大小上限，超过时不视为占位文件
+const MAX_STUB_LEN: u64 = 64 * 1024;
+
+/// 占位文件内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stub {
+    /// 存储条目的ID，重命名和移动后保持不变
+    pub entry_id: String,
+    /// 存储时的原始路径
+    #[serde(with = "crate::paths::serde_path")]
+    pub original_path: PathBuf,
+    pub file_size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+impl Stub {
+    pub fn for_entry(entry: &FileEntry) -> Self {
+        Self {
+            entry_id: entry.id.clone(),
+            original_path: entry.original_path.clone(),
+            file_size: entry.file_size,
+            hash: entry.hash.clone(),
+        }
+    }
+
+    /// 写入占位文件
+    pub fn write(&self, stub_path: &Path) -> Result<()> {
+        let mut data = serde_json::to_vec_pretty(self)
+            .context("Failed to serialize stub")?;
+        data.push(b'\n');
+        fs::write(stub_path, data)
+            .with_context(|| format!("Failed to write stub: {}", stub_path.display()))
+    }
+
+    /// 读取占位文件
+    pub fn read(stub_path: &Path) -> Result<Self> {
+        let len = fs::metadata(stub_path)
+            .with_context(|| format!("Failed to read stub: {}", stub_path.display()))?
+            .len();
+        if len > MAX_STUB_LEN {
+            return Err(anyhow!("Not a stowr stub: {}", stub_path.display()));
+        }
+        let data = fs::read(stub_path)
+            .with_context(|| format!("Failed to read stub: {}", stub_path.display()))?;
+        serde_json::from_slice(&data)
+            .with_context(|| format!("Not a stowr stub: {}", stub_path.display()))
+    }
+}
+
+/// 文件对应的占位文件路径
+pub fn stub_path(file_path: &Path) -> PathBuf {
+    let mut name = file_path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(STUB_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// 是否为占位文件路径
+pub fn is_stub_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == STUB_EXTENSION)
+}
+
+/// 占位文件所代替的文件路径（去掉占位文件扩展名）
+pub fn restore_path(stub_path: &Path) -> Option<PathBuf> {
+    is_stub_path(stub_path).then(|| stub_path.with_extension(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stub_paths_and_round_trip() {
+        let path = Path::new("docs/report.v2.pdf");
+        let stub = stub_path(path);
+        assert_eq!(stub, Path::new("docs/report.v2.pdf.stowr-stub"));
+        assert!(is_stub_path(&stub));
+        assert!(!is_stub_path(path));
+        assert_eq!(restore_path(&stub).unwrap(), path);
+        assert_eq!(restore_path(path), None);
+
+        let dir = TempDir::new().unwrap();
+        let stub_file = dir.path().join("a.txt.stowr-stub");
+        let stub = Stub {
+            entry_id: "id-1".to_string(),
+            original_path: PathBuf::from("/data/a.txt"),
+            file_size: 3,
+            hash: Some("abc".to_string()),
+        };
+        stub.write(&stub_file).unwrap();
+        assert_eq!(Stub::read(&stub_file).unwrap(), stub);
+
+        fs::write(&stub_file, b"not json").unwrap();
+        assert!(Stub::read(&stub_file).is_err());
+    }
+}
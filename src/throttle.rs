@@ -0,0 +1,221 @@
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// IO 限速器
+///
+/// 基于令牌桶分别限制每秒字节数和每秒操作数，桶容量为一秒的配额。
+/// 单次请求超过剩余配额时会透支，并按透支量休眠，
+/// 因此大文件也能平滑地按配置速率处理。多个线程可以共享同一个限速器。
+#[derive(Debug)]
+pub struct IoThrottle {
+    bytes_per_sec: u64,
+    ops_per_sec: u64,
+    state: Mutex<ThrottleState>,
+}
+
+#[derive(Debug)]
+struct ThrottleState {
+    bytes: f64,
+    ops: f64,
+    last_refill: Instant,
+}
+
+impl IoThrottle {
+    /// 创建限速器，0 表示对应项不限制
+    pub fn new(bytes_per_sec: u64, ops_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            ops_per_sec,
+            state: Mutex::new(ThrottleState {
+                bytes: bytes_per_sec as f64,
+                ops: ops_per_sec as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// 不限速
+    pub fn unlimited() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// 根据配置创建限速器
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.throttle_bytes_per_sec, config.throttle_ops_per_sec)
+    }
+
+    /// 是否不限速
+    pub fn is_unlimited(&self) -> bool {
+        self.bytes_per_sec == 0 && self.ops_per_sec == 0
+    }
+
+    /// 每秒字节数上限
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// 每秒操作数上限
+    pub fn ops_per_sec(&self) -> u64 {
+        self.ops_per_sec
+    }
+
+    /// 申请一次处理 `bytes` 字节的操作，必要时阻塞当前线程
+    pub fn acquire(&self, bytes: u64) {
+        if self.is_unlimited() {
+            return;
+        }
+
+        let wait = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+            state.last_refill = now;
+
+            let mut wait: f64 = 0.0;
+            if self.bytes_per_sec > 0 {
+                let rate = self.bytes_per_sec as f64;
+                state.bytes = (state.bytes + elapsed * rate).min(rate) - bytes as f64;
+                if state.bytes < 0.0 {
+                    wait = wait.max(-state.bytes / rate);
+                }
+            }
+            if self.ops_per_sec > 0 {
+                let rate = self.ops_per_sec as f64;
+                state.ops = (state.ops + elapsed * rate).min(rate) - 1.0;
+                if state.ops < 0.0 {
+                    wait = wait.max(-state.ops / rate);
+                }
+            }
+            wait
+        };
+
+        // 在锁外休眠，其他线程会看到透支的配额并相应等待
+        if wait > 0.0 {
+            thread::sleep(Duration::from_secs_f64(wait));
+        }
+    }
+}
+
+impl Default for IoThrottle {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+/// 多个线程同时占用的字节数上限，如并行解压时内存中的内容总量
+///
+/// 申请超过剩余额度时阻塞，直到其他线程归还。单个请求超过上限时按上限计算，
+/// 因此大文件不会永远等待，只是需要独占全部额度。
+#[derive(Debug)]
+pub struct ByteBudget {
+    limit: u64,
+    in_use: Mutex<u64>,
+    released: Condvar,
+}
+
+/// [`ByteBudget::acquire`] 返回的额度，离开作用域时归还
+#[derive(Debug)]
+pub struct BudgetPermit<'a> {
+    budget: &'a ByteBudget,
+    bytes: u64,
+}
+
+impl ByteBudget {
+    /// 创建额度，0 表示不限制
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            in_use: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// 占用 `bytes` 字节的额度，必要时阻塞当前线程
+    pub fn acquire(&self, bytes: u64) -> BudgetPermit<'_> {
+        if self.limit == 0 {
+            return BudgetPermit { budget: self, bytes: 0 };
+        }
+        let bytes = bytes.min(self.limit);
+        let mut in_use = self.in_use.lock().unwrap_or_else(|e| e.into_inner());
+        while *in_use + bytes > self.limit {
+            in_use = self.released.wait(in_use).unwrap_or_else(|e| e.into_inner());
+        }
+        *in_use += bytes;
+        BudgetPermit { budget: self, bytes }
+    }
+}
+
+impl Drop for BudgetPermit<'_> {
+    fn drop(&mut self) {
+        if self.bytes == 0 {
+            return;
+        }
+        let mut in_use = self.budget.in_use.lock().unwrap_or_else(|e| e.into_inner());
+        *in_use -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlimited_does_not_block() {
+        let throttle = IoThrottle::unlimited();
+        let start = Instant::now();
+        for _ in 0..1000 {
+            throttle.acquire(u64::MAX);
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_bytes_limit_paces_after_burst() {
+        let throttle = IoThrottle::new(1000, 0);
+        let start = Instant::now();
+
+        // 第一秒的配额可以立即使用
+        throttle.acquire(1000);
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        throttle.acquire(200);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_byte_budget_blocks_until_released() {
+        let budget = ByteBudget::new(100);
+        let first = budget.acquire(60);
+        let acquired = std::sync::atomic::AtomicBool::new(false);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                // 超过上限的请求按上限计算，需要等前一个额度归还
+                let _second = budget.acquire(500);
+                acquired.store(true, std::sync::atomic::Ordering::SeqCst);
+            });
+            thread::sleep(Duration::from_millis(50));
+            assert!(!acquired.load(std::sync::atomic::Ordering::SeqCst));
+            drop(first);
+        });
+        assert!(acquired.load(std::sync::atomic::Ordering::SeqCst));
+
+        // 不限制时立即返回
+        let unlimited = ByteBudget::new(0);
+        let _a = unlimited.acquire(u64::MAX);
+        let _b = unlimited.acquire(u64::MAX);
+    }
+
+    #[test]
+    fn test_ops_limit() {
+        let throttle = IoThrottle::new(0, 10);
+        let start = Instant::now();
+        for _ in 0..12 {
+            throttle.acquire(0);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+}
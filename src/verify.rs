@@ -0,0 +1,182 @@
+//! 读取时抽样校验
+//!
+//! 按 `Config::verify_sample_rate` 的概率对读取和提取的内容重新计算哈希，
+//! 与条目记录的哈希比较，在长期运行的部署中尽早发现静默损坏，而不需要完整扫描。
+//! 校验结果汇总到 `StorageManager::health_check`。
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::dedup::ContentDeduplicator;
+use crate::error::StowrError;
+use crate::index::FileEntry;
+
+/// 保留的损坏条目路径数量上限
+const MAX_RECORDED_FAILURES: usize = 100;
+
+/// 抽样校验的累计结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyStats {
+    /// 已校验的读取次数
+    pub verified: u64,
+    /// 校验失败的次数
+    pub failed: u64,
+    /// 校验失败的条目路径（最多保留最近 100 个）
+    #[serde(with = "crate::paths::serde_paths")]
+    pub corrupted: Vec<PathBuf>,
+}
+
+/// 读取时的抽样校验器，可在线程间共享
+#[derive(Debug)]
+pub struct ReadVerifier {
+    rate: f32,
+    verified: AtomicU64,
+    failed: AtomicU64,
+    corrupted: Mutex<Vec<PathBuf>>,
+}
+
+impl ReadVerifier {
+    pub fn new(rate: f32) -> Self {
+        Self {
+            rate,
+            verified: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            corrupted: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 按抽样概率校验读取的完整内容，内容与条目哈希不一致时返回 `StowrError::ChecksumMismatch`
+    ///
+    /// 没有记录哈希的条目不参与校验
+    pub fn check(&self, entry: &FileEntry, content: &[u8]) -> anyhow::Result<()> {
+        if !self.should_check(entry) {
+            return Ok(());
+        }
+        self.check_hash(entry, ContentDeduplicator::calculate_hash(content))
+    }
+
+    /// 本次读取是否需要校验：条目记录了哈希且被抽中
+    ///
+    /// 流式读取在开始前调用，需要校验时边读边计算哈希，结束后交给 [`ReadVerifier::check_hash`]
+    pub(crate) fn should_check(&self, entry: &FileEntry) -> bool {
+        entry.hash.is_some() && self.should_sample()
+    }
+
+    /// 比较已计算的内容哈希并计入统计
+    pub(crate) fn check_hash(&self, entry: &FileEntry, actual: String) -> anyhow::Result<()> {
+        let Some(expected) = &entry.hash else {
+            return Ok(());
+        };
+
+        self.verified.fetch_add(1, Ordering::Relaxed);
+        if &actual == expected {
+            return Ok(());
+        }
+
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut corrupted) = self.corrupted.lock() {
+            if corrupted.len() >= MAX_RECORDED_FAILURES {
+                corrupted.remove(0);
+            }
+            corrupted.push(entry.original_path.clone());
+        }
+        Err(StowrError::ChecksumMismatch {
+            path: entry.original_path.clone(),
+            expected: expected.clone(),
+            actual,
+        }.into())
+    }
+
+    pub fn stats(&self) -> VerifyStats {
+        VerifyStats {
+            verified: self.verified.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            corrupted: self.corrupted.lock().map(|c| c.clone()).unwrap_or_default(),
+        }
+    }
+
+    fn should_sample(&self) -> bool {
+        if self.rate <= 0.0 {
+            return false;
+        }
+        if self.rate >= 1.0 {
+            return true;
+        }
+        // 随机 UUID 的高 64 位作为均匀分布的随机数
+        let random = (uuid::Uuid::new_v4().as_u128() >> 64) as u64;
+        (random as f64) < self.rate as f64 * u64::MAX as f64
+    }
+}
+
+/// 写入时按需计算 SHA256 的包装，结果与 `ContentDeduplicator::calculate_hash` 一致
+pub(crate) struct HashingWriter<'a> {
+    inner: &'a mut dyn Write,
+    hasher: Option<Sha256>,
+}
+
+impl<'a> HashingWriter<'a> {
+    /// `hash` 为 false 时只转发写入
+    pub(crate) fn new(inner: &'a mut dyn Write, hash: bool) -> Self {
+        Self { inner, hasher: hash.then(Sha256::new) }
+    }
+
+    /// 已写入内容的哈希，未计算时为 None
+    pub(crate) fn finish(self) -> Option<String> {
+        self.hasher.map(|hasher| format!("{:x}", hasher.finalize()))
+    }
+}
+
+impl Write for HashingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CompressionAlgorithm;
+
+    fn entry(content: &[u8]) -> FileEntry {
+        let mut entry = FileEntry::new(
+            "id".to_string(),
+            PathBuf::from("/data/a.txt"),
+            PathBuf::from("id.gz"),
+            content.len() as u64,
+            0,
+            CompressionAlgorithm::Gzip,
+        );
+        entry.hash = Some(ContentDeduplicator::calculate_hash(content));
+        entry
+    }
+
+    #[test]
+    fn test_check_counts_and_records_failures() {
+        let verifier = ReadVerifier::new(1.0);
+        let entry = entry(b"original");
+        verifier.check(&entry, b"original").unwrap();
+        let err = verifier.check(&entry, b"corrupted").unwrap_err();
+        assert!(matches!(err.downcast_ref::<StowrError>(), Some(StowrError::ChecksumMismatch { .. })));
+
+        let stats = verifier.stats();
+        assert_eq!((stats.verified, stats.failed), (2, 1));
+        assert_eq!(stats.corrupted, vec![PathBuf::from("/data/a.txt")]);
+
+        // 关闭抽样时不校验
+        let disabled = ReadVerifier::new(0.0);
+        disabled.check(&entry, b"corrupted").unwrap();
+        assert_eq!(disabled.stats(), VerifyStats::default());
+    }
+}
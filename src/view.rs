@@ -0,0 +1,416 @@
+//! 存储库的只读文件系统视图
+//!
+//! 按索引中的原始路径组织目录树，为每个目录和文件分配 inode 编号，
+//! 读取文件时按需解压并缓存最近读取的内容，超过缓存容量的文件解码到临时文件后按偏移读取。提供查找、属性、列目录和按偏移读取，
+//! 与挂载实现无关，可以接到 FUSE 等用户态文件系统上，让用户在文件管理器中浏览已存储的内容。
+//!
+//! 视图在创建或调用 [`ReadOnlyView::refresh`] 时从索引构建，之后的存储和删除不会自动反映。
+
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::index::FileEntry;
+use crate::storage::StorageManager;
+
+/// 默认缓存的解压内容大小
+pub const DEFAULT_VIEW_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+/// 同时保留的解码临时文件数，超过时删除最久未读取的
+const MAX_SPILL_FILES: usize = 16;
+
+/// 节点类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Directory,
+    File,
+}
+
+/// 节点属性
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeAttr {
+    pub ino: u64,
+    pub kind: NodeKind,
+    /// 文件的原始大小，目录为 0
+    pub size: u64,
+    /// 文件存储时源文件的修改时间，目录和未记录时为 None
+    pub modified: Option<SystemTime>,
+}
+
+struct Node {
+    attr: NodeAttr,
+    /// 文件节点对应的条目路径
+    entry_path: Option<PathBuf>,
+    /// 目录节点的子节点（名称 -> inode）
+    children: BTreeMap<OsString, u64>,
+}
+
+/// 存储库的只读视图
+pub struct ReadOnlyView {
+    manager: StorageManager,
+    /// inode 从 1（根目录）开始，`nodes[ino - 1]`
+    nodes: Vec<Node>,
+    cache: Mutex<ContentCache>,
+    spills: Mutex<SpillFiles>,
+}
+
+impl ReadOnlyView {
+    /// 根目录的 inode
+    pub const ROOT: u64 = 1;
+
+    /// 从索引构建视图，最多缓存 `cache_bytes` 字节的解压内容（0 表示不缓存）
+    pub fn new(mut manager: StorageManager, cache_bytes: u64) -> Result<Self> {
+        manager.set_read_only();
+        let spill_dir = manager.config().temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+        let mut view = Self {
+            manager,
+            nodes: Vec::new(),
+            cache: Mutex::new(ContentCache::new(cache_bytes)),
+            spills: Mutex::new(SpillFiles::new(spill_dir)),
+        };
+        view.refresh()?;
+        Ok(view)
+    }
+
+    /// 重新从索引构建目录树并清空缓存，已分配的 inode 编号可能改变
+    pub fn refresh(&mut self) -> Result<()> {
+        self.nodes = vec![Node::directory(Self::ROOT)];
+        let mut entries = self.manager.list_files()?;
+        entries.sort_by(|a, b| a.original_path.cmp(&b.original_path));
+        for entry in &entries {
+            self.insert(entry);
+        }
+        self.cache.lock().map_err(|_| anyhow!("View cache lock poisoned"))?.clear();
+        self.spills.lock().map_err(|_| anyhow!("View cache lock poisoned"))?.clear();
+        Ok(())
+    }
+
+    /// 底层的存储管理器
+    pub fn manager(&self) -> &StorageManager {
+        &self.manager
+    }
+
+    /// 在目录中按名称查找
+    pub fn lookup(&self, parent: u64, name: &OsStr) -> Option<NodeAttr> {
+        let ino = *self.node(parent)?.children.get(name)?;
+        self.getattr(ino)
+    }
+
+    /// 按视图中的相对路径查找（如 `home/alice/a.txt`，Windows 盘符为顶层目录 `C`），
+    /// 供 ProjFS 等按路径而不是 inode 回调的虚拟化实现使用；空路径为根目录
+    pub fn resolve(&self, path: &Path) -> Option<NodeAttr> {
+        let mut ino = Self::ROOT;
+        for name in view_components(path) {
+            ino = *self.node(ino)?.children.get(&name)?;
+        }
+        self.getattr(ino)
+    }
+
+    pub fn getattr(&self, ino: u64) -> Option<NodeAttr> {
+        self.node(ino).map(|node| node.attr.clone())
+    }
+
+    /// 列出目录内容，按名称排序；不是目录时返回 None
+    pub fn readdir(&self, ino: u64) -> Option<Vec<(OsString, NodeAttr)>> {
+        let node = self.node(ino)?;
+        if node.attr.kind != NodeKind::Directory {
+            return None;
+        }
+        Some(node.children.iter()
+            .map(|(name, &child)| (name.clone(), self.nodes[child as usize - 1].attr.clone()))
+            .collect())
+    }
+
+    /// 从 `offset` 开始读取最多 `size` 字节，超出文件末尾时返回较短的内容
+    ///
+    /// 超过缓存容量的文件第一次读取时解码到临时文件，之后的读取不再重复解压
+    pub fn read(&self, ino: u64, offset: u64, size: usize) -> Result<Vec<u8>> {
+        let node = self.node(ino).ok_or_else(|| anyhow!("No such inode: {}", ino))?;
+        let path = node.entry_path.as_ref()
+            .ok_or_else(|| anyhow!("Inode {} is a directory", ino))?;
+
+        let mut cache = self.cache.lock().map_err(|_| anyhow!("View cache lock poisoned"))?;
+        if node.attr.size > cache.capacity {
+            drop(cache);
+            let mut spills = self.spills.lock().map_err(|_| anyhow!("View cache lock poisoned"))?;
+            return spills.read(&self.manager, ino, path, offset, size);
+        }
+        let cached = cache.get(ino);
+        drop(cache);
+        let content = match cached {
+            Some(content) => content,
+            None => {
+                let content: Arc<[u8]> = self.manager.read_file(path)?.into();
+                self.cache.lock().map_err(|_| anyhow!("View cache lock poisoned"))?.insert(ino, content.clone());
+                content
+            }
+        };
+
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(content.len());
+        let end = start.saturating_add(size).min(content.len());
+        Ok(content[start..end].to_vec())
+    }
+
+    fn node(&self, ino: u64) -> Option<&Node> {
+        let index = usize::try_from(ino).ok()?.checked_sub(1)?;
+        self.nodes.get(index)
+    }
+
+    fn insert(&mut self, entry: &FileEntry) {
+        let names = view_components(&entry.original_path);
+        let Some((file_name, dirs)) = names.split_last() else {
+            return;
+        };
+
+        let mut parent = Self::ROOT;
+        for name in dirs {
+            parent = match self.nodes[parent as usize - 1].children.get(name) {
+                Some(&child) if self.nodes[child as usize - 1].attr.kind == NodeKind::Directory => child,
+                // 与文件同名的目录无法表示，跳过该条目
+                Some(_) => return,
+                None => {
+                    let ino = self.nodes.len() as u64 + 1;
+                    self.nodes.push(Node::directory(ino));
+                    self.nodes[parent as usize - 1].children.insert(name.clone(), ino);
+                    ino
+                }
+            };
+        }
+
+        if self.nodes[parent as usize - 1].children.contains_key(file_name) {
+            return;
+        }
+        let ino = self.nodes.len() as u64 + 1;
+        self.nodes.push(Node {
+            attr: NodeAttr {
+                ino,
+                kind: NodeKind::File,
+                size: entry.file_size,
+                modified: entry.source_mtime
+                    .and_then(|nanos| u64::try_from(nanos).ok())
+                    .map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos)),
+            },
+            entry_path: Some(entry.original_path.clone()),
+            children: BTreeMap::new(),
+        });
+        self.nodes[parent as usize - 1].children.insert(file_name.clone(), ino);
+    }
+}
+
+impl Node {
+    fn directory(ino: u64) -> Self {
+        Self {
+            attr: NodeAttr { ino, kind: NodeKind::Directory, size: 0, modified: None },
+            entry_path: None,
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+/// 原始路径在视图中的各级名称；Windows 盘符（如 `C:`）作为顶层目录 `C`
+fn view_components(path: &Path) -> Vec<OsString> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Prefix(prefix) => {
+                let text = prefix.as_os_str().to_string_lossy();
+                Some(OsString::from(text.trim_end_matches(':')))
+            }
+            Component::Normal(name) => Some(name.to_os_string()),
+            Component::RootDir | Component::CurDir | Component::ParentDir => None,
+        })
+        .collect()
+}
+
+/// 按总大小淘汰最久未读取内容的缓存
+struct ContentCache {
+    capacity: u64,
+    used: u64,
+    entries: HashMap<u64, Arc<[u8]>>,
+    /// 从最久到最近读取的 inode
+    order: VecDeque<u64>,
+}
+
+impl ContentCache {
+    fn new(capacity: u64) -> Self {
+        Self { capacity, used: 0, entries: HashMap::new(), order: VecDeque::new() }
+    }
+
+    fn get(&mut self, ino: u64) -> Option<Arc<[u8]>> {
+        let content = self.entries.get(&ino)?.clone();
+        self.order.retain(|&i| i != ino);
+        self.order.push_back(ino);
+        Some(content)
+    }
+
+    fn insert(&mut self, ino: u64, content: Arc<[u8]>) {
+        let size = content.len() as u64;
+        if size > self.capacity || self.entries.contains_key(&ino) {
+            return;
+        }
+        while self.used + size > self.capacity {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.used -= evicted.len() as u64;
+            }
+        }
+        self.used += size;
+        self.entries.insert(ino, content);
+        self.order.push_back(ino);
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.used = 0;
+    }
+}
+
+/// 解码到临时文件的内容，按最久未读取的顺序删除
+struct SpillFiles {
+    dir: PathBuf,
+    files: HashMap<u64, SpillFile>,
+    /// 从最久到最近读取的 inode
+    order: VecDeque<u64>,
+}
+
+impl SpillFiles {
+    fn new(dir: PathBuf) -> Self {
+        Self { dir, files: HashMap::new(), order: VecDeque::new() }
+    }
+
+    /// 从 inode 对应的临时文件读取，还没有时先把条目的完整内容解码到临时文件
+    fn read(&mut self, manager: &StorageManager, ino: u64, path: &Path, offset: u64, size: usize) -> Result<Vec<u8>> {
+        if !self.files.contains_key(&ino) {
+            if self.order.len() >= MAX_SPILL_FILES {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.files.remove(&oldest);
+                }
+            }
+            fs::create_dir_all(&self.dir)?;
+            let spill_path = self.dir.join(format!("stowr-view-{}.tmp", uuid::Uuid::new_v4().simple()));
+            let spill = SpillFile::decode(manager, path, spill_path)?;
+            self.files.insert(ino, spill);
+        } else {
+            self.order.retain(|&i| i != ino);
+        }
+        self.order.push_back(ino);
+
+        let file = self.files.get_mut(&ino).and_then(|spill| spill.file.as_mut())
+            .ok_or_else(|| anyhow!("No decoded content for inode {}", ino))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::with_capacity(size.min(1 << 20));
+        file.take(size as u64).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn clear(&mut self) {
+        self.files.clear();
+        self.order.clear();
+    }
+}
+
+/// 删除时一并删除的临时文件
+struct SpillFile {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl SpillFile {
+    fn decode(manager: &StorageManager, entry_path: &Path, path: PathBuf) -> Result<Self> {
+        manager.read_file_to(entry_path, &path)?;
+        // 先构造再打开，打开失败时也会删除已写出的临时文件
+        let mut spill = Self { path, file: None };
+        spill.file = Some(File::open(&spill.path)?);
+        Ok(spill)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // Windows 上需要先关闭文件才能删除
+        self.file.take();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::index::create_index;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_view_browses_and_reads_entries() {
+        let dir = TempDir::new().unwrap();
+        let config = Config {
+            storage_path: dir.path().join("storage"),
+            temp_dir: Some(dir.path().join("tmp")),
+            ..Config::default()
+        };
+        let mut manager = StorageManager::new(config.clone(), create_index(&config).unwrap());
+        let docs = dir.path().join("docs");
+        fs_write(&docs.join("a.txt"), b"alpha content");
+        fs_write(&docs.join("sub/b.txt"), b"beta");
+        manager.store_file(&docs.join("a.txt"), true).unwrap();
+        manager.store_file(&docs.join("sub/b.txt"), true).unwrap();
+
+        let view = ReadOnlyView::new(manager, 8).unwrap();
+        let mut ino = ReadOnlyView::ROOT;
+        for name in view_components(&docs) {
+            ino = view.lookup(ino, &name).unwrap().ino;
+        }
+        let listing: Vec<_> = view.readdir(ino).unwrap().into_iter()
+            .map(|(name, attr)| (name.into_string().unwrap(), attr.kind))
+            .collect();
+        assert_eq!(listing, vec![("a.txt".to_string(), NodeKind::File), ("sub".to_string(), NodeKind::Directory)]);
+
+        let a = view.lookup(ino, OsStr::new("a.txt")).unwrap();
+        let relative: PathBuf = view_components(&docs.join("a.txt")).into_iter().collect();
+        assert_eq!(view.resolve(&relative), Some(a.clone()));
+        assert_eq!(view.resolve(Path::new("")).unwrap().ino, ReadOnlyView::ROOT);
+        assert!(view.resolve(&relative.join("missing")).is_none());
+        assert_eq!(a.size, 13);
+        assert!(a.modified.is_some());
+        assert_eq!(view.read(a.ino, 6, 100).unwrap(), b"content");
+        assert_eq!(view.read(a.ino, 100, 10).unwrap(), b"");
+        assert!(view.readdir(a.ino).is_none());
+        assert!(view.read(ino, 0, 10).is_err());
+
+        // 超过缓存容量的内容只解码一次到临时文件，较小的内容淘汰最久未读取的
+        let spilled = || std::fs::read_dir(dir.path().join("tmp")).map_or(0, |entries| entries.count());
+        assert_eq!(spilled(), 1);
+        assert_eq!(view.read(a.ino, 0, 5).unwrap(), b"alpha");
+        assert_eq!(spilled(), 1);
+        let sub = view.lookup(ino, OsStr::new("sub")).unwrap();
+        let b = view.lookup(sub.ino, OsStr::new("b.txt")).unwrap();
+        assert_eq!(view.read(b.ino, 0, 4).unwrap(), b"beta");
+        {
+            let cache = view.cache.lock().unwrap();
+            assert_eq!(cache.used, 4);
+            assert!(cache.entries.contains_key(&b.ino));
+            assert!(!cache.entries.contains_key(&a.ino));
+        }
+
+        // 刷新和释放视图时删除临时文件
+        let mut view = view;
+        assert_eq!(view.read(a.ino, 0, 5).unwrap(), b"alpha");
+        view.refresh().unwrap();
+        assert_eq!(spilled(), 0);
+        assert_eq!(view.read(a.ino, 6, 7).unwrap(), b"content");
+        assert_eq!(spilled(), 1);
+        drop(view);
+        assert_eq!(spilled(), 0);
+    }
+
+    fn fs_write(path: &Path, content: &[u8]) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+}